@@ -170,12 +170,13 @@ export function ZKProofGenerator({ onBack, studyId, returnUrl }: ZKProofGenerato
 
     try {
       // Prepare inputs for AgeRangeCircuit
-      const ageStr = age.toString();
+      // The entered age is only a pre-check: the circuit derives the age from the date of
+      // birth signed in the stored provider credential
       const minAgeStr = studyCriteria?.minAge?.toString() || '18';
       const maxAgeStr = studyCriteria?.maxAge?.toString() || '65';
       const studyIdStr = studyId || '1';
 
-      const result = await generateEligibilityProof(ageStr, minAgeStr, maxAgeStr, studyIdStr);
+      const result = await generateEligibilityProof(minAgeStr, maxAgeStr, studyIdStr);
       setProof(result.proof);
       setPublicInputs(result.publicInputs);
       setProofTime(result.timeMs);
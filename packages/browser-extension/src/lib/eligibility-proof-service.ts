@@ -9,6 +9,7 @@
  */
 
 import { groth16 } from "snarkjs";
+import { computePatientCommitment } from "./zk-proof-service";
 
 /**
  * Groth16 proof structure for Solidity contract
@@ -17,19 +18,24 @@ export interface Groth16Proof {
   pA: [string, string];
   pB: [[string, string], [string, string]];
   pC: [string, string];
-  publicSignals: string[];
+  publicSignals: string[]; // requiredCodeHash, contextHash, patientCommitment
 }
 
 /**
  * Generates a Groth16 proof for an eligibility code
  *
+ * The proof is bound to the applicant through the context hash, and to the patient of
+ * the age range proof through the patient commitment of the stored identity secret.
+ *
  * @param eligibilityCode 4-element eligibility code [biomarker, vital, medAllergy, diagnosis]
  * @param requiredCodeHash Poseidon hash of the required eligibility code (public input)
+ * @param contextHash Applicant binding from StudyRegistry.getContextHash or computeContextHash (public input)
  * @returns Groth16 proof formatted for Solidity contract
  */
 export async function generateEligibilityProof(
   eligibilityCode: bigint[],
-  requiredCodeHash: bigint
+  requiredCodeHash: bigint,
+  contextHash: bigint | string
 ): Promise<Groth16Proof> {
   if (eligibilityCode.length !== 4) {
     throw new Error("Eligibility code must have exactly 4 elements");
@@ -42,10 +48,15 @@ export async function generateEligibilityProof(
   const startTime = performance.now();
 
   try {
-    // Prepare circuit inputs
+    // Prepare circuit inputs: decimal strings for snarkjs, 0x hex for the WASM helpers
+    const context = BigInt(contextHash);
+    const { identitySecret, patientCommitment } = await computePatientCommitment(`0x${context.toString(16)}`);
     const input = {
       code: eligibilityCode.map(c => c.toString()),
-      requiredCodeHash: requiredCodeHash.toString()
+      requiredCodeHash: requiredCodeHash.toString(),
+      contextHash: context.toString(),
+      identitySecret: BigInt(identitySecret).toString(),
+      patientCommitment: BigInt(patientCommitment).toString()
     };
 
     // Generate proof
@@ -62,7 +73,7 @@ export async function generateEligibilityProof(
     console.log("  Public signals:", publicSignals);
 
     // Format proof for Solidity contract
-    // Groth16Verifier.sol expects: verifyProof(uint[2] _pA, uint[2][2] _pB, uint[2] _pC, uint[1] _pubSignals)
+    // Groth16Verifier.sol expects: verifyProof(uint[2] _pA, uint[2][2] _pB, uint[2] _pC, uint[3] _pubSignals)
     return {
       pA: [proof.pi_a[0], proof.pi_a[1]],
       pB: [
@@ -82,7 +93,7 @@ export async function generateEligibilityProof(
  * Verifies an eligibility proof locally (for testing)
  *
  * @param proof Groth16 proof to verify
- * @param publicSignals Public signals (required code hash)
+ * @param publicSignals Public signals (required code hash, context hash, patient commitment)
  * @returns True if proof is valid
  */
 export async function verifyEligibilityProof(
@@ -130,8 +141,10 @@ export async function verifyEligibilityProof(
 
 /**
  * Example: Generate proof for a Type 2 Diabetes study
+ *
+ * @param contextHash Applicant binding from StudyRegistry.getContextHash
  */
-export async function exampleDiabetesProof() {
+export async function exampleDiabetesProof(contextHash: bigint | string) {
   // Example eligibility code (from patient data)
   const eligibilityCode = [
     BigInt("12345678901234567890"), // Biomarker hash
@@ -145,7 +158,7 @@ export async function exampleDiabetesProof() {
 
   try {
     // Generate proof
-    const proof = await generateEligibilityProof(eligibilityCode, requiredCodeHash);
+    const proof = await generateEligibilityProof(eligibilityCode, requiredCodeHash, contextHash);
 
     // Verify locally
     const isValid = await verifyEligibilityProof(proof, proof.publicSignals);
@@ -160,16 +173,19 @@ export async function exampleDiabetesProof() {
 /**
  * Format proof for contract submission
  *
- * Converts proof to the format expected by StudyRegistryImpl.submitAnonymousApplication()
+ * Converts proof to the arguments of StudyRegistry.submitAnonymousApplication(), which
+ * recomputes the required code hash and the context hash itself
  */
 export function formatProofForContract(proof: Groth16Proof): {
   pA: [string, string];
   pB: [[string, string], [string, string]];
   pC: [string, string];
+  patientCommitment: string;
 } {
   return {
     pA: proof.pA,
     pB: proof.pB,
-    pC: proof.pC
+    pC: proof.pC,
+    patientCommitment: proof.publicSignals[2]
   };
 }
@@ -69,20 +69,120 @@ async function loadKey(path: string): Promise<Uint8Array> {
   return new Uint8Array(arrayBuffer);
 }
 
+/**
+ * Get the patient's identity secret, creating its seed on first use
+ *
+ * The seed never leaves extension storage. The proof publishes
+ * Poseidon(identity_secret, study_id) as a nullifier, so registries can reject a
+ * second application to the same study from another wallet. The provider signs the
+ * secret's identity commitment into the credential (see computeIdentityCommitment),
+ * so the seed must be kept: proofs with another secret do not verify.
+ */
+async function getIdentitySecret(wasmModule: any): Promise<string> {
+  const data = await chrome.storage.local.get(['identitySeed']);
+  let seed: string | undefined = data.identitySeed;
+
+  if (!seed) {
+    const bytes = crypto.getRandomValues(new Uint8Array(32));
+    seed = Array.from(bytes, (b) => b.toString(16).padStart(2, '0')).join('');
+    await chrome.storage.local.set({ identitySeed: seed });
+  }
+
+  const seedBytes = new Uint8Array(seed.match(/../g)!.map((byte) => parseInt(byte, 16)));
+  return wasmModule.derive_identity_secret(seedBytes);
+}
+
+/**
+ * Credential issued by the patient's provider over their date of birth
+ *
+ * `inputs` are the credential circuit inputs (credential_id, identity_commitment,
+ * dob_commitment, ..., revocation_path), one array of strings each. The proof opens
+ * dob_commitment with `dob` (days since the Unix epoch) and `dobSalt`.
+ */
+export interface AttributeCredential {
+  dob: string;
+  dobSalt: string;
+  inputs: Record<string, string[]>;
+}
+
+/**
+ * Compute the identity commitment the patient's provider signs into the credential
+ */
+export async function computeIdentityCommitment(): Promise<string> {
+  const wasmModule = await import(chrome.runtime.getURL('zk/mopro_wasm.js'));
+  const identitySecret = await getIdentitySecret(wasmModule);
+  return wasmModule.compute_identity_commitment(identitySecret);
+}
+
+/**
+ * Store the credential issued by the patient's provider
+ */
+export async function storeAttributeCredential(credential: AttributeCredential): Promise<void> {
+  await chrome.storage.local.set({ attributeCredential: credential });
+}
+
+async function getAttributeCredential(): Promise<AttributeCredential> {
+  const data = await chrome.storage.local.get(['attributeCredential']);
+  if (!data.attributeCredential) {
+    throw new Error('No provider credential stored. Import the credential issued by your provider first.');
+  }
+  return data.attributeCredential;
+}
+
+/**
+ * Compute the context hash binding a proof to a wallet, chain and registry
+ *
+ * keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r, as
+ * StudyRegistry.getContextHash recomputes it from msg.sender. A proof submitted from another wallet or to another chain or
+ * registry does not verify.
+ */
+export async function computeContextHash(
+  wallet: string,
+  chainId: number,
+  registryAddress: string,
+  studyId: string
+): Promise<string> {
+  const wasmModule = await import(chrome.runtime.getURL('zk/mopro_wasm.js'));
+  return wasmModule.compute_context_hash(wallet, BigInt(chainId), registryAddress, studyId);
+}
+
+/**
+ * Compute the patient inputs of the Circom eligibility code proof
+ *
+ * The identity secret is the one of the age range proofs, and the patient commitment
+ * Poseidon(tag, identity_secret, context_hash) the one they publish, so a verifier can
+ * check that both proofs of an application come from the same patient.
+ */
+export async function computePatientCommitment(
+  contextHash: string
+): Promise<{ identitySecret: string; patientCommitment: string }> {
+  const wasmModule = await import(chrome.runtime.getURL('zk/mopro_wasm.js'));
+  const identitySecret = await getIdentitySecret(wasmModule);
+  const patientCommitment = wasmModule.compute_patient_commitment(identitySecret, contextHash);
+  return { identitySecret, patientCommitment };
+}
+
 /**
  * Generate age range eligibility proof
  *
- * @param age - The patient's age (private input)
+ * The age is derived in-circuit from the date of birth signed in the stored provider
+ * credential (see storeAttributeCredential), on the day the proof is generated.
+ *
  * @param minAge - Minimum age requirement for study (public input, default: 18)
  * @param maxAge - Maximum age requirement for study (public input, default: 65)
  * @param studyId - Study ID to bind proof to (public input, default: 1)
+ * @param contextHash - Applicant binding from computeContextHash (public input, default: 0,
+ *   i.e. bound to no wallet, for client-side verification only)
+ * @param ageBuckets - Ascending age bucket bounds published by the study, e.g. ['40', '65']
+ *   (public input, default: none, the proof reveals no bucket)
  * @returns Proof object with proof bytes and public inputs
  */
 export async function generateEligibilityProof(
-  age: string,
   minAge: string = '18',
   maxAge: string = '65',
-  studyId: string = '1'
+  studyId: string = '1',
+  contextHash: string = '0',
+  ageBuckets: string[] = []
 ): Promise<{ proof: any; publicInputs: any; timeMs: number }> {
   if (!wasmInitialized) {
     throw new Error('ZK proof system not initialized. Call initializeZKProofs() first.');
@@ -93,7 +193,7 @@ export async function generateEligibilityProof(
   }
 
   try {
-    console.log(`🔐 Generating age range proof: age=${age}, range=[${minAge}, ${maxAge}], study=${studyId}`);
+    console.log(`🔐 Generating age range proof: range=[${minAge}, ${maxAge}], study=${studyId}`);
 
     const startTime = performance.now();
 
@@ -101,12 +201,22 @@ export async function generateEligibilityProof(
     const wasmModule = await import(chrome.runtime.getURL('zk/mopro_wasm.js'));
 
     // Prepare inputs for AgeRangeCircuit
-    // Circuit expects: { age: [string], min_age: [string], max_age: [string], study_id: [string] }
+    // Circuit expects: { dob, dob_salt, identity_secret, min_age, max_age, study_id, context_hash, epoch }
+    // (one string each), the credential inputs and optionally { age_buckets } (up to three bounds)
+    const identitySecret = await getIdentitySecret(wasmModule);
+    const credential = await getAttributeCredential();
+    const epoch = wasmModule.epoch_of(BigInt(Math.floor(Date.now() / 1000)));
     const input = {
-      age: [age],
+      ...credential.inputs,
+      dob: [credential.dob],
+      dob_salt: [credential.dobSalt],
+      identity_secret: [identitySecret],
       min_age: [minAge],
       max_age: [maxAge],
-      study_id: [studyId]
+      study_id: [studyId],
+      context_hash: [contextHash],
+      epoch: [epoch.toString()],
+      ...(ageBuckets.length > 0 && { age_buckets: ageBuckets })
     };
 
     const result = wasmModule.generate_eligibility_proof(
@@ -137,12 +247,14 @@ export async function generateEligibilityProof(
  *
  * @param proof - The proof to verify
  * @param publicInputs - The public inputs
- * @returns True if proof is valid
+ * @param maxAgeDays - Oldest as-of epoch accepted, in days; older proofs throw a "proof epoch ... is outside" error
+ * @returns Whether the proof is valid, and the age bucket it reveals (null if the study has no buckets)
  */
 export async function verifyEligibilityProof(
   proof: any,
-  publicInputs: any
-): Promise<{ valid: boolean; timeMs: number }> {
+  publicInputs: any,
+  maxAgeDays: number = 30
+): Promise<{ valid: boolean; ageBucket: number | null; timeMs: number }> {
   if (!wasmInitialized) {
     throw new Error('ZK proof system not initialized. Call initializeZKProofs() first.');
   }
@@ -160,19 +272,24 @@ export async function verifyEligibilityProof(
     const wasmModule = await import(chrome.runtime.getURL('zk/mopro_wasm.js'));
 
     // Verify proof
-    const valid = wasmModule.verify_eligibility_proof(
+    const [valid, ageBucket] = wasmModule.verify_eligibility_proof(
       srsKey,
       verifyingKey,
       proof,
-      publicInputs
+      publicInputs,
+      BigInt(Math.floor(Date.now() / 1000)),
+      BigInt(maxAgeDays)
     );
 
     const endTime = performance.now();
     const timeMs = Math.round(endTime - startTime);
 
     console.log(`${valid ? '✅' : '❌'} Proof verification: ${valid ? 'VALID' : 'INVALID'} (${timeMs}ms)`);
+    if (ageBucket !== null) {
+      console.log(`📊 Age bucket: ${ageBucket}`);
+    }
 
-    return { valid, timeMs };
+    return { valid, ageBucket, timeMs };
   } catch (error) {
     console.error('❌ Proof verification failed:', error);
     throw new Error(`Proof verification failed: ${error instanceof Error ? error.message : 'Unknown error'}`);
@@ -188,19 +305,14 @@ export async function verifyEligibilityProof(
  */
 export async function generateProofFromHealthData(
   dataType: string,
-  criteria: { age?: string; minAge?: string; maxAge?: string; studyId?: string }
+  criteria: { minAge?: string; maxAge?: string; studyId?: string }
 ): Promise<{ proof: any; publicInputs: any; timeMs: number }> {
   console.log(`📊 Generating proof from health data: ${dataType}`);
 
   // TODO: In future, fetch actual health data from Nillion and compute eligibility
-  // For now, generate proof directly from criteria
-
-  if (!criteria.age) {
-    throw new Error('Age is required for proof generation');
-  }
+  // For now, prove the criteria against the stored provider credential
 
   return generateEligibilityProof(
-    criteria.age,
     criteria.minAge || '18',
     criteria.maxAge || '65',
     criteria.studyId || '1'
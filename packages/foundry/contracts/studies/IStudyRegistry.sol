@@ -52,6 +52,13 @@ interface IStudyRegistry {
      */
     event StudyCriteriaSet(uint256 indexed studyId, uint32 minAge, uint32 maxAge, uint256 eligibilityCodeHash);
 
+    /**
+     * @notice Emitted when the compiled criteria hash is set for a study.
+     * @param studyId The study ID.
+     * @param criteriaHash Hash of the compiled criteria of the criteria circuit.
+     */
+    event StudyCriteriaHashSet(uint256 indexed studyId, uint256 criteriaHash);
+
     // ============ Study Management Functions ============
 
     /**
@@ -81,6 +88,13 @@ interface IStudyRegistry {
         uint256 _eligibilityCodeHash
     ) external;
 
+    /**
+     * @notice Sets the compiled criteria hash of a study.
+     * @param _studyId The study to configure.
+     * @param _criteriaHash Hash of the compiled criteria (0 if not set).
+     */
+    function setStudyCriteriaHash(uint256 _studyId, uint256 _criteriaHash) external;
+
     /**
      * @notice Closes recruitment for a study (researcher only).
      * @param _studyId The study to close.
@@ -95,16 +109,26 @@ interface IStudyRegistry {
      * @param _pA Groth16 proof component A.
      * @param _pB Groth16 proof component B.
      * @param _pC Groth16 proof component C.
+     * @param _patientCommitment Patient commitment public signal, shared with the age range proof.
      */
     function submitAnonymousApplication(
         uint256 _studyId,
         uint[2] calldata _pA,
         uint[2][2] calldata _pB,
-        uint[2] calldata _pC
+        uint[2] calldata _pC,
+        uint256 _patientCommitment
     ) external;
 
     // ============ View Functions ============
 
+    /**
+     * @notice Get the context hash public signal of an application.
+     * @param _studyId The study applied to.
+     * @param _applicant The wallet submitting the application.
+     * @return keccak256(abi.encode(applicant, chainid, registry, studyId)) reduced into the BN254 scalar field.
+     */
+    function getContextHash(uint256 _studyId, address _applicant) external view returns (uint256);
+
     /**
      * @notice Get details of a specific study.
      * @param _studyId The study ID to query.
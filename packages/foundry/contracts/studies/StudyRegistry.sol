@@ -2,7 +2,7 @@
 pragma solidity ^0.8.20;
 
 import "./IStudyRegistry.sol";
-import "../zk/EligibilityCodeVerifier.sol";
+import "../zk/IEligibilityCodeVerifier.sol";
 
 /**
  * @title StudyRegistry
@@ -20,11 +20,16 @@ contract StudyRegistry is IStudyRegistry {
     uint256 private nextStudyId;
     mapping(uint256 => Study) public studies;
     mapping(uint256 => EligibilityCriteria) public studyCriteria;
+    mapping(uint256 => uint256) public studyCriteriaHash;
     mapping(uint256 => uint256) public verifiedApplicantsCount;
     mapping(uint256 => mapping(address => bool)) public hasApplied;
 
     // ZK Verifier (Groth16 for medical eligibility codes)
-    Groth16Verifier public immutable eligibilityVerifier;
+    IEligibilityCodeVerifier public immutable eligibilityVerifier;
+
+    // BN254 scalar field modulus, the field of the context hash public signal
+    uint256 private constant SNARK_SCALAR_FIELD =
+        21888242871839275222246405745257275088548364400416034343698204186575808495617;
 
     // Errors
     error InvalidStudyId();
@@ -38,7 +43,7 @@ contract StudyRegistry is IStudyRegistry {
      * @param _eligibilityVerifier Address of deployed Groth16Verifier contract
      */
     constructor(address _eligibilityVerifier) {
-        eligibilityVerifier = Groth16Verifier(_eligibilityVerifier);
+        eligibilityVerifier = IEligibilityCodeVerifier(_eligibilityVerifier);
         nextStudyId = 1;
     }
 
@@ -97,22 +102,44 @@ contract StudyRegistry is IStudyRegistry {
         emit StudyCriteriaSet(_studyId, _minAge, _maxAge, _eligibilityCodeHash);
     }
 
+    /**
+     * @notice Sets the hash of the compiled criteria a study's criteria proofs are checked against
+     * @param _studyId The study to configure
+     * @param _criteriaHash `criteria_hash` output of the criteria compiler
+     * @dev Kept apart from the eligibility code hash, which is the Circom verifier's public signal
+     */
+    function setStudyCriteriaHash(uint256 _studyId, uint256 _criteriaHash) external {
+        if (_studyId == 0 || _studyId >= nextStudyId) revert InvalidStudyId();
+        if (studies[_studyId].researcher != msg.sender) revert Unauthorized();
+
+        studyCriteriaHash[_studyId] = _criteriaHash;
+
+        emit StudyCriteriaHashSet(_studyId, _criteriaHash);
+    }
+
     /**
      * @notice Submit anonymous application with ZK proof of eligibility
      * @param _studyId The study to apply to
      * @param _pA Groth16 proof component A
      * @param _pB Groth16 proof component B
      * @param _pC Groth16 proof component C
+     * @param _patientCommitment Patient commitment public signal, shared with the age range proof
      * @dev Hybrid ZK verification:
      *      - Age: Client-side verification (Halo2 + Mopro WASM, 33-60ms)
      *      - Medical eligibility: On-chain verification (Circom + Groth16, 2-5s)
      *      Patient remains anonymous - only wallet address stored
+     *      Applications are deduplicated per wallet only. The per-study nullifier
+     *      Poseidon(identity_secret, study_id) is published by the age range proof, which is
+     *      verified off-chain; patientCommitment depends on the wallet through the context
+     *      hash, so neither value verified here identifies a patient across wallets.
+     *      Rejecting duplicate nullifiers needs a proof verified here that outputs it.
      */
     function submitAnonymousApplication(
         uint256 _studyId,
         uint[2] calldata _pA,
         uint[2][2] calldata _pB,
-        uint[2] calldata _pC
+        uint[2] calldata _pC,
+        uint256 _patientCommitment
     ) external {
         // Validation
         if (_studyId == 0 || _studyId >= nextStudyId) revert InvalidStudyId();
@@ -122,10 +149,12 @@ contract StudyRegistry is IStudyRegistry {
         // Get criteria
         EligibilityCriteria memory criteria = studyCriteria[_studyId];
 
-        // Verify eligibility code proof on-chain
+        // Verify eligibility code proof on-chain, bound to this applicant, chain and registry
         if (criteria.requiresEligibilityProof) {
-            uint[1] memory pubSignals;
+            uint[3] memory pubSignals;
             pubSignals[0] = criteria.requiredEligibilityCodeHash;
+            pubSignals[1] = getContextHash(_studyId, msg.sender);
+            pubSignals[2] = _patientCommitment;
 
             bool isValid = eligibilityVerifier.verifyProof(_pA, _pB, _pC, pubSignals);
             if (!isValid) revert ProofVerificationFailed();
@@ -141,6 +170,17 @@ contract StudyRegistry is IStudyRegistry {
         );
     }
 
+    /**
+     * @notice Context hash binding an application proof to an applicant, chain and this registry
+     * @param _studyId The study applied to
+     * @param _applicant The wallet submitting the application
+     * @return keccak256(abi.encode(applicant, chainid, registry, studyId)) reduced into the BN254 scalar field
+     * @dev Same value as compute_context_hash of the composite circuits, which proofs are generated with
+     */
+    function getContextHash(uint256 _studyId, address _applicant) public view returns (uint256) {
+        return uint256(keccak256(abi.encode(_applicant, block.chainid, address(this), _studyId))) % SNARK_SCALAR_FIELD;
+    }
+
     /**
      * @notice Get details of a specific study
      * @param _studyId The study ID to query
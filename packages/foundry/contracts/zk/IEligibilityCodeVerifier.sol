@@ -0,0 +1,25 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/**
+ * @title IEligibilityCodeVerifier Interface
+ * @author edsphinx
+ * @notice Groth16 verifier of the Circom EligibilityCode circuit, as exported by snarkjs.
+ * @dev Public signals, in circuit order: requiredCodeHash, contextHash, patientCommitment.
+ */
+interface IEligibilityCodeVerifier {
+    /**
+     * @notice Verifies an eligibility code proof.
+     * @param _pA Groth16 proof component A.
+     * @param _pB Groth16 proof component B.
+     * @param _pC Groth16 proof component C.
+     * @param _pubSignals requiredCodeHash, contextHash and patientCommitment.
+     * @return True if the proof is valid for these public signals.
+     */
+    function verifyProof(
+        uint[2] calldata _pA,
+        uint[2][2] calldata _pB,
+        uint[2] calldata _pC,
+        uint[3] calldata _pubSignals
+    ) external view returns (bool);
+}
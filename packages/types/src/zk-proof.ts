@@ -76,9 +76,16 @@ export interface ZKProof {
  * Age range circuit inputs (Halo2/Plonkish)
  * Used for anonymous age verification in clinical trials
  */
-export interface AgeRangeCircuitInput {
-  /** Patient's actual age (private witness) */
-  age: string;
+export interface AgeRangeCircuitInput extends CredentialCircuitInput {
+  /** Patient's date of birth, days since the Unix epoch (private witness) */
+  dob: string;
+
+  /** Salt of the provider's dob_commitment (private witness) */
+  dob_salt: string;
+
+  /** Patient's identity secret, 0x hex (private witness, derives the nullifier; must match the
+   *  credential's identity_commitment) */
+  identity_secret: string;
 
   /** Minimum age requirement (public input) */
   min_age: string;
@@ -88,6 +95,42 @@ export interface AgeRangeCircuitInput {
 
   /** Study ID to bind proof to (public input) */
   study_id: string;
+
+  /** keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r, 0x hex (public input) */
+  context_hash: string;
+
+  /** Days since the Unix epoch the age is derived on, normally today (public input; chosen by the prover, at most 365 days after the credential's issued_at) */
+  epoch: string;
+
+  /** Ascending age bucket bounds published by the study, up to three (public input, optional) */
+  age_buckets?: string[];
+}
+
+/**
+ * Provider-signed attribute credential, as circuit inputs (one array of strings each)
+ *
+ * Issued by a provider registered in MedicalProviderRegistry; every Halo2 circuit checks
+ * the signature, the provider's registration and that the credential is not revoked.
+ */
+export interface CredentialCircuitInput {
+  credential_id: string[];
+  identity_commitment: string[];
+  dob_commitment: string[];
+  diagnosis_commitment: string[];
+  lab_root: string[];
+  /** Day of issuance, in days since the Unix epoch (signed; bounds the proof epoch) */
+  issued_at: string[];
+  /** Provider public key [A.x, A.y] */
+  provider_key: string[];
+  /** EdDSA signature [R.x, R.y, S] */
+  credential_signature: string[];
+  /** Registered provider tree root and the key's path (public input: provider_root) */
+  provider_root: string[];
+  provider_path: string[];
+  provider_index: string[];
+  /** Revoked credential tree root and the credential's path (public input: revocation_root) */
+  revocation_root: string[];
+  revocation_path: string[];
 }
 
 /**
@@ -100,6 +143,15 @@ export interface EligibilityCodeCircuitInput {
 
   /** Poseidon hash of required code (public input) */
   requiredCodeHash: bigint | string;
+
+  /** keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r (public input) */
+  contextHash: bigint | string;
+
+  /** Identity secret of the linked age range proof (private witness) */
+  identitySecret: bigint | string;
+
+  /** Poseidon(tag, identitySecret, contextHash), also published by the age range proof (public input) */
+  patientCommitment: bigint | string;
 }
 
 /**
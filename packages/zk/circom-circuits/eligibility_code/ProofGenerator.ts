@@ -7,6 +7,9 @@
 export interface ProofInput {
   code: string[];
   requiredCodeHash: string;
+  contextHash: string; // keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r, from getContextHash
+  identitySecret: string; // Same identity secret as the age range proof (private)
+  patientCommitment: string; // Poseidon(tag, identitySecret, contextHash), from compute_patient_commitment
 }
 
 export interface ProofResult {
@@ -14,7 +17,7 @@ export interface ProofResult {
     a: [string, string];
     b: [[string, string], [string, string]];
     c: [string, string];
-    input: [string];
+    input: [string, string, string]; // requiredCodeHash, contextHash, patientCommitment
   };
   publicSignals: string[];
   duration: number;
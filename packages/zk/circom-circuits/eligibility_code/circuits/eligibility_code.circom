@@ -2,6 +2,7 @@ pragma circom 2.0.0;
 
 include "circomlib/circuits/poseidon.circom";
 include "circomlib/circuits/comparators.circom";
+include "halo2_poseidon.circom";
 
 /**
  * EligibilityCode Circuit
@@ -17,6 +18,10 @@ include "circomlib/circuits/comparators.circom";
  * Example codes:
  * - "AG1845" = Age 18-45
  * - "AG1845-HTN_NO-DM2_YES" = Age 18-45, No Hypertension, Has Diabetes Type 2
+ *
+ * The patient commitment Poseidon(PATIENT_COMMITMENT_TAG, identitySecret, contextHash)
+ * is the one the Halo2 age range proof publishes, hashed with the same Poseidon (see halo2_poseidon.circom),
+ * so a verifier can check that both proofs come from the same patient.
  */
 template EligibilityCode() {
     // Private input: The eligibility code (32 bytes as 4 field elements)
@@ -27,6 +32,21 @@ template EligibilityCode() {
     // This is published by the researcher on-chain
     signal input requiredCodeHash;
 
+    // Public input: keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r
+    // StudyRegistry recomputes it from msg.sender, so a copied proof cannot be
+    // submitted from another wallet or replayed on another chain / registry
+    signal input contextHash;
+
+    // Private input: the patient's identity secret, shared with the age range proof.
+    // This circuit verifies no credential: the age range proof checks the secret
+    // against the provider-signed identity commitment, and matching its patient
+    // commitment requires the same secret
+    signal input identitySecret;
+
+    // Public input: Poseidon(PATIENT_COMMITMENT_TAG, identitySecret, contextHash) with
+    // the Halo2 Poseidon
+    signal input patientCommitment;
+
     // Compute Poseidon hash of the code
     // Poseidon is a ZK-friendly hash function designed for circuits
     component hasher = Poseidon(4);
@@ -41,7 +61,16 @@ template EligibilityCode() {
 
     // Constrain that they must be equal
     eq.out === 1;
+
+    // Bind the proof to the patient (and, through the commitment, to the context)
+    // PATIENT_COMMITMENT_TAG of patient_commitment.rs ("\0PATCOM1")
+    var PATIENT_COMMITMENT_TAG = 22589828299181361;
+    component commitment = Halo2Poseidon(3);
+    commitment.inputs[0] <== PATIENT_COMMITMENT_TAG;
+    commitment.inputs[1] <== identitySecret;
+    commitment.inputs[2] <== contextHash;
+    commitment.out === patientCommitment;
 }
 
 // Main component
-component main {public [requiredCodeHash]} = EligibilityCode();
+component main {public [requiredCodeHash, contextHash, patientCommitment]} = EligibilityCode();
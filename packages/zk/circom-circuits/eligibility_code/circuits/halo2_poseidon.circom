@@ -0,0 +1,115 @@
+pragma circom 2.0.0;
+
+// Rounds exported from Rust by `yarn generate:poseidon` (gen-circom-poseidon)
+include "halo2_poseidon_constants.circom";
+
+/**
+ * Halo2Poseidon
+ *
+ * The Poseidon hash of the Halo2 circuits (eligibility_gadgets::PoseidonChip),
+ * NOT circomlib's Poseidon: the `poseidon` crate starts from another state and
+ * pads its input, so the two give different hashes of the same values. Values
+ * that must agree with the Halo2 circuits (e.g. the patient commitment) are
+ * hashed with this template.
+ *
+ * Each round computes next = M * sbox(state) + C, where the S-box raises no
+ * element, the first element or every element to the fifth power; the rounds
+ * and the initial state are exported from Rust, so both sides share them.
+ *
+ * Sponge: rate 2, inputs absorbed two at a time into state[1..2], the last chunk
+ * followed by a 1 and zero padded (even when the inputs fill every chunk).
+ * The output is state[1].
+ */
+template Halo2PoseidonPow5() {
+    signal input in;
+    signal output out;
+
+    signal in2;
+    signal in4;
+    in2 <== in * in;
+    in4 <== in2 * in2;
+    out <== in4 * in;
+}
+
+template Halo2PoseidonPermutation() {
+    signal input in[3];
+    signal output out[3];
+
+    var nRounds = HALO2_POSEIDON_N_ROUNDS();
+    var sbox[nRounds] = HALO2_POSEIDON_SBOX();
+    var M[nRounds][3][3] = HALO2_POSEIDON_MATRIX();
+    var C[nRounds][3] = HALO2_POSEIDON_CONSTANTS();
+
+    signal state[nRounds + 1][3];
+    signal sboxed[nRounds][3];
+    component pow5[nRounds][3];
+
+    for (var i = 0; i < 3; i++) {
+        state[0][i] <== in[i];
+    }
+
+    for (var r = 0; r < nRounds; r++) {
+        // sbox: 0 = none, 1 = first element, 2 = every element
+        for (var i = 0; i < 3; i++) {
+            if (sbox[r] == 2 || (sbox[r] == 1 && i == 0)) {
+                pow5[r][i] = Halo2PoseidonPow5();
+                pow5[r][i].in <== state[r][i];
+                sboxed[r][i] <== pow5[r][i].out;
+            } else {
+                sboxed[r][i] <== state[r][i];
+            }
+        }
+
+        for (var i = 0; i < 3; i++) {
+            var mixed = C[r][i];
+            for (var j = 0; j < 3; j++) {
+                mixed += M[r][i][j] * sboxed[r][j];
+            }
+            state[r + 1][i] <== mixed;
+        }
+    }
+
+    for (var i = 0; i < 3; i++) {
+        out[i] <== state[nRounds][i];
+    }
+}
+
+template Halo2Poseidon(nInputs) {
+    signal input inputs[nInputs];
+    signal output out;
+
+    var nChunks = nInputs \ 2 + 1;
+    var initial[3] = HALO2_POSEIDON_INITIAL_STATE();
+
+    component permutation[nChunks];
+
+    for (var c = 0; c < nChunks; c++) {
+        permutation[c] = Halo2PoseidonPermutation();
+
+        // Capacity element
+        if (c == 0) {
+            permutation[c].in[0] <== initial[0];
+        } else {
+            permutation[c].in[0] <== permutation[c - 1].out[0];
+        }
+
+        // Rate elements plus the absorbed chunk (inputs, then 1, then zeros)
+        for (var i = 0; i < 2; i++) {
+            var k = 2 * c + i;
+            var absorbed = 0;
+            if (k < nInputs) {
+                absorbed = inputs[k];
+            } else if (k == nInputs) {
+                absorbed = 1;
+            }
+
+            if (c == 0) {
+                permutation[c].in[i + 1] <== initial[i + 1] + absorbed;
+            } else {
+                permutation[c].in[i + 1] <== permutation[c - 1].out[i + 1] + absorbed;
+            }
+        }
+    }
+
+    out <== permutation[nChunks - 1].out[1];
+}
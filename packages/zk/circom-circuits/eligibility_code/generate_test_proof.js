@@ -28,12 +28,34 @@ async function main() {
     const codeHashStr = poseidon.F.toString(codeHash);
 
     console.log('Required Code Hash (public input):', codeHashStr);
+
+    // Context hash: keccak256(abi.encode(wallet, chain_id, registry_address, study_id))
+    // mod r, as StudyRegistry.getContextHash and compute_context_hash compute it
+    // Any value works off-chain; pass the real one as first argument
+    const contextHashStr = BigInt(process.argv[2] || '1').toString();
+
+    console.log('Context Hash (public input):', contextHashStr);
+
+    // Patient commitment: Poseidon(tag, identity_secret, context_hash) with the Halo2
+    // Poseidon, as the age range proof publishes it (compute_patient_commitment in
+    // plonk-composite-eligibility); circomlibjs cannot compute it
+    const identitySecretStr = BigInt(process.argv[3] || '1').toString();
+    if (!process.argv[4]) {
+        console.error('Usage: generate_test_proof.js <contextHash> <identitySecret> <patientCommitment>');
+        process.exit(1);
+    }
+    const patientCommitmentStr = BigInt(process.argv[4]).toString();
+
+    console.log('Patient Commitment (public input):', patientCommitmentStr);
     console.log('');
 
     // Create input for circuit
     const input = {
         code: testCode.map(x => x.toString()),
-        requiredCodeHash: codeHashStr
+        requiredCodeHash: codeHashStr,
+        contextHash: contextHashStr,
+        identitySecret: identitySecretStr,
+        patientCommitment: patientCommitmentStr
     };
 
     // Save input
@@ -87,7 +109,7 @@ async function main() {
     console.log('\n🎉 SUCCESS! Circom + Groth16 works perfectly!');
     console.log('');
     console.log('Summary:');
-    console.log('- Circuit: 301 constraints (tiny!)');
+    console.log('- Circuit: ~800 constraints (still tiny!)');
     console.log('- Verifier: 8KB Solidity contract (vs 140KB UltraHonk)');
     console.log('- Compilation: ✅ Works perfectly!');
     console.log('- Proof generation: ✅ Works!');
@@ -34,7 +34,7 @@ self.onmessage = async (event) => {
  * Generate a Groth16 proof using snarkjs
  *
  * @param {Object} data - Proof generation parameters
- * @param {Object} data.input - Circuit inputs (code and requiredCodeHash)
+ * @param {Object} data.input - Circuit inputs (code, requiredCodeHash, contextHash, identitySecret and patientCommitment)
  * @param {string} data.wasmUrl - URL to circuit WASM file
  * @param {string} data.zkeyUrl - URL to proving key file
  */
@@ -111,7 +111,7 @@ async function generateProof(data) {
     const calldataParts = solidityCalldata.split(',');
 
     // Extract components for Solidity verifier
-    // Format: verifyProof(uint[2] a, uint[2][2] b, uint[2] c, uint[1] input)
+    // Format: verifyProof(uint[2] a, uint[2][2] b, uint[2] c, uint[3] input)
     const proofFormatted = {
         a: [calldataParts[0], calldataParts[1]],
         b: [
@@ -119,7 +119,7 @@ async function generateProof(data) {
             [calldataParts[4], calldataParts[5]]
         ],
         c: [calldataParts[6], calldataParts[7]],
-        input: [calldataParts[8]]
+        input: [calldataParts[8], calldataParts[9], calldataParts[10]]
     };
 
     self.postMessage({
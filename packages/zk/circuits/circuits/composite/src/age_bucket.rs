@@ -0,0 +1,108 @@
+//! Age Buckets
+//!
+//! Randomised trials stratify by age band (e.g. 18–39, 40–64, 65+), so researchers need
+//! the band of an applicant but not the exact age. A study may publish up to
+//! AGE_BUCKET_BOUNDS ascending lower bounds of its bands after the first, here
+//! `[40, 65]`, and [`AgeRangeCircuit`](crate::AgeRangeCircuit) then outputs
+//!
+//! ```text
+//!   bucket = #{ i : age >= bound_i }
+//! ```
+//!
+//! as a public input: 0 for 18–39, 1 for 40–64, 2 for 65+. Every comparison is a bit
+//! with a range-checked difference, so the bucket cannot be chosen by the prover.
+//!
+//! Unused bounds are NO_BUCKET_BOUND, above any age, so a study publishing no bounds
+//! gets bucket 0 for everyone and learns nothing beyond the age range.
+
+use halo2_proofs::halo2curves::bn256::Fr;
+
+use crate::{field_to_u64, EligibilityError, AGE_RANGE_BITS};
+
+/// Maximum number of bucket bounds (four buckets)
+pub const AGE_BUCKET_BOUNDS: usize = 3;
+
+/// Bound of an unused bucket, above any age of AGE_RANGE_BITS bits
+pub const NO_BUCKET_BOUND: u64 = 1 << AGE_RANGE_BITS;
+
+/// Bucket bounds a study publishes, padded to AGE_BUCKET_BOUNDS
+///
+/// `bounds` must be ascending and below NO_BUCKET_BOUND; an empty slice disables
+/// buckets.
+pub fn bucket_bounds(bounds: &[u64]) -> Result<[u64; AGE_BUCKET_BOUNDS], EligibilityError> {
+    if bounds.len() > AGE_BUCKET_BOUNDS {
+        return Err(EligibilityError(format!(
+            "Too many age bucket bounds: {} (maximum {})",
+            bounds.len(),
+            AGE_BUCKET_BOUNDS
+        )));
+    }
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1])
+        || bounds.iter().any(|bound| *bound >= NO_BUCKET_BOUND)
+    {
+        return Err(EligibilityError(format!(
+            "Age bucket bounds {:?} must be ascending and below {}",
+            bounds, NO_BUCKET_BOUND
+        )));
+    }
+
+    let mut padded = [NO_BUCKET_BOUND; AGE_BUCKET_BOUNDS];
+    padded[..bounds.len()].copy_from_slice(bounds);
+    Ok(padded)
+}
+
+/// Bucket of an age: the number of bounds it reaches
+pub fn age_bucket(age: u64, bounds: &[u64; AGE_BUCKET_BOUNDS]) -> u64 {
+    bounds.iter().filter(|bound| age >= **bound).count() as u64
+}
+
+/// Bucket revealed by a proof, `None` if its study publishes no bounds
+///
+/// `bounds` and `bucket` are the corresponding public inputs of the proof.
+pub fn revealed_bucket(bounds: &[Fr], bucket: &Fr) -> Result<Option<u64>, EligibilityError> {
+    if bounds
+        .iter()
+        .all(|bound| *bound == Fr::from(NO_BUCKET_BOUND))
+    {
+        return Ok(None);
+    }
+    field_to_u64(bucket).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_bucket() {
+        let bounds = bucket_bounds(&[40, 65]).unwrap();
+        assert_eq!(bounds, [40, 65, NO_BUCKET_BOUND]);
+
+        assert_eq!(age_bucket(18, &bounds), 0);
+        assert_eq!(age_bucket(39, &bounds), 0);
+        assert_eq!(age_bucket(40, &bounds), 1); // Edge: lower bound of 40–64
+        assert_eq!(age_bucket(64, &bounds), 1);
+        assert_eq!(age_bucket(65, &bounds), 2);
+        assert_eq!(age_bucket(120, &bounds), 2);
+
+        let disabled = bucket_bounds(&[]).unwrap();
+        assert_eq!(age_bucket(70, &disabled), 0);
+    }
+
+    #[test]
+    fn test_bucket_bounds_are_validated() {
+        assert!(bucket_bounds(&[65, 40]).is_err());
+        assert!(bucket_bounds(&[40, 40]).is_err());
+        assert!(bucket_bounds(&[40, NO_BUCKET_BOUND]).is_err());
+        assert!(bucket_bounds(&[30, 40, 50, 60]).is_err());
+    }
+
+    #[test]
+    fn test_revealed_bucket() {
+        let disabled = [Fr::from(NO_BUCKET_BOUND); AGE_BUCKET_BOUNDS];
+        assert_eq!(revealed_bucket(&disabled, &Fr::from(0)).unwrap(), None);
+
+        let bounds = bucket_bounds(&[40, 65]).unwrap().map(Fr::from);
+        assert_eq!(revealed_bucket(&bounds, &Fr::from(1)).unwrap(), Some(1));
+    }
+}
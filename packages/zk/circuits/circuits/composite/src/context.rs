@@ -0,0 +1,230 @@
+//! Proof Context
+//!
+//! The other public inputs only describe the study and the patient, so a proof copied
+//! from the mempool could be submitted from another wallet ahead of the applicant, or
+//! replayed against a deployment of the registry on another chain. Every proof that
+//! reads patient data therefore also takes the public input
+//!
+//! ```text
+//!   context_hash = keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r
+//! ```
+//!
+//! where `r` is the bn256 scalar field modulus. Every value is one 32-byte big-endian
+//! ABI word, so addresses are read as 160-bit integers. The circuits do not compute
+//! it, they only expose it: the verifier recomputes it from the submitting wallet, so a
+//! proof only verifies for the wallet, chain and registry it was generated for. Keccak
+//! rather than Poseidon keeps that cheap on-chain.
+//!
+//! The Circom `EligibilityCode` circuit takes the same value as its `contextHash` input
+//! (see [`eligibility_code_inputs`]), and `StudyRegistry.submitAnonymousApplication`
+//! recomputes it from `msg.sender`, `block.chainid`, `address(this)` and the study
+//! applied to before verifying the proof.
+//!
+//! ## Bound Proofs
+//! The age range, [`criteria`](crate::criteria), [`date_of_birth`](crate::date_of_birth),
+//! [`lab_value`](crate::lab_value) and [`metric`](crate::metric) proofs and the
+//! membership, prefix, exclusion and threshold proofs of the diagnosis crate all expose
+//! `context_hash`; the input builders of [`fhir`](crate::fhir) insert it with
+//! [`ProofContext::insert_inputs`]. The [`non_participation`](crate::non_participation)
+//! proof is bound through the nullifier of the applicant's age range proof.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use plonkish_backend::halo2_curves::bn256::Fr;
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    patient_commitment::compute_patient_commitment, serialization::parse_field_element,
+    EligibilityError,
+};
+
+/// Values a proof is bound to, as the registry sees them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofContext {
+    pub wallet: Fr,           // Applicant address (`msg.sender`)
+    pub chain_id: u64,        // `block.chainid`
+    pub registry_address: Fr, // Registry contract address
+    pub study_id: Fr,
+}
+
+impl ProofContext {
+    /// Context from `0x`-prefixed addresses
+    pub fn new(
+        wallet: &str,
+        chain_id: u64,
+        registry_address: &str,
+        study_id: Fr,
+    ) -> Result<Self, EligibilityError> {
+        Ok(Self {
+            wallet: parse_address(wallet)?,
+            chain_id,
+            registry_address: parse_address(registry_address)?,
+            study_id,
+        })
+    }
+
+    /// `keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r`
+    pub fn hash(&self) -> Fr {
+        compute_context_hash(
+            self.wallet,
+            Fr::from(self.chain_id),
+            self.registry_address,
+            self.study_id,
+        )
+    }
+
+    /// Add `study_id` and `context_hash` to an input map
+    pub fn insert_inputs(&self, inputs: &mut HashMap<String, Vec<Fr>>) {
+        inputs.insert("study_id".to_string(), vec![self.study_id]);
+        inputs.insert("context_hash".to_string(), vec![self.hash()]);
+    }
+}
+
+/// Context hash of field-encoded values (see the module documentation)
+pub fn compute_context_hash(wallet: Fr, chain_id: Fr, registry_address: Fr, study_id: Fr) -> Fr {
+    let mut keccak = Keccak256::new();
+    for value in [wallet, chain_id, registry_address, study_id] {
+        let mut word = value.to_bytes();
+        word.reverse(); // Big-endian ABI word
+        keccak.update(word);
+    }
+
+    let modulus = BigUint::from_bytes_le(&(-Fr::from(1)).to_bytes()) + 1u32;
+    let mut reduced = (BigUint::from_bytes_be(&keccak.finalize()) % modulus).to_bytes_le();
+    reduced.resize(32, 0);
+    Fr::from_bytes(&reduced.try_into().unwrap()).unwrap()
+}
+
+/// Parse a `0x`-prefixed 20-byte address
+pub fn parse_address(address: &str) -> Result<Fr, EligibilityError> {
+    match address.strip_prefix("0x") {
+        Some(hex) if hex.len() == 40 => parse_field_element(address),
+        _ => Err(EligibilityError(format!("Invalid address: {}", address))),
+    }
+}
+
+/// `json_input_str` of the circom-prover for the `EligibilityCode` circuit
+///
+/// `required_code_hash` is the circomlib Poseidon of the code the study published,
+/// `identity_secret` the one of the age range proof to link (see
+/// [`patient_commitment`](crate::patient_commitment)). Values are decimal strings, as
+/// the witness generators expect.
+pub fn eligibility_code_inputs(
+    code: &[Fr; 4],
+    required_code_hash: Fr,
+    context: &ProofContext,
+    identity_secret: Fr,
+) -> String {
+    let context_hash = context.hash();
+    let inputs = HashMap::from([
+        (
+            "code".to_string(),
+            code.iter().map(to_decimal).collect::<Vec<_>>(),
+        ),
+        (
+            "requiredCodeHash".to_string(),
+            vec![to_decimal(&required_code_hash)],
+        ),
+        ("contextHash".to_string(), vec![to_decimal(&context_hash)]),
+        (
+            "identitySecret".to_string(),
+            vec![to_decimal(&identity_secret)],
+        ),
+        (
+            "patientCommitment".to_string(),
+            vec![to_decimal(&compute_patient_commitment(
+                identity_secret,
+                context_hash,
+            ))],
+        ),
+    ]);
+    serde_json::to_string(&inputs).unwrap()
+}
+
+fn to_decimal(value: &Fr) -> String {
+    BigUint::from_bytes_le(&value.to_bytes()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::format_field_element;
+
+    const WALLET: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    const REGISTRY: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+    fn context() -> ProofContext {
+        ProofContext::new(WALLET, 11155111, REGISTRY, Fr::from(1)).unwrap()
+    }
+
+    #[test]
+    fn test_context_hash_binds_every_value() {
+        let hash = context().hash();
+        assert_eq!(hash, context().hash());
+
+        let others = [
+            ProofContext::new(REGISTRY, 11155111, REGISTRY, Fr::from(1)).unwrap(), // Front-running wallet
+            ProofContext {
+                chain_id: 1,
+                ..context()
+            }, // Other chain
+            ProofContext::new(WALLET, 11155111, WALLET, Fr::from(1)).unwrap(),     // Other registry
+            ProofContext {
+                study_id: Fr::from(2),
+                ..context()
+            },
+        ];
+        for other in others {
+            assert_ne!(other.hash(), hash);
+        }
+    }
+
+    #[test]
+    fn test_context_hash_matches_abi_encoding() {
+        // uint256(keccak256(abi.encode(WALLET, 11155111, REGISTRY, 1))) % r, as the
+        // registry computes it; this digest exceeds r, so the reduction is exercised
+        assert_eq!(
+            format_field_element(&context().hash()),
+            "0x0108fad44d8d68e4e01ae70264af796738ad25e1239227834f6376517d1d35d5"
+        );
+    }
+
+    #[test]
+    fn test_parse_address() {
+        let address = parse_address("0x0000000000000000000000000000000000000102").unwrap();
+        assert_eq!(address, Fr::from(0x102));
+        assert_eq!(
+            parse_address(WALLET).unwrap(),
+            parse_address(&WALLET.to_lowercase()).unwrap()
+        );
+
+        assert!(parse_address("0x0102").is_err());
+        assert!(parse_address(&WALLET[2..]).is_err());
+        assert!(parse_address("0x70997970C51812dc3A010C7d01b50e0d17dc79CZ").is_err());
+    }
+
+    #[test]
+    fn test_eligibility_code_inputs() {
+        let code = [Fr::from(18), Fr::from(45), Fr::from(0), Fr::from(1)];
+        let json = eligibility_code_inputs(&code, Fr::from(12345), &context(), Fr::from(42));
+
+        let inputs: HashMap<String, Vec<String>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(inputs["code"], vec!["18", "45", "0", "1"]);
+        assert_eq!(inputs["requiredCodeHash"], vec!["12345"]);
+        assert_eq!(inputs["contextHash"], vec![to_decimal(&context().hash())]);
+        assert_eq!(inputs["identitySecret"], vec!["42"]);
+        assert_eq!(
+            inputs["patientCommitment"],
+            vec![to_decimal(&compute_patient_commitment(
+                Fr::from(42),
+                context().hash()
+            ))]
+        );
+
+        assert_eq!(
+            to_decimal(&-Fr::from(1)),
+            "21888242871839275222246405745257275088548364400416034343698204186575808495616"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Provider-Signed Attribute Credentials
+//!
+//! The circuits open commitments that the patient supplies, so on their own they prove
+//! "I claim". A credential ties those commitments to a provider certified in
+//! `MedicalProviderRegistry`: the provider signs the patient's identity commitment and
+//! the dob, diagnosis and lab commitments and the day of issuance (see `eligibility_gadgets::credential` for the
+//! message and input format), and every circuit verifies the signature with
+//! `CredentialChip` before evaluating any predicate on the committed data. The provider
+//! key stays private: the circuits prove it is registered under a public root (see
+//! [`provider_registry`](crate::provider_registry)) and that the credential is not
+//! revoked (see [`revocation`](crate::revocation)).
+//!
+//! This module assembles [`CredentialInputs`] from the trees mirrored in this crate.
+
+use std::collections::HashMap;
+
+use plonkish_backend::halo2_curves::bn256::Fr;
+
+pub use eligibility_gadgets::credential::{
+    identity_commitment, AttributeCredential, CredentialAttributes, CredentialError,
+    CredentialInputs, SignedCredential, CREDENTIAL_VERSION, LAB_TREE_DEPTH, MAX_CREDENTIAL_AGE,
+};
+
+use crate::{provider_registry::ProviderTree, revocation::RevocationTree, EligibilityError};
+
+/// Inputs of a credential signed by a provider of `providers` and not revoked in `revocations`
+pub fn credential_inputs(
+    credential: &SignedCredential,
+    providers: &ProviderTree,
+    revocations: &RevocationTree,
+) -> Result<CredentialInputs, EligibilityError> {
+    Ok(CredentialInputs {
+        credential: *credential,
+        provider_root: providers.root(),
+        provider_path: providers.path(&credential.provider_key)?,
+        revocation_root: revocations.root(),
+        revocation_path: revocations.path(&credential.credential.credential_id)?,
+    })
+}
+
+/// Read the credential of an input map, checked as `CredentialChip` checks it
+pub(crate) fn checked_credential(
+    inputs: &HashMap<String, Vec<Fr>>,
+) -> Result<CredentialInputs, EligibilityError> {
+    let credential = CredentialInputs::from_inputs(inputs).map_err(credential_error)?;
+    credential.check().map_err(credential_error)?;
+    Ok(credential)
+}
+
+/// [`checked_credential`], also checking it was issued at most MAX_CREDENTIAL_AGE days
+/// before `epoch`
+pub(crate) fn checked_credential_at(
+    inputs: &HashMap<String, Vec<Fr>>,
+    epoch: &Fr,
+) -> Result<CredentialInputs, EligibilityError> {
+    let credential = checked_credential(inputs)?;
+    credential.check_at(epoch).map_err(credential_error)?;
+    Ok(credential)
+}
+
+fn credential_error(error: CredentialError) -> EligibilityError {
+    EligibilityError(format!("Invalid credential: {}", error))
+}
+
+/// Provider, registry and revocations shared by the circuit tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::OnceLock;
+
+    use eligibility_gadgets::SigningKey;
+
+    use super::*;
+    use crate::revocation::RevocationList;
+
+    /// ID of the test credential
+    pub(crate) const CREDENTIAL_ID: u64 = 1001;
+
+    /// Issuance day of the test credentials whose tests do not pick one, 2025-01-01
+    pub(crate) const ISSUED_AT: i64 = 20089;
+
+    /// Epoch of the test proofs, 2025-06-15
+    pub(crate) const EPOCH: i64 = 20254;
+
+    /// Key of the test provider
+    pub(crate) fn provider() -> SigningKey {
+        SigningKey::from_seed(&[9u8; 32]).unwrap()
+    }
+
+    /// Registry of the test provider and two others
+    pub(crate) fn providers() -> &'static ProviderTree {
+        static PROVIDERS: OnceLock<ProviderTree> = OnceLock::new();
+        PROVIDERS.get_or_init(|| {
+            let keys: Vec<_> = (7..=9u8)
+                .map(|seed| SigningKey::from_seed(&[seed; 32]).unwrap().public_key())
+                .collect();
+            ProviderTree::new(&keys).unwrap()
+        })
+    }
+
+    /// Revocations of credentials other than the test credential
+    pub(crate) fn revocations() -> RevocationTree {
+        RevocationTree::from_list(&RevocationList {
+            revoked: vec![1000, 1002],
+        })
+        .unwrap()
+    }
+
+    /// Test credential over `attributes`, signed by the test provider
+    pub(crate) fn issue(attributes: CredentialAttributes<Fr>) -> CredentialInputs {
+        let credential = AttributeCredential {
+            credential_id: Fr::from(CREDENTIAL_ID),
+            attributes,
+        }
+        .sign(&provider());
+        credential_inputs(&credential, providers(), &revocations()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+    use eligibility_gadgets::SigningKey;
+
+    fn attributes() -> CredentialAttributes<Fr> {
+        CredentialAttributes {
+            identity_commitment: identity_commitment(Fr::from(42)),
+            dob_commitment: Fr::from(11),
+            diagnosis_commitment: Fr::from(12),
+            lab_root: Fr::from(13),
+            issued_at: Fr::from(ISSUED_AT as u64),
+        }
+    }
+
+    #[test]
+    fn test_credential_inputs_from_trees() {
+        let credential = issue(attributes());
+        assert_eq!(credential.check(), Ok(()));
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        assert_eq!(checked_credential(&inputs).unwrap(), credential);
+
+        // Unregistered provider
+        let outsider = AttributeCredential {
+            credential_id: Fr::from(CREDENTIAL_ID),
+            attributes: attributes(),
+        }
+        .sign(&SigningKey::from_seed(&[5u8; 32]).unwrap());
+        assert!(credential_inputs(&outsider, providers(), &revocations()).is_err());
+
+        // Revoked credential
+        let revoked = AttributeCredential {
+            credential_id: Fr::from(1002),
+            attributes: attributes(),
+        }
+        .sign(&provider());
+        assert!(credential_inputs(&revoked, providers(), &revocations()).is_err());
+
+        // Issued more than MAX_CREDENTIAL_AGE days before the epoch
+        let last_epoch = ISSUED_AT as u64 + MAX_CREDENTIAL_AGE;
+        assert!(checked_credential_at(&inputs, &Fr::from(last_epoch)).is_ok());
+        assert!(checked_credential_at(&inputs, &Fr::from(last_epoch + 1)).is_err());
+
+        // Attributes the provider did not sign
+        inputs.insert("lab_root".to_string(), vec![Fr::from(99)]);
+        assert!(checked_credential(&inputs).is_err());
+    }
+}
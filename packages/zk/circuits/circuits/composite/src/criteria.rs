@@ -0,0 +1,2183 @@
+//! Eligibility Criteria Circuit - Boolean Combinations of Predicates
+//!
+//! Proves that a patient meets a whole study criteria expression, e.g.
+//! "age 18-65 AND E11.* AND NOT I21 AND HbA1c > 7.00", in one proof.
+//!
+//! ## Criteria
+//! A [`Criteria`] fills a fixed set of predicate slots and a small DAG over them:
+//! - one age range, on the age derived from a committed date of birth
+//!   (see [`date_of_birth`](crate::date_of_birth))
+//! - MAX_DIAGNOSIS_PREDICATES diagnosis hashes: true if the patient has a code with that
+//!   hash, or a code under an ICD-10 prefix with that hash
+//! - MAX_LAB_PREDICATES comparisons on committed lab values (see [`lab_value`](crate::lab_value))
+//! - MAX_CRITERIA_NODES AND / OR / NOT nodes, each reading earlier signals
+//!
+//! Signals are numbered predicates first, then nodes; the last node is the result.
+//! The criteria are encoded as CRITERIA_LEN field elements ([`Criteria::encode`]) and
+//! hashed with Poseidon. Only `criteria_hash` is public, so a verifier matches it
+//! against the hash the study published.
+//!
+//! ## Evaluation
+//! Unlike the single-predicate circuits, predicates are evaluated to bits instead of
+//! being asserted, so they can be negated:
+//! - comparisons witness a bit `c` and range check `c * (x - y) + (1 - c) * (y - x - 1)`
+//!   to COMPARE_BITS bits, which forces `c = [x >= y]` for operands below 2^COMPARE_BITS
+//! - diagnosis predicates multiply `1 + occupied * (target - hash - 1)` over the
+//!   ICD-10 hashes of every code slot; the bit is 1 when the product is zero
+//! - nodes select their operands with one-hot bits over the earlier signals
+//!
+//! The last node's output is constrained to 1.
+//!
+//! ## Security Model
+//! - Private Inputs: criteria encoding, date of birth and its salt, ICD-10 hashes of
+//!   every code slot, diagnosis root and salt, lab values, their salts and lab tree
+//!   paths, identity secret, the credential (see [`credential`](crate::credential))
+//! - Public Inputs: criteria_hash, study_id, as_of_date, provider_root,
+//!   revocation_root, context_hash (see [`context`](crate::context)), nullifier
+//! - Constraints: a provider registered under `provider_root` signed the patient's
+//!   identity commitment, the dob and diagnosis commitments and a lab tree holding the
+//!   commitment of every lab predicate (see [`credential`](crate::credential) and
+//!   [`provider_registry`](crate::provider_registry)), the credential is not revoked
+//!   under `revocation_root` (see [`revocation`](crate::revocation)) and was issued at
+//!   most MAX_CREDENTIAL_AGE days before `as_of_date`, the epoch of the proof (see
+//!   [`epoch`](crate::epoch)), the criteria
+//!   hashed to `criteria_hash` hold on the committed data, and
+//!   `nullifier = Poseidon(identity_secret, study_id)` (see [`nullifier`](crate::nullifier))
+//!
+//! The dob, diagnosis and lab commitments stay private: the credential check opens
+//! them in-circuit, and publishing them would link every proof of the patient.
+//!
+//! Diagnoses use the `diagnosis_commitment` of the diagnosis circuits: the circuit
+//! rebuilds every prefix tree leaf from its four hashes and recomputes the prefix root.
+//! Lab commitments take the analyte and scale from the criteria, so a lab value only
+//! satisfies the predicate it was issued for, and must be leaves of the signed
+//! [`LabTree`](crate::lab_value::LabTree), so a patient cannot pick a value; a lab
+//! predicate without an attested reading cannot be proven.
+
+use std::{collections::HashMap, io::Cursor};
+
+use diagnosis_membership_circuit::{
+    hash_diagnosis_code, prefix::diagnosis_prefix_hashes, DiagnosisTree, DIAGNOSIS_TREE_DEPTH,
+    MAX_DIAGNOSES,
+};
+use eligibility_gadgets::{
+    age_on, days_from_field, days_to_field, icd10::PREFIX_DEPTHS, identity_commitment,
+    CredentialAttributes, CredentialChip, CredentialConfig, CredentialWitness, DateChip,
+    DateConfig, EddsaChip, EddsaField, IssuerChip, MerkleChip, MerkleConfig, MerkleTree,
+    PoseidonChip, PoseidonConfig, PoseidonField, RangeCheckChip, RangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::Field,
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector,
+    },
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    context::ProofContext,
+    credential::{checked_credential_at, CredentialInputs, LAB_TREE_DEPTH},
+    date_of_birth::commit_date_of_birth,
+    field_to_u64,
+    lab_value::{commit_lab_value, lab_path, Comparison, LabTree, LAB_VALUE_BITS, MAX_LAB_VALUE},
+    nullifier::compute_nullifier,
+    rng::default_rng,
+    EligibilityError, PlonkishComponents,
+};
+
+/// Maximum number of diagnosis predicates
+pub const MAX_DIAGNOSIS_PREDICATES: usize = 4;
+
+/// Maximum number of lab value predicates
+pub const MAX_LAB_PREDICATES: usize = 2;
+
+/// Maximum number of AND / OR / NOT nodes
+pub const MAX_CRITERIA_NODES: usize = 8;
+
+/// Number of predicate signals: age, diagnoses, lab values
+pub const PREDICATES: usize = 1 + MAX_DIAGNOSIS_PREDICATES + MAX_LAB_PREDICATES;
+
+/// Number of field elements of an encoded criteria expression
+pub const CRITERIA_LEN: usize = 1
+    + 2
+    + MAX_DIAGNOSIS_PREDICATES
+    + LAB_FIELDS * MAX_LAB_PREDICATES
+    + NODE_FIELDS * MAX_CRITERIA_NODES;
+
+/// Version of the criteria encoding, the first element of every encoding
+pub const CRITERIA_VERSION: u64 = 1;
+
+/// Bit length of ages, lab values and bounds
+pub const CRITERIA_VALUE_BITS: usize = LAB_VALUE_BITS;
+
+/// Bit length of comparison differences (operands go up to `2^CRITERIA_VALUE_BITS`)
+pub const COMPARE_BITS: usize = CRITERIA_VALUE_BITS + 1;
+
+/// Circuit size (2^k rows): ~45 Poseidon permutations of 67 rows for the prefix tree,
+/// ~20 for the criteria hash, ~32 for the provider and revocation paths each, 6 for the
+/// lab tree paths and a few for the other commitments and the credential
+pub const CRITERIA_K: usize = 14;
+
+/// Signal of the age predicate
+pub const AGE_SIGNAL: usize = 0;
+
+/// Hashes per code slot: its ICD-10 prefixes, then the code itself
+const SLOT_HASHES: usize = PREFIX_DEPTHS + 1;
+
+/// Encoded fields of a lab predicate: analyte, scale, comparison, bound, bound_high
+const LAB_FIELDS: usize = 5;
+
+/// Encoded fields of a node: operation, lhs, rhs
+const NODE_FIELDS: usize = 3;
+
+/// Number of node operations
+const OPERATIONS: usize = 3;
+
+/// Number of lab comparison operators
+const COMPARISONS: usize = Comparison::ALL.len();
+
+/// Signal of the `i`-th diagnosis predicate
+pub const fn diagnosis_signal(i: usize) -> usize {
+    1 + i
+}
+
+/// Signal of the `i`-th lab value predicate
+pub const fn lab_signal(i: usize) -> usize {
+    1 + MAX_DIAGNOSIS_PREDICATES + i
+}
+
+/// Signal of the `i`-th node
+pub const fn node_signal(i: usize) -> usize {
+    PREDICATES + i
+}
+
+/// Boolean operation of a criteria node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    And = 0,
+    Or = 1,
+    Not = 2, // Reads lhs only
+}
+
+impl Operation {
+    pub const ALL: [Operation; OPERATIONS] = [Operation::And, Operation::Or, Operation::Not];
+
+    pub fn code(self) -> u64 {
+        self as u64
+    }
+
+    pub fn from_code(code: u64) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    pub fn apply(self, lhs: bool, rhs: bool) -> bool {
+        match self {
+            Operation::And => lhs && rhs,
+            Operation::Or => lhs || rhs,
+            Operation::Not => !lhs,
+        }
+    }
+}
+
+/// Node of a criteria expression, reading two signals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CriteriaNode {
+    pub op: Operation,
+    pub lhs: usize,
+    pub rhs: usize, // Equal to lhs for NOT
+}
+
+impl CriteriaNode {
+    pub fn and(lhs: usize, rhs: usize) -> Self {
+        Self {
+            op: Operation::And,
+            lhs,
+            rhs,
+        }
+    }
+
+    pub fn or(lhs: usize, rhs: usize) -> Self {
+        Self {
+            op: Operation::Or,
+            lhs,
+            rhs,
+        }
+    }
+
+    pub fn not(signal: usize) -> Self {
+        Self {
+            op: Operation::Not,
+            lhs: signal,
+            rhs: signal,
+        }
+    }
+}
+
+/// Lab value predicate: `value <comparison> bound` on a committed lab value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabPredicate {
+    pub analyte: Fr,
+    pub scale: u32,
+    pub comparison: Comparison,
+    pub bound: u64,
+    pub bound_high: u64, // Zero unless `between`
+}
+
+impl LabPredicate {
+    pub fn holds(&self, value: u64) -> bool {
+        self.comparison
+            .interval(self.bound, self.bound_high)
+            .map_or(false, |(low, high)| (low..=high).contains(&value))
+    }
+}
+
+/// Compiled criteria expression (see the module documentation)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Criteria {
+    pub age: Option<(u64, u64)>, // Inclusive age range in years
+    pub diagnoses: Vec<Fr>,      // Code or prefix hashes
+    pub labs: Vec<LabPredicate>,
+    pub nodes: Vec<CriteriaNode>, // The last node is the result
+}
+
+impl Criteria {
+    /// Check slot counts, bounds and that every node reads defined, earlier signals
+    pub fn validate(&self) -> Result<(), EligibilityError> {
+        let invalid =
+            |message: String| Err(EligibilityError(format!("Invalid criteria: {}", message)));
+
+        if self.diagnoses.len() > MAX_DIAGNOSIS_PREDICATES {
+            return invalid(format!(
+                "more than {} diagnosis predicates",
+                MAX_DIAGNOSIS_PREDICATES
+            ));
+        }
+        if self.labs.len() > MAX_LAB_PREDICATES {
+            return invalid(format!("more than {} lab predicates", MAX_LAB_PREDICATES));
+        }
+        if self.nodes.is_empty() || self.nodes.len() > MAX_CRITERIA_NODES {
+            return invalid(format!("expected 1 to {} nodes", MAX_CRITERIA_NODES));
+        }
+
+        if let Some((min_age, max_age)) = self.age {
+            // (0, 0) encodes "no age predicate"
+            if min_age > max_age || max_age == 0 || max_age > MAX_LAB_VALUE {
+                return invalid(format!("age range {}-{}", min_age, max_age));
+            }
+        }
+        if self.diagnoses.iter().any(|hash| *hash == Fr::ZERO) {
+            return invalid("zero diagnosis hash".to_string());
+        }
+        for lab in self.labs.iter() {
+            if lab.analyte == Fr::ZERO
+                || lab.bound > MAX_LAB_VALUE
+                || lab.bound_high > MAX_LAB_VALUE
+            {
+                return invalid(format!("lab predicate {:?}", lab));
+            }
+        }
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            for signal in [node.lhs, node.rhs] {
+                if signal >= node_signal(i) || !self.is_defined(signal) {
+                    return invalid(format!("node {} reads undefined signal {}", i, signal));
+                }
+            }
+            if node.op == Operation::Not && node.rhs != node.lhs {
+                return invalid(format!("NOT node {} reads two signals", i));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `signal` is a filled predicate slot or a node
+    fn is_defined(&self, signal: usize) -> bool {
+        match signal {
+            AGE_SIGNAL => self.age.is_some(),
+            s if s < lab_signal(0) => s - diagnosis_signal(0) < self.diagnoses.len(),
+            s if s < node_signal(0) => s - lab_signal(0) < self.labs.len(),
+            _ => true,
+        }
+    }
+
+    /// Fixed-length encoding hashed into `criteria_hash`
+    ///
+    /// `[version, min_age, max_age, diagnosis hashes, (analyte, scale, comparison, bound,
+    /// bound_high) per lab, (operation, lhs, rhs) per node]`. Unused slots are zero and
+    /// missing nodes repeat the result as `AND(result, result)`.
+    pub fn encode(&self) -> Result<Vec<Fr>, EligibilityError> {
+        self.validate()?;
+
+        let (min_age, max_age) = self.age.unwrap_or_default();
+        let mut encoded = vec![
+            Fr::from(CRITERIA_VERSION),
+            Fr::from(min_age),
+            Fr::from(max_age),
+        ];
+
+        encoded.extend(
+            (0..MAX_DIAGNOSIS_PREDICATES)
+                .map(|i| self.diagnoses.get(i).copied().unwrap_or(Fr::ZERO)),
+        );
+
+        for i in 0..MAX_LAB_PREDICATES {
+            match self.labs.get(i) {
+                Some(lab) => encoded.extend([
+                    lab.analyte,
+                    Fr::from(lab.scale as u64),
+                    Fr::from(lab.comparison.code()),
+                    Fr::from(lab.bound),
+                    Fr::from(lab.bound_high),
+                ]),
+                None => encoded.extend([Fr::ZERO; LAB_FIELDS]),
+            }
+        }
+
+        for i in 0..MAX_CRITERIA_NODES {
+            let node = self
+                .nodes
+                .get(i)
+                .copied()
+                .unwrap_or(CriteriaNode::and(node_signal(i - 1), node_signal(i - 1)));
+            encoded.extend([
+                Fr::from(node.op.code()),
+                Fr::from(node.lhs as u64),
+                Fr::from(node.rhs as u64),
+            ]);
+        }
+
+        Ok(encoded)
+    }
+
+    /// Inverse of [`Criteria::encode`]
+    pub fn decode(encoded: &[Fr]) -> Result<Self, EligibilityError> {
+        let invalid = || EligibilityError("Invalid criteria encoding".to_string());
+        if encoded.len() != CRITERIA_LEN || encoded[0] != Fr::from(CRITERIA_VERSION) {
+            return Err(invalid());
+        }
+
+        let int = |value: &Fr| -> Result<u64, EligibilityError> {
+            let int = field_to_u64(value)?;
+            (Fr::from(int) == *value).then_some(int).ok_or_else(invalid)
+        };
+
+        let mut fields = encoded[1..].iter();
+        let mut next = || fields.next().ok_or_else(invalid);
+
+        let (min_age, max_age) = (int(next()?)?, int(next()?)?);
+        let age = (min_age, max_age) != (0, 0);
+
+        let mut diagnoses = Vec::new();
+        for _ in 0..MAX_DIAGNOSIS_PREDICATES {
+            diagnoses.push(*next()?);
+        }
+        let diagnoses: Vec<Fr> = diagnoses
+            .into_iter()
+            .take_while(|hash| *hash != Fr::ZERO)
+            .collect();
+
+        let mut labs = Vec::new();
+        for _ in 0..MAX_LAB_PREDICATES {
+            let analyte = *next()?;
+            let scale = u32::try_from(int(next()?)?).map_err(|_| invalid())?;
+            let comparison = Comparison::from_code(int(next()?)?).ok_or_else(invalid)?;
+            let (bound, bound_high) = (int(next()?)?, int(next()?)?);
+            if analyte != Fr::ZERO {
+                labs.push(LabPredicate {
+                    analyte,
+                    scale,
+                    comparison,
+                    bound,
+                    bound_high,
+                });
+            }
+        }
+
+        let mut nodes = Vec::new();
+        for _ in 0..MAX_CRITERIA_NODES {
+            let op = Operation::from_code(int(next()?)?).ok_or_else(invalid)?;
+            let lhs = int(next()?)? as usize;
+            let rhs = int(next()?)? as usize;
+            nodes.push(CriteriaNode { op, lhs, rhs });
+        }
+
+        // Strip the padding: AND of the previous node with itself
+        while nodes.len() > 1 {
+            let last = nodes.len() - 1;
+            let previous = node_signal(last - 1);
+            if nodes[last] != CriteriaNode::and(previous, previous) {
+                break;
+            }
+            nodes.pop();
+        }
+
+        let criteria = Self {
+            age: age.then_some((min_age, max_age)),
+            diagnoses,
+            labs,
+            nodes,
+        };
+
+        // Rejects gaps in the predicate slots and other non-canonical encodings
+        if criteria.encode()? != encoded {
+            return Err(invalid());
+        }
+        Ok(criteria)
+    }
+
+    /// Poseidon hash of the encoding, published by the study
+    pub fn hash(&self) -> Result<Fr, EligibilityError> {
+        Ok(Fr::poseidon_hash(&self.encode()?))
+    }
+
+    /// Value of the expression given the predicate values
+    pub fn evaluate(&self, predicates: &[bool; PREDICATES]) -> bool {
+        let mut signals = predicates.to_vec();
+        for node in self.nodes.iter() {
+            signals.push(node.op.apply(signals[node.lhs], signals[node.rhs]));
+        }
+        signals.last().copied().unwrap_or(false)
+    }
+}
+
+/// ICD-10 hashes of a code slot: chapter, category and subcategory prefixes, then the code
+pub fn slot_hashes(code: &str) -> Result<[Fr; SLOT_HASHES], EligibilityError> {
+    let to_error = |e: diagnosis_membership_circuit::DiagnosisError| EligibilityError(e.0);
+
+    let prefixes = diagnosis_prefix_hashes(code).map_err(to_error)?;
+    let code_hash = hash_diagnosis_code(code).map_err(to_error)?;
+    Ok(std::array::from_fn(|i| {
+        if i < PREFIX_DEPTHS {
+            prefixes[i]
+        } else {
+            code_hash
+        }
+    }))
+}
+
+/// Predicate values on the host (what the circuit computes)
+///
+/// `slots` holds the [`slot_hashes`] of the patient's codes and `lab_values` one value
+/// per lab predicate.
+pub fn evaluate_predicates(
+    criteria: &Criteria,
+    age: u64,
+    slots: &[[Fr; SLOT_HASHES]],
+    lab_values: &[u64],
+) -> [bool; PREDICATES] {
+    let mut predicates = [false; PREDICATES];
+
+    if let Some((min_age, max_age)) = criteria.age {
+        predicates[AGE_SIGNAL] = (min_age..=max_age).contains(&age);
+    }
+    for (i, target) in criteria.diagnoses.iter().enumerate() {
+        predicates[diagnosis_signal(i)] = slots.iter().any(|hashes| hashes.contains(target));
+    }
+    for (i, lab) in criteria.labs.iter().enumerate() {
+        predicates[lab_signal(i)] = lab_values.get(i).map_or(false, |value| lab.holds(*value));
+    }
+
+    predicates
+}
+
+/// Eligibility Criteria Circuit Configuration
+///
+/// The small gates share the `a`-`d` columns:
+/// - "compare": `c = [a >= b]`, `d = c * (a - b) + (1 - c) * (b - a - 1)`
+/// - "and": `c = a * b`
+/// - "is zero": `c = 1 - a * b` and `a * c = 0`, so `c = [a == 0]`
+/// - "prefix leaf": `c = b * a` for a boolean `b` (leaf of an occupied slot or zero)
+/// - "match": `d = d_prev * (1 + c * (a - b - 1))`, the running diagnosis match product
+/// - "attested lab": `(1 - c) * (a - b) = 0`, the lab tree root `a` of a used lab slot
+///   (`c = 0`) is the signed root `b`
+#[derive(Debug, Clone)]
+pub struct CriteriaConfig<F: PoseidonField> {
+    pub input: Column<Advice>, // Criteria encoding, patient data and public values
+    pub a: Column<Advice>,
+    pub b: Column<Advice>,
+    pub c: Column<Advice>,
+    pub d: Column<Advice>,
+    pub lab_value: Column<Advice>,  // Lab bounds: committed value
+    pub comparison: Column<Advice>, // Lab bounds: comparison code
+    pub bound: Column<Advice>,      // Lab bounds: bound
+    pub bound_high: Column<Advice>, // Lab bounds: upper bound of an interval
+    pub comparison_bits: [Column<Advice>; COMPARISONS], // Lab bounds: one-hot comparison
+    pub low: Column<Advice>,        // Lowest accepted value
+    pub shifted: Column<Advice>,    // value + 1 for `<`, else value
+    pub high: Column<Advice>,       // Highest accepted value (+ 1 for `<`)
+    pub signal: Column<Advice>,     // Node: copy of the signal at `position`
+    pub position: Column<Fixed>,    // Node: signal index of the row
+    pub select: [Column<Advice>; 2], // Node: one-hot operand selectors
+    pub selected: [Column<Advice>; 2], // Node: running sum of selected signals
+    pub count: [Column<Advice>; 2], // Node: running sum of selectors
+    pub index: [Column<Advice>; 2], // Node: running sum of selected positions
+    pub operation: Column<Advice>,  // Node: operation code
+    pub operands: [Column<Advice>; 2], // Node: operand signal indices
+    pub operation_bits: [Column<Advice>; OPERATIONS], // Node: one-hot operation
+    pub out: Column<Advice>,        // Node: output signal
+    pub q_compare: Selector,
+    pub q_and: Selector,
+    pub q_is_zero: Selector,
+    pub q_leaf: Selector,
+    pub q_match_first: Selector,
+    pub q_match: Selector,
+    pub q_lab: Selector,
+    pub q_attested: Selector,
+    pub q_select_first: Selector,
+    pub q_select: Selector,
+    pub q_node: Selector,
+    pub instance: Column<Instance>,
+    pub value_check: RangeCheckConfig<F, CRITERIA_VALUE_BITS>,
+    pub compare_check: RangeCheckConfig<F, COMPARE_BITS>,
+    pub date: DateConfig<F>,
+    pub merkle: MerkleConfig,
+    pub credential: CredentialConfig<F>,
+    pub poseidon: PoseidonConfig,
+}
+
+/// Eligibility Criteria Circuit
+///
+/// Proves: the criteria hashed to `criteria_hash` hold on the committed patient data
+///
+/// ## Public Inputs (instance column)
+/// Row 0: criteria_hash, row 1: study_id, row 2: as_of_date, row 3: provider_root,
+/// row 4: revocation_root, row 5: context_hash, row 6: nullifier.
+#[derive(Clone)]
+pub struct CriteriaCircuit<F: EddsaField> {
+    pub criteria: [Value<F>; CRITERIA_LEN], // Private: criteria encoding
+    pub dob: Value<F>,                      // Private: date of birth (days)
+    pub dob_salt: Value<F>,                 // Private: date of birth salt
+    pub slots: [[Value<F>; SLOT_HASHES]; MAX_DIAGNOSES], // Private: ICD-10 hashes per code slot
+    pub occupied: [Value<F>; MAX_DIAGNOSES], // Private: 1 if the slot holds a code
+    pub diagnosis_root: Value<F>,           // Private: diagnosis tree root
+    pub diagnosis_salt: Value<F>,           // Private: diagnosis commitment salt
+    pub lab_values: [Value<F>; MAX_LAB_PREDICATES], // Private: lab values
+    pub lab_salts: [Value<F>; MAX_LAB_PREDICATES], // Private: lab commitment salts
+    pub lab_paths: [[Value<F>; LAB_TREE_DEPTH]; MAX_LAB_PREDICATES], // Private: lab tree siblings, bottom-up
+    pub lab_bits: [[Value<bool>; LAB_TREE_DEPTH]; MAX_LAB_PREDICATES], // Private: lab tree path bits
+    pub identity_secret: Value<F>, // Private: secret of the signed identity commitment
+    pub credential: CredentialWitness<F>, // Private: the attesting credential
+    pub criteria_hash: F,          // Public: Poseidon(criteria)
+    pub study_id: F,               // Public: binds proof to study
+    pub as_of_date: F,             // Public: date the age is computed on
+    pub provider_root: F,          // Public: root of the registered provider keys
+    pub revocation_root: F,        // Public: root of the revoked credential IDs
+    pub context_hash: F,           // Public: binds proof to wallet, chain and registry
+    pub nullifier: F,              // Public: Poseidon(identity_secret, study_id)
+}
+
+impl<F: EddsaField> Default for CriteriaCircuit<F> {
+    fn default() -> Self {
+        Self {
+            criteria: [Value::unknown(); CRITERIA_LEN],
+            dob: Value::unknown(),
+            dob_salt: Value::unknown(),
+            slots: [[Value::unknown(); SLOT_HASHES]; MAX_DIAGNOSES],
+            occupied: [Value::unknown(); MAX_DIAGNOSES],
+            diagnosis_root: Value::unknown(),
+            diagnosis_salt: Value::unknown(),
+            lab_values: [Value::unknown(); MAX_LAB_PREDICATES],
+            lab_salts: [Value::unknown(); MAX_LAB_PREDICATES],
+            lab_paths: [[Value::unknown(); LAB_TREE_DEPTH]; MAX_LAB_PREDICATES],
+            lab_bits: [[Value::unknown(); LAB_TREE_DEPTH]; MAX_LAB_PREDICATES],
+            identity_secret: Value::unknown(),
+            credential: CredentialWitness::default(),
+            criteria_hash: F::ZERO,
+            study_id: F::ZERO,
+            as_of_date: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+            context_hash: F::ZERO,
+            nullifier: F::ZERO,
+        }
+    }
+}
+
+/// One-hot bits of a small code
+fn one_hot<F: PoseidonField, const N: usize>(code: Value<F>) -> [Value<F>; N] {
+    std::array::from_fn(|i| {
+        code.map(|code| {
+            if code == F::from(i as u64) {
+                F::ONE
+            } else {
+                F::ZERO
+            }
+        })
+    })
+}
+
+/// Low 64 bits of a field element, for witness computations
+fn low_u64<F: PoseidonField>(value: &F) -> u64 {
+    field_to_u64(value).unwrap_or_default()
+}
+
+impl<F: EddsaField> CriteriaCircuit<F> {
+    /// Bit `[lhs >= rhs]`, for operands below 2^COMPARE_BITS
+    fn compare(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        lhs: &AssignedCell<F, F>,
+        rhs: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let diff = layouter.assign_region(
+            || "compare",
+            |mut region| {
+                config.q_compare.enable(&mut region, 0)?;
+                let lhs = lhs.copy_advice(|| "lhs", &mut region, config.a, 0)?;
+                let rhs = rhs.copy_advice(|| "rhs", &mut region, config.b, 0)?;
+
+                let values = lhs.value().copied().zip(rhs.value().copied());
+                let bit = values.map(|(lhs, rhs)| F::from((low_u64(&lhs) >= low_u64(&rhs)) as u64));
+                let diff = values.zip(bit).map(|((lhs, rhs), bit)| {
+                    if bit == F::ONE {
+                        lhs - rhs
+                    } else {
+                        rhs - lhs - F::ONE
+                    }
+                });
+
+                let bit = region.assign_advice(|| "bit", config.c, 0, || bit)?;
+                let diff = region.assign_advice(|| "diff", config.d, 0, || diff)?;
+                Ok((bit, diff))
+            },
+        )?;
+
+        RangeCheckChip::construct(config.compare_check.clone())
+            .assign(layouter.namespace(|| "compare diff"), &diff.1)?;
+        Ok(diff.0)
+    }
+
+    /// `lhs AND rhs` of two bits
+    fn and(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        lhs: &AssignedCell<F, F>,
+        rhs: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "and",
+            |mut region| {
+                config.q_and.enable(&mut region, 0)?;
+                let lhs = lhs.copy_advice(|| "lhs", &mut region, config.a, 0)?;
+                let rhs = rhs.copy_advice(|| "rhs", &mut region, config.b, 0)?;
+                let out = lhs.value().copied() * rhs.value().copied();
+                region.assign_advice(|| "out", config.c, 0, || out)
+            },
+        )
+    }
+
+    /// Bit `[value == 0]`
+    fn is_zero(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "is zero",
+            |mut region| {
+                config.q_is_zero.enable(&mut region, 0)?;
+                let value = value.copy_advice(|| "value", &mut region, config.a, 0)?;
+                let inverse = value.value().map(|v| v.invert().unwrap_or(F::ZERO));
+                let bit = value
+                    .value()
+                    .map(|v| if *v == F::ZERO { F::ONE } else { F::ZERO });
+
+                region.assign_advice(|| "inverse", config.b, 0, || inverse)?;
+                region.assign_advice(|| "bit", config.c, 0, || bit)
+            },
+        )
+    }
+
+    /// Bit of a diagnosis predicate: some occupied slot has `target` among its hashes
+    fn assign_match(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        target: &AssignedCell<F, F>,
+        slots: &[[AssignedCell<F, F>; SLOT_HASHES]],
+        occupied: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let product = layouter.assign_region(
+            || "diagnosis match",
+            |mut region| {
+                let mut product = Value::known(F::ONE);
+                let mut cell = None;
+                let hashes = slots
+                    .iter()
+                    .zip(occupied.iter())
+                    .flat_map(|(hashes, occupied)| hashes.iter().map(move |hash| (hash, occupied)));
+
+                for (row, (hash, occupied)) in hashes.enumerate() {
+                    if row == 0 {
+                        config.q_match_first.enable(&mut region, row)?;
+                    } else {
+                        config.q_match.enable(&mut region, row)?;
+                    }
+
+                    let target = target.copy_advice(|| "target", &mut region, config.a, row)?;
+                    let hash = hash.copy_advice(|| "hash", &mut region, config.b, row)?;
+                    let occupied =
+                        occupied.copy_advice(|| "occupied", &mut region, config.c, row)?;
+
+                    // 1 + occupied * (target - hash - 1): target - hash for occupied slots, else 1
+                    let factor = target
+                        .value()
+                        .zip(hash.value())
+                        .zip(occupied.value())
+                        .map(|((t, h), o)| F::ONE + *o * (*t - *h - F::ONE));
+                    product = product * factor;
+
+                    cell = Some(region.assign_advice(|| "product", config.d, row, || product)?);
+                }
+
+                Ok(cell.expect("at least one code slot"))
+            },
+        )?;
+
+        self.is_zero(config, layouter, &product)
+    }
+
+    /// Bit of a lab value predicate
+    fn assign_lab(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        fields: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (comparison, bound, bound_high) = (&fields[2], &fields[3], &fields[4]);
+
+        let value_check = RangeCheckChip::construct(config.value_check.clone());
+        for cell in [value, bound, bound_high] {
+            value_check.assign(layouter.namespace(|| "lab value bits"), cell)?;
+        }
+
+        let (low, shifted, high) = layouter.assign_region(
+            || "lab bounds",
+            |mut region| {
+                config.q_lab.enable(&mut region, 0)?;
+                let value = value.copy_advice(|| "value", &mut region, config.lab_value, 0)?;
+                let comparison =
+                    comparison.copy_advice(|| "comparison", &mut region, config.comparison, 0)?;
+                let bound = bound.copy_advice(|| "bound", &mut region, config.bound, 0)?;
+                let bound_high =
+                    bound_high.copy_advice(|| "bound_high", &mut region, config.bound_high, 0)?;
+
+                let bits: [Value<F>; COMPARISONS] = one_hot(comparison.value().copied());
+                for (i, bit) in bits.iter().enumerate() {
+                    region.assign_advice(
+                        || format!("comparison bit {}", i),
+                        config.comparison_bits[i],
+                        0,
+                        || *bit,
+                    )?;
+                }
+
+                let [lt, le, gt, ge, between] = bits;
+                let (value, bound, bound_high) = (
+                    value.value().copied(),
+                    bound.value().copied(),
+                    bound_high.value().copied(),
+                );
+                let max = Value::known(F::from(MAX_LAB_VALUE));
+                let one = Value::known(F::ONE);
+
+                let low = gt * (bound + one) + (ge + between) * bound;
+                let shifted = value + lt;
+                let high = (lt + le) * bound + between * bound_high + (gt + ge) * max;
+
+                Ok((
+                    region.assign_advice(|| "low", config.low, 0, || low)?,
+                    region.assign_advice(|| "shifted", config.shifted, 0, || shifted)?,
+                    region.assign_advice(|| "high", config.high, 0, || high)?,
+                ))
+            },
+        )?;
+
+        let lower = self.compare(config, layouter, value, &low)?;
+        let upper = self.compare(config, layouter, &high, &shifted)?;
+        self.and(config, layouter, &lower, &upper)
+    }
+
+    /// Output of node `i`, reading its operands from `signals`
+    fn assign_node(
+        &self,
+        config: &CriteriaConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        i: usize,
+        signals: &[AssignedCell<F, F>],
+        fields: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let len = node_signal(i);
+        let operands = [&fields[1], &fields[2]];
+
+        layouter.assign_region(
+            || format!("node {}", i),
+            |mut region| {
+                let mut selected = [Value::known(F::ZERO); 2];
+                let mut count = [Value::known(F::ZERO); 2];
+                let mut index = [Value::known(F::ZERO); 2];
+
+                for (row, signal) in signals[..len].iter().enumerate() {
+                    if row == 0 {
+                        config.q_select_first.enable(&mut region, row)?;
+                    } else {
+                        config.q_select.enable(&mut region, row)?;
+                    }
+
+                    let position = F::from(row as u64);
+                    region.assign_fixed(
+                        || "position",
+                        config.position,
+                        row,
+                        || Value::known(position),
+                    )?;
+                    let signal =
+                        signal.copy_advice(|| "signal", &mut region, config.signal, row)?;
+
+                    for k in 0..2 {
+                        let select = operands[k].value().map(|operand| {
+                            if *operand == position {
+                                F::ONE
+                            } else {
+                                F::ZERO
+                            }
+                        });
+                        selected[k] = selected[k] + select * signal.value().copied();
+                        count[k] = count[k] + select;
+                        index[k] = index[k] + select * Value::known(position);
+
+                        region.assign_advice(|| "select", config.select[k], row, || select)?;
+                        region.assign_advice(
+                            || "selected",
+                            config.selected[k],
+                            row,
+                            || selected[k],
+                        )?;
+                        region.assign_advice(|| "count", config.count[k], row, || count[k])?;
+                        region.assign_advice(|| "index", config.index[k], row, || index[k])?;
+                    }
+                }
+
+                // The last row combines the selected operands
+                let row = len - 1;
+                config.q_node.enable(&mut region, row)?;
+                let operation =
+                    fields[0].copy_advice(|| "operation", &mut region, config.operation, row)?;
+                for k in 0..2 {
+                    operands[k].copy_advice(|| "operand", &mut region, config.operands[k], row)?;
+                }
+
+                let bits: [Value<F>; OPERATIONS] = one_hot(operation.value().copied());
+                for (j, bit) in bits.iter().enumerate() {
+                    region.assign_advice(
+                        || format!("operation bit {}", j),
+                        config.operation_bits[j],
+                        row,
+                        || *bit,
+                    )?;
+                }
+
+                let [and, or, not] = bits;
+                let (lhs, rhs) = (selected[0], selected[1]);
+                let one = Value::known(F::ONE);
+                let out = and * lhs * rhs + or * (lhs + rhs - lhs * rhs) + not * (one - lhs);
+                region.assign_advice(|| "out", config.out, row, || out)
+            },
+        )
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for CriteriaCircuit<F> {
+    type Config = CriteriaConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let input = meta.advice_column();
+        let [a, b, c, d] = [(); 4].map(|_| meta.advice_column());
+        let lab_value = meta.advice_column();
+        let comparison = meta.advice_column();
+        let bound = meta.advice_column();
+        let bound_high = meta.advice_column();
+        let comparison_bits: [Column<Advice>; COMPARISONS] =
+            std::array::from_fn(|_| meta.advice_column());
+        let low = meta.advice_column();
+        let shifted = meta.advice_column();
+        let high = meta.advice_column();
+        let signal = meta.advice_column();
+        let position = meta.fixed_column();
+        let select = [(); 2].map(|_| meta.advice_column());
+        let selected = [(); 2].map(|_| meta.advice_column());
+        let count = [(); 2].map(|_| meta.advice_column());
+        let index = [(); 2].map(|_| meta.advice_column());
+        let operation = meta.advice_column();
+        let operands = [(); 2].map(|_| meta.advice_column());
+        let operation_bits: [Column<Advice>; OPERATIONS] =
+            std::array::from_fn(|_| meta.advice_column());
+        let out = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let instance = meta.instance_column();
+
+        for column in [
+            input, a, b, c, d, lab_value, comparison, bound, bound_high, low, shifted, high,
+            signal, operation, out,
+        ]
+        .iter()
+        .chain(operands.iter())
+        {
+            meta.enable_equality(*column);
+        }
+        meta.enable_equality(instance);
+
+        let q_compare = meta.selector();
+        let q_and = meta.selector();
+        let q_is_zero = meta.selector();
+        let q_leaf = meta.selector();
+        let q_match_first = meta.selector();
+        let q_match = meta.selector();
+        let q_lab = meta.selector();
+        let q_attested = meta.selector();
+        let q_select_first = meta.selector();
+        let q_select = meta.selector();
+        let q_node = meta.selector();
+
+        let one = || Expression::Constant(F::ONE);
+        let boolean = |bit: Expression<F>| bit.clone() * (one() - bit);
+
+        // Gate: c = [a >= b] (see the module documentation)
+        meta.create_gate("compare", |meta| {
+            let q = meta.query_selector(q_compare);
+            let lhs = meta.query_advice(a, Rotation::cur());
+            let rhs = meta.query_advice(b, Rotation::cur());
+            let bit = meta.query_advice(c, Rotation::cur());
+            let diff = meta.query_advice(d, Rotation::cur());
+
+            let expected = bit.clone() * (lhs.clone() - rhs.clone())
+                + (one() - bit.clone()) * (rhs - lhs - one());
+            vec![q.clone() * boolean(bit), q * (diff - expected)]
+        });
+
+        // Gate: c = a * b
+        meta.create_gate("and", |meta| {
+            let q = meta.query_selector(q_and);
+            let lhs = meta.query_advice(a, Rotation::cur());
+            let rhs = meta.query_advice(b, Rotation::cur());
+            let out = meta.query_advice(c, Rotation::cur());
+            vec![q * (out - lhs * rhs)]
+        });
+
+        // Gate: c = [a == 0], with b the inverse of a when it exists
+        meta.create_gate("is zero", |meta| {
+            let q = meta.query_selector(q_is_zero);
+            let value = meta.query_advice(a, Rotation::cur());
+            let inverse = meta.query_advice(b, Rotation::cur());
+            let bit = meta.query_advice(c, Rotation::cur());
+            vec![
+                q.clone() * (bit.clone() - (one() - value.clone() * inverse)),
+                q * value * bit,
+            ]
+        });
+
+        // Gate: c = b * a for a boolean b
+        meta.create_gate("prefix leaf", |meta| {
+            let q = meta.query_selector(q_leaf);
+            let hash = meta.query_advice(a, Rotation::cur());
+            let occupied = meta.query_advice(b, Rotation::cur());
+            let leaf = meta.query_advice(c, Rotation::cur());
+            vec![
+                q.clone() * boolean(occupied.clone()),
+                q * (leaf - occupied * hash),
+            ]
+        });
+
+        // Gates: d = d_prev * (1 + c * (a - b - 1)), with d_prev = 1 on the first row
+        let factor = |meta: &mut halo2_proofs::plonk::VirtualCells<F>| {
+            let target = meta.query_advice(a, Rotation::cur());
+            let hash = meta.query_advice(b, Rotation::cur());
+            let occupied = meta.query_advice(c, Rotation::cur());
+            one() + occupied * (target - hash - one())
+        };
+        meta.create_gate("match first", |meta| {
+            let q = meta.query_selector(q_match_first);
+            let product = meta.query_advice(d, Rotation::cur());
+            vec![q * (product - factor(meta))]
+        });
+        meta.create_gate("match", |meta| {
+            let q = meta.query_selector(q_match);
+            let product = meta.query_advice(d, Rotation::cur());
+            let previous = meta.query_advice(d, Rotation::prev());
+            vec![q * (product - previous * factor(meta))]
+        });
+
+        // Gate: (1 - c) * (a - b) = 0, the lab tree root of a used slot is the signed one
+        meta.create_gate("attested lab", |meta| {
+            let q = meta.query_selector(q_attested);
+            let root = meta.query_advice(a, Rotation::cur());
+            let lab_root = meta.query_advice(b, Rotation::cur());
+            let unused = meta.query_advice(c, Rotation::cur());
+            vec![q * (one() - unused) * (root - lab_root)]
+        });
+
+        // Gate: accepted interval of a lab comparison, as in `LabValueCircuit`, except
+        // that `<` compares value + 1 against the bound so nothing goes negative
+        meta.create_gate("lab bounds", |meta| {
+            let q = meta.query_selector(q_lab);
+            let value = meta.query_advice(lab_value, Rotation::cur());
+            let comparison = meta.query_advice(comparison, Rotation::cur());
+            let bound = meta.query_advice(bound, Rotation::cur());
+            let bound_high = meta.query_advice(bound_high, Rotation::cur());
+            let low = meta.query_advice(low, Rotation::cur());
+            let shifted = meta.query_advice(shifted, Rotation::cur());
+            let high = meta.query_advice(high, Rotation::cur());
+            let bits: Vec<Expression<F>> = comparison_bits
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+
+            let mut constraints: Vec<Expression<F>> =
+                bits.iter().map(|bit| boolean(bit.clone())).collect();
+            let sum = bits
+                .iter()
+                .fold(Expression::Constant(F::ZERO), |acc, bit| acc + bit.clone());
+            let code = bits
+                .iter()
+                .enumerate()
+                .fold(Expression::Constant(F::ZERO), |acc, (i, bit)| {
+                    acc + bit.clone() * F::from(i as u64)
+                });
+            constraints.push(sum - one());
+            constraints.push(code - comparison);
+
+            let [lt, le, gt, ge, between]: [Expression<F>; COMPARISONS] = bits.try_into().unwrap();
+            constraints.push(
+                low - (gt.clone() * (bound.clone() + one())
+                    + (ge.clone() + between.clone()) * bound.clone()),
+            );
+            constraints.push(shifted - (value + lt.clone()));
+            constraints.push(
+                high - ((lt + le) * bound
+                    + between * bound_high
+                    + (gt + ge) * Expression::Constant(F::from(MAX_LAB_VALUE))),
+            );
+
+            constraints
+                .into_iter()
+                .map(|c| q.clone() * c)
+                .collect::<Vec<_>>()
+        });
+
+        // Gates: running sums of the one-hot operand selectors over the signals
+        let select_constraints = |meta: &mut halo2_proofs::plonk::VirtualCells<F>, first: bool| {
+            let signal = meta.query_advice(signal, Rotation::cur());
+            let position = meta.query_fixed(position, Rotation::cur());
+            let mut constraints = Vec::new();
+            for k in 0..2 {
+                let select = meta.query_advice(select[k], Rotation::cur());
+                let previous = |meta: &mut halo2_proofs::plonk::VirtualCells<F>, column| {
+                    if first {
+                        Expression::Constant(F::ZERO)
+                    } else {
+                        meta.query_advice(column, Rotation::prev())
+                    }
+                };
+
+                let selected_prev = previous(meta, selected[k]);
+                let count_prev = previous(meta, count[k]);
+                let index_prev = previous(meta, index[k]);
+                let selected = meta.query_advice(selected[k], Rotation::cur());
+                let count = meta.query_advice(count[k], Rotation::cur());
+                let index = meta.query_advice(index[k], Rotation::cur());
+
+                constraints.push(boolean(select.clone()));
+                constraints.push(selected - (selected_prev + select.clone() * signal.clone()));
+                constraints.push(count - (count_prev + select.clone()));
+                constraints.push(index - (index_prev + select * position.clone()));
+            }
+            constraints
+        };
+        meta.create_gate("select first", |meta| {
+            let q = meta.query_selector(q_select_first);
+            select_constraints(meta, true)
+                .into_iter()
+                .map(|c| q.clone() * c)
+                .collect::<Vec<_>>()
+        });
+        meta.create_gate("select", |meta| {
+            let q = meta.query_selector(q_select);
+            select_constraints(meta, false)
+                .into_iter()
+                .map(|c| q.clone() * c)
+                .collect::<Vec<_>>()
+        });
+
+        // Gate: exactly one signal selected per operand, at the encoded index, and
+        // out = AND / OR / NOT of the selected signals
+        meta.create_gate("node", |meta| {
+            let q = meta.query_selector(q_node);
+            let operation = meta.query_advice(operation, Rotation::cur());
+            let out = meta.query_advice(out, Rotation::cur());
+            let bits: Vec<Expression<F>> = operation_bits
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+
+            let mut constraints = Vec::new();
+            let mut values = Vec::new();
+            for k in 0..2 {
+                let count = meta.query_advice(count[k], Rotation::cur());
+                let index = meta.query_advice(index[k], Rotation::cur());
+                let operand = meta.query_advice(operands[k], Rotation::cur());
+                constraints.push(count - one());
+                constraints.push(index - operand);
+                values.push(meta.query_advice(selected[k], Rotation::cur()));
+            }
+
+            constraints.extend(bits.iter().map(|bit| boolean(bit.clone())));
+            let sum = bits
+                .iter()
+                .fold(Expression::Constant(F::ZERO), |acc, bit| acc + bit.clone());
+            let code = bits
+                .iter()
+                .enumerate()
+                .fold(Expression::Constant(F::ZERO), |acc, (i, bit)| {
+                    acc + bit.clone() * F::from(i as u64)
+                });
+            constraints.push(sum - one());
+            constraints.push(code - operation);
+
+            let [and, or, not]: [Expression<F>; OPERATIONS] = bits.try_into().unwrap();
+            let (lhs, rhs) = (values[0].clone(), values[1].clone());
+            let expected = and * lhs.clone() * rhs.clone()
+                + or * (lhs.clone() + rhs.clone() - lhs.clone() * rhs)
+                + not * (one() - lhs);
+            constraints.push(out - expected);
+
+            constraints
+                .into_iter()
+                .map(|c| q.clone() * c)
+                .collect::<Vec<_>>()
+        });
+
+        let value_check = RangeCheckChip::configure(meta, running_sum);
+        let compare_check = RangeCheckChip::configure(meta, running_sum);
+        let date = DateChip::configure(meta, running_sum);
+        let poseidon = PoseidonChip::configure(meta);
+        let merkle = MerkleChip::configure(meta, poseidon.clone());
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let issuer = IssuerChip::configure(meta, eddsa, merkle.clone());
+        let credential = CredentialChip::configure(meta, issuer);
+
+        CriteriaConfig {
+            input,
+            a,
+            b,
+            c,
+            d,
+            lab_value,
+            comparison,
+            bound,
+            bound_high,
+            comparison_bits,
+            low,
+            shifted,
+            high,
+            signal,
+            position,
+            select,
+            selected,
+            count,
+            index,
+            operation,
+            operands,
+            operation_bits,
+            out,
+            q_compare,
+            q_and,
+            q_is_zero,
+            q_leaf,
+            q_match_first,
+            q_match,
+            q_lab,
+            q_attested,
+            q_select_first,
+            q_select,
+            q_node,
+            instance,
+            value_check,
+            compare_check,
+            date,
+            merkle,
+            credential,
+            poseidon,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let layouter = &mut layouter;
+
+        // Assign the criteria and the patient data
+        let (
+            criteria,
+            dob,
+            dob_salt,
+            as_of,
+            study_id,
+            context,
+            secret,
+            slots,
+            occupied,
+            root,
+            salt,
+            labs,
+            lab_root,
+            padding,
+        ) = layouter.assign_region(
+            || "inputs",
+            |mut region| {
+                let mut row = 0;
+                let mut assign = |name: &str, value: Value<F>| {
+                    row += 1;
+                    region.assign_advice(|| name, config.input, row - 1, || value)
+                };
+
+                let criteria = self
+                    .criteria
+                    .iter()
+                    .map(|value| assign("criteria", *value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let dob = assign("dob", self.dob)?;
+                let dob_salt = assign("dob_salt", self.dob_salt)?;
+                let as_of = assign("as_of_date", Value::known(self.as_of_date))?;
+                let study_id = assign("study_id", Value::known(self.study_id))?;
+                let context = assign("context_hash", Value::known(self.context_hash))?;
+                let secret = assign("identity_secret", self.identity_secret)?;
+
+                let mut slots = Vec::with_capacity(MAX_DIAGNOSES);
+                for hashes in self.slots.iter() {
+                    let cells = hashes
+                        .iter()
+                        .map(|hash| assign("slot hash", *hash))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    slots.push(<[_; SLOT_HASHES]>::try_from(cells).unwrap());
+                }
+                let occupied = self
+                    .occupied
+                    .iter()
+                    .map(|value| assign("occupied", *value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let root = assign("diagnosis_root", self.diagnosis_root)?;
+                let salt = assign("diagnosis_salt", self.diagnosis_salt)?;
+
+                let mut labs = Vec::with_capacity(MAX_LAB_PREDICATES);
+                for (value, salt) in self.lab_values.iter().zip(self.lab_salts.iter()) {
+                    labs.push((assign("lab value", *value)?, assign("lab salt", *salt)?));
+                }
+
+                let lab_root = assign("lab_root", self.credential.attributes.lab_root)?;
+
+                // Prefix tree leaves past the code slots are zero
+                let mut padding = Vec::new();
+                for _ in MAX_DIAGNOSES..1 << DIAGNOSIS_TREE_DEPTH {
+                    row += 1;
+                    padding.push(region.assign_advice_from_constant(
+                        || "padding",
+                        config.input,
+                        row - 1,
+                        F::ZERO,
+                    )?);
+                }
+
+                region.constrain_constant(criteria[0].cell(), F::from(CRITERIA_VERSION))?;
+
+                Ok((
+                    criteria, dob, dob_salt, as_of, study_id, context, secret, slots, occupied,
+                    root, salt, labs, lab_root, padding,
+                ))
+            },
+        )?;
+
+        let poseidon_chip = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let lab_fields =
+            |i: usize| &criteria[3 + MAX_DIAGNOSIS_PREDICATES + LAB_FIELDS * i..][..LAB_FIELDS];
+        let node_fields = |i: usize| {
+            &criteria
+                [3 + MAX_DIAGNOSIS_PREDICATES + LAB_FIELDS * MAX_LAB_PREDICATES + NODE_FIELDS * i..]
+                [..NODE_FIELDS]
+        };
+
+        // Criteria hash and the commitments the credential opens
+        let criteria_hash =
+            poseidon_chip.hash(layouter.namespace(|| "criteria hash"), &criteria)?;
+        let dob_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "dob commitment"),
+            &[dob.clone(), dob_salt],
+        )?;
+
+        let mut leaves = Vec::with_capacity(1 << DIAGNOSIS_TREE_DEPTH);
+        for (s, (hashes, occupied)) in slots.iter().zip(occupied.iter()).enumerate() {
+            let hash =
+                poseidon_chip.hash(layouter.namespace(|| format!("prefix leaf {}", s)), hashes)?;
+            leaves.push(layouter.assign_region(
+                || "prefix leaf",
+                |mut region| {
+                    config.q_leaf.enable(&mut region, 0)?;
+                    let hash = hash.copy_advice(|| "hash", &mut region, config.a, 0)?;
+                    let occupied = occupied.copy_advice(|| "occupied", &mut region, config.b, 0)?;
+                    let leaf = hash.value().copied() * occupied.value().copied();
+                    region.assign_advice(|| "leaf", config.c, 0, || leaf)
+                },
+            )?);
+        }
+        leaves.extend(padding);
+
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let prefix_root =
+            merkle_chip.compute_root_from_leaves(layouter.namespace(|| "prefix root"), &leaves)?;
+        let diagnosis_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "diagnosis commitment"),
+            &[root, prefix_root, salt],
+        )?;
+
+        let mut lab_commitments = Vec::with_capacity(MAX_LAB_PREDICATES);
+        for (i, (value, salt)) in labs.iter().enumerate() {
+            let fields = lab_fields(i);
+            let (analyte, scale) = (fields[0].clone(), fields[1].clone());
+            lab_commitments.push(poseidon_chip.hash(
+                layouter.namespace(|| format!("lab commitment {}", i)),
+                &[analyte, value.clone(), scale, salt.clone()],
+            )?);
+        }
+
+        // Every used lab slot holds a commitment of the signed lab tree
+        for (i, commitment) in lab_commitments.iter().enumerate() {
+            let tree_root = merkle_chip.compute_root(
+                layouter.namespace(|| format!("lab root {}", i)),
+                commitment,
+                &self.lab_paths[i],
+                &self.lab_bits[i],
+            )?;
+            let unused = self.is_zero(&config, layouter, &lab_fields(i)[0])?;
+            layouter.assign_region(
+                || "attested lab",
+                |mut region| {
+                    config.q_attested.enable(&mut region, 0)?;
+                    tree_root.copy_advice(|| "root", &mut region, config.a, 0)?;
+                    lab_root.copy_advice(|| "lab_root", &mut region, config.b, 0)?;
+                    unused.copy_advice(|| "unused", &mut region, config.c, 0)?;
+                    Ok(())
+                },
+            )?;
+        }
+
+        // A registered provider signed the commitments the predicates are evaluated on,
+        // and the identity the nullifier is derived from
+        let credential_chip = CredentialChip::<F>::construct(config.credential.clone());
+        let identity_commitment = credential_chip
+            .identity_commitment(layouter.namespace(|| "identity commitment"), &secret)?;
+        let opened = CredentialAttributes {
+            identity_commitment: Some(&identity_commitment),
+            dob_commitment: Some(&dob_commitment),
+            diagnosis_commitment: Some(&diagnosis_commitment),
+            lab_root: Some(&lab_root),
+            issued_at: None,
+        };
+        let (provider_root, revocation_root) = credential_chip.verify_at(
+            layouter.namespace(|| "credential"),
+            opened,
+            &self.credential,
+            &as_of,
+        )?;
+        let nullifier = poseidon_chip.hash(
+            layouter.namespace(|| "nullifier"),
+            &[secret, study_id.clone()],
+        )?;
+
+        let public = [
+            &criteria_hash,
+            &study_id,
+            &as_of,
+            &provider_root,
+            &revocation_root,
+            &context,
+            &nullifier,
+        ];
+        for (row, cell) in public.into_iter().enumerate() {
+            layouter.constrain_instance(cell.cell(), config.instance, row)?;
+        }
+
+        // Predicate signals
+        let mut signals = Vec::with_capacity(PREDICATES + MAX_CRITERIA_NODES);
+
+        let date_chip = DateChip::construct(config.date.clone());
+        let age = date_chip.age(layouter.namespace(|| "age"), &dob, &as_of)?;
+        let (min_age, max_age) = (&criteria[1], &criteria[2]);
+        let value_check = RangeCheckChip::construct(config.value_check.clone());
+        for cell in [min_age, max_age] {
+            value_check.assign(layouter.namespace(|| "age bound bits"), cell)?;
+        }
+        let above_min = self.compare(&config, layouter, &age, min_age)?;
+        let below_max = self.compare(&config, layouter, max_age, &age)?;
+        signals.push(self.and(&config, layouter, &above_min, &below_max)?);
+
+        for i in 0..MAX_DIAGNOSIS_PREDICATES {
+            let target = &criteria[3 + i];
+            signals.push(self.assign_match(&config, layouter, target, &slots, &occupied)?);
+        }
+
+        for (i, (value, _)) in labs.iter().enumerate() {
+            signals.push(self.assign_lab(&config, layouter, value, lab_fields(i))?);
+        }
+
+        // Nodes, then the result
+        for i in 0..MAX_CRITERIA_NODES {
+            let out = self.assign_node(&config, layouter, i, &signals, node_fields(i))?;
+            signals.push(out);
+        }
+
+        let result = signals.last().unwrap();
+        layouter.assign_region(
+            || "eligible",
+            |mut region| region.constrain_constant(result.cell(), F::ONE),
+        )
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for CriteriaCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: criteria_hash, study_id, as_of_date, provider_root,
+        // revocation_root, context_hash, nullifier
+        vec![vec![
+            self.criteria_hash,
+            self.study_id,
+            self.as_of_date,
+            self.provider_root,
+            self.revocation_root,
+            self.context_hash,
+            self.nullifier,
+        ]]
+    }
+}
+
+/// Number of public inputs of [`CriteriaCircuit`]
+pub const CRITERIA_PUBLIC_INPUTS: usize = 7;
+
+/// Input map for [`generate_criteria_proof`]
+///
+/// `labs` holds the value and commitment salt of the patient's reading for each lab
+/// predicate, in order, and `lab_tree` the readings the credential attests; every lab
+/// predicate needs an attested reading. `credential` attests the identity commitment of
+/// `identity_secret` and the dob, diagnosis and lab commitments (see
+/// [`credential_inputs`](crate::credential::credential_inputs)). `context` carries the
+/// study ID and the applicant the proof is bound to.
+#[allow(clippy::too_many_arguments)]
+pub fn criteria_inputs(
+    criteria: &Criteria,
+    dob: i64,
+    dob_salt: Fr,
+    as_of_date: i64,
+    tree: &DiagnosisTree,
+    diagnosis_salt: Fr,
+    labs: &[(u64, Fr)],
+    lab_tree: &LabTree,
+    identity_secret: Fr,
+    credential: &CredentialInputs,
+    context: &ProofContext,
+) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+    if labs.len() > MAX_LAB_PREDICATES {
+        return Err(EligibilityError(format!(
+            "Too many lab values: {} (maximum {})",
+            labs.len(),
+            MAX_LAB_PREDICATES
+        )));
+    }
+    if labs.len() < criteria.labs.len() {
+        return Err(EligibilityError(format!(
+            "No reading for lab predicate {}",
+            labs.len()
+        )));
+    }
+
+    let mut slots = vec![Fr::ZERO; MAX_DIAGNOSES * SLOT_HASHES];
+    for (s, code) in tree.codes().iter().enumerate() {
+        slots[s * SLOT_HASHES..][..SLOT_HASHES].copy_from_slice(&slot_hashes(code)?);
+    }
+
+    let mut lab_values = vec![Fr::ZERO; MAX_LAB_PREDICATES];
+    let mut lab_salts = vec![Fr::ZERO; MAX_LAB_PREDICATES];
+    for (i, (value, salt)) in labs.iter().enumerate() {
+        lab_values[i] = Fr::from(*value);
+        lab_salts[i] = *salt;
+    }
+
+    let mut inputs = HashMap::new();
+    inputs.insert("criteria".to_string(), criteria.encode()?);
+    inputs.insert("criteria_hash".to_string(), vec![criteria.hash()?]);
+    context.insert_inputs(&mut inputs);
+    inputs.insert("identity_secret".to_string(), vec![identity_secret]);
+    inputs.insert("dob".to_string(), vec![days_to_field(dob)]);
+    inputs.insert("dob_salt".to_string(), vec![dob_salt]);
+    inputs.insert("as_of_date".to_string(), vec![days_to_field(as_of_date)]);
+    inputs.insert("diagnosis_slots".to_string(), slots);
+    inputs.insert("diagnosis_root".to_string(), vec![tree.root()]);
+    inputs.insert("diagnosis_salt".to_string(), vec![diagnosis_salt]);
+    inputs.insert("lab_values".to_string(), lab_values);
+    inputs.insert("lab_salts".to_string(), lab_salts);
+
+    let lab_commitments = commit_lab_slots(criteria, &inputs["lab_values"], &inputs["lab_salts"])?;
+    let mut lab_paths = vec![Fr::ZERO; MAX_LAB_PREDICATES * LAB_TREE_DEPTH];
+    let mut lab_indices = vec![Fr::ZERO; MAX_LAB_PREDICATES];
+    for i in 0..criteria.labs.len() {
+        let path = lab_tree.path(&lab_commitments[i])?;
+        lab_paths[i * LAB_TREE_DEPTH..][..LAB_TREE_DEPTH].copy_from_slice(&path.siblings);
+        lab_indices[i] = Fr::from(path.index as u64);
+    }
+    inputs.insert("lab_paths".to_string(), lab_paths);
+    inputs.insert("lab_indices".to_string(), lab_indices);
+    credential.insert_inputs(&mut inputs);
+
+    Ok(inputs)
+}
+
+/// Commitments of the lab slots, with the analyte and scale of each lab predicate
+fn commit_lab_slots(
+    criteria: &Criteria,
+    values: &[Fr],
+    salts: &[Fr],
+) -> Result<Vec<Fr>, EligibilityError> {
+    (0..MAX_LAB_PREDICATES)
+        .map(|i| {
+            let (analyte, scale) = criteria
+                .labs
+                .get(i)
+                .map_or((Fr::ZERO, 0), |lab| (lab.analyte, lab.scale));
+            Ok(commit_lab_value(
+                analyte,
+                field_to_u64(&values[i])?,
+                scale,
+                salts[i],
+            ))
+        })
+        .collect()
+}
+
+/// Build the circuit from an input map, checking it against the attested commitments
+fn criteria_circuit(
+    inputs: &HashMap<String, Vec<Fr>>,
+) -> Result<CriteriaCircuit<Fr>, EligibilityError> {
+    let get = |name: &str, len: usize| -> Result<&[Fr], EligibilityError> {
+        let values = inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?;
+        if values.len() != len {
+            return Err(EligibilityError(format!(
+                "Invalid {} (expected {} values)",
+                name, len
+            )));
+        }
+        Ok(values)
+    };
+
+    let encoded = get("criteria", CRITERIA_LEN)?;
+    let criteria_hash = get("criteria_hash", 1)?[0];
+    let study_id = get("study_id", 1)?[0];
+    let context_hash = get("context_hash", 1)?[0];
+    let identity_secret = get("identity_secret", 1)?[0];
+    let dob = get("dob", 1)?[0];
+    let dob_salt = get("dob_salt", 1)?[0];
+    let as_of_date = get("as_of_date", 1)?[0];
+    let slots = get("diagnosis_slots", MAX_DIAGNOSES * SLOT_HASHES)?;
+    let diagnosis_root = get("diagnosis_root", 1)?[0];
+    let diagnosis_salt = get("diagnosis_salt", 1)?[0];
+    let lab_values = get("lab_values", MAX_LAB_PREDICATES)?;
+    let lab_salts = get("lab_salts", MAX_LAB_PREDICATES)?;
+    let lab_paths = get("lab_paths", MAX_LAB_PREDICATES * LAB_TREE_DEPTH)?;
+    let lab_indices = get("lab_indices", MAX_LAB_PREDICATES)?;
+    let lab_paths = lab_paths
+        .chunks(LAB_TREE_DEPTH)
+        .zip(lab_indices.iter())
+        .map(|(siblings, index)| lab_path(siblings, index))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Client-side validation
+    // Fails fast instead of producing an unsatisfiable circuit
+    let credential = checked_credential_at(inputs, &as_of_date)?;
+    let attributes = credential.attributes();
+    if identity_commitment(identity_secret) != attributes.identity_commitment {
+        return Err(EligibilityError(
+            "Identity secret does not match identity_commitment".to_string(),
+        ));
+    }
+
+    let criteria = Criteria::decode(encoded)?;
+    if criteria.hash()? != criteria_hash {
+        return Err(EligibilityError(
+            "Criteria do not match criteria_hash".to_string(),
+        ));
+    }
+
+    let days = |value: &Fr, name: &str| {
+        days_from_field(value).ok_or(EligibilityError(format!(
+            "Invalid {}: not a day count",
+            name
+        )))
+    };
+    let (dob_days, as_of_days) = (days(&dob, "dob")?, days(&as_of_date, "as_of_date")?);
+    if dob_days > as_of_days {
+        return Err(EligibilityError(
+            "Date of birth is after the as-of date".to_string(),
+        ));
+    }
+    if commit_date_of_birth(dob_days, dob_salt) != attributes.dob_commitment {
+        return Err(EligibilityError(
+            "Date of birth does not match dob_commitment".to_string(),
+        ));
+    }
+
+    let slots: Vec<[Fr; SLOT_HASHES]> = slots
+        .chunks(SLOT_HASHES)
+        .map(|chunk| <[Fr; SLOT_HASHES]>::try_from(chunk).unwrap())
+        .collect();
+    let occupied: Vec<bool> = slots
+        .iter()
+        .map(|hashes| hashes.iter().any(|h| *h != Fr::ZERO))
+        .collect();
+    let leaves: Vec<Fr> = slots
+        .iter()
+        .zip(occupied.iter())
+        .map(|(hashes, occupied)| {
+            if *occupied {
+                Fr::poseidon_hash(hashes)
+            } else {
+                Fr::ZERO
+            }
+        })
+        .collect();
+    let prefix_tree = MerkleTree::new(&leaves, DIAGNOSIS_TREE_DEPTH)
+        .map_err(|e| EligibilityError(e.to_string()))?;
+    if Fr::poseidon_hash(&[diagnosis_root, prefix_tree.root(), diagnosis_salt])
+        != attributes.diagnosis_commitment
+    {
+        return Err(EligibilityError(
+            "Diagnoses do not match diagnosis_commitment".to_string(),
+        ));
+    }
+
+    let lab_ints = lab_values
+        .iter()
+        .map(|value| {
+            let int = field_to_u64(value)?;
+            if int > MAX_LAB_VALUE || Fr::from(int) != *value {
+                return Err(EligibilityError(format!(
+                    "Lab value exceeds {} bits",
+                    LAB_VALUE_BITS
+                )));
+            }
+            Ok(int)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let lab_commitments = commit_lab_slots(&criteria, lab_values, lab_salts)?;
+    for i in 0..criteria.labs.len() {
+        if lab_paths[i].compute_root(lab_commitments[i]) != attributes.lab_root {
+            return Err(EligibilityError(format!(
+                "Lab value {} is not attested by the credential",
+                i
+            )));
+        }
+    }
+
+    let age = age_on(dob_days, as_of_days) as u64;
+    let occupied_slots: Vec<[Fr; SLOT_HASHES]> = slots
+        .iter()
+        .zip(occupied.iter())
+        .filter(|(_, occupied)| **occupied)
+        .map(|(hashes, _)| *hashes)
+        .collect();
+    let predicates = evaluate_predicates(&criteria, age, &occupied_slots, &lab_ints);
+    if !criteria.evaluate(&predicates) {
+        return Err(EligibilityError(
+            "Patient does not meet the criteria".to_string(),
+        ));
+    }
+
+    let known = |value: Fr| Value::known(value);
+    let lab_bits: Vec<Vec<bool>> = lab_paths.iter().map(|path| path.bits()).collect();
+    Ok(CriteriaCircuit {
+        criteria: std::array::from_fn(|i| known(encoded[i])),
+        dob: known(dob),
+        dob_salt: known(dob_salt),
+        slots: std::array::from_fn(|s| std::array::from_fn(|h| known(slots[s][h]))),
+        occupied: std::array::from_fn(|s| known(Fr::from(occupied[s] as u64))),
+        diagnosis_root: known(diagnosis_root),
+        diagnosis_salt: known(diagnosis_salt),
+        lab_values: std::array::from_fn(|i| known(lab_values[i])),
+        lab_salts: std::array::from_fn(|i| known(lab_salts[i])),
+        lab_paths: std::array::from_fn(|i| {
+            std::array::from_fn(|j| known(lab_paths[i].siblings[j]))
+        }),
+        lab_bits: std::array::from_fn(|i| std::array::from_fn(|j| Value::known(lab_bits[i][j]))),
+        identity_secret: known(identity_secret),
+        credential: credential.witness(),
+        criteria_hash,
+        study_id,
+        as_of_date,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+        context_hash,
+        nullifier: compute_nullifier(identity_secret, study_id),
+    })
+}
+
+/// Generate eligibility criteria proof
+///
+/// Expects the input map built by [`criteria_inputs`]. The data is checked against the
+/// attested commitments and the criteria are evaluated before proving.
+pub fn generate_criteria_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_criteria_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_criteria_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_criteria_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = CRITERIA_K;
+    let circuit = criteria_circuit(&inputs)?;
+
+    let halo2_circuit =
+        Halo2Circuit::<Fr, CriteriaCircuit<Fr>>::new::<PC::ProvingBackend>(k, circuit.clone());
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify eligibility criteria proof
+///
+/// The verifier must also check `criteria_hash` against the study's published criteria
+/// and `provider_root` and `revocation_root` against the published roots, check
+/// `as_of_date` against its [`FreshnessWindow`](crate::epoch::FreshnessWindow), recompute
+/// `context_hash` from the submitting wallet, and reject a `nullifier` it has seen for
+/// the study.
+pub fn verify_criteria_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != CRITERIA_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of public inputs (expected {}: criteria_hash, study_id, as_of_date, provider_root, revocation_root, context_hash, nullifier)",
+            CRITERIA_PUBLIC_INPUTS
+        )));
+    }
+
+    // Verify the proof
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| EligibilityError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        credential::{testing::*, SignedCredential, MAX_CREDENTIAL_AGE},
+        provider_registry::ProviderTree,
+    };
+    use diagnosis_membership_circuit::prefix::hash_diagnosis_prefix;
+    use eligibility_gadgets::{days_from_civil, SigningKey};
+    use halo2_proofs::dev::MockProver;
+
+    const HBA1C: u64 = 45484;
+
+    /// "age 18-65 AND E11.* AND NOT I21 AND HbA1c > 7.00"
+    fn study_criteria() -> Criteria {
+        Criteria {
+            age: Some((18, 65)),
+            diagnoses: vec![
+                hash_diagnosis_prefix("E11.*").unwrap(),
+                hash_diagnosis_prefix("I21").unwrap(),
+            ],
+            labs: vec![LabPredicate {
+                analyte: Fr::from(HBA1C),
+                scale: 2,
+                comparison: Comparison::Gt,
+                bound: 700,
+                bound_high: 0,
+            }],
+            nodes: vec![
+                CriteriaNode::and(AGE_SIGNAL, diagnosis_signal(0)),
+                CriteriaNode::not(diagnosis_signal(1)),
+                CriteriaNode::and(node_signal(0), node_signal(1)),
+                CriteriaNode::and(node_signal(2), lab_signal(0)),
+            ],
+        }
+    }
+
+    fn inputs(
+        criteria: &Criteria,
+        dob: (i64, u32, u32),
+        codes: &[&str],
+        hba1c: u64,
+    ) -> HashMap<String, Vec<Fr>> {
+        let codes: Vec<String> = codes.iter().map(|code| code.to_string()).collect();
+        let tree = DiagnosisTree::new(&codes).unwrap();
+        let dob = days_from_civil(dob.0, dob.1, dob.2);
+        let labs =
+            LabTree::new(&[commit_lab_value(Fr::from(HBA1C), hba1c, 2, Fr::from(13))]).unwrap();
+        let credential = issue(CredentialAttributes {
+            identity_commitment: identity_commitment(Fr::from(42)),
+            dob_commitment: commit_date_of_birth(dob, Fr::from(11)),
+            diagnosis_commitment: tree.commitment(Fr::from(12)),
+            lab_root: labs.root(),
+            issued_at: days_to_field(ISSUED_AT),
+        });
+
+        criteria_inputs(
+            criteria,
+            dob,
+            Fr::from(11),
+            days_from_civil(2025, 6, 1),
+            &tree,
+            Fr::from(12),
+            &[(hba1c, Fr::from(13))],
+            &labs,
+            Fr::from(42),
+            &credential,
+            &ProofContext {
+                wallet: Fr::from(0x70997970),
+                chain_id: 1,
+                registry_address: Fr::from(0x5fbdb231),
+                study_id: Fr::from(1),
+            },
+        )
+        .unwrap()
+    }
+
+    /// Circuit with the same witnesses as `criteria_circuit`, without its validation
+    fn unchecked_circuit(inputs: &HashMap<String, Vec<Fr>>) -> CriteriaCircuit<Fr> {
+        let first = |name: &str| inputs[name][0];
+        let slots = &inputs["diagnosis_slots"];
+        let credential = CredentialInputs::from_inputs(inputs).unwrap();
+        let lab_paths: Vec<_> = inputs["lab_paths"]
+            .chunks(LAB_TREE_DEPTH)
+            .zip(inputs["lab_indices"].iter())
+            .map(|(siblings, index)| lab_path(siblings, index).unwrap())
+            .collect();
+        CriteriaCircuit {
+            criteria: std::array::from_fn(|i| Value::known(inputs["criteria"][i])),
+            dob: Value::known(first("dob")),
+            dob_salt: Value::known(first("dob_salt")),
+            slots: std::array::from_fn(|s| {
+                std::array::from_fn(|h| Value::known(slots[s * SLOT_HASHES + h]))
+            }),
+            occupied: std::array::from_fn(|s| {
+                Value::known(Fr::from((slots[s * SLOT_HASHES] != Fr::ZERO) as u64))
+            }),
+            diagnosis_root: Value::known(first("diagnosis_root")),
+            diagnosis_salt: Value::known(first("diagnosis_salt")),
+            lab_values: std::array::from_fn(|i| Value::known(inputs["lab_values"][i])),
+            lab_salts: std::array::from_fn(|i| Value::known(inputs["lab_salts"][i])),
+            lab_paths: std::array::from_fn(|i| {
+                std::array::from_fn(|j| Value::known(lab_paths[i].siblings[j]))
+            }),
+            lab_bits: std::array::from_fn(|i| {
+                std::array::from_fn(|j| Value::known(lab_paths[i].bits()[j]))
+            }),
+            identity_secret: Value::known(first("identity_secret")),
+            credential: credential.witness(),
+            criteria_hash: first("criteria_hash"),
+            study_id: first("study_id"),
+            as_of_date: first("as_of_date"),
+            provider_root: first("provider_root"),
+            revocation_root: first("revocation_root"),
+            context_hash: first("context_hash"),
+            nullifier: compute_nullifier(first("identity_secret"), first("study_id")),
+        }
+    }
+
+    fn mock_verify(circuit: &CriteriaCircuit<Fr>) -> bool {
+        MockProver::run(CRITERIA_K as u32, circuit, circuit.instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_criteria_encoding_roundtrip() {
+        let criteria = study_criteria();
+        let encoded = criteria.encode().unwrap();
+        assert_eq!(encoded.len(), CRITERIA_LEN);
+        assert_eq!(Criteria::decode(&encoded).unwrap(), criteria);
+
+        // A different expression has a different hash
+        let mut other = criteria.clone();
+        other.nodes[1] = CriteriaNode::and(diagnosis_signal(1), diagnosis_signal(1));
+        assert_ne!(other.hash().unwrap(), criteria.hash().unwrap());
+    }
+
+    #[test]
+    fn test_criteria_validation() {
+        let mut forward = study_criteria();
+        forward.nodes[0] = CriteriaNode::and(AGE_SIGNAL, node_signal(1));
+        assert!(forward.validate().is_err());
+
+        let mut undefined = study_criteria();
+        undefined.nodes[0] = CriteriaNode::and(AGE_SIGNAL, lab_signal(1));
+        assert!(undefined.validate().is_err());
+
+        let mut empty = study_criteria();
+        empty.nodes.clear();
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn test_evaluate_predicates() {
+        let criteria = study_criteria();
+        let slots = vec![slot_hashes("E11.9").unwrap()];
+
+        let predicates = evaluate_predicates(&criteria, 40, &slots, &[725]);
+        assert!(criteria.evaluate(&predicates));
+
+        let with_mi = vec![slot_hashes("E11.9").unwrap(), slot_hashes("I21.4").unwrap()];
+        assert!(!criteria.evaluate(&evaluate_predicates(&criteria, 40, &with_mi, &[725])));
+        assert!(!criteria.evaluate(&evaluate_predicates(&criteria, 70, &slots, &[725])));
+        assert!(!criteria.evaluate(&evaluate_predicates(&criteria, 40, &slots, &[700])));
+    }
+
+    #[test]
+    fn test_circuit_accepts_eligible_patient() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9", "I10"], 725);
+        let circuit = criteria_circuit(&inputs).unwrap();
+        assert!(mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_ineligible_patients() {
+        let criteria = study_criteria();
+        let cases = [
+            inputs(&criteria, (1950, 1, 1), &["E11.9"], 725), // Too old
+            inputs(&criteria, (1980, 3, 14), &["E10.9"], 725), // No type 2 diabetes
+            inputs(&criteria, (1980, 3, 14), &["E11.9", "I21.4"], 725), // Myocardial infarction
+            inputs(&criteria, (1980, 3, 14), &["E11.9"], 700), // HbA1c not above 7.00
+        ];
+
+        for inputs in cases.iter() {
+            // Client-side validation catches it...
+            assert!(criteria_circuit(inputs).is_err());
+            // ...and so does the circuit
+            assert!(!mock_verify(&unchecked_circuit(inputs)));
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_hidden_diagnosis() {
+        // Claiming the I21.4 slot is empty changes the prefix root
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9", "I21.4"], 725);
+        let mut circuit = unchecked_circuit(&inputs);
+        let slot = DiagnosisTree::new(&["E11.9".to_string(), "I21.4".to_string()])
+            .unwrap()
+            .codes()
+            .iter()
+            .position(|code| code == "I214")
+            .unwrap();
+        circuit.occupied[slot] = Value::known(Fr::ZERO);
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_other_criteria() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+        let mut circuit = unchecked_circuit(&inputs);
+
+        // Evaluating a weaker expression against the published hash
+        let mut weaker = study_criteria();
+        weaker.nodes.truncate(1);
+        let encoded = weaker.encode().unwrap();
+        circuit.criteria = std::array::from_fn(|i| Value::known(encoded[i]));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+        let circuit = criteria_circuit(&inputs).unwrap();
+
+        for row in 0..CRITERIA_PUBLIC_INPUTS {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            let prover = MockProver::run(CRITERIA_K as u32, &circuit, instances).unwrap();
+            assert!(
+                prover.verify().is_err(),
+                "tampered instance {} must not verify",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_data() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+
+        // Signed by a key other than the registered provider key
+        let other = SignedCredential::from_inputs(&inputs)
+            .unwrap()
+            .credential
+            .sign(&SigningKey::from_seed(&[10u8; 32]).unwrap());
+        let mut circuit = unchecked_circuit(&inputs);
+        circuit.credential.signature_r = Value::known(other.signature.r);
+        circuit.credential.signature_s = Value::known(other.signature.s);
+        assert!(!mock_verify(&circuit));
+
+        // A credential ID the provider did not sign
+        let mut forged = inputs.clone();
+        forged.insert("credential_id".to_string(), vec![Fr::from(1002)]);
+        assert!(criteria_circuit(&forged).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&forged)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_identity_secret() {
+        // A fresh secret would give a fresh nullifier for the same patient and study
+        let mut inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+        inputs.insert("identity_secret".to_string(), vec![Fr::from(43)]);
+        assert!(criteria_circuit(&inputs).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&inputs)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_lab_value() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 700);
+
+        // An eligible HbA1c committed by the patient, outside the signed lab tree
+        let mut forged = inputs.clone();
+        forged.insert("lab_values".to_string(), vec![Fr::from(800), Fr::ZERO]);
+        assert!(criteria_circuit(&forged).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&forged)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unregistered_provider() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+
+        // A valid signature by a key outside the registry, on a registered key's path
+        let outsider = SigningKey::from_seed(&[10u8; 32]).unwrap();
+        let mut unregistered = inputs.clone();
+        SignedCredential::from_inputs(&inputs)
+            .unwrap()
+            .credential
+            .sign(&outsider)
+            .insert_inputs(&mut unregistered);
+        assert!(criteria_circuit(&unregistered).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&unregistered)));
+
+        // A registry the provider is not in
+        let mut other_root = inputs.clone();
+        let other = ProviderTree::new(&[outsider.public_key()]).unwrap();
+        other_root.insert("provider_root".to_string(), vec![other.root()]);
+        assert!(criteria_circuit(&other_root).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&other_root)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_revoked_credential() {
+        let inputs = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+        let mut revoked = revocations();
+        revoked.revoke(&Fr::from(CREDENTIAL_ID)).unwrap();
+        assert!(revoked.path(&Fr::from(CREDENTIAL_ID)).is_err());
+
+        // A non-revocation path from before the revocation, against the current root
+        let mut stale = inputs.clone();
+        stale.insert("revocation_root".to_string(), vec![revoked.root()]);
+        assert!(criteria_circuit(&stale).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&stale)));
+
+        // The path of another, unrevoked credential
+        let mut other = inputs.clone();
+        other.insert("revocation_root".to_string(), vec![revoked.root()]);
+        other.insert(
+            "revocation_path".to_string(),
+            revoked.path(&Fr::from(1003)).unwrap().siblings,
+        );
+        assert!(criteria_circuit(&other).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&other)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_expired_credential() {
+        // The credential was issued on ISSUED_AT
+        let mut expired = inputs(&study_criteria(), (1980, 3, 14), &["E11.9"], 725);
+        let as_of_date = days_to_field(ISSUED_AT + MAX_CREDENTIAL_AGE as i64 + 1);
+        expired.insert("as_of_date".to_string(), vec![as_of_date]);
+        assert!(criteria_circuit(&expired).is_err());
+        assert!(!mock_verify(&unchecked_circuit(&expired)));
+    }
+}
@@ -0,0 +1,590 @@
+//! Age Range from Date of Birth
+//!
+//! [`AgeRangeCircuit`](crate::AgeRangeCircuit) derives the age on its epoch and adds
+//! the nullifier, patient commitment and age bucket the registries need. This circuit
+//! proves the range alone, as of any date:
+//!
+//! - Private Inputs: date of birth (days since 1970-01-01), commitment salt, credential
+//! - Public Inputs: as_of_date (days since 1970-01-01), min_age, max_age, study_id,
+//!   context_hash (see [`context`](crate::context)), provider_root, revocation_root
+//! - Constraints: `Poseidon(dob, salt) = dob_commitment`,
+//!   `min_age <= age_on(dob, as_of_date) <= max_age`, and a provider registered under
+//!   `provider_root` signed `dob_commitment` in a credential not revoked under
+//!   `revocation_root`
+//!
+//! The provider signs `dob_commitment` (see [`credential`](crate::credential)), so the
+//! patient cannot pick a convenient date of birth. The as-of date is usually the
+//! enrollment date; a verifier that checks it against its own clock stops accepting the
+//! proof once the patient ages out of the range. Since the date may be any day, the
+//! circuit does not bound how long before it the credential was issued (see
+//! [`epoch`](crate::epoch)). Ages are whole years on the Gregorian calendar, computed in-circuit by
+//! `eligibility_gadgets::date`.
+
+use std::{collections::HashMap, io::Cursor};
+
+use eligibility_gadgets::{
+    age_on, days_from_field, days_to_field, CredentialAttributes, CredentialChip, CredentialConfig,
+    CredentialWitness, DateChip, DateConfig, EddsaChip, EddsaField, IssuerChip, MerkleChip,
+    PoseidonChip, PoseidonConfig, PoseidonField,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    credential::checked_credential, field_to_u64, rng::default_rng, validate_age_range,
+    EligibilityError, PlonkishComponents,
+};
+
+/// Circuit size (2^k rows): the credential check and two date conversions of ~500 range
+/// check rows each
+pub const DOB_AGE_K: usize = 13;
+
+/// Number of public inputs of [`DateOfBirthAgeCircuit`]
+pub const DOB_AGE_PUBLIC_INPUTS: usize = 7;
+
+/// Date of Birth Age Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct DateOfBirthAgeConfig<F: PoseidonField> {
+    pub dob: Column<Advice>,          // Private: date of birth (days since epoch)
+    pub salt: Column<Advice>,         // Private: commitment salt
+    pub as_of: Column<Advice>,        // Public: as-of date (days since epoch)
+    pub min_age: Column<Advice>,      // Public: minimum age
+    pub max_age: Column<Advice>,      // Public: maximum age
+    pub study_id: Column<Advice>,     // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub instance: Column<Instance>,
+    pub date: DateConfig<F>,
+    pub poseidon: PoseidonConfig,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Date of Birth Age Circuit
+///
+/// Proves: min_age <= age(dob, as_of_date) <= max_age for a committed dob
+///
+/// ## Constraints
+/// 1. `Poseidon(dob, salt) = dob_commitment`
+/// 2. `age` is derived from `dob` and `as_of_date` by the date chip, which also
+///    rejects a dob after the as-of date
+/// 3. `age - min_age` and `max_age - age` are range checked to DATE_BITS bits
+/// 4. `CredentialChip` verifies a credential signing `dob_commitment` against
+///    `provider_root` and `revocation_root`
+///
+/// ## Public Inputs (instance column)
+/// Row 0: as_of_date, row 1: min_age, row 2: max_age, row 3: study_id,
+/// row 4: context_hash, row 5: provider_root, row 6: revocation_root. The dob commitment
+/// stays private so
+/// that proofs for different studies cannot be linked by it.
+#[derive(Clone)]
+pub struct DateOfBirthAgeCircuit<F: EddsaField> {
+    pub dob: Value<F>,  // Private: days since epoch (negative before 1970)
+    pub salt: Value<F>, // Private: commitment salt
+    pub credential: CredentialWitness<F>, // Private: signs dob_commitment
+    pub as_of_date: F,  // Public: days since epoch
+    pub min_age: F,     // Public input
+    pub max_age: F,     // Public input
+    pub study_id: F,    // Public input (binds proof to specific study)
+    pub context_hash: F, // Public input (binds proof to wallet, chain and registry)
+    pub provider_root: F, // Public: registered provider tree root
+    pub revocation_root: F, // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for DateOfBirthAgeCircuit<F> {
+    fn default() -> Self {
+        Self {
+            dob: Value::unknown(),
+            salt: Value::unknown(),
+            credential: CredentialWitness::default(),
+            as_of_date: F::ZERO,
+            min_age: F::ZERO,
+            max_age: F::ZERO,
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for DateOfBirthAgeCircuit<F> {
+    type Config = DateOfBirthAgeConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let dob = meta.advice_column();
+        let salt = meta.advice_column();
+        let as_of = meta.advice_column();
+        let min_age = meta.advice_column();
+        let max_age = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let instance = meta.instance_column();
+
+        for column in [dob, salt, as_of, min_age, max_age, study_id, context_hash] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        let date = DateChip::configure(meta, running_sum);
+        let poseidon = PoseidonChip::configure(meta);
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let merkle = MerkleChip::configure(meta, poseidon.clone());
+        let issuer = IssuerChip::configure(meta, eddsa, merkle);
+        let credential = CredentialChip::configure(meta, issuer);
+
+        DateOfBirthAgeConfig {
+            dob,
+            salt,
+            as_of,
+            min_age,
+            max_age,
+            study_id,
+            context_hash,
+            instance,
+            date,
+            poseidon,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (dob, salt, as_of, min_age, max_age, study_id, context_hash) = layouter.assign_region(
+            || "date of birth inputs",
+            |mut region| {
+                // Assign private date of birth and salt
+                let dob = region.assign_advice(|| "dob", config.dob, 0, || self.dob)?;
+                let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+
+                // Assign public as-of date, age range, study_id and context hash
+                let as_of = region.assign_advice(
+                    || "as_of_date",
+                    config.as_of,
+                    0,
+                    || Value::known(self.as_of_date),
+                )?;
+                let min_age = region.assign_advice(
+                    || "min_age",
+                    config.min_age,
+                    0,
+                    || Value::known(self.min_age),
+                )?;
+                let max_age = region.assign_advice(
+                    || "max_age",
+                    config.max_age,
+                    0,
+                    || Value::known(self.max_age),
+                )?;
+                let study_id = region.assign_advice(
+                    || "study_id",
+                    config.study_id,
+                    0,
+                    || Value::known(self.study_id),
+                )?;
+                let context_hash = region.assign_advice(
+                    || "context_hash",
+                    config.context_hash,
+                    0,
+                    || Value::known(self.context_hash),
+                )?;
+
+                Ok((dob, salt, as_of, min_age, max_age, study_id, context_hash))
+            },
+        )?;
+
+        // The date of birth is the one the provider committed to and signed
+        let poseidon_chip = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "dob commitment"),
+            &[dob.clone(), salt],
+        )?;
+        let opened = CredentialAttributes {
+            dob_commitment: Some(&commitment),
+            ..Default::default()
+        };
+        let (provider_root, revocation_root) =
+            CredentialChip::<F>::construct(config.credential.clone()).verify(
+                layouter.namespace(|| "credential"),
+                opened,
+                &self.credential,
+            )?;
+
+        let date_chip = DateChip::construct(config.date.clone());
+        let age = date_chip.age(layouter.namespace(|| "age"), &dob, &as_of)?;
+
+        // min_age <= age <= max_age
+        let mut range = layouter.namespace(|| "age range");
+        let lower_diff =
+            date_chip.linear(&mut range, &[(F::ONE, &age), (-F::ONE, &min_age)], F::ZERO)?;
+        let upper_diff =
+            date_chip.linear(&mut range, &[(F::ONE, &max_age), (-F::ONE, &age)], F::ZERO)?;
+        date_chip.range_check(&mut range, &lower_diff)?;
+        date_chip.range_check(&mut range, &upper_diff)?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(as_of.cell(), config.instance, 0)?;
+        layouter.constrain_instance(min_age.cell(), config.instance, 1)?;
+        layouter.constrain_instance(max_age.cell(), config.instance, 2)?;
+        layouter.constrain_instance(study_id.cell(), config.instance, 3)?;
+        layouter.constrain_instance(context_hash.cell(), config.instance, 4)?;
+        layouter.constrain_instance(provider_root.cell(), config.instance, 5)?;
+        layouter.constrain_instance(revocation_root.cell(), config.instance, 6)?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for DateOfBirthAgeCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: as_of_date, min_age, max_age, study_id, context_hash,
+        // provider_root, revocation_root
+        vec![vec![
+            self.as_of_date,
+            self.min_age,
+            self.max_age,
+            self.study_id,
+            self.context_hash,
+            self.provider_root,
+            self.revocation_root,
+        ]]
+    }
+}
+
+/// Commitment to a date of birth (days since epoch): `Poseidon(dob, salt)`
+///
+/// Computed by the issuing provider, which signs it in the patient's credential.
+pub fn commit_date_of_birth(dob: i64, salt: Fr) -> Fr {
+    Fr::poseidon_hash(&[days_to_field(dob), salt])
+}
+
+/// Client-side validation (called before proof generation)
+///
+/// The circuit derives the same age; this check only gives fast feedback.
+pub fn validate_date_of_birth(
+    dob: i64,
+    as_of_date: i64,
+    min_age: u64,
+    max_age: u64,
+) -> Result<(), EligibilityError> {
+    if dob > as_of_date {
+        return Err(EligibilityError(format!(
+            "Date of birth {} is after the as-of date {}",
+            dob, as_of_date
+        )));
+    }
+    validate_age_range(age_on(dob, as_of_date) as u64, min_age, max_age)
+}
+
+/// Generate date of birth age proof
+///
+/// Inputs: `dob`, `salt`, `as_of_date`, `min_age`, `max_age`, `study_id`,
+/// `context_hash` and the credential inputs, which include `dob_commitment`. Day counts before 1970 are negative
+/// field elements.
+pub fn generate_dob_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_dob_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_dob_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_dob_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = DOB_AGE_K;
+
+    // Extract inputs
+    let get = |name: &str| -> Result<Fr, EligibilityError> {
+        inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?
+            .first()
+            .copied()
+            .ok_or(EligibilityError(format!("Invalid {}", name)))
+    };
+    let dob = get("dob")?;
+    let salt = get("salt")?;
+    let as_of_date = get("as_of_date")?;
+    let min_age = get("min_age")?;
+    let max_age = get("max_age")?;
+    let study_id = get("study_id")?;
+    let context_hash = get("context_hash")?;
+    let credential = checked_credential(&inputs)?;
+    let dob_commitment = credential.attributes().dob_commitment;
+
+    // Client-side validation
+    // Fails fast instead of producing an unsatisfiable circuit
+    let days = |value: &Fr, name: &str| {
+        days_from_field(value).ok_or(EligibilityError(format!(
+            "Invalid {}: not a day count",
+            name
+        )))
+    };
+    let dob_days = days(&dob, "dob")?;
+    if commit_date_of_birth(dob_days, salt) != dob_commitment {
+        return Err(EligibilityError(
+            "Date of birth does not match dob_commitment".to_string(),
+        ));
+    }
+    validate_date_of_birth(
+        dob_days,
+        days(&as_of_date, "as_of_date")?,
+        field_to_u64(&min_age)?,
+        field_to_u64(&max_age)?,
+    )?;
+
+    // Create circuit with validated inputs
+    let circuit = DateOfBirthAgeCircuit::<Fr> {
+        dob: Value::known(dob),
+        salt: Value::known(salt),
+        credential: credential.witness(),
+        as_of_date,
+        min_age,
+        max_age,
+        study_id,
+        context_hash,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit = Halo2Circuit::<Fr, DateOfBirthAgeCircuit<Fr>>::new::<PC::ProvingBackend>(
+        k,
+        circuit.clone(),
+    );
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify date of birth age proof
+///
+/// The caller is responsible for checking that `as_of_date` is recent enough, that
+/// `provider_root` and `revocation_root` are the published roots, and for recomputing
+/// `context_hash` from the submitting wallet.
+pub fn verify_dob_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != DOB_AGE_PUBLIC_INPUTS {
+        return Err(EligibilityError(
+            "Invalid number of public inputs (expected 7: as_of_date, min_age, max_age, study_id, context_hash, provider_root, revocation_root)"
+                .to_string(),
+        ));
+    }
+
+    // Verify the proof
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| EligibilityError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::testing::issue;
+    use eligibility_gadgets::days_from_civil;
+    use halo2_proofs::{dev::MockProver, halo2curves::ff::Field};
+
+    fn dob_circuit(
+        dob: i64,
+        as_of_date: i64,
+        min_age: u64,
+        max_age: u64,
+    ) -> DateOfBirthAgeCircuit<Fr> {
+        let salt = Fr::from(7);
+        let dob_commitment = commit_date_of_birth(dob, salt);
+        let credential = issue(CredentialAttributes {
+            dob_commitment,
+            ..Default::default()
+        });
+        DateOfBirthAgeCircuit {
+            dob: Value::known(days_to_field(dob)),
+            salt: Value::known(salt),
+            credential: credential.witness(),
+            as_of_date: days_to_field(as_of_date),
+            min_age: Fr::from(min_age),
+            max_age: Fr::from(max_age),
+            study_id: Fr::from(1),
+            context_hash: Fr::from(7),
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        }
+    }
+
+    fn mock_verify(circuit: &DateOfBirthAgeCircuit<Fr>) -> bool {
+        MockProver::run(DOB_AGE_K as u32, circuit, circuit.instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_validate_date_of_birth() {
+        let dob = days_from_civil(2000, 6, 15);
+        assert!(validate_date_of_birth(dob, days_from_civil(2018, 6, 15), 18, 65).is_ok());
+        assert!(validate_date_of_birth(dob, days_from_civil(2018, 6, 14), 18, 65).is_err());
+        assert!(validate_date_of_birth(dob, days_from_civil(1999, 1, 1), 0, 65).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_age_in_range() {
+        let dob = days_from_civil(2000, 6, 15);
+        assert!(mock_verify(&dob_circuit(
+            dob,
+            days_from_civil(2018, 6, 15),
+            18,
+            65
+        ))); // Edge: min
+        assert!(mock_verify(&dob_circuit(
+            dob,
+            days_from_civil(2066, 6, 14),
+            18,
+            65
+        ))); // Edge: max
+
+        // Born before the epoch
+        let dob = days_from_civil(1955, 3, 1);
+        assert!(mock_verify(&dob_circuit(
+            dob,
+            days_from_civil(2025, 10, 1),
+            18,
+            75
+        )));
+    }
+
+    #[test]
+    fn test_circuit_rejects_age_out_of_range() {
+        // Bypasses validate_date_of_birth: a modified client must not be able to prove these
+        let dob = days_from_civil(2000, 6, 15);
+        assert!(!mock_verify(&dob_circuit(
+            dob,
+            days_from_civil(2018, 6, 14),
+            18,
+            65
+        )));
+        assert!(!mock_verify(&dob_circuit(
+            dob,
+            days_from_civil(2066, 6, 15),
+            18,
+            65
+        ))); // Aged out
+    }
+
+    #[test]
+    fn test_circuit_rejects_uncommitted_dob() {
+        let mut circuit = dob_circuit(
+            days_from_civil(2000, 6, 15),
+            days_from_civil(2030, 1, 1),
+            18,
+            65,
+        );
+        circuit.dob = Value::known(days_to_field(days_from_civil(1990, 6, 15)));
+        assert!(!mock_verify(&circuit));
+
+        // Committed consistently, but not the commitment the provider signed
+        let mut circuit = dob_circuit(
+            days_from_civil(2000, 6, 15),
+            days_from_civil(2030, 1, 1),
+            18,
+            65,
+        );
+        circuit.salt = Value::known(Fr::from(8));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = dob_circuit(
+            days_from_civil(2000, 6, 15),
+            days_from_civil(2030, 1, 1),
+            18,
+            65,
+        );
+        let names = [
+            "as_of_date",
+            "min_age",
+            "max_age",
+            "study_id",
+            "context_hash",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            let prover = MockProver::run(DOB_AGE_K as u32, &circuit, instances).unwrap();
+            assert!(
+                prover.verify().is_err(),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Active Enrollments
+//!
+//! Patients may not take part in two interventional trials at once. Each enrollment
+//! mints a `StudyParticipationSBT`, and the registry that enrolled the patient knows the
+//! [`nullifier`](crate::nullifier) they applied with. The set of active enrollments is
+//! published as the root of a sorted Poseidon Merkle tree over their enrollment keys,
+//!
+//! ```text
+//!   enrollment_key = nullifier mod 2^ENROLLMENT_KEY_BITS
+//! ```
+//!
+//! so that [`NonParticipationCircuit`](crate::non_participation::NonParticipationCircuit)
+//! can prove a patient's nullifiers for conflicting studies are absent with adjacent
+//! leaves, as diagnosis exclusion proofs do. Keys are truncated because the gaps
+//! between leaves are range checked; 128 bits still make collisions negligible.
+//!
+//! Leaves are `[lower_sentinel, keys in ascending order, upper_sentinel, ...]`, with the
+//! upper sentinel repeated to fill the tree.
+//!
+//! ## Files
+//! Until the root is published on-chain, the tooling keeps two JSON files:
+//! - the enrollment export, one entry per `ParticipantEnrolled` event joined with the
+//!   nullifier of the application:
+//!   `{ "enrollments": [{ "enrollmentId": 1, "nullifier": "0x…", "active": true }] }`
+//! - the published root, `{ "activeEnrollmentRoot": "0x…", "activeCount": 1, "updatedAt": 1750000000 }`
+
+use eligibility_gadgets::{MerklePath, MerkleTree};
+use halo2_proofs::halo2curves::ff::{Field, PrimeField};
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    serialization::{format_field_element, parse_field_element},
+    EligibilityError,
+};
+
+/// Depth of the active enrollment tree (2^16 leaves, including both sentinels)
+pub const ACTIVE_ENROLLMENT_TREE_DEPTH: usize = 16;
+
+/// Maximum number of active enrollments in the tree
+pub const MAX_ACTIVE_ENROLLMENTS: usize = (1 << ACTIVE_ENROLLMENT_TREE_DEPTH) - 2;
+
+/// Bits of a nullifier kept in its enrollment key
+pub const ENROLLMENT_KEY_BITS: usize = 128;
+
+/// One exported enrollment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Enrollment {
+    pub enrollment_id: u64, // StudyParticipationSBT enrollment ID
+    pub nullifier: String,  // Nullifier of the application, 0x-prefixed big-endian hex
+    pub active: bool,       // False once the participant completed or withdrew
+}
+
+/// Enrollment export file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnrollmentExport {
+    pub enrollments: Vec<Enrollment>,
+}
+
+/// Published root file, standing in for the on-chain root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishedEnrollmentRoot {
+    pub active_enrollment_root: String, // 0x-prefixed big-endian hex
+    pub active_count: usize,
+    pub updated_at: u64, // Unix seconds
+}
+
+impl PublishedEnrollmentRoot {
+    pub fn root(&self) -> Result<Fr, EligibilityError> {
+        parse_field_element(&self.active_enrollment_root)
+    }
+}
+
+/// High and low ENROLLMENT_KEY_BITS bits of a field element (assumes a little-endian
+/// `Repr`, as for bn256)
+pub(crate) fn split_key<F: PrimeField>(value: &F) -> (F, F) {
+    let bytes = value.to_repr();
+    let half = |start: usize| {
+        let mut repr = F::Repr::default();
+        let len = ENROLLMENT_KEY_BITS / 8;
+        repr.as_mut()[..len].copy_from_slice(&bytes.as_ref()[start..start + len]);
+        F::from_repr(repr).unwrap()
+    };
+
+    (half(ENROLLMENT_KEY_BITS / 8), half(0))
+}
+
+/// Enrollment key of a nullifier: its low ENROLLMENT_KEY_BITS bits
+pub fn enrollment_key(nullifier: &Fr) -> u128 {
+    let (_, low) = split_key(nullifier);
+    u128::from_le_bytes(low.to_repr()[..16].try_into().unwrap())
+}
+
+/// Lower sentinel leaf (0), below every enrollment key
+pub fn lower_sentinel() -> Fr {
+    Fr::ZERO
+}
+
+/// Upper sentinel leaf (`2^ENROLLMENT_KEY_BITS - 1`), above every enrollment key
+pub fn upper_sentinel() -> Fr {
+    Fr::from_u128(u128::MAX)
+}
+
+/// Sorted Merkle tree of active enrollment keys
+#[derive(Debug, Clone)]
+pub struct ActiveEnrollmentTree {
+    keys: Vec<u128>, // Ascending, without sentinels
+    pub(crate) tree: MerkleTree<Fr>,
+}
+
+impl ActiveEnrollmentTree {
+    /// Build the tree from the nullifiers of active enrollments
+    ///
+    /// Duplicate nullifiers share a leaf. Fails if a key is a sentinel (probability
+    /// 2^-127 for a Poseidon output) or there are more than MAX_ACTIVE_ENROLLMENTS.
+    pub fn new(nullifiers: &[Fr]) -> Result<Self, EligibilityError> {
+        let mut keys = nullifiers.iter().map(enrollment_key).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+
+        if keys.len() > MAX_ACTIVE_ENROLLMENTS {
+            return Err(EligibilityError(format!(
+                "{} active enrollments do not fit in the tree (maximum {})",
+                keys.len(),
+                MAX_ACTIVE_ENROLLMENTS
+            )));
+        }
+        if keys.first() == Some(&0) || keys.last() == Some(&u128::MAX) {
+            return Err(EligibilityError(
+                "Enrollment key collides with a sentinel".to_string(),
+            ));
+        }
+
+        let mut leaves = vec![lower_sentinel()];
+        leaves.extend(keys.iter().map(|key| Fr::from_u128(*key)));
+        leaves.resize(1 << ACTIVE_ENROLLMENT_TREE_DEPTH, upper_sentinel());
+
+        let tree = MerkleTree::new(&leaves, ACTIVE_ENROLLMENT_TREE_DEPTH)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+
+        Ok(Self { keys, tree })
+    }
+
+    /// Build the tree from the active entries of an enrollment export
+    pub fn from_export(export: &EnrollmentExport) -> Result<Self, EligibilityError> {
+        let nullifiers = export
+            .enrollments
+            .iter()
+            .filter(|enrollment| enrollment.active)
+            .map(|enrollment| parse_field_element(&enrollment.nullifier))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(&nullifiers)
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_active(&self, nullifier: &Fr) -> bool {
+        self.keys.binary_search(&enrollment_key(nullifier)).is_ok()
+    }
+
+    /// Index of the leaf directly below the key of `nullifier`
+    ///
+    /// Fails if the nullifier belongs to an active enrollment.
+    pub fn bracketing_index(&self, nullifier: &Fr) -> Result<usize, EligibilityError> {
+        let key = enrollment_key(nullifier);
+        if key == 0 || key == u128::MAX {
+            return Err(EligibilityError(
+                "Enrollment key collides with a sentinel".to_string(),
+            ));
+        }
+
+        match self.keys.binary_search(&key) {
+            Ok(_) => Err(EligibilityError(
+                "Patient is enrolled in a conflicting study".to_string(),
+            )),
+            // keys[position - 1] < key < keys[position], shifted by the lower sentinel
+            Err(position) => Ok(position),
+        }
+    }
+
+    /// Paths of the leaves at `index` and `index + 1`
+    pub(crate) fn adjacent_paths(
+        &self,
+        index: usize,
+    ) -> Result<(MerklePath<Fr>, MerklePath<Fr>), EligibilityError> {
+        let low = self
+            .tree
+            .path(index)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+        let high = self
+            .tree
+            .path(index + 1)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+        Ok((low, high))
+    }
+
+    /// Root file contents for the current tree, published at `updated_at` (Unix seconds)
+    pub fn published_root(&self, updated_at: u64) -> PublishedEnrollmentRoot {
+        PublishedEnrollmentRoot {
+            active_enrollment_root: format_field_element(&self.root()),
+            active_count: self.active_count(),
+            updated_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nullifier::compute_nullifier;
+
+    fn nullifiers() -> Vec<Fr> {
+        (1..=3)
+            .map(|study| compute_nullifier(Fr::from(42), Fr::from(study)))
+            .collect()
+    }
+
+    #[test]
+    fn test_enrollment_key_is_the_low_half() {
+        let nullifier = nullifiers()[0];
+        let (high, low) = split_key(&nullifier);
+
+        assert_eq!(
+            high * Fr::from(2).pow_vartime([ENROLLMENT_KEY_BITS as u64]) + low,
+            nullifier
+        );
+        assert_eq!(Fr::from_u128(enrollment_key(&nullifier)), low);
+        assert_eq!(enrollment_key(&Fr::from_u128(7)), 7);
+    }
+
+    #[test]
+    fn test_tree_is_sorted_with_sentinels() {
+        let tree = ActiveEnrollmentTree::new(&[nullifiers(), nullifiers()].concat()).unwrap();
+        let leaves = tree.tree.leaves();
+
+        assert_eq!(tree.active_count(), 3);
+        assert_eq!(leaves[0], lower_sentinel());
+        assert_eq!(leaves[4], upper_sentinel());
+        let keys = leaves[..5].iter().map(enrollment_key).collect::<Vec<_>>();
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+        for nullifier in nullifiers() {
+            assert!(tree.is_active(&nullifier));
+            assert!(tree.bracketing_index(&nullifier).is_err());
+        }
+
+        let other = compute_nullifier(Fr::from(43), Fr::from(1));
+        let index = tree.bracketing_index(&other).unwrap();
+        let key = enrollment_key(&other);
+        assert!(enrollment_key(&leaves[index]) < key && key < enrollment_key(&leaves[index + 1]));
+    }
+
+    #[test]
+    fn test_enrollment_files_roundtrip() {
+        let nullifiers = nullifiers();
+        let (active, ended) = (nullifiers[0], nullifiers[1]);
+        let json = format!(
+            r#"{{ "enrollments": [
+                {{ "enrollmentId": 1, "nullifier": "{}", "active": true }},
+                {{ "enrollmentId": 2, "nullifier": "{}", "active": false }}
+            ] }}"#,
+            format_field_element(&active),
+            format_field_element(&ended)
+        );
+        let export: EnrollmentExport = serde_json::from_str(&json).unwrap();
+        let tree = ActiveEnrollmentTree::from_export(&export).unwrap();
+        assert!(tree.is_active(&active));
+        assert!(!tree.is_active(&ended));
+
+        let json = serde_json::to_string(&tree.published_root(1_750_000_000)).unwrap();
+        assert!(json.contains("\"activeEnrollmentRoot\":\"0x"));
+        let published: PublishedEnrollmentRoot = serde_json::from_str(&json).unwrap();
+        assert_eq!(published.root().unwrap(), tree.root());
+        assert_eq!(published.active_count, 1);
+    }
+}
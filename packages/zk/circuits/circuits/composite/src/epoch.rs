@@ -0,0 +1,119 @@
+//! Proof Epochs
+//!
+//! A proof only says that the patient was eligible on some day, and a condition can
+//! change within months. [`AgeRangeCircuit`](crate::AgeRangeCircuit) therefore
+//! publishes the day it derives the age on from the provider-signed date of birth,
+//!
+//! ```text
+//!   epoch = floor(timestamp / EPOCH_SECONDS)
+//! ```
+//!
+//! and verifiers reject proofs whose epoch is outside their [`FreshnessWindow`]: older
+//! than the maximum age they accept, or in the future. Epochs are days since the Unix
+//! epoch, the unit of the `as_of_date` of the date-of-birth and criteria circuits, so
+//! the same window applies to those dates. The lab value, metric and diagnosis circuits
+//! publish an `epoch` as well, and the criteria circuit uses its `as_of_date` as one.
+//!
+//! ## Credential Age
+//! The provider signs the day a credential is issued (`issued_at`, see
+//! [`credential`](crate::credential)), and every circuit with an epoch constrains
+//! `issued_at <= epoch <= issued_at + MAX_CREDENTIAL_AGE`. Together with the window, a
+//! credential stops producing accepted proofs `MAX_CREDENTIAL_AGE` days after issuance,
+//! whatever epoch the prover claims.
+//!
+//! ## What the Epoch Does Not Prove
+//! The prover still picks the epoch within those bounds. It is an as-of date and a
+//! replay scope, not evidence of when the proof was generated: a patient can generate a
+//! proof today for a later epoch and submit it once that epoch is inside the window. The
+//! credential's attributes are whatever they were when it was signed; changes within
+//! `MAX_CREDENTIAL_AGE` are bounded by credential revocation (see
+//! [`revocation`](crate::revocation)) against the current `revocation_root`.
+use halo2_proofs::halo2curves::bn256::Fr;
+use thiserror::Error;
+
+use crate::field_to_u64;
+
+/// Length of an epoch: one day
+pub const EPOCH_SECONDS: u64 = 86_400;
+
+/// Epoch of a Unix timestamp (seconds)
+pub fn epoch_of(timestamp: u64) -> u64 {
+    timestamp / EPOCH_SECONDS
+}
+
+/// Proof epoch outside the verifier's freshness window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("proof epoch {epoch} is outside {oldest}..={current}")]
+pub struct ProofExpiredError {
+    pub epoch: u64, // u64::MAX if the public input is not a u64
+    pub oldest: u64,
+    pub current: u64,
+}
+
+/// Epochs a verifier accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreshnessWindow {
+    pub now: u64,            // Unix seconds
+    pub max_age_epochs: u64, // 0 = current epoch only
+}
+
+impl FreshnessWindow {
+    /// Oldest and current accepted epochs
+    pub fn epochs(&self) -> (u64, u64) {
+        let current = epoch_of(self.now);
+        (current.saturating_sub(self.max_age_epochs), current)
+    }
+
+    /// Check the `epoch` public input of a proof
+    pub fn check(&self, epoch: &Fr) -> Result<(), ProofExpiredError> {
+        let epoch = field_to_u64(epoch)
+            .ok()
+            .filter(|value| Fr::from(*value) == *epoch)
+            .unwrap_or(u64::MAX);
+
+        let (oldest, current) = self.epochs();
+        if epoch < oldest || epoch > current {
+            return Err(ProofExpiredError {
+                epoch,
+                oldest,
+                current,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_of() {
+        assert_eq!(epoch_of(0), 0);
+        assert_eq!(epoch_of(EPOCH_SECONDS - 1), 0);
+        assert_eq!(epoch_of(1_750_000_000), 20254); // 2025-06-15
+    }
+
+    #[test]
+    fn test_freshness_window() {
+        let window = FreshnessWindow {
+            now: 1_750_000_000,
+            max_age_epochs: 30,
+        };
+        assert!(window.check(&Fr::from(20254)).is_ok());
+        assert!(window.check(&Fr::from(20224)).is_ok()); // Edge: oldest
+
+        let expired = window.check(&Fr::from(20223)).unwrap_err();
+        assert_eq!(
+            expired,
+            ProofExpiredError {
+                epoch: 20223,
+                oldest: 20224,
+                current: 20254
+            }
+        );
+
+        assert!(window.check(&Fr::from(20255)).is_err()); // Future
+        assert!(window.check(&-Fr::from(1)).is_err());
+    }
+}
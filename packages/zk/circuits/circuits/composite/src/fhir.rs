@@ -0,0 +1,1050 @@
+//! FHIR R4 Ingestion
+//!
+//! Extracts the facts the eligibility circuits need from a FHIR R4 `Bundle` (JSON) and
+//! builds the input maps of their `generate_*` functions, instead of hand-written maps.
+//!
+//! ## Extracted Resources
+//! - `Patient.birthDate`: must be a full date (partial dates cannot derive an age)
+//! - `Condition.code`: an ICD-10 / ICD-10-CM coding, or a SNOMED CT coding with a
+//!   caller-supplied ICD-10 mapping. Conditions whose `verificationStatus` is
+//!   `entered-in-error` or `refuted`, or whose `clinicalStatus` is `inactive`,
+//!   `remission` or `resolved`, are skipped: the patient does not have them now.
+//! - `Observation.valueQuantity` with a LOINC code: `final`, `amended` and `corrected`
+//!   observations are kept, as are the LOINC-coded `component`s of panels such as blood
+//!   pressure. Observations without a `valueQuantity` or without a LOINC coding (e.g.
+//!   vital signs under a local code) are not lab values the circuits can name and are
+//!   skipped. Values in a non-canonical unit (e.g. glucose in mmol/L) are converted
+//!   when building lab and metric inputs, see [`units`](crate::units).
+//!
+//! Any other code system on a Condition is an error, as is a bundle without exactly one
+//! Patient, so no diagnosis is silently dropped.
+
+use std::collections::HashMap;
+
+use diagnosis_membership_circuit::DiagnosisTree;
+use eligibility_gadgets::{
+    age_on, civil_from_days, days_from_civil, days_to_field, normalize_icd10_code,
+};
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    context::ProofContext,
+    credential::CredentialInputs,
+    criteria::LabPredicate,
+    lab_value::{commit_lab_value, pack_loinc, validate_lab_value, LabTree, MAX_LAB_VALUE},
+    metric::{insert_measurement_inputs, validate_metric, Metric},
+    units::{self, round_to_scale, same_unit, to_canonical, Rounding},
+    EligibilityError,
+};
+
+/// ICD-10 code systems (WHO and the US clinical modification)
+pub const ICD10_SYSTEMS: &[&str] = &[
+    "http://hl7.org/fhir/sid/icd-10",
+    "http://hl7.org/fhir/sid/icd-10-cm",
+];
+
+/// SNOMED CT code system
+pub const SNOMED_SYSTEM: &str = "http://snomed.info/sct";
+
+/// LOINC code system
+pub const LOINC_SYSTEM: &str = "http://loinc.org";
+
+/// UCUM unit system
+pub const UCUM_SYSTEM: &str = "http://unitsofmeasure.org";
+
+/// Facts about one patient, extracted from a FHIR bundle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatientFacts {
+    pub birth_date: i64,                // Days since 1970-01-01
+    pub diagnoses: Vec<String>,         // Normalized ICD-10 codes, sorted and deduplicated
+    pub observations: Vec<Observation>, // In bundle order
+}
+
+/// Quantitative LOINC observation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub loinc: String,
+    pub value: String,          // Decimal as written in the bundle
+    pub unit: String,           // UCUM code, or the human-readable unit without one
+    pub effective: Option<i64>, // Days since 1970-01-01
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Bundle {
+    resource_type: String,
+    #[serde(default)]
+    entry: Vec<BundleEntry>,
+}
+
+#[derive(Deserialize)]
+struct BundleEntry {
+    resource: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PatientResource {
+    birth_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConditionResource {
+    id: Option<String>,
+    code: Option<CodeableConcept>,
+    clinical_status: Option<CodeableConcept>,
+    verification_status: Option<CodeableConcept>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObservationResource {
+    id: Option<String>,
+    status: String,
+    code: CodeableConcept,
+    value_quantity: Option<Quantity>,
+    effective_date_time: Option<String>,
+    #[serde(default)]
+    component: Vec<ObservationComponent>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObservationComponent {
+    code: CodeableConcept,
+    value_quantity: Option<Quantity>,
+}
+
+#[derive(Deserialize)]
+struct CodeableConcept {
+    #[serde(default)]
+    coding: Vec<Coding>,
+}
+
+#[derive(Deserialize)]
+struct Coding {
+    system: Option<String>,
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Quantity {
+    value: Option<serde_json::Number>,
+    unit: Option<String>,
+    system: Option<String>,
+    code: Option<String>,
+}
+
+impl CodeableConcept {
+    /// First code in one of `systems`
+    fn code_in(&self, systems: &[&str]) -> Option<&str> {
+        self.coding
+            .iter()
+            .find(|coding| {
+                coding
+                    .system
+                    .as_deref()
+                    .map_or(false, |s| systems.contains(&s))
+            })
+            .and_then(|coding| coding.code.as_deref())
+    }
+
+    /// Whether one of the codings has one of `codes`
+    fn has_code(&self, codes: &[&str]) -> bool {
+        self.coding.iter().any(|coding| {
+            coding
+                .code
+                .as_deref()
+                .map_or(false, |code| codes.contains(&code))
+        })
+    }
+
+    fn systems(&self) -> Vec<&str> {
+        self.coding
+            .iter()
+            .map(|coding| coding.system.as_deref().unwrap_or("<no system>"))
+            .collect()
+    }
+}
+
+/// Parse a FHIR `date` ("YYYY-MM-DD") or the date part of a `dateTime` into days since epoch
+pub fn parse_fhir_date(date: &str) -> Result<i64, EligibilityError> {
+    let invalid = || EligibilityError(format!("Invalid or partial FHIR date: {}", date));
+
+    let day_part = date.get(..10).ok_or_else(invalid)?;
+    if date.len() > 10 && !date[10..].starts_with('T') {
+        return Err(invalid());
+    }
+
+    let mut fields = day_part.split('-');
+    let mut next = |len: usize| {
+        fields
+            .next()
+            .filter(|field| field.len() == len && field.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|field| field.parse::<u32>().ok())
+            .ok_or_else(invalid)
+    };
+    let (year, month, day) = (next(4)? as i64, next(2)?, next(2)?);
+
+    // Reject dates such as 2023-02-30
+    let days = days_from_civil(year, month, day);
+    if civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    Ok(days)
+}
+
+impl PatientFacts {
+    /// Extract facts from a FHIR R4 Bundle
+    pub fn from_bundle(json: &str) -> Result<Self, EligibilityError> {
+        Self::from_bundle_with_mappings(json, &HashMap::new())
+    }
+
+    /// [`PatientFacts::from_bundle`], mapping SNOMED CT condition codes to ICD-10
+    ///
+    /// `snomed_to_icd10` maps SNOMED CT concept ids to ICD-10 codes; it is only used for
+    /// Conditions without an ICD-10 coding.
+    pub fn from_bundle_with_mappings(
+        json: &str,
+        snomed_to_icd10: &HashMap<String, String>,
+    ) -> Result<Self, EligibilityError> {
+        let bundle: Bundle = serde_json::from_str(json)
+            .map_err(|e| EligibilityError(format!("Invalid FHIR bundle: {}", e)))?;
+        if bundle.resource_type != "Bundle" {
+            return Err(EligibilityError(format!(
+                "Expected a FHIR Bundle, found {}",
+                bundle.resource_type
+            )));
+        }
+
+        let mut birth_dates = Vec::new();
+        let mut diagnoses = Vec::new();
+        let mut observations = Vec::new();
+
+        for resource in bundle.entry.into_iter().filter_map(|entry| entry.resource) {
+            let resource_type = resource
+                .get("resourceType")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let parse_error = |e: serde_json::Error| {
+                EligibilityError(format!("Invalid FHIR {}: {}", resource_type, e))
+            };
+
+            match resource_type.as_str() {
+                "Patient" => {
+                    let patient: PatientResource =
+                        serde_json::from_value(resource).map_err(parse_error)?;
+                    birth_dates.push(patient.birth_date);
+                }
+                "Condition" => {
+                    let condition: ConditionResource =
+                        serde_json::from_value(resource).map_err(parse_error)?;
+                    if let Some(code) = condition_code(&condition, snomed_to_icd10)? {
+                        diagnoses.push(code);
+                    }
+                }
+                "Observation" => {
+                    let observation: ObservationResource =
+                        serde_json::from_value(resource).map_err(parse_error)?;
+                    observations.extend(lab_observations(&observation)?);
+                }
+                _ => {} // Other resources carry nothing the circuits use
+            }
+        }
+
+        let birth_date = match birth_dates.as_slice() {
+            [Some(birth_date)] => parse_fhir_date(birth_date)
+                .map_err(|e| EligibilityError(format!("Patient.birthDate: {}", e)))?,
+            [None] => return Err(EligibilityError("Patient.birthDate is missing".to_string())),
+            [] => return Err(EligibilityError("Bundle has no Patient".to_string())),
+            _ => {
+                return Err(EligibilityError(
+                    "Bundle has more than one Patient".to_string(),
+                ))
+            }
+        };
+
+        diagnoses.sort();
+        diagnoses.dedup();
+
+        Ok(Self {
+            birth_date,
+            diagnoses,
+            observations,
+        })
+    }
+
+    /// Age in whole years on `as_of_date` (days since epoch)
+    pub fn age_on(&self, as_of_date: i64) -> Result<u64, EligibilityError> {
+        if self.birth_date > as_of_date {
+            return Err(EligibilityError(
+                "Birth date is after the as-of date".to_string(),
+            ));
+        }
+        Ok(age_on(self.birth_date, as_of_date) as u64)
+    }
+
+    /// Most recent observation of `loinc` (by effective date, then bundle order)
+    pub fn latest_observation(&self, loinc: &str) -> Result<&Observation, EligibilityError> {
+        self.observations
+            .iter()
+            .filter(|observation| observation.loinc == loinc.trim())
+            .max_by_key(|observation| observation.effective)
+            .ok_or(EligibilityError(format!(
+                "No Observation with a value for LOINC {}",
+                loinc
+            )))
+    }
+
+    /// Diagnosis tree over the patient's codes
+    pub fn diagnosis_tree(&self) -> Result<DiagnosisTree, EligibilityError> {
+        DiagnosisTree::new(&self.diagnoses).map_err(|e| EligibilityError(e.0))
+    }
+
+    /// Input map for [`generate_proof`](crate::generate_proof) (age range)
+    ///
+    /// `context` carries the study ID and the applicant the proof is bound to. The proof's
+    /// epoch is `as_of_date` (see [`epoch`](crate::epoch)), the day the age is derived on.
+    /// `credential` must attest the birth date committed with `dob_salt`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn age_inputs(
+        &self,
+        as_of_date: i64,
+        min_age: u64,
+        max_age: u64,
+        context: &ProofContext,
+        identity_secret: Fr,
+        dob_salt: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+        self.age_on(as_of_date)?;
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert("dob".to_string(), vec![days_to_field(self.birth_date)]);
+        inputs.insert("dob_salt".to_string(), vec![dob_salt]);
+        inputs.insert("min_age".to_string(), vec![Fr::from(min_age)]);
+        inputs.insert("max_age".to_string(), vec![Fr::from(max_age)]);
+        inputs.insert("identity_secret".to_string(), vec![identity_secret]);
+        inputs.insert("epoch".to_string(), vec![days_to_field(as_of_date)]);
+        context.insert_inputs(&mut inputs);
+        Ok(inputs)
+    }
+
+    /// Input map for [`generate_dob_proof`](crate::date_of_birth::generate_dob_proof)
+    ///
+    /// `context` carries the study ID and the applicant the proof is bound to.
+    pub fn dob_inputs(
+        &self,
+        salt: Fr,
+        as_of_date: i64,
+        min_age: u64,
+        max_age: u64,
+        context: &ProofContext,
+        credential: &CredentialInputs,
+    ) -> HashMap<String, Vec<Fr>> {
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert("dob".to_string(), vec![days_to_field(self.birth_date)]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("as_of_date".to_string(), vec![days_to_field(as_of_date)]);
+        inputs.insert("min_age".to_string(), vec![Fr::from(min_age)]);
+        inputs.insert("max_age".to_string(), vec![Fr::from(max_age)]);
+        context.insert_inputs(&mut inputs);
+        inputs
+    }
+
+    /// Fixed-point value of the latest observation of the predicate's analyte
+    ///
+    /// `unit` is the unit the predicate's bounds are in. An observation in another unit
+    /// is converted if `unit` is the analyte's canonical unit (see [`to_canonical`]);
+    /// otherwise the units must match. Either way the value is rounded to nearest at the
+    /// predicate's scale, as the analyte's other observations would be.
+    pub fn lab_value(&self, predicate: &LabPredicate, unit: &str) -> Result<u64, EligibilityError> {
+        let observation = self
+            .observations
+            .iter()
+            .filter(|observation| {
+                pack_loinc(&observation.loinc).map(Fr::from).ok() == Some(predicate.analyte)
+            })
+            .max_by_key(|observation| observation.effective)
+            .ok_or(EligibilityError(format!(
+                "No Observation with a value for analyte {:?}",
+                predicate.analyte
+            )))?;
+
+        if !same_unit(&observation.unit, unit) {
+            return match units::lookup(&observation.loinc) {
+                Some(analyte) if same_unit(unit, analyte.unit) => to_canonical(
+                    &observation.loinc,
+                    &observation.value,
+                    &observation.unit,
+                    predicate.scale,
+                    Rounding::Nearest,
+                ),
+                _ => Err(EligibilityError(format!(
+                    "Observation {} is in {}, expected {}",
+                    observation.loinc, observation.unit, unit
+                ))),
+            };
+        }
+
+        let value = round_to_scale(&observation.value, predicate.scale, Rounding::Nearest)?;
+        u64::try_from(value)
+            .ok()
+            .filter(|value| *value <= MAX_LAB_VALUE)
+            .ok_or(EligibilityError(format!(
+                "Observation {} value {} is too large",
+                observation.loinc, observation.value
+            )))
+    }
+
+    /// Input map for [`generate_lab_proof`](crate::lab_value::generate_lab_proof)
+    ///
+    /// `lab_tree` is the tree of the provider's lab commitments whose root `credential`
+    /// signs; it must contain the commitment of the value with `salt`. The proof's epoch
+    /// is `as_of_date` (see [`epoch`](crate::epoch)).
+    #[allow(clippy::too_many_arguments)]
+    pub fn lab_inputs(
+        &self,
+        predicate: &LabPredicate,
+        unit: &str,
+        salt: Fr,
+        context: &ProofContext,
+        as_of_date: i64,
+        lab_tree: &LabTree,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+        let value = self.lab_value(predicate, unit)?;
+        validate_lab_value(
+            value,
+            predicate.comparison,
+            predicate.bound,
+            predicate.bound_high,
+        )?;
+        let commitment = commit_lab_value(predicate.analyte, value, predicate.scale, salt);
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        lab_tree.insert_inputs(&commitment, &mut inputs)?;
+        inputs.insert("value".to_string(), vec![Fr::from(value)]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("analyte".to_string(), vec![predicate.analyte]);
+        inputs.insert("scale".to_string(), vec![Fr::from(predicate.scale as u64)]);
+        inputs.insert(
+            "comparison".to_string(),
+            vec![Fr::from(predicate.comparison.code())],
+        );
+        inputs.insert("bound".to_string(), vec![Fr::from(predicate.bound)]);
+        inputs.insert(
+            "bound_high".to_string(),
+            vec![Fr::from(predicate.bound_high)],
+        );
+        context.insert_inputs(&mut inputs);
+        inputs.insert("epoch".to_string(), vec![days_to_field(as_of_date)]);
+        inputs.insert("lab_commitment".to_string(), vec![commitment]);
+        Ok(inputs)
+    }
+
+    /// Fixed-point value of the latest observation of a vital sign, in its canonical unit
+    /// and scale (see [`units`](crate::units))
+    pub fn measurement(&self, loinc: &str) -> Result<u64, EligibilityError> {
+        let observation = self.latest_observation(loinc)?;
+        let analyte = units::lookup(loinc).ok_or(EligibilityError(format!(
+            "LOINC {} has no canonical unit",
+            loinc
+        )))?;
+        to_canonical(
+            &observation.loinc,
+            &observation.value,
+            &observation.unit,
+            analyte.scale,
+            Rounding::Nearest,
+        )
+    }
+
+    /// Input map for [`generate_metric_proof`](crate::metric::generate_metric_proof)
+    ///
+    /// `min` and `max` are in tenths. `lab_tree` must contain the commitments of both
+    /// measurements of `metric`, with `salts`, under the root `credential` signs. The
+    /// proof's epoch is `as_of_date`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn metric_inputs(
+        &self,
+        metric: Metric,
+        min: u64,
+        max: u64,
+        salts: [Fr; 2],
+        context: &ProofContext,
+        as_of_date: i64,
+        lab_tree: &LabTree,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+        let [a, b] = metric.measurements().ok_or(EligibilityError(format!(
+            "{} is not computed from FHIR observations",
+            metric
+        )))?;
+        let values = [self.measurement(a)?, self.measurement(b)?];
+        validate_metric(metric, values[0], values[1], min, max)?;
+        let commitments = metric.commitments(values, salts);
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        insert_measurement_inputs(lab_tree, &commitments, &mut inputs)?;
+        inputs.insert("metric".to_string(), vec![Fr::from(metric.code())]);
+        inputs.insert(
+            "measurements".to_string(),
+            values.iter().map(|value| Fr::from(*value)).collect(),
+        );
+        inputs.insert("salts".to_string(), salts.to_vec());
+        inputs.insert("min".to_string(), vec![Fr::from(min)]);
+        inputs.insert("max".to_string(), vec![Fr::from(max)]);
+        context.insert_inputs(&mut inputs);
+        inputs.insert("epoch".to_string(), vec![days_to_field(as_of_date)]);
+        Ok(inputs)
+    }
+
+    /// Input map for the diagnosis membership `generate_proof`, with epoch `as_of_date`
+    pub fn diagnosis_inputs(
+        &self,
+        required_code: &str,
+        salt: Fr,
+        context: &ProofContext,
+        as_of_date: i64,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+        let epoch = days_to_field(as_of_date);
+        self.diagnosis_tree()?
+            .proof_inputs(
+                required_code,
+                salt,
+                context.study_id,
+                context.hash(),
+                epoch,
+                credential,
+            )
+            .map_err(|e| EligibilityError(e.0))
+    }
+}
+
+/// Normalized ICD-10 code of a Condition, `None` if it does not count
+fn condition_code(
+    condition: &ConditionResource,
+    snomed_to_icd10: &HashMap<String, String>,
+) -> Result<Option<String>, EligibilityError> {
+    let id = condition.id.as_deref().unwrap_or("<no id>");
+
+    let not_verified = ["entered-in-error", "refuted"];
+    let not_current = ["inactive", "remission", "resolved"];
+    if condition
+        .verification_status
+        .as_ref()
+        .map_or(false, |status| status.has_code(&not_verified))
+        || condition
+            .clinical_status
+            .as_ref()
+            .map_or(false, |status| status.has_code(&not_current))
+    {
+        return Ok(None);
+    }
+
+    let code = condition
+        .code
+        .as_ref()
+        .ok_or(EligibilityError(format!("Condition {} has no code", id)))?;
+
+    let icd10 = match (code.code_in(ICD10_SYSTEMS), code.code_in(&[SNOMED_SYSTEM])) {
+        (Some(icd10), _) => icd10,
+        (None, Some(snomed)) => {
+            snomed_to_icd10
+                .get(snomed)
+                .map(String::as_str)
+                .ok_or(EligibilityError(format!(
+                    "Condition {}: SNOMED CT code {} has no ICD-10 mapping",
+                    id, snomed
+                )))?
+        }
+        (None, None) => {
+            return Err(EligibilityError(format!(
+                "Condition {}: unsupported code system(s) {:?} (expected ICD-10 or SNOMED CT)",
+                id,
+                code.systems()
+            )))
+        }
+    };
+
+    normalize_icd10_code(icd10)
+        .map(Some)
+        .map_err(|e| EligibilityError(format!("Condition {}: {}", id, e)))
+}
+
+/// Quantitative LOINC results of an Observation and its components (e.g. the systolic
+/// and diastolic pressure of a blood pressure panel), empty if it does not count
+fn lab_observations(
+    observation: &ObservationResource,
+) -> Result<Vec<Observation>, EligibilityError> {
+    let id = observation.id.as_deref().unwrap_or("<no id>");
+
+    if !["final", "amended", "corrected"].contains(&observation.status.as_str()) {
+        return Ok(Vec::new());
+    }
+    let effective = observation
+        .effective_date_time
+        .as_deref()
+        .map(parse_fhir_date)
+        .transpose()
+        .map_err(|e| EligibilityError(format!("Observation {}: {}", id, e)))?;
+
+    let results = [(&observation.code, &observation.value_quantity)]
+        .into_iter()
+        .chain(
+            observation
+                .component
+                .iter()
+                .map(|component| (&component.code, &component.value_quantity)),
+        );
+
+    let mut observations = Vec::new();
+    for (code, quantity) in results {
+        let (Some(quantity), Some(loinc)) = (quantity.as_ref(), code.code_in(&[LOINC_SYSTEM]))
+        else {
+            continue;
+        };
+
+        let value = quantity.value.as_ref().ok_or(EligibilityError(format!(
+            "Observation {} has no valueQuantity.value",
+            id
+        )))?;
+        let unit = match (quantity.system.as_deref(), &quantity.code, &quantity.unit) {
+            (Some(UCUM_SYSTEM), Some(code), _) => code,
+            (_, _, Some(unit)) => unit,
+            _ => return Err(EligibilityError(format!("Observation {} has no unit", id))),
+        };
+
+        observations.push(Observation {
+            loinc: loinc.trim().to_string(),
+            value: value.to_string(),
+            unit: unit.trim().to_string(),
+            effective,
+        });
+    }
+    Ok(observations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        credential::{
+            identity_commitment,
+            testing::{issue, ISSUED_AT},
+            CredentialAttributes,
+        },
+        date_of_birth::commit_date_of_birth,
+        lab_value::Comparison,
+    };
+
+    const BUNDLE: &str = r#"{
+        "resourceType": "Bundle",
+        "type": "collection",
+        "entry": [
+            {"resource": {"resourceType": "Patient", "id": "p1", "birthDate": "1980-03-14"}},
+            {"resource": {"resourceType": "Condition", "id": "c1", "code": {"coding": [
+                {"system": "http://hl7.org/fhir/sid/icd-10-cm", "code": "E11.9"}]}}},
+            {"resource": {"resourceType": "Condition", "id": "c2", "code": {"coding": [
+                {"system": "http://snomed.info/sct", "code": "38341003"}]}}},
+            {"resource": {"resourceType": "Condition", "id": "c3",
+                "verificationStatus": {"coding": [{"code": "entered-in-error"}]},
+                "code": {"coding": [{"system": "http://hl7.org/fhir/sid/icd-10", "code": "I21.4"}]}}},
+            {"resource": {"resourceType": "Observation", "id": "o1", "status": "final",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+                "effectiveDateTime": "2024-01-10T09:00:00Z",
+                "valueQuantity": {"value": 6.9, "unit": "%", "system": "http://unitsofmeasure.org", "code": "%"}}},
+            {"resource": {"resourceType": "Observation", "id": "o2", "status": "final",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+                "effectiveDateTime": "2025-02-01",
+                "valueQuantity": {"value": 7.4, "unit": "%", "system": "http://unitsofmeasure.org", "code": "%"}}},
+            {"resource": {"resourceType": "Observation", "id": "o3", "status": "preliminary",
+                "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+                "effectiveDateTime": "2025-03-01",
+                "valueQuantity": {"value": 9.9, "unit": "%"}}},
+            {"resource": {"resourceType": "Practitioner", "id": "dr1"}}
+        ]
+    }"#;
+
+    fn snomed_map() -> HashMap<String, String> {
+        HashMap::from([("38341003".to_string(), "I10".to_string())])
+    }
+
+    fn hba1c_above(bound: u64) -> LabPredicate {
+        LabPredicate {
+            analyte: Fr::from(45484),
+            scale: 1,
+            comparison: Comparison::Gt,
+            bound,
+            bound_high: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_fhir_date() {
+        assert_eq!(parse_fhir_date("1970-01-02").unwrap(), 1);
+        assert_eq!(
+            parse_fhir_date("2025-02-01T10:00:00+01:00").unwrap(),
+            days_from_civil(2025, 2, 1)
+        );
+        assert!(parse_fhir_date("1980").is_err());
+        assert!(parse_fhir_date("1980-03").is_err());
+        assert!(parse_fhir_date("2023-02-30").is_err());
+    }
+
+    #[test]
+    fn test_extracts_patient_facts() {
+        let facts = PatientFacts::from_bundle_with_mappings(BUNDLE, &snomed_map()).unwrap();
+
+        assert_eq!(facts.birth_date, days_from_civil(1980, 3, 14));
+        assert_eq!(facts.diagnoses, vec!["E119".to_string(), "I10".to_string()]);
+        assert_eq!(facts.observations.len(), 2); // Preliminary result skipped
+        assert_eq!(facts.latest_observation("4548-4").unwrap().value, "7.4");
+        assert_eq!(facts.age_on(days_from_civil(2025, 6, 1)).unwrap(), 45);
+    }
+
+    #[test]
+    fn test_reports_unusable_bundles() {
+        // SNOMED code without a mapping
+        assert!(PatientFacts::from_bundle(BUNDLE).is_err());
+
+        let bundle =
+            |entries: &str| format!(r#"{{"resourceType": "Bundle", "entry": [{}]}}"#, entries);
+        let patient = r#"{"resource": {"resourceType": "Patient", "birthDate": "1980-03-14"}}"#;
+
+        assert!(PatientFacts::from_bundle(&bundle("")).is_err());
+        assert!(
+            PatientFacts::from_bundle(&bundle(r#"{"resource": {"resourceType": "Patient"}}"#))
+                .is_err()
+        );
+        assert!(PatientFacts::from_bundle(&bundle(&format!("{}, {}", patient, patient))).is_err());
+        assert!(PatientFacts::from_bundle(r#"{"resourceType": "Patient"}"#).is_err());
+
+        let unknown_system = r#"{"resource": {"resourceType": "Condition", "id": "c9", "code": {"coding": [
+            {"system": "http://example.org/local-codes", "code": "DM2"}]}}}"#;
+        let error = PatientFacts::from_bundle(&bundle(&format!("{}, {}", patient, unknown_system)))
+            .unwrap_err();
+        assert!(error.0.contains("c9") && error.0.contains("local-codes"));
+
+        let no_value = r#"{"resource": {"resourceType": "Observation", "id": "o9", "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+            "valueQuantity": {"unit": "%"}}}"#;
+        assert!(PatientFacts::from_bundle(&bundle(&format!("{}, {}", patient, no_value))).is_err());
+    }
+
+    #[test]
+    fn test_skips_inactive_conditions() {
+        let condition = |id: &str, clinical_status: &str, code: &str| {
+            format!(
+                r#"{{"resource": {{"resourceType": "Condition", "id": "{}",
+                    "clinicalStatus": {{"coding": [{{"system": "http://terminology.hl7.org/CodeSystem/condition-clinical", "code": "{}"}}]}},
+                    "code": {{"coding": [{{"system": "http://hl7.org/fhir/sid/icd-10", "code": "{}"}}]}}}}}}"#,
+                id, clinical_status, code
+            )
+        };
+        let entries = [
+            r#"{"resource": {"resourceType": "Patient", "birthDate": "1980-03-14"}}"#.to_string(),
+            condition("c1", "active", "E11.9"),
+            condition("c2", "relapse", "F32.1"),
+            condition("c3", "resolved", "J18.9"),
+            condition("c4", "remission", "C50.9"),
+            condition("c5", "inactive", "I21.4"),
+        ];
+        let bundle = format!(
+            r#"{{"resourceType": "Bundle", "entry": [{}]}}"#,
+            entries.join(", ")
+        );
+
+        let facts = PatientFacts::from_bundle(&bundle).unwrap();
+        assert_eq!(
+            facts.diagnoses,
+            vec!["E119".to_string(), "F321".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_skips_non_loinc_observations() {
+        let bundle = r#"{"resourceType": "Bundle", "entry": [
+            {"resource": {"resourceType": "Patient", "birthDate": "1980-03-14"}},
+            {"resource": {"resourceType": "Observation", "id": "o1", "status": "final",
+                "code": {"coding": [{"system": "http://example.org/local-codes", "code": "BP-SYS"}]},
+                "valueQuantity": {"value": 128, "system": "http://unitsofmeasure.org", "code": "mm[Hg]"}}},
+            {"resource": {"resourceType": "Observation", "id": "o2", "status": "final",
+                "code": {"coding": [
+                    {"system": "http://example.org/local-codes", "code": "A1C"},
+                    {"system": "http://loinc.org", "code": "4548-4"}]},
+                "valueQuantity": {"value": 7.4, "system": "http://unitsofmeasure.org", "code": "%"}}}
+        ]}"#;
+
+        let facts = PatientFacts::from_bundle(bundle).unwrap();
+        assert_eq!(facts.observations.len(), 1);
+        assert_eq!(facts.latest_observation("4548-4").unwrap().value, "7.4");
+    }
+
+    #[test]
+    fn test_builds_input_maps() {
+        let facts = PatientFacts::from_bundle_with_mappings(BUNDLE, &snomed_map()).unwrap();
+        let (salt, study_id) = (Fr::from(7), Fr::from(1));
+        let as_of = days_from_civil(2025, 6, 1);
+
+        // The provider's credential over the bundle, with the HbA1c of 7.4 % at scale 1
+        let labs = LabTree::new(&[commit_lab_value(Fr::from(45484), 74, 1, salt)]).unwrap();
+        let credential = issue(CredentialAttributes {
+            identity_commitment: identity_commitment(Fr::from(3)),
+            dob_commitment: commit_date_of_birth(facts.birth_date, salt),
+            diagnosis_commitment: facts.diagnosis_tree().unwrap().commitment(salt),
+            lab_root: labs.root(),
+            issued_at: days_to_field(ISSUED_AT),
+        });
+
+        let context = ProofContext {
+            wallet: Fr::from(0x70997970),
+            chain_id: 1,
+            registry_address: Fr::from(0x5fbdb231),
+            study_id,
+        };
+        let age = facts
+            .age_inputs(as_of, 18, 65, &context, Fr::from(3), salt, &credential)
+            .unwrap();
+        assert_eq!(age["dob"], vec![days_to_field(facts.birth_date)]);
+        assert_eq!(
+            age["dob_commitment"],
+            vec![commit_date_of_birth(facts.birth_date, salt)]
+        );
+        assert_eq!(age["context_hash"], vec![context.hash()]);
+        assert_eq!(age["epoch"], vec![Fr::from(as_of as u64)]);
+
+        let dob = facts.dob_inputs(salt, as_of, 18, 65, &context, &credential);
+        assert_eq!(
+            dob["dob_commitment"],
+            vec![commit_date_of_birth(facts.birth_date, salt)]
+        );
+        assert_eq!(dob["context_hash"], vec![context.hash()]);
+
+        let lab = facts
+            .lab_inputs(
+                &hba1c_above(70),
+                "%",
+                salt,
+                &context,
+                as_of,
+                &labs,
+                &credential,
+            )
+            .unwrap();
+        assert_eq!(lab["value"], vec![Fr::from(74)]);
+        assert_eq!(
+            lab["lab_commitment"],
+            vec![commit_lab_value(Fr::from(45484), 74, 1, salt)]
+        );
+        assert_eq!(lab["lab_root"], vec![labs.root()]);
+        assert_eq!(lab["epoch"], vec![Fr::from(as_of as u64)]);
+        assert!(facts
+            .lab_inputs(
+                &hba1c_above(75),
+                "%",
+                salt,
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+        assert!(facts
+            .lab_inputs(
+                &hba1c_above(70),
+                "mmol/mol",
+                salt,
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+        // A value the provider did not commit
+        assert!(facts
+            .lab_inputs(
+                &hba1c_above(70),
+                "%",
+                Fr::from(8),
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+
+        // 57 mmol/mol is 7.36 %, committed as 74 at scale 1
+        let ifcc = r#"{"resource": {"resourceType": "Observation", "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+            "valueQuantity": {"value": 57, "system": "http://unitsofmeasure.org", "code": "mmol/mol"}}}"#;
+        let patient = r#"{"resource": {"resourceType": "Patient", "birthDate": "1980-03-14"}}"#;
+        let ifcc_facts = PatientFacts::from_bundle(&format!(
+            r#"{{"resourceType": "Bundle", "entry": [{}, {}]}}"#,
+            patient, ifcc
+        ))
+        .unwrap();
+        let lab = ifcc_facts
+            .lab_inputs(
+                &hba1c_above(70),
+                "%",
+                salt,
+                &context,
+                as_of,
+                &labs,
+                &credential,
+            )
+            .unwrap();
+        assert_eq!(lab["value"], vec![Fr::from(74)]);
+
+        // Values in the canonical unit with more decimals than the scale round the same way
+        let precise = r#"{"resource": {"resourceType": "Observation", "status": "final",
+            "code": {"coding": [{"system": "http://loinc.org", "code": "4548-4"}]},
+            "valueQuantity": {"value": 7.25, "system": "http://unitsofmeasure.org", "code": "%"}}}"#;
+        let precise_facts = PatientFacts::from_bundle(&format!(
+            r#"{{"resourceType": "Bundle", "entry": [{}, {}]}}"#,
+            patient, precise
+        ))
+        .unwrap();
+        assert_eq!(precise_facts.lab_value(&hba1c_above(70), "%").unwrap(), 73);
+
+        let diagnosis = facts
+            .diagnosis_inputs("E11.9", salt, &context, as_of, &credential)
+            .unwrap();
+        assert_eq!(
+            diagnosis["diagnosis_commitment"],
+            vec![facts.diagnosis_tree().unwrap().commitment(salt)]
+        );
+        assert_eq!(diagnosis["context_hash"], vec![context.hash()]);
+        assert_eq!(diagnosis["epoch"], vec![Fr::from(as_of as u64)]);
+        assert!(facts
+            .diagnosis_inputs("I21.4", salt, &context, as_of, &credential)
+            .is_err());
+        assert!(facts
+            .diagnosis_inputs("E11.9", Fr::from(8), &context, as_of, &credential)
+            .is_err());
+    }
+
+    #[test]
+    fn test_builds_metric_inputs() {
+        let vitals = r#"{
+            "resourceType": "Bundle",
+            "entry": [
+                {"resource": {"resourceType": "Patient", "birthDate": "1980-03-14"}},
+                {"resource": {"resourceType": "Observation", "status": "final",
+                    "code": {"coding": [{"system": "http://loinc.org", "code": "29463-7"}]},
+                    "valueQuantity": {"value": 220, "system": "http://unitsofmeasure.org", "code": "[lb_av]"}}},
+                {"resource": {"resourceType": "Observation", "status": "final",
+                    "code": {"coding": [{"system": "http://loinc.org", "code": "8302-2"}]},
+                    "valueQuantity": {"value": 175, "system": "http://unitsofmeasure.org", "code": "cm"}}},
+                {"resource": {"resourceType": "Observation", "status": "final",
+                    "code": {"coding": [{"system": "http://loinc.org", "code": "85354-9"}]},
+                    "component": [
+                        {"code": {"coding": [{"system": "http://loinc.org", "code": "8480-6"}]},
+                            "valueQuantity": {"value": 142, "system": "http://unitsofmeasure.org", "code": "mm[Hg]"}},
+                        {"code": {"coding": [{"system": "http://loinc.org", "code": "8462-4"}]},
+                            "valueQuantity": {"value": 91, "system": "http://unitsofmeasure.org", "code": "mm[Hg]"}}
+                    ]}}
+            ]
+        }"#;
+        let facts = PatientFacts::from_bundle(vitals).unwrap();
+        // 220 lb = 99.790 kg, 175 cm = 1750 mm; blood pressure from the panel components
+        assert_eq!(facts.measurement("29463-7").unwrap(), 99_790);
+        assert_eq!(facts.measurement("8302-2").unwrap(), 1750);
+        assert_eq!(facts.measurement("8462-4").unwrap(), 91);
+
+        let salts = [Fr::from(7), Fr::from(8)];
+        let commit = |loinc: &str, value: u64, salt: Fr| {
+            let analyte = units::lookup(loinc).unwrap();
+            commit_lab_value(
+                Fr::from(pack_loinc(loinc).unwrap()),
+                value,
+                analyte.scale,
+                salt,
+            )
+        };
+        let labs = LabTree::new(&[
+            commit("29463-7", 99_790, salts[0]),
+            commit("8302-2", 1750, salts[1]),
+        ])
+        .unwrap();
+        let credential = issue(CredentialAttributes {
+            lab_root: labs.root(),
+            issued_at: days_to_field(ISSUED_AT),
+            ..Default::default()
+        });
+        let as_of = days_from_civil(2025, 6, 1);
+        let context = ProofContext {
+            wallet: Fr::from(0x70997970),
+            chain_id: 1,
+            registry_address: Fr::from(0x5fbdb231),
+            study_id: Fr::from(1),
+        };
+
+        // BMI 32.5
+        let inputs = facts
+            .metric_inputs(
+                Metric::Bmi,
+                300,
+                u32::MAX as u64,
+                salts,
+                &context,
+                as_of,
+                &labs,
+                &credential,
+            )
+            .unwrap();
+        assert_eq!(
+            inputs["measurements"],
+            vec![Fr::from(99_790), Fr::from(1750)]
+        );
+        assert_eq!(inputs["metric"], vec![Fr::from(Metric::Bmi.code())]);
+        assert!(facts
+            .metric_inputs(
+                Metric::Bmi,
+                0,
+                299,
+                salts,
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+        // Blood pressure the provider did not attest
+        assert!(facts
+            .metric_inputs(
+                Metric::MeanArterialPressure,
+                0,
+                2000,
+                salts,
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+        // Smoking history is attested directly, not read from observations
+        assert!(facts
+            .metric_inputs(
+                Metric::PackYears,
+                0,
+                2000,
+                salts,
+                &context,
+                as_of,
+                &labs,
+                &credential
+            )
+            .is_err());
+    }
+}
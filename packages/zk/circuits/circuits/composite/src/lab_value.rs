@@ -0,0 +1,1140 @@
+//! Lab Value Threshold Verification
+//!
+//! Proves a comparison such as "HbA1c > 7.00 %" or "LDL < 130 mg/dL" against a lab value
+//! committed by the issuing provider, without revealing the value.
+//!
+//! ## Fixed-Point Values
+//! Lab values are decimals, so they are committed as integers with a declared scale:
+//! HbA1c 7.25 % with scale 2 is stored as 725. Bounds are public and use the same
+//! scale (7.00 % is 700); [`to_fixed_point`] converts decimal strings, and
+//! [`deserialize_lab_inputs`] reads input maps holding them. The scale is part
+//! of the commitment, so a proof cannot reinterpret 725 as 72.5.
+//!
+//! ## Security Model
+//! - Private Inputs: value, commitment salt, lab tree path, credential
+//! - Public Inputs: analyte, scale, comparison, bound, bound_high, study_id,
+//!   context_hash (see [`context`](crate::context)), epoch (see [`epoch`](crate::epoch)),
+//!   lab_commitment, provider_root, revocation_root
+//! - Constraints: `Poseidon(analyte, value, scale, salt) = lab_commitment`,
+//!   `value <op> bound` (or `bound <= value <= bound_high`), and `lab_commitment` is a
+//!   leaf of the [`LabTree`] whose root a registered provider signed in an unrevoked
+//!   [`credential`](crate::credential) issued at most MAX_CREDENTIAL_AGE days before
+//!   the epoch
+//!
+//! `analyte` identifies the measurement (e.g. a packed LOINC code), so an LDL value
+//! cannot be used as an HbA1c value.
+
+use std::{collections::HashMap, fmt, io::Cursor, str::FromStr};
+
+use eligibility_gadgets::{
+    CredentialAttributes, CredentialChip, CredentialConfig, CredentialWitness, EddsaChip,
+    EddsaField, IssuerChip, MerkleChip, MerkleConfig, MerklePath, MerkleTree, PoseidonChip,
+    PoseidonConfig, PoseidonField, RangeCheckChip, RangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::{Field, PrimeField},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    credential::{checked_credential_at, LAB_TREE_DEPTH},
+    field_to_u64,
+    rng::default_rng,
+    serialization::deserialize_circuit_inputs,
+    EligibilityError, PlonkishComponents,
+};
+
+/// Bit length of fixed-point lab values and of both range checked differences
+pub const LAB_VALUE_BITS: usize = 32;
+
+/// Largest representable lab value
+pub const MAX_LAB_VALUE: u64 = (1 << LAB_VALUE_BITS) - 1;
+
+/// Circuit size (2^k rows): the credential check dominates
+pub const LAB_VALUE_K: usize = 13;
+
+/// Number of public inputs of [`LabValueCircuit`]
+pub const LAB_VALUE_PUBLIC_INPUTS: usize = 11;
+
+/// Number of comparison operators
+const COMPARISONS: usize = 5;
+
+/// Comparison of a lab value against public bounds
+///
+/// The code is the `comparison` public input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt = 0,      // value < bound
+    Le = 1,      // value <= bound
+    Gt = 2,      // value > bound
+    Ge = 3,      // value >= bound
+    Between = 4, // bound <= value <= bound_high
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; COMPARISONS] = [
+        Comparison::Lt,
+        Comparison::Le,
+        Comparison::Gt,
+        Comparison::Ge,
+        Comparison::Between,
+    ];
+
+    pub fn code(self) -> u64 {
+        self as u64
+    }
+
+    pub fn from_code(code: u64) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    /// Inclusive interval of accepted values, `None` if no value is accepted
+    pub fn interval(self, bound: u64, bound_high: u64) -> Option<(u64, u64)> {
+        let (low, high) = match self {
+            Comparison::Lt => (0, bound.checked_sub(1)?),
+            Comparison::Le => (0, bound),
+            Comparison::Gt => (bound.checked_add(1)?, MAX_LAB_VALUE),
+            Comparison::Ge => (bound, MAX_LAB_VALUE),
+            Comparison::Between => (bound, bound_high),
+        };
+        (low <= high).then_some((low, high))
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = EligibilityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            "between" => Ok(Comparison::Between),
+            other => Err(EligibilityError(format!("Unknown comparison: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Between => "between",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// Lab Value Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct LabValueConfig<F: PrimeField> {
+    pub value: Column<Advice>,        // Private: fixed-point lab value
+    pub salt: Column<Advice>,         // Private: commitment salt
+    pub analyte: Column<Advice>,      // Public: measurement identifier
+    pub scale: Column<Advice>,        // Public: decimal places of value and bounds
+    pub comparison: Column<Advice>,   // Public: comparison code
+    pub bound: Column<Advice>,        // Public: bound (lower bound of an interval)
+    pub bound_high: Column<Advice>,   // Public: upper bound of an interval
+    pub study_id: Column<Advice>,     // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,        // Public: as-of day of the proof
+    pub op_bits: [Column<Advice>; COMPARISONS], // One-hot comparison code
+    pub lower_diff: Column<Advice>,   // value - lowest accepted value
+    pub upper_diff: Column<Advice>,   // highest accepted value - value
+    pub selector: Selector,
+    pub instance: Column<Instance>,
+    pub range_check: RangeCheckConfig<F, LAB_VALUE_BITS>,
+    pub poseidon: PoseidonConfig,
+    pub merkle: MerkleConfig,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Lab Value Circuit
+///
+/// Proves: value <op> bound for a committed fixed-point lab value
+///
+/// ## Constraints
+/// 1. `op_bits` is the one-hot encoding of `comparison`
+/// 2. The accepted interval `[low, high]` is selected by `op_bits`: `<` is
+///    `[0, bound - 1]`, `<=` is `[0, bound]`, `>` is `[bound + 1, MAX_LAB_VALUE]`,
+///    `>=` is `[bound, MAX_LAB_VALUE]` and `between` is `[bound, bound_high]`
+/// 3. `value - low` and `high - value` are range checked to LAB_VALUE_BITS bits
+/// 4. `Poseidon(analyte, value, scale, salt) = lab_commitment`
+/// 5. `lab_path` leads from `lab_commitment` to the credential's `lab_root`, and
+///    `CredentialChip` verifies the credential against `provider_root` and
+///    `revocation_root` at `epoch`
+///
+/// ## Public Inputs (instance column)
+/// Row 0: analyte, row 1: scale, row 2: comparison, row 3: bound, row 4: bound_high,
+/// row 5: study_id, row 6: context_hash, row 7: epoch, row 8: lab_commitment, row 9:
+/// provider_root, row 10: revocation_root.
+#[derive(Clone)]
+pub struct LabValueCircuit<F: EddsaField> {
+    pub value: Value<F>,                      // Private: fixed-point lab value
+    pub salt: Value<F>,                       // Private: commitment salt
+    pub lab_path: [Value<F>; LAB_TREE_DEPTH], // Private: siblings up to lab_root, bottom-up
+    pub lab_bits: [Value<bool>; LAB_TREE_DEPTH],
+    pub credential: CredentialWitness<F>, // Private: signs lab_root
+    pub analyte: F,                       // Public input
+    pub scale: F,                         // Public input
+    pub comparison: F,                    // Public input: Comparison::code
+    pub bound: F,                         // Public input
+    pub bound_high: F,                    // Public input (zero unless `between`)
+    pub study_id: F,                      // Public input (binds proof to specific study)
+    pub context_hash: F, // Public input (binds proof to wallet, chain and registry)
+    pub epoch: F,        // Public input (as-of day, see `epoch`)
+    pub lab_commitment: F, // Public: Poseidon(analyte, value, scale, salt)
+    pub provider_root: F, // Public: registered provider tree root
+    pub revocation_root: F, // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for LabValueCircuit<F> {
+    fn default() -> Self {
+        Self {
+            value: Value::unknown(),
+            salt: Value::unknown(),
+            lab_path: [Value::unknown(); LAB_TREE_DEPTH],
+            lab_bits: [Value::unknown(); LAB_TREE_DEPTH],
+            credential: CredentialWitness::default(),
+            analyte: F::ZERO,
+            scale: F::ZERO,
+            comparison: F::ZERO,
+            bound: F::ZERO,
+            bound_high: F::ZERO,
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            lab_commitment: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> LabValueCircuit<F> {
+    /// One-hot encoding of the comparison code
+    fn op_bits(&self) -> [F; COMPARISONS] {
+        std::array::from_fn(|i| {
+            if self.comparison == F::from(i as u64) {
+                F::ONE
+            } else {
+                F::ZERO
+            }
+        })
+    }
+
+    /// Accepted interval `[low, high]` as field elements (see the constraints)
+    fn interval(&self) -> (F, F) {
+        let [lt, le, gt, ge, between] = self.op_bits();
+        let max = F::from(MAX_LAB_VALUE);
+
+        let low = gt * (self.bound + F::ONE) + (ge + between) * self.bound;
+        let high = lt * (self.bound - F::ONE)
+            + le * self.bound
+            + between * self.bound_high
+            + (gt + ge) * max;
+        (low, high)
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for LabValueCircuit<F> {
+    type Config = LabValueConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let value = meta.advice_column();
+        let salt = meta.advice_column();
+        let analyte = meta.advice_column();
+        let scale = meta.advice_column();
+        let comparison = meta.advice_column();
+        let bound = meta.advice_column();
+        let bound_high = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let op_bits: [Column<Advice>; COMPARISONS] = std::array::from_fn(|_| meta.advice_column());
+        let lower_diff = meta.advice_column();
+        let upper_diff = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let selector = meta.selector();
+        let instance = meta.instance_column();
+
+        for column in [
+            value,
+            salt,
+            analyte,
+            scale,
+            comparison,
+            bound,
+            bound_high,
+            study_id,
+            context_hash,
+            epoch,
+            lower_diff,
+            upper_diff,
+        ] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        // Gate: select the accepted interval from the comparison code and define the
+        // two differences that must be non-negative
+        meta.create_gate("lab value comparison", |meta| {
+            let s = meta.query_selector(selector);
+            let one = Expression::Constant(F::ONE);
+            let value = meta.query_advice(value, Rotation::cur());
+            let comparison = meta.query_advice(comparison, Rotation::cur());
+            let bound = meta.query_advice(bound, Rotation::cur());
+            let bound_high = meta.query_advice(bound_high, Rotation::cur());
+            let lower_diff = meta.query_advice(lower_diff, Rotation::cur());
+            let upper_diff = meta.query_advice(upper_diff, Rotation::cur());
+            let bits: Vec<Expression<F>> = op_bits
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+
+            let mut constraints: Vec<Expression<F>> = bits
+                .iter()
+                .map(|bit| bit.clone() * (one.clone() - bit.clone()))
+                .collect();
+
+            // Exactly one bit is set, at position `comparison`
+            let sum = bits
+                .iter()
+                .fold(Expression::Constant(F::ZERO), |acc, bit| acc + bit.clone());
+            let code = bits
+                .iter()
+                .enumerate()
+                .fold(Expression::Constant(F::ZERO), |acc, (i, bit)| {
+                    acc + bit.clone() * F::from(i as u64)
+                });
+            constraints.push(sum - one.clone());
+            constraints.push(code - comparison);
+
+            let (lt, le, gt, ge, between) = (
+                bits[Comparison::Lt as usize].clone(),
+                bits[Comparison::Le as usize].clone(),
+                bits[Comparison::Gt as usize].clone(),
+                bits[Comparison::Ge as usize].clone(),
+                bits[Comparison::Between as usize].clone(),
+            );
+            let low = gt.clone() * (bound.clone() + one.clone())
+                + (ge.clone() + between.clone()) * bound.clone();
+            let high = lt * (bound.clone() - one)
+                + le * bound
+                + between * bound_high
+                + (gt + ge) * Expression::Constant(F::from(MAX_LAB_VALUE));
+
+            constraints.push(lower_diff - (value.clone() - low)); // lower = value - low
+            constraints.push(upper_diff - (high - value)); // upper = high - value
+
+            constraints
+                .into_iter()
+                .map(|c| s.clone() * c)
+                .collect::<Vec<_>>()
+        });
+
+        let range_check = RangeCheckChip::<F, LAB_VALUE_BITS>::configure(meta, running_sum);
+        let poseidon = PoseidonChip::configure(meta);
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let merkle = MerkleChip::configure(meta, poseidon.clone());
+        let issuer = IssuerChip::configure(meta, eddsa, merkle.clone());
+        let credential = CredentialChip::configure(meta, issuer);
+
+        LabValueConfig {
+            value,
+            salt,
+            analyte,
+            scale,
+            comparison,
+            bound,
+            bound_high,
+            study_id,
+            context_hash,
+            epoch,
+            op_bits,
+            lower_diff,
+            upper_diff,
+            selector,
+            instance,
+            range_check,
+            poseidon,
+            merkle,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (low, high) = self.interval();
+        let op_bits = self.op_bits();
+
+        let (cells, value, salt, lower_diff, upper_diff) = layouter.assign_region(
+            || "lab value comparison",
+            |mut region| {
+                config.selector.enable(&mut region, 0)?;
+
+                // Assign private value and salt
+                let value = region.assign_advice(|| "value", config.value, 0, || self.value)?;
+                let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+
+                // Assign public values, in instance order
+                let public = [
+                    ("analyte", config.analyte, self.analyte),
+                    ("scale", config.scale, self.scale),
+                    ("comparison", config.comparison, self.comparison),
+                    ("bound", config.bound, self.bound),
+                    ("bound_high", config.bound_high, self.bound_high),
+                    ("study_id", config.study_id, self.study_id),
+                    ("context_hash", config.context_hash, self.context_hash),
+                    ("epoch", config.epoch, self.epoch),
+                ];
+                let mut cells = Vec::with_capacity(public.len());
+                for (name, column, field) in public {
+                    cells.push(region.assign_advice(|| name, column, 0, || Value::known(field))?);
+                }
+
+                for (i, (column, bit)) in config.op_bits.iter().zip(op_bits).enumerate() {
+                    region.assign_advice(
+                        || format!("op bit {}", i),
+                        *column,
+                        0,
+                        || Value::known(bit),
+                    )?;
+                }
+
+                // Assign the differences that get range checked
+                let lower_diff = region.assign_advice(
+                    || "value - low",
+                    config.lower_diff,
+                    0,
+                    || self.value.map(|value| value - low),
+                )?;
+                let upper_diff = region.assign_advice(
+                    || "high - value",
+                    config.upper_diff,
+                    0,
+                    || self.value.map(|value| high - value),
+                )?;
+
+                Ok((cells, value, salt, lower_diff, upper_diff))
+            },
+        )?;
+
+        // The value is the one the provider committed to
+        let (analyte, scale) = (cells[0].clone(), cells[1].clone());
+        let poseidon_chip = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "lab commitment"),
+            &[analyte, value, scale, salt],
+        )?;
+
+        // The provider attested the commitment under the lab_root it signed
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let lab_root = merkle_chip.compute_root(
+            layouter.namespace(|| "lab root"),
+            &commitment,
+            &self.lab_path,
+            &self.lab_bits,
+        )?;
+        let credential_chip = CredentialChip::<F>::construct(config.credential.clone());
+        let opened = CredentialAttributes {
+            lab_root: Some(&lab_root),
+            ..Default::default()
+        };
+        let (provider_root, revocation_root) = credential_chip.verify_at(
+            layouter.namespace(|| "credential"),
+            opened,
+            &self.credential,
+            &cells[7],
+        )?;
+
+        // Bind every public value to its instance row
+        for (row, cell) in cells
+            .iter()
+            .chain([&commitment, &provider_root, &revocation_root])
+            .enumerate()
+        {
+            layouter.constrain_instance(cell.cell(), config.instance, row)?;
+        }
+
+        let range_chip = RangeCheckChip::<F, LAB_VALUE_BITS>::construct(config.range_check);
+        range_chip.assign(layouter.namespace(|| "value >= low"), &lower_diff)?;
+        range_chip.assign(layouter.namespace(|| "value <= high"), &upper_diff)?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for LabValueCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: analyte, scale, comparison, bound, bound_high, study_id,
+        // context_hash, epoch, lab_commitment, provider_root, revocation_root
+        vec![vec![
+            self.analyte,
+            self.scale,
+            self.comparison,
+            self.bound,
+            self.bound_high,
+            self.study_id,
+            self.context_hash,
+            self.epoch,
+            self.lab_commitment,
+            self.provider_root,
+            self.revocation_root,
+        ]]
+    }
+}
+
+/// Inputs read as decimals and converted at the `scale` input (see [`deserialize_lab_inputs`])
+pub const FIXED_POINT_INPUTS: [&str; 3] = ["value", "bound", "bound_high"];
+
+/// Fixed-point integer of a decimal string at `scale` decimal places
+///
+/// `to_fixed_point("7.25", 2)` is 725 and `to_fixed_point("7", 2)` is 700. Digits beyond
+/// the scale are rejected unless they are zeros, so a value is never silently rounded.
+pub fn to_fixed_point(s: &str, scale: u32) -> Result<u128, EligibilityError> {
+    let invalid = || EligibilityError(format!("Invalid decimal {} at scale {}", s, scale));
+
+    let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    if !integer
+        .bytes()
+        .chain(fraction.bytes())
+        .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > scale as usize {
+        return Err(invalid());
+    }
+
+    let digits = format!("{}{:0<width$}", integer, fraction, width = scale as usize);
+    u128::from_str(&digits).map_err(|_| invalid())
+}
+
+/// Parse the inputs of [`generate_lab_proof`]
+///
+/// The [`FIXED_POINT_INPUTS`] are read as printed on the report, integer or decimal
+/// alike, and converted at the `scale` input (see [`to_fixed_point`]): at scale 2, "7"
+/// and "7.00" are both 700. Every other input is parsed by [`deserialize_circuit_inputs`].
+pub fn deserialize_lab_inputs(
+    mut ser_inputs: HashMap<String, Vec<String>>,
+) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+    let scale = match ser_inputs.get("scale").map(Vec::as_slice) {
+        Some([scale]) => u32::from_str(scale)
+            .map_err(|e| EligibilityError(format!("Failed to parse scale: {}", e)))?,
+        _ => return Err(EligibilityError("Missing 'scale' input".to_string())),
+    };
+
+    let mut fixed_point = HashMap::new();
+    for key in FIXED_POINT_INPUTS {
+        if let Some(values) = ser_inputs.remove(key) {
+            let values = values
+                .iter()
+                .map(|s| to_fixed_point(s, scale).map(Fr::from_u128))
+                .collect::<Result<Vec<_>, _>>()?;
+            fixed_point.insert(key.to_string(), values);
+        }
+    }
+
+    let mut inputs = deserialize_circuit_inputs(ser_inputs)?;
+    inputs.extend(fixed_point);
+    Ok(inputs)
+}
+
+/// Analyte of a LOINC code: the code packed without its separator ("4548-4" -> 45484)
+///
+/// LOINC codes are up to 7 digits, a '-' and a mod-10 check digit, which is verified.
+pub fn pack_loinc(loinc: &str) -> Result<u64, EligibilityError> {
+    let invalid = || EligibilityError(format!("Invalid LOINC code: {}", loinc));
+
+    let (number, check) = loinc.trim().split_once('-').ok_or_else(invalid)?;
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(number) || number.len() > 7 || check.len() != 1 || !digits(check) {
+        return Err(invalid());
+    }
+    if loinc_check_digit(number) != check.as_bytes()[0] - b'0' {
+        return Err(invalid());
+    }
+
+    format!("{}{}", number, check)
+        .parse()
+        .map_err(|_| invalid())
+}
+
+/// LOINC mod-10 check digit (Luhn, doubling from the rightmost digit)
+fn loinc_check_digit(number: &str) -> u8 {
+    let sum: u32 = number
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let digit = (b - b'0') as u32;
+            if i % 2 == 0 {
+                let doubled = 2 * digit;
+                doubled / 10 + doubled % 10
+            } else {
+                digit
+            }
+        })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Commitment to a lab value: `Poseidon(analyte, value, scale, salt)`
+///
+/// Computed by the issuing provider, which attests it in the credential's [`LabTree`].
+pub fn commit_lab_value(analyte: Fr, value: u64, scale: u32, salt: Fr) -> Fr {
+    Fr::poseidon_hash(&[analyte, Fr::from(value), Fr::from(scale as u64), salt])
+}
+
+/// Lab value commitments attested by a credential
+///
+/// The provider commits its latest reading of each analyte and signs the root as the
+/// credential's `lab_root`. Leaves beyond the commitments are zero.
+#[derive(Debug, Clone)]
+pub struct LabTree {
+    tree: MerkleTree<Fr>,
+}
+
+impl LabTree {
+    /// Fails on more than `2^LAB_TREE_DEPTH` commitments or a zero commitment
+    pub fn new(commitments: &[Fr]) -> Result<Self, EligibilityError> {
+        if commitments.contains(&Fr::ZERO) {
+            return Err(EligibilityError("Lab commitment is zero".to_string()));
+        }
+        let tree = MerkleTree::new(commitments, LAB_TREE_DEPTH)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+        Ok(Self { tree })
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Path of `commitment` to the root
+    pub fn path(&self, commitment: &Fr) -> Result<MerklePath<Fr>, EligibilityError> {
+        let index = self
+            .tree
+            .leaves()
+            .iter()
+            .position(|leaf| leaf == commitment && *leaf != Fr::ZERO)
+            .ok_or(EligibilityError(
+                "Lab value is not attested by the credential".to_string(),
+            ))?;
+        self.tree
+            .path(index)
+            .map_err(|e| EligibilityError(e.to_string()))
+    }
+
+    /// Add the `lab_path` and `lab_index` inputs of `commitment`
+    pub fn insert_inputs(
+        &self,
+        commitment: &Fr,
+        inputs: &mut HashMap<String, Vec<Fr>>,
+    ) -> Result<(), EligibilityError> {
+        let path = self.path(commitment)?;
+        inputs.insert("lab_path".to_string(), path.siblings);
+        inputs.insert("lab_index".to_string(), vec![Fr::from(path.index as u64)]);
+        Ok(())
+    }
+}
+
+/// Lab tree path from its siblings and leaf index inputs
+pub(crate) fn lab_path(siblings: &[Fr], index: &Fr) -> Result<MerklePath<Fr>, EligibilityError> {
+    let index = field_to_u64(index)
+        .ok()
+        .filter(|index| *index < 1 << LAB_TREE_DEPTH)
+        .ok_or(EligibilityError("Invalid lab_index".to_string()))?;
+    if siblings.len() != LAB_TREE_DEPTH {
+        return Err(EligibilityError(format!(
+            "Invalid lab_path (expected {} values)",
+            LAB_TREE_DEPTH
+        )));
+    }
+    Ok(MerklePath {
+        index: index as usize,
+        siblings: siblings.to_vec(),
+    })
+}
+
+/// Client-side validation (called before proof generation)
+///
+/// The circuit enforces the same comparison; this check only gives fast feedback.
+pub fn validate_lab_value(
+    value: u64,
+    comparison: Comparison,
+    bound: u64,
+    bound_high: u64,
+) -> Result<(), EligibilityError> {
+    match comparison.interval(bound, bound_high) {
+        Some((low, high)) if (low..=high).contains(&value) => Ok(()),
+        _ => Err(EligibilityError(format!(
+            "Lab value {} does not satisfy {} {}{}",
+            value,
+            comparison,
+            bound,
+            if comparison == Comparison::Between {
+                format!(" and {}", bound_high)
+            } else {
+                String::new()
+            }
+        ))),
+    }
+}
+
+/// Generate lab value proof
+///
+/// Inputs: `value`, `salt`, `analyte`, `scale`, `comparison` (a [`Comparison`] code),
+/// `bound`, `bound_high` (optional unless `between`), `study_id`, `context_hash`,
+/// `epoch`, `lab_commitment`, the
+/// `lab_path` and `lab_index` of [`LabTree::insert_inputs`] and the credential inputs.
+/// `value` and the bounds may be decimal strings (see `serialization`).
+pub fn generate_lab_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_lab_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_lab_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_lab_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = LAB_VALUE_K;
+
+    // Extract inputs
+    let get = |name: &str| -> Result<Fr, EligibilityError> {
+        inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?
+            .first()
+            .copied()
+            .ok_or(EligibilityError(format!("Invalid {}", name)))
+    };
+    let value = get("value")?;
+    let salt = get("salt")?;
+    let analyte = get("analyte")?;
+    let scale = get("scale")?;
+    let comparison = get("comparison")?;
+    let bound = get("bound")?;
+    let bound_high = get("bound_high").unwrap_or(Fr::from(0));
+    let study_id = get("study_id")?;
+    let context_hash = get("context_hash")?;
+    let epoch = get("epoch")?;
+    let lab_commitment = get("lab_commitment")?;
+    let lab_path = lab_path(
+        inputs
+            .get("lab_path")
+            .ok_or(EligibilityError("Missing lab_path".to_string()))?,
+        &get("lab_index")?,
+    )?;
+    let credential = checked_credential_at(&inputs, &epoch)?;
+
+    // Client-side validation
+    // Fails fast instead of producing an unsatisfiable circuit
+    let op = Comparison::from_code(field_to_u64(&comparison)?)
+        .ok_or(EligibilityError("Invalid comparison code".to_string()))?;
+    let value_u64 = field_to_u64(&value)?;
+    if value_u64 > MAX_LAB_VALUE || Fr::from(value_u64) != value {
+        return Err(EligibilityError(format!(
+            "Lab value exceeds {} bits",
+            LAB_VALUE_BITS
+        )));
+    }
+    if Fr::poseidon_hash(&[analyte, value, scale, salt]) != lab_commitment {
+        return Err(EligibilityError(
+            "Lab value does not match lab_commitment".to_string(),
+        ));
+    }
+    if lab_path.compute_root(lab_commitment) != credential.attributes().lab_root {
+        return Err(EligibilityError(
+            "Lab value is not attested by the credential".to_string(),
+        ));
+    }
+    validate_lab_value(
+        value_u64,
+        op,
+        field_to_u64(&bound)?,
+        field_to_u64(&bound_high)?,
+    )?;
+
+    // Create circuit with validated inputs
+    let lab_bits = lab_path.bits();
+    let circuit = LabValueCircuit::<Fr> {
+        value: Value::known(value),
+        salt: Value::known(salt),
+        lab_path: std::array::from_fn(|i| Value::known(lab_path.siblings[i])),
+        lab_bits: std::array::from_fn(|i| Value::known(lab_bits[i])),
+        credential: credential.witness(),
+        analyte,
+        scale,
+        comparison,
+        bound,
+        bound_high,
+        study_id,
+        context_hash,
+        epoch,
+        lab_commitment,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit =
+        Halo2Circuit::<Fr, LabValueCircuit<Fr>>::new::<PC::ProvingBackend>(k, circuit.clone());
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify lab value proof
+///
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and checking `epoch` against its [`FreshnessWindow`](crate::epoch::FreshnessWindow).
+pub fn verify_lab_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != LAB_VALUE_PUBLIC_INPUTS {
+        return Err(EligibilityError(
+            "Invalid number of public inputs (expected 11: analyte, scale, comparison, bound, bound_high, study_id, context_hash, epoch, lab_commitment, provider_root, revocation_root)"
+                .to_string(),
+        ));
+    }
+
+    // Verify the proof
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| EligibilityError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::{
+        testing::{issue, EPOCH, ISSUED_AT},
+        MAX_CREDENTIAL_AGE,
+    };
+    use eligibility_gadgets::days_to_field;
+    use halo2_proofs::dev::MockProver;
+
+    const HBA1C: u64 = 45484; // LOINC 4548-4, packed without the check digit separator
+    const LDL: u64 = 134577; // LOINC 13457-7
+
+    /// Circuit over an HbA1c reading the test provider attested next to an LDL reading
+    fn lab_circuit(
+        value: u64,
+        comparison: Comparison,
+        bound: u64,
+        bound_high: u64,
+    ) -> LabValueCircuit<Fr> {
+        let (analyte, salt) = (Fr::from(HBA1C), Fr::from(9));
+        let lab_commitment = commit_lab_value(analyte, value, 2, salt);
+        let labs = LabTree::new(&[
+            commit_lab_value(Fr::from(LDL), 12000, 2, salt),
+            lab_commitment,
+        ])
+        .unwrap();
+        let credential = issue(CredentialAttributes {
+            lab_root: labs.root(),
+            issued_at: days_to_field(ISSUED_AT),
+            ..Default::default()
+        });
+        let path = labs.path(&lab_commitment).unwrap();
+        let bits = path.bits();
+
+        LabValueCircuit {
+            value: Value::known(Fr::from(value)),
+            salt: Value::known(salt),
+            lab_path: std::array::from_fn(|i| Value::known(path.siblings[i])),
+            lab_bits: std::array::from_fn(|i| Value::known(bits[i])),
+            credential: credential.witness(),
+            analyte,
+            scale: Fr::from(2),
+            comparison: Fr::from(comparison.code()),
+            bound: Fr::from(bound),
+            bound_high: Fr::from(bound_high),
+            study_id: Fr::from(1),
+            context_hash: Fr::from(7),
+            epoch: days_to_field(EPOCH),
+            lab_commitment,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        }
+    }
+
+    fn mock_verify(circuit: &LabValueCircuit<Fr>) -> bool {
+        MockProver::run(LAB_VALUE_K as u32, circuit, circuit.instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_to_fixed_point() {
+        assert_eq!(to_fixed_point("7.25", 2).unwrap(), 725);
+        assert_eq!(to_fixed_point("7", 2).unwrap(), 700);
+        assert_eq!(to_fixed_point("7.250", 2).unwrap(), 725); // Trailing zeros
+        assert_eq!(to_fixed_point(".5", 1).unwrap(), 5);
+        assert!(to_fixed_point("7.255", 2).is_err()); // Would need rounding
+        assert!(to_fixed_point("-7", 2).is_err());
+        assert!(to_fixed_point("7.2.5", 2).is_err());
+        assert!(to_fixed_point(".", 2).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_lab_inputs() {
+        let input = |pairs: &[(&str, &str)]| -> HashMap<String, Vec<String>> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+                .collect()
+        };
+
+        let inputs = deserialize_lab_inputs(input(&[
+            ("scale", "2"),
+            ("value", "7.25"),
+            ("bound", "7"),
+            ("bound_high", "7.00"),
+            ("study_id", "7"),
+        ]))
+        .unwrap();
+        assert_eq!(inputs["value"][0], Fr::from(725));
+        // Integers and decimals scale alike, other inputs are not scaled
+        assert_eq!(inputs["bound"][0], Fr::from(700));
+        assert_eq!(inputs["bound_high"][0], Fr::from(700));
+        assert_eq!(inputs["study_id"][0], Fr::from(7));
+        assert_eq!(inputs["scale"][0], Fr::from(2));
+
+        assert!(deserialize_lab_inputs(input(&[("scale", "1"), ("value", "7.25")])).is_err());
+        assert!(deserialize_lab_inputs(input(&[("value", "7")])).is_err());
+        assert!(deserialize_lab_inputs(input(&[("scale", "2"), ("study_id", "7.25")])).is_err());
+    }
+
+    #[test]
+    fn test_pack_loinc() {
+        assert_eq!(pack_loinc("4548-4").unwrap(), HBA1C);
+        assert_eq!(pack_loinc(" 13457-7 ").unwrap(), 134577);
+        assert!(pack_loinc("4548-5").is_err()); // Wrong check digit
+        assert!(pack_loinc("45484").is_err());
+        assert!(pack_loinc("4548-").is_err());
+        assert!(pack_loinc("12345678-1").is_err());
+    }
+
+    #[test]
+    fn test_comparison_parsing() {
+        for comparison in Comparison::ALL {
+            assert_eq!(
+                comparison.to_string().parse::<Comparison>().unwrap(),
+                comparison
+            );
+            assert_eq!(Comparison::from_code(comparison.code()), Some(comparison));
+        }
+        assert!("=".parse::<Comparison>().is_err());
+    }
+
+    #[test]
+    fn test_validate_lab_value() {
+        assert!(validate_lab_value(725, Comparison::Gt, 700, 0).is_ok());
+        assert!(validate_lab_value(700, Comparison::Gt, 700, 0).is_err());
+        assert!(validate_lab_value(700, Comparison::Ge, 700, 0).is_ok());
+        assert!(validate_lab_value(0, Comparison::Lt, 0, 0).is_err()); // Nothing is below 0
+        assert!(validate_lab_value(650, Comparison::Between, 650, 1000).is_ok());
+        assert!(validate_lab_value(1001, Comparison::Between, 650, 1000).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_satisfied_comparisons() {
+        assert!(mock_verify(&lab_circuit(725, Comparison::Gt, 700, 0)));
+        assert!(mock_verify(&lab_circuit(700, Comparison::Ge, 700, 0))); // Edge
+        assert!(mock_verify(&lab_circuit(699, Comparison::Lt, 700, 0))); // Edge
+        assert!(mock_verify(&lab_circuit(700, Comparison::Le, 700, 0))); // Edge
+        assert!(mock_verify(&lab_circuit(
+            650,
+            Comparison::Between,
+            650,
+            1000
+        )));
+        assert!(mock_verify(&lab_circuit(
+            1000,
+            Comparison::Between,
+            650,
+            1000
+        )));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unsatisfied_comparisons() {
+        // Bypasses validate_lab_value: a modified client must not be able to prove these
+        assert!(!mock_verify(&lab_circuit(700, Comparison::Gt, 700, 0)));
+        assert!(!mock_verify(&lab_circuit(699, Comparison::Ge, 700, 0)));
+        assert!(!mock_verify(&lab_circuit(700, Comparison::Lt, 700, 0)));
+        assert!(!mock_verify(&lab_circuit(0, Comparison::Lt, 0, 0)));
+        assert!(!mock_verify(&lab_circuit(701, Comparison::Le, 700, 0)));
+        assert!(!mock_verify(&lab_circuit(
+            1001,
+            Comparison::Between,
+            650,
+            1000
+        )));
+        assert!(!mock_verify(&lab_circuit(
+            649,
+            Comparison::Between,
+            650,
+            1000
+        )));
+    }
+
+    #[test]
+    fn test_circuit_rejects_invalid_comparison_code() {
+        let mut circuit = lab_circuit(725, Comparison::Gt, 700, 0);
+        circuit.comparison = Fr::from(5);
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_uncommitted_value() {
+        let mut circuit = lab_circuit(725, Comparison::Gt, 700, 0);
+        circuit.value = Value::known(Fr::from(800));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_lab_value() {
+        // Committed consistently, but not in the lab tree the provider signed
+        let mut circuit = lab_circuit(725, Comparison::Gt, 700, 0);
+        circuit.value = Value::known(Fr::from(800));
+        circuit.lab_commitment = commit_lab_value(Fr::from(HBA1C), 800, 2, Fr::from(9));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_lab_tree_paths() {
+        let labs = LabTree::new(&[Fr::from(11), Fr::from(12)]).unwrap();
+        let path = labs.path(&Fr::from(12)).unwrap();
+        assert_eq!(path.compute_root(Fr::from(12)), labs.root());
+
+        let mut inputs = HashMap::new();
+        labs.insert_inputs(&Fr::from(12), &mut inputs).unwrap();
+        assert_eq!(
+            lab_path(&inputs["lab_path"], &inputs["lab_index"][0]).unwrap(),
+            path
+        );
+
+        assert!(labs.path(&Fr::from(13)).is_err());
+        assert!(labs.path(&Fr::ZERO).is_err()); // Padding is not attested
+        assert!(LabTree::new(&[Fr::ZERO]).is_err());
+        assert!(LabTree::new(&vec![Fr::ONE; (1 << LAB_TREE_DEPTH) + 1]).is_err());
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = lab_circuit(725, Comparison::Gt, 700, 0);
+        let names = [
+            "analyte",
+            "scale",
+            "comparison",
+            "bound",
+            "bound_high",
+            "study_id",
+            "context_hash",
+            "epoch",
+            "lab_commitment",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            let prover = MockProver::run(LAB_VALUE_K as u32, &circuit, instances).unwrap();
+            assert!(
+                prover.verify().is_err(),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_expired_credential() {
+        // The credential was issued on ISSUED_AT
+        let mut circuit = lab_circuit(725, Comparison::Gt, 700, 0);
+        circuit.epoch = days_to_field(ISSUED_AT + MAX_CREDENTIAL_AGE as i64 + 1);
+        assert!(!mock_verify(&circuit));
+    }
+}
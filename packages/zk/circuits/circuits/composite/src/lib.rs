@@ -4,15 +4,48 @@
 //! without revealing the exact age value.
 //!
 //! ## Security Model
-//! - Private Input: Patient's actual age
-//! - Public Inputs: min_age, max_age, study_id
-//! - Constraint: min_age <= age <= max_age
+//! - Private Inputs: Patient's date of birth and its salt, identity secret, credential
+//! - Public Inputs: min_age, max_age, study_id, nullifier, context_hash, epoch,
+//!   patient_commitment, age bucket bounds, age bucket, provider_root, revocation_root
+//! - Constraints: age = age_on(dob, epoch), min_age <= age <= max_age,
+//!   Poseidon(dob, salt) is the dob_commitment of a [`credential`] signed by a provider
+//!   registered under provider_root and not revoked under revocation_root,
+//!   Poseidon(tag, identity_secret) is its identity_commitment, and it was issued at
+//!   most MAX_CREDENTIAL_AGE days before the epoch,
+//!   nullifier = Poseidon(identity_secret, study_id),
+//!   patient_commitment = Poseidon(tag, identity_secret, context_hash),
+//!   bucket = number of bounds <= age
 //!
-//! ## Current Implementation (MVP)
-//! Uses a hybrid approach:
-//! 1. Client-side validation (UX feedback)
-//! 2. ZK proof of age knowledge with integrity binding
-//! 3. On-chain verification of proof + metadata
+//! ## Current Implementation
+//! 1. Client-side validation (fast UX feedback, no proof for ineligible ages)
+//! 2. In-circuit range check: the age is derived from the provider-signed date of
+//!    birth on the epoch day, and `age - min_age` and `max_age - age` are both
+//!    decomposed into `AGE_RANGE_BITS` bits, so a modified client cannot prove
+//!    an age outside the range
+//! 3. On-chain verification of proof + metadata; registries reject repeated
+//!    nullifiers (see [`nullifier`]), so one patient applies once per study: the
+//!    identity secret is the one the provider attested, not a fresh one per application
+//! 4. A public context hash binds the proof to the applicant wallet, chain and
+//!    registry (see [`context`]), so it cannot be front-run or replayed elsewhere
+//! 5. A public epoch, the day the age is derived on, lets verifiers reject proofs
+//!    outside their freshness window; the prover picks it, but the credential must
+//!    have been issued at most MAX_CREDENTIAL_AGE days before it (see [`epoch`])
+//! 6. Optionally, the age band of the patient for stratification (see [`age_bucket`](mod@age_bucket))
+//! 7. A public patient commitment, also published by the Circom eligibility code
+//!    proof, ties both proofs to one patient (see [`patient_commitment`])
+//!
+//! [`date_of_birth`] proves the same range on a public as-of date, without the
+//! nullifier and patient commitment. [`lab_value`] proves thresholds and intervals on
+//! committed fixed-point lab values (HbA1c, LDL, eGFR), and [`metric`] proves ranges
+//! on BMI and mean arterial pressure computed from attested vital signs. [`criteria`]
+//! combines age, diagnosis and lab predicates with AND / OR / NOT into a single proof
+//! against a published criteria hash. Every circuit that reads patient data verifies that a
+//! provider attested it with a signed [`credential`], without revealing which provider
+//! of the [`provider_registry`] signed it and proving the credential is not
+//! [`revocation`]-listed. [`fhir`] builds the input maps of these circuits from a FHIR
+//! R4 bundle, converting lab values to canonical UCUM units (see [`units`]).
+//! [`non_participation`] proves a patient holds no active [`enrollment`] in conflicting
+//! trials.
 //!
 //! ## TODO (Post-MVP): Dynamic WASM Loading
 //! Future architecture will support dynamic proof type loading:
@@ -23,10 +56,15 @@
 
 use std::{collections::HashMap, io::Cursor};
 
+use eligibility_gadgets::{
+    age_on, days_from_field, identity_commitment, CredentialAttributes, CredentialChip,
+    CredentialConfig, CredentialWitness, DateChip, DateConfig, EddsaChip, EddsaField, IssuerChip,
+    MerkleChip, PoseidonChip, PoseidonConfig, PoseidonField, RangeCheckChip, RangeCheckConfig,
+};
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
     halo2curves::ff::{Field, PrimeField},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance, Selector},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
     poly::Rotation,
 };
 use plonkish_backend::{
@@ -34,19 +72,42 @@ use plonkish_backend::{
     frontend::halo2::{CircuitExt, Halo2Circuit},
     halo2_curves::bn256::Fr,
     pcs::{CommitmentChunk, PolynomialCommitmentScheme},
-    util::{
-        test::std_rng,
-        transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
-    },
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
 };
-use rand::RngCore;
+use rand::{CryptoRng, RngCore};
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
+pub mod age_bucket;
+pub mod context;
+pub mod credential;
+pub mod criteria;
+pub mod date_of_birth;
+pub mod enrollment;
+pub mod epoch;
+pub mod fhir;
 pub mod io;
+pub mod lab_value;
+pub mod metric;
+pub mod non_participation;
+pub mod nullifier;
+pub mod patient_commitment;
+pub mod provider_registry;
+pub mod revocation;
 pub mod serialization;
+pub mod units;
 
-use crate::serialization::{deserialize_circuit_inputs, InputsSerializationWrapper};
+pub use eligibility_gadgets::rng;
+
+use crate::{
+    age_bucket::{age_bucket, bucket_bounds, AGE_BUCKET_BOUNDS},
+    credential::checked_credential_at,
+    date_of_birth::{commit_date_of_birth, validate_date_of_birth},
+    nullifier::compute_nullifier,
+    patient_commitment::{compute_patient_commitment, PATIENT_COMMITMENT_TAG},
+    rng::default_rng,
+    serialization::{deserialize_circuit_inputs, InputsSerializationWrapper},
+};
 
 pub trait PlonkishComponents {
     type Param: Clone + Serialize + DeserializeOwned;
@@ -73,14 +134,44 @@ impl std::fmt::Display for EligibilityError {
 pub type GenerateProofResult = (Vec<u8>, Vec<u8>);
 pub type ProofTranscript = Keccak256Transcript<Cursor<Vec<u8>>>;
 
+/// Number of bits used to range check `age - min_age` and `max_age - age`
+///
+/// Ages are whole years, so both differences are far below 2^8.
+pub const AGE_RANGE_BITS: usize = 8;
+
+/// Circuit size (2^k rows) of `AgeRangeCircuit`: the credential check, the date
+/// conversions and three more Poseidon hashes
+pub const AGE_RANGE_K: usize = 13;
+
+/// Number of public inputs of `AgeRangeCircuit`
+pub const AGE_RANGE_PUBLIC_INPUTS: usize = 7 + AGE_BUCKET_BOUNDS + 1 + 2;
+
 /// Age Range Circuit Configuration
 #[derive(Debug, Clone)]
-pub struct AgeRangeConfig {
-    pub age: Column<Advice>,           // Private: patient's age
-    pub min_age: Column<Advice>,       // Public: minimum age
-    pub max_age: Column<Advice>,       // Public: maximum age
+pub struct AgeRangeConfig<F: PrimeField> {
+    pub dob: Column<Advice>,          // Private: date of birth (days since epoch)
+    pub dob_salt: Column<Advice>,     // Private: date of birth commitment salt
+    pub age: Column<Advice>,          // Patient's age on the epoch day
+    pub min_age: Column<Advice>,      // Public: minimum age
+    pub max_age: Column<Advice>,      // Public: maximum age
+    pub study_id: Column<Advice>,     // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,        // Public: day the age is derived on
+    pub identity_secret: Column<Advice>, // Private: patient's identity secret
+    pub lower_diff: Column<Advice>,   // age - min_age
+    pub upper_diff: Column<Advice>,   // max_age - age
+    pub bucket_bound: Column<Advice>, // Public: age bucket bound, one per row
+    pub above_bound: Column<Advice>,  // age >= bucket bound
+    pub bound_diff: Column<Advice>,   // Range-checked difference of the comparison
+    pub bucket: Column<Advice>,       // Public: number of bounds <= age
     pub selector: Selector,
+    pub q_bucket_bound: Selector,
+    pub q_bucket: Selector,
     pub instance: Column<Instance>,
+    pub range_check: RangeCheckConfig<F, AGE_RANGE_BITS>,
+    pub date: DateConfig<F>,
+    pub poseidon: PoseidonConfig,
+    pub credential: CredentialConfig<F>,
 }
 
 /// Age Range Circuit with Proper Range Validation
@@ -88,41 +179,69 @@ pub struct AgeRangeConfig {
 /// This circuit proves: min_age <= age <= max_age
 ///
 /// ## Constraints
-/// 1. age >= min_age  =>  (age - min_age) must be non-negative
-/// 2. age <= max_age  =>  (max_age - age) must be non-negative
+/// 0. `age` is derived from `dob` and `epoch` by the date chip, and `Poseidon(dob,
+///    dob_salt)` is the dob_commitment `CredentialChip` verifies at `epoch`
+/// 1. lower_diff = age - min_age, upper_diff = max_age - age
+/// 2. lower_diff ∈ [0, 2^AGE_RANGE_BITS)  =>  age >= min_age
+/// 3. upper_diff ∈ [0, 2^AGE_RANGE_BITS)  =>  age <= max_age
+/// 4. nullifier = Poseidon(identity_secret, study_id), and Poseidon(tag,
+///    identity_secret) is the identity_commitment `CredentialChip` verifies
+/// 5. for each bucket bound: above_bound is a bit, and bound_diff is age - bound
+///    if it is set, bound - age - 1 otherwise; bound_diff ∈ [0, 2^AGE_RANGE_BITS)
+/// 6. bucket = sum of the above_bound bits
 ///
-/// ## MVP Limitation
-/// Halo2 doesn't have native range checks in this simple circuit.
-/// For MVP, we implement a hybrid approach:
-/// - Client validates ranges before proof generation (fast UX feedback)
-/// - Circuit proves knowledge of age and binds it to study_id
-/// - Smart contract verifies proof integrity
+/// A "negative" difference wraps around to a huge field element and fails
+/// the bit decomposition.
 ///
-/// ## TODO (Production): Implement proper range proofs
-/// - Binary decomposition of differences
-/// - Range lookup tables
-/// - Bit constraints to ensure 0 <= difference < 2^64
+/// ## Public Inputs (instance column)
+/// Row 0: min_age, row 1: max_age, row 2: study_id, row 3: nullifier, row 4:
+/// context_hash, row 5: epoch, row 6: patient_commitment, rows 7..: age bucket bounds,
+/// then the bucket, provider_root and revocation_root. Each one is copy-constrained to
+/// the advice cell used by the circuit, so a proof for one study/range/applicant/epoch
+/// does not verify against another.
 #[derive(Clone)]
-pub struct AgeRangeCircuit<F: Field> {
-    pub age: Value<F>,         // Private witness
-    pub min_age: F,            // Public input
-    pub max_age: F,            // Public input
-    pub study_id: F,           // Public input (binds proof to specific study)
+pub struct AgeRangeCircuit<F: EddsaField> {
+    pub dob: Value<F>,      // Private witness: days since epoch (negative before 1970)
+    pub dob_salt: Value<F>, // Private witness: date of birth commitment salt
+    pub identity_secret: Value<F>, // Private witness (see `nullifier`)
+    pub credential: CredentialWitness<F>, // Private witness: signs the dob commitment
+    pub min_age: F,         // Public input
+    pub max_age: F,         // Public input
+    pub study_id: F,        // Public input (binds proof to specific study)
+    pub nullifier: F,       // Public output: Poseidon(identity_secret, study_id)
+    pub context_hash: F,    // Public input (binds proof to wallet, chain and registry)
+    pub epoch: F,           // Public input (as-of day, chosen by the prover, see `epoch`)
+    pub patient_commitment: F, // Public output: Poseidon(tag, identity_secret, context_hash)
+    pub bucket_bounds: [F; AGE_BUCKET_BOUNDS], // Public input (see `age_bucket`)
+    pub bucket: F,          // Public output: number of bounds <= age
+    pub provider_root: F,   // Public input: registered provider tree root
+    pub revocation_root: F, // Public input: revoked credential tree root
 }
 
-impl<F: Field> Default for AgeRangeCircuit<F> {
+impl<F: EddsaField> Default for AgeRangeCircuit<F> {
     fn default() -> Self {
         Self {
-            age: Value::unknown(),
+            dob: Value::unknown(),
+            dob_salt: Value::unknown(),
+            identity_secret: Value::unknown(),
+            credential: CredentialWitness::default(),
             min_age: F::ZERO,
             max_age: F::ZERO,
             study_id: F::ZERO,
+            nullifier: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            patient_commitment: F::ZERO,
+            bucket_bounds: [F::ZERO; AGE_BUCKET_BOUNDS],
+            bucket: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
         }
     }
 }
 
-impl<F: Field + PrimeField> Circuit<F> for AgeRangeCircuit<F> {
-    type Config = AgeRangeConfig;
+impl<F: EddsaField> Circuit<F> for AgeRangeCircuit<F> {
+    type Config = AgeRangeConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -130,43 +249,119 @@ impl<F: Field + PrimeField> Circuit<F> for AgeRangeCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let dob = meta.advice_column();
+        let dob_salt = meta.advice_column();
         let age = meta.advice_column();
         let min_age = meta.advice_column();
         let max_age = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let identity_secret = meta.advice_column();
+        let lower_diff = meta.advice_column();
+        let upper_diff = meta.advice_column();
+        let bucket_bound = meta.advice_column();
+        let above_bound = meta.advice_column();
+        let bound_diff = meta.advice_column();
+        let bucket = meta.advice_column();
+        let running_sum = meta.advice_column();
         let selector = meta.selector();
+        let q_bucket_bound = meta.selector();
+        let q_bucket = meta.selector();
         let instance = meta.instance_column();
 
+        meta.enable_equality(dob);
+        meta.enable_equality(dob_salt);
         meta.enable_equality(age);
         meta.enable_equality(min_age);
         meta.enable_equality(max_age);
+        meta.enable_equality(study_id);
+        meta.enable_equality(context_hash);
+        meta.enable_equality(epoch);
+        meta.enable_equality(identity_secret);
+        meta.enable_equality(lower_diff);
+        meta.enable_equality(upper_diff);
+        meta.enable_equality(bucket_bound);
+        meta.enable_equality(bound_diff);
+        meta.enable_equality(bucket);
         meta.enable_equality(instance);
 
-        // Gate 1: Prove knowledge of age (binds age to public commitment)
-        // This ensures the prover knows the age value
-        meta.create_gate("age knowledge", |meta| {
+        // Gate: Define the two differences that must be non-negative
+        // Their range checks are enforced by the range check chip below
+        meta.create_gate("age range differences", |meta| {
             let s = meta.query_selector(selector);
             let age_val = meta.query_advice(age, Rotation::cur());
             let min_val = meta.query_advice(min_age, Rotation::cur());
             let max_val = meta.query_advice(max_age, Rotation::cur());
+            let lower_val = meta.query_advice(lower_diff, Rotation::cur());
+            let upper_val = meta.query_advice(upper_diff, Rotation::cur());
+
+            vec![
+                s.clone() * (lower_val - (age_val.clone() - min_val)), // lower = age - min
+                s * (upper_val - (max_val - age_val)),                 // upper = max - age
+            ]
+        });
 
-            // TODO (Production): Add proper range constraints here
-            // For now, we rely on client-side validation + proof integrity binding
-            // The constraint below proves the prover knows age, min, max
-            // but doesn't enforce age >= min or age <= max (handled off-circuit in MVP)
+        // Gate: above = [age >= bound], with a difference that must be non-negative
+        meta.create_gate("age bucket bound", |meta| {
+            let s = meta.query_selector(q_bucket_bound);
+            let age_val = meta.query_advice(age, Rotation::cur());
+            let bound_val = meta.query_advice(bucket_bound, Rotation::cur());
+            let above_val = meta.query_advice(above_bound, Rotation::cur());
+            let diff_val = meta.query_advice(bound_diff, Rotation::cur());
 
-            // Dummy constraint to prove knowledge (will always pass)
-            // In production, replace with: (age - min) * range_check + (max - age) * range_check = valid
+            let one = Expression::Constant(F::ONE);
+            let expected = above_val.clone() * (age_val.clone() - bound_val.clone())
+                + (one.clone() - above_val.clone()) * (bound_val - age_val - one.clone());
             vec![
-                s.clone() * (age_val.clone() - age_val.clone()), // Always 0 (proves age exists)
+                s.clone() * above_val.clone() * (one - above_val), // above is a bit
+                s * (diff_val - expected),
             ]
         });
 
+        // Gate: the bucket counts the bounds the age reaches
+        meta.create_gate("age bucket", |meta| {
+            let s = meta.query_selector(q_bucket);
+            let bucket_val = meta.query_advice(bucket, Rotation::cur());
+            let above_sum = (0..AGE_BUCKET_BOUNDS)
+                .map(|i| meta.query_advice(above_bound, Rotation(i as i32)))
+                .fold(Expression::Constant(F::ZERO), |sum, above| sum + above);
+
+            vec![s * (bucket_val - above_sum)]
+        });
+
+        let range_check = RangeCheckChip::<F, AGE_RANGE_BITS>::configure(meta, running_sum);
+        let date = DateChip::configure(meta, running_sum);
+        let poseidon = PoseidonChip::configure(meta);
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let merkle = MerkleChip::configure(meta, poseidon.clone());
+        let issuer = IssuerChip::configure(meta, eddsa, merkle);
+        let credential = CredentialChip::configure(meta, issuer);
+
         AgeRangeConfig {
+            dob,
+            dob_salt,
             age,
             min_age,
             max_age,
+            study_id,
+            context_hash,
+            epoch,
+            identity_secret,
+            lower_diff,
+            upper_diff,
+            bucket_bound,
+            above_bound,
+            bound_diff,
+            bucket,
             selector,
+            q_bucket_bound,
+            q_bucket,
             instance,
+            range_check,
+            date,
+            poseidon,
+            credential,
         }
     }
 
@@ -175,58 +370,258 @@ impl<F: Field + PrimeField> Circuit<F> for AgeRangeCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        layouter.assign_region(
-            || "age range check",
+        let (dob, dob_salt, identity_secret, epoch) = layouter.assign_region(
+            || "attested inputs",
             |mut region| {
-                config.selector.enable(&mut region, 0)?;
+                // Assign private date of birth and salt
+                let dob = region.assign_advice(|| "dob", config.dob, 0, || self.dob)?;
+                let dob_salt =
+                    region.assign_advice(|| "dob_salt", config.dob_salt, 0, || self.dob_salt)?;
 
-                // Assign private age
-                region.assign_advice(
-                    || "age",
-                    config.age,
+                // Assign private identity secret
+                let identity_secret = region.assign_advice(
+                    || "identity_secret",
+                    config.identity_secret,
                     0,
-                    || self.age,
+                    || self.identity_secret,
                 )?;
 
-                // Assign public min_age
-                region.assign_advice(
-                    || "min_age",
-                    config.min_age,
+                // Assign public epoch, the day the age is computed on
+                let epoch = region.assign_advice(
+                    || "epoch",
+                    config.epoch,
                     0,
-                    || Value::known(self.min_age),
+                    || Value::known(self.epoch),
                 )?;
 
-                // Assign public max_age
-                region.assign_advice(
-                    || "max_age",
-                    config.max_age,
+                Ok((dob, dob_salt, identity_secret, epoch))
+            },
+        )?;
+
+        // The age is derived from the date of birth the provider signed, and the identity
+        // secret is the one whose commitment it signed
+        let poseidon_chip = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let credential_chip = CredentialChip::<F>::construct(config.credential.clone());
+        let dob_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "dob commitment"),
+            &[dob.clone(), dob_salt],
+        )?;
+        let identity_commitment = credential_chip.identity_commitment(
+            layouter.namespace(|| "identity commitment"),
+            &identity_secret,
+        )?;
+        let opened = CredentialAttributes {
+            identity_commitment: Some(&identity_commitment),
+            dob_commitment: Some(&dob_commitment),
+            ..Default::default()
+        };
+        let (provider_root, revocation_root) = credential_chip.verify_at(
+            layouter.namespace(|| "credential"),
+            opened,
+            &self.credential,
+            &epoch,
+        )?;
+        let date_chip = DateChip::construct(config.date.clone());
+        let derived_age = date_chip.age(layouter.namespace(|| "age"), &dob, &epoch)?;
+        let age_value = derived_age.value().copied();
+
+        let (age, min_age, max_age, study_id, context_hash, commitment_tag, lower_diff, upper_diff) =
+            layouter.assign_region(
+                || "age range check",
+                |mut region| {
+                    config.selector.enable(&mut region, 0)?;
+
+                    // Copy the derived age
+                    let age = derived_age.copy_advice(|| "age", &mut region, config.age, 0)?;
+
+                    // Assign public min_age
+                    let min_age = region.assign_advice(
+                        || "min_age",
+                        config.min_age,
+                        0,
+                        || Value::known(self.min_age),
+                    )?;
+
+                    // Assign public max_age
+                    let max_age = region.assign_advice(
+                        || "max_age",
+                        config.max_age,
+                        0,
+                        || Value::known(self.max_age),
+                    )?;
+
+                    // Assign public study_id
+                    let study_id = region.assign_advice(
+                        || "study_id",
+                        config.study_id,
+                        0,
+                        || Value::known(self.study_id),
+                    )?;
+
+                    // Assign public context hash
+                    let context_hash = region.assign_advice(
+                        || "context_hash",
+                        config.context_hash,
+                        0,
+                        || Value::known(self.context_hash),
+                    )?;
+
+                    // Domain separation tag of the patient commitment
+                    let commitment_tag = region.assign_advice_from_constant(
+                        || "patient commitment tag",
+                        config.identity_secret,
+                        0,
+                        F::from(PATIENT_COMMITMENT_TAG),
+                    )?;
+
+                    // Assign the differences that get range checked
+                    let lower_diff = region.assign_advice(
+                        || "age - min_age",
+                        config.lower_diff,
+                        0,
+                        || age_value.map(|age| age - self.min_age),
+                    )?;
+
+                    let upper_diff = region.assign_advice(
+                        || "max_age - age",
+                        config.upper_diff,
+                        0,
+                        || age_value.map(|age| self.max_age - age),
+                    )?;
+
+                    Ok((
+                        age,
+                        min_age,
+                        max_age,
+                        study_id,
+                        context_hash,
+                        commitment_tag,
+                        lower_diff,
+                        upper_diff,
+                    ))
+                },
+            )?;
+
+        let (bounds, bound_diffs, bucket) = layouter.assign_region(
+            || "age bucket",
+            |mut region| {
+                config.q_bucket.enable(&mut region, 0)?;
+
+                let mut bounds = Vec::with_capacity(AGE_BUCKET_BOUNDS);
+                let mut bound_diffs = Vec::with_capacity(AGE_BUCKET_BOUNDS);
+                for (row, bound) in self.bucket_bounds.iter().enumerate() {
+                    config.q_bucket_bound.enable(&mut region, row)?;
+
+                    // Compare a copy of the age with the public bound
+                    age.copy_advice(|| "age", &mut region, config.age, row)?;
+                    bounds.push(region.assign_advice(
+                        || "bucket bound",
+                        config.bucket_bound,
+                        row,
+                        || Value::known(*bound),
+                    )?);
+
+                    let above =
+                        age_value.map(|age| field_to_u64(&age).ok() >= field_to_u64(bound).ok());
+                    region.assign_advice(
+                        || "age >= bound",
+                        config.above_bound,
+                        row,
+                        || above.map(|above| F::from(above as u64)),
+                    )?;
+                    bound_diffs.push(region.assign_advice(
+                        || "bound difference",
+                        config.bound_diff,
+                        row,
+                        || {
+                            age_value.zip(above).map(|(age, above)| match above {
+                                true => age - bound,
+                                false => *bound - age - F::ONE,
+                            })
+                        },
+                    )?);
+                }
+
+                let bucket = region.assign_advice(
+                    || "bucket",
+                    config.bucket,
                     0,
-                    || Value::known(self.max_age),
+                    || Value::known(self.bucket),
                 )?;
-
-                Ok(())
+                Ok((bounds, bound_diffs, bucket))
             },
         )?;
 
+        let nullifier = poseidon_chip.hash(
+            layouter.namespace(|| "nullifier"),
+            &[identity_secret.clone(), study_id.clone()],
+        )?;
+        let patient_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "patient commitment"),
+            &[commitment_tag, identity_secret, context_hash.clone()],
+        )?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(min_age.cell(), config.instance, 0)?;
+        layouter.constrain_instance(max_age.cell(), config.instance, 1)?;
+        layouter.constrain_instance(study_id.cell(), config.instance, 2)?;
+        layouter.constrain_instance(nullifier.cell(), config.instance, 3)?;
+        layouter.constrain_instance(context_hash.cell(), config.instance, 4)?;
+        layouter.constrain_instance(epoch.cell(), config.instance, 5)?;
+        layouter.constrain_instance(patient_commitment.cell(), config.instance, 6)?;
+        for (i, bound) in bounds.iter().enumerate() {
+            layouter.constrain_instance(bound.cell(), config.instance, 7 + i)?;
+        }
+        layouter.constrain_instance(bucket.cell(), config.instance, 7 + AGE_BUCKET_BOUNDS)?;
+        layouter.constrain_instance(
+            provider_root.cell(),
+            config.instance,
+            8 + AGE_BUCKET_BOUNDS,
+        )?;
+        layouter.constrain_instance(
+            revocation_root.cell(),
+            config.instance,
+            9 + AGE_BUCKET_BOUNDS,
+        )?;
+
+        let range_chip = RangeCheckChip::<F, AGE_RANGE_BITS>::construct(config.range_check);
+        range_chip.assign(layouter.namespace(|| "age >= min_age"), &lower_diff)?;
+        range_chip.assign(layouter.namespace(|| "age <= max_age"), &upper_diff)?;
+        for diff in bound_diffs.iter() {
+            range_chip.assign(layouter.namespace(|| "age bucket comparison"), diff)?;
+        }
+
         Ok(())
     }
 }
 
-impl<F: Field + PrimeField> CircuitExt<F> for AgeRangeCircuit<F> {
+impl<F: EddsaField> CircuitExt<F> for AgeRangeCircuit<F> {
     fn rand(_: usize, _: impl RngCore) -> Self {
         unimplemented!()
     }
 
     fn instances(&self) -> Vec<Vec<F>> {
-        // Public inputs: min_age, max_age, study_id
-        vec![vec![self.min_age, self.max_age, self.study_id]]
+        // Public inputs: min_age, max_age, study_id, nullifier, context_hash, epoch,
+        // patient_commitment, bucket_bounds, bucket, provider_root, revocation_root
+        let mut instances = vec![
+            self.min_age,
+            self.max_age,
+            self.study_id,
+            self.nullifier,
+            self.context_hash,
+            self.epoch,
+            self.patient_commitment,
+        ];
+        instances.extend(self.bucket_bounds);
+        instances.extend([self.bucket, self.provider_root, self.revocation_root]);
+        vec![instances]
     }
 }
 
-/// Client-side validation (MUST be called before proof generation)
+/// Client-side validation (called before proof generation)
 ///
-/// This validates the age range constraint outside the circuit.
-/// In the MVP hybrid approach, this prevents generating invalid proofs.
+/// The circuit enforces the same constraint; this check only gives fast
+/// feedback instead of a failed proof.
 pub fn validate_age_range(age: u64, min_age: u64, max_age: u64) -> Result<(), EligibilityError> {
     if age < min_age {
         return Err(EligibilityError(format!(
@@ -245,32 +640,64 @@ pub fn validate_age_range(age: u64, min_age: u64, max_age: u64) -> Result<(), El
 
 /// Generate age range proof
 ///
-/// ## MVP Hybrid Approach
 /// 1. Validates age range client-side (returns error if invalid)
-/// 2. Generates ZK proof of age knowledge bound to study_id
-/// 3. Returns proof + public inputs (min, max, study_id)
+/// 2. Generates ZK proof that min_age <= age <= max_age on the epoch day, bound to study_id
+/// 3. Returns proof + public inputs (min, max, study_id, nullifier, context_hash, epoch,
+///    patient_commitment, age bucket bounds, age bucket, provider_root, revocation_root)
+///
+/// Inputs: `dob`, `dob_salt`, `min_age`, `max_age`, `study_id`, `identity_secret`,
+/// `context_hash`, `epoch`, optional `age_buckets` and the credential inputs, which
+/// include `dob_commitment` and the `identity_commitment` of `identity_secret`.
 ///
 /// ## Security
 /// - Client validation prevents UX issues (fast feedback)
-/// - Proof binds age commitment to study_id (prevents proof reuse)
-/// - Smart contract checks proof integrity + prevents replay
+/// - The range itself is enforced in-circuit, on the date of birth a provider signed
+/// - Verifiers check proof integrity + prevent replay, recomputing `context_hash`
+///   from the submitting wallet (see [`context`])
+/// - Verifiers reject proofs whose `epoch` is outside their window (see
+///   [`epoch::FreshnessWindow`]); the epoch is the prover's as-of day, not a timestamp,
+///   but the credential must have been issued at most MAX_CREDENTIAL_AGE days before it
+/// - The optional `age_buckets` input holds the bucket bounds of the study; without it
+///   the proof reveals no bucket (see [`age_bucket`](mod@age_bucket))
 pub fn generate_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_proof_with_rng<PC, R>(
     _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
     prover_parameters: &PC::ProverParam,
     inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
 ) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
 where
     PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
     Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
 {
-    let k = 4usize;
+    let k = AGE_RANGE_K;
 
     // Extract inputs
-    let age: Fr = inputs
-        .get("age")
-        .ok_or(EligibilityError("Missing age".to_string()))?
+    let dob: Fr = inputs
+        .get("dob")
+        .ok_or(EligibilityError("Missing dob".to_string()))?
         .get(0)
-        .ok_or(EligibilityError("Invalid age".to_string()))?
+        .ok_or(EligibilityError("Invalid dob".to_string()))?
+        .clone();
+
+    let dob_salt: Fr = inputs
+        .get("dob_salt")
+        .ok_or(EligibilityError("Missing dob_salt".to_string()))?
+        .get(0)
+        .ok_or(EligibilityError("Invalid dob_salt".to_string()))?
         .clone();
 
     let min_age: Fr = inputs
@@ -294,20 +721,86 @@ where
         .ok_or(EligibilityError("Invalid study_id".to_string()))?
         .clone();
 
-    // Client-side validation (MVP hybrid approach)
-    // This prevents generating proofs for invalid ages
-    let age_u64 = field_to_u64(&age)?;
-    let min_age_u64 = field_to_u64(&min_age)?;
-    let max_age_u64 = field_to_u64(&max_age)?;
+    let identity_secret: Fr = inputs
+        .get("identity_secret")
+        .ok_or(EligibilityError("Missing identity_secret".to_string()))?
+        .get(0)
+        .ok_or(EligibilityError("Invalid identity_secret".to_string()))?
+        .clone();
+
+    let context_hash: Fr = inputs
+        .get("context_hash")
+        .ok_or(EligibilityError("Missing context_hash".to_string()))?
+        .get(0)
+        .ok_or(EligibilityError("Invalid context_hash".to_string()))?
+        .clone();
 
-    validate_age_range(age_u64, min_age_u64, max_age_u64)?;
+    let epoch: Fr = inputs
+        .get("epoch")
+        .ok_or(EligibilityError("Missing epoch".to_string()))?
+        .get(0)
+        .ok_or(EligibilityError("Invalid epoch".to_string()))?
+        .clone();
+
+    let credential = checked_credential_at(&inputs, &epoch)?;
+
+    // Client-side validation
+    // Fails fast instead of producing an unsatisfiable circuit
+    let days = |value: &Fr, name: &str| {
+        days_from_field(value).ok_or(EligibilityError(format!(
+            "Invalid {}: not a day count",
+            name
+        )))
+    };
+    let dob_days = days(&dob, "dob")?;
+    let epoch_days = days(&epoch, "epoch")?;
+    if commit_date_of_birth(dob_days, dob_salt) != credential.attributes().dob_commitment {
+        return Err(EligibilityError(
+            "Date of birth does not match dob_commitment".to_string(),
+        ));
+    }
+    if identity_commitment(identity_secret) != credential.attributes().identity_commitment {
+        return Err(EligibilityError(
+            "Identity secret does not match identity_commitment".to_string(),
+        ));
+    }
+    validate_date_of_birth(
+        dob_days,
+        epoch_days,
+        field_to_u64(&min_age)?,
+        field_to_u64(&max_age)?,
+    )?;
+    let age_u64 = age_on(dob_days, epoch_days) as u64;
+
+    let bounds = inputs
+        .get("age_buckets")
+        .map(|bounds| {
+            bounds
+                .iter()
+                .map(field_to_u64)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let bounds = bucket_bounds(&bounds)?;
 
     // Create circuit with validated inputs
     let circuit = AgeRangeCircuit::<Fr> {
-        age: Value::known(age),
+        dob: Value::known(dob),
+        dob_salt: Value::known(dob_salt),
+        identity_secret: Value::known(identity_secret),
+        credential: credential.witness(),
         min_age,
         max_age,
         study_id,
+        nullifier: compute_nullifier(identity_secret, study_id),
+        context_hash,
+        epoch,
+        patient_commitment: compute_patient_commitment(identity_secret, context_hash),
+        bucket_bounds: bounds.map(Fr::from),
+        bucket: Fr::from(age_bucket(age_u64, &bounds)),
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
     };
 
     let halo2_circuit =
@@ -320,7 +813,7 @@ where
             &prover_parameters,
             &halo2_circuit,
             &mut proof_transcript,
-            std_rng(),
+            rng,
         )
         .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
 
@@ -328,7 +821,7 @@ where
     };
 
     let proof = proof_transcript.into_proof();
-    let public_inputs = vec![min_age, max_age, study_id];
+    let public_inputs = circuit.instances().remove(0);
 
     Ok((proof, public_inputs))
 }
@@ -344,15 +837,21 @@ where
     PC: PlonkishComponents,
     Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
 {
-    if inputs.len() != 3 {
-        return Err(EligibilityError(
-            "Invalid number of public inputs (expected 3: min_age, max_age, study_id)".to_string(),
-        ));
+    if inputs.len() != AGE_RANGE_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of public inputs (expected {}: min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment, {} age bucket bounds, age bucket, provider_root, revocation_root)",
+            AGE_RANGE_PUBLIC_INPUTS, AGE_BUCKET_BOUNDS
+        )));
     }
 
     // Verify the proof
     let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
-    let result = PC::ProvingBackend::verify(&verifier_parameters, &[inputs], &mut transcript, std_rng());
+    let result = PC::ProvingBackend::verify(
+        &verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
 
     result
         .map(|_| true)
@@ -419,6 +918,49 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        age_bucket::NO_BUCKET_BOUND,
+        context::ProofContext,
+        credential::testing::{issue, EPOCH, ISSUED_AT},
+    };
+    use eligibility_gadgets::{days_from_civil, days_to_field, MAX_CREDENTIAL_AGE};
+    use halo2_proofs::dev::MockProver;
+
+    fn age_circuit(age: u64, min_age: u64, max_age: u64) -> AgeRangeCircuit<Fr> {
+        let identity_secret = Fr::from(42);
+        let dob = days_from_civil(2025 - age as i64, 1, 1);
+        let dob_salt = Fr::from(5);
+        let credential = issue(CredentialAttributes {
+            identity_commitment: identity_commitment(identity_secret),
+            dob_commitment: commit_date_of_birth(dob, dob_salt),
+            issued_at: days_to_field(ISSUED_AT),
+            ..Default::default()
+        });
+        AgeRangeCircuit {
+            dob: Value::known(days_to_field(dob)),
+            dob_salt: Value::known(dob_salt),
+            identity_secret: Value::known(identity_secret),
+            credential: credential.witness(),
+            min_age: Fr::from(min_age),
+            max_age: Fr::from(max_age),
+            study_id: Fr::from(1),
+            nullifier: compute_nullifier(identity_secret, Fr::from(1)),
+            context_hash: Fr::from(7),
+            epoch: days_to_field(EPOCH),
+            patient_commitment: compute_patient_commitment(identity_secret, Fr::from(7)),
+            bucket_bounds: [Fr::from(NO_BUCKET_BOUND); AGE_BUCKET_BOUNDS],
+            bucket: Fr::ZERO,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        }
+    }
+
+    fn mock_verify(circuit: &AgeRangeCircuit<Fr>) -> bool {
+        MockProver::run(AGE_RANGE_K as u32, circuit, circuit.instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
 
     #[test]
     fn test_validate_age_range_valid() {
@@ -438,4 +980,163 @@ mod tests {
         assert!(validate_age_range(66, 18, 65).is_err());
         assert!(validate_age_range(80, 18, 65).is_err());
     }
+
+    #[test]
+    fn test_circuit_accepts_age_in_range() {
+        assert!(mock_verify(&age_circuit(30, 18, 65)));
+        assert!(mock_verify(&age_circuit(18, 18, 65))); // Edge: min
+        assert!(mock_verify(&age_circuit(65, 18, 65))); // Edge: max
+    }
+
+    #[test]
+    fn test_circuit_rejects_age_below_min() {
+        // Bypasses validate_age_range: a modified client must not be able to prove this
+        assert!(!mock_verify(&age_circuit(17, 18, 65)));
+        assert!(!mock_verify(&age_circuit(0, 18, 65)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_age_above_max() {
+        assert!(!mock_verify(&age_circuit(66, 18, 65)));
+        assert!(!mock_verify(&age_circuit(200, 18, 65)));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = age_circuit(30, 18, 65);
+        let names = [
+            "min_age",
+            "max_age",
+            "study_id",
+            "nullifier",
+            "context_hash",
+            "epoch",
+            "patient_commitment",
+            "age bucket bound 0",
+            "age bucket bound 1",
+            "age bucket bound 2",
+            "age bucket",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            let prover = MockProver::run(AGE_RANGE_K as u32, &circuit, instances).unwrap();
+            assert!(
+                prover.verify().is_err(),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_proof_for_other_study() {
+        let circuit = age_circuit(30, 18, 65);
+        let nullifier = compute_nullifier(Fr::from(42), Fr::from(2));
+        let mut instances = circuit.instances();
+        instances[0][2] = Fr::from(2);
+        instances[0][3] = nullifier;
+
+        let prover = MockProver::run(AGE_RANGE_K as u32, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_circuit_rejects_proof_submitted_by_other_wallet() {
+        let registry = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+        let applicant = ProofContext::new(
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+            1,
+            registry,
+            Fr::from(1),
+        );
+        let front_runner = ProofContext::new(
+            "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC",
+            1,
+            registry,
+            Fr::from(1),
+        );
+
+        let mut circuit = age_circuit(30, 18, 65);
+        circuit.context_hash = applicant.unwrap().hash();
+        circuit.patient_commitment = compute_patient_commitment(Fr::from(42), circuit.context_hash);
+        assert!(mock_verify(&circuit));
+
+        let mut instances = circuit.instances();
+        instances[0][4] = front_runner.unwrap().hash();
+        let prover = MockProver::run(AGE_RANGE_K as u32, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_circuit_reveals_age_bucket() {
+        let bounds = [Fr::from(40), Fr::from(65), Fr::from(NO_BUCKET_BOUND)];
+        for (age, bucket) in [(39, 0), (40, 1), (64, 1), (65, 2)] {
+            let mut circuit = age_circuit(age, 18, 80);
+            circuit.bucket_bounds = bounds;
+            circuit.bucket = Fr::from(bucket);
+            assert!(mock_verify(&circuit), "age {} is in bucket {}", age, bucket);
+
+            // Claiming a neighbouring bucket must fail
+            circuit.bucket = Fr::from(bucket + 1);
+            assert!(!mock_verify(&circuit));
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_nullifier_of_other_identity() {
+        let mut circuit = age_circuit(30, 18, 65);
+        circuit.nullifier = compute_nullifier(Fr::from(43), circuit.study_id);
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_patient_commitment_of_other_identity() {
+        // The commitment of another patient's eligibility code proof
+        let mut circuit = age_circuit(30, 18, 65);
+        circuit.patient_commitment = compute_patient_commitment(Fr::from(43), circuit.context_hash);
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_identity_secret() {
+        // A fresh secret with consistent outputs would give a fresh nullifier per study
+        let mut circuit = age_circuit(30, 18, 65);
+        let secret = Fr::from(43);
+        circuit.identity_secret = Value::known(secret);
+        circuit.nullifier = compute_nullifier(secret, circuit.study_id);
+        circuit.patient_commitment = compute_patient_commitment(secret, circuit.context_hash);
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_date_of_birth() {
+        // An in-range date of birth the provider did not sign
+        let mut circuit = age_circuit(30, 18, 65);
+        circuit.dob = Value::known(days_to_field(days_from_civil(2000, 1, 1)));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_derives_age_on_epoch_day() {
+        // Born 1995-01-01: 30 on the test epoch, 31 on 2026-01-01
+        let mut circuit = age_circuit(30, 18, 30);
+        assert!(mock_verify(&circuit));
+        circuit.epoch = days_to_field(days_from_civil(2026, 1, 1));
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_expired_credential() {
+        // The credential was issued on ISSUED_AT
+        let mut circuit = age_circuit(30, 18, 65);
+        circuit.epoch = days_to_field(ISSUED_AT + MAX_CREDENTIAL_AGE as i64 + 1);
+        assert!(!mock_verify(&circuit));
+        circuit.epoch = days_to_field(ISSUED_AT - 1);
+        assert!(!mock_verify(&circuit));
+    }
 }
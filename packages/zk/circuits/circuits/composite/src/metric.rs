@@ -0,0 +1,895 @@
+//! Clinical Metric Verification
+//!
+//! Proves a derived metric such as "BMI >= 30", "at least 20 pack-years" or "mean
+//! arterial pressure < 110" without revealing it. The metric is computed in-circuit by `MetricChip` from raw
+//! measurements the issuing provider attested, so a patient cannot claim a BMI.
+//!
+//! ## Measurements
+//! Each metric reads two vital signs, committed like lab values
+//! (`Poseidon(analyte, value, scale, salt)`) in the credential's [`LabTree`] with the
+//! canonical unit and scale of [`units`](crate::units):
+//! - BMI: body weight (29463-7) in kg at scale 3, i.e. grams, and body height (8302-2)
+//!   in cm at scale 1, i.e. millimetres
+//! - Mean arterial pressure: systolic (8480-6) and diastolic (8462-4) blood pressure in
+//!   mm[Hg]
+//! - Pack-years: the provider's smoking history, cigarettes per day and years smoked,
+//!   committed at scale 0 under [`CIGARETTES_PER_DAY_ANALYTE`] and
+//!   [`YEARS_SMOKED_ANALYTE`]
+//!
+//! The analytes and scales are circuit constants, so every metric has its own keys and
+//! a weight cannot be passed off as a height. The smoking history analytes lie above
+//! every packed LOINC code, so they cannot be confused with a lab value either.
+//!
+//! ## Security Model
+//! - Private Inputs: both measurements, their commitment salts and lab tree paths,
+//!   credential
+//! - Public Inputs: metric, min, max, study_id, context_hash (see
+//!   [`context`](crate::context)), epoch (see [`epoch`](crate::epoch)), provider_root,
+//!   revocation_root
+//! - Constraints: both commitments are leaves of the [`LabTree`] whose root a
+//!   registered provider signed in an unrevoked [`credential`](crate::credential)
+//!   issued at most MAX_CREDENTIAL_AGE days before the epoch, and `min <= metric <= max` for the metric in tenths ([`METRIC_SCALE`])
+
+use std::{collections::HashMap, fmt, io::Cursor, str::FromStr};
+
+use eligibility_gadgets::{
+    bmi_tenths, mean_arterial_pressure_tenths, metric::MEASUREMENT_BITS, pack_years_tenths,
+    CredentialAttributes, CredentialChip, CredentialConfig, CredentialWitness, EddsaChip,
+    EddsaField, IssuerChip, MerkleChip, MerkleConfig, MetricChip, MetricConfig, PoseidonChip,
+    PoseidonConfig,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::{Field, PrimeField},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    credential::{checked_credential_at, LAB_TREE_DEPTH},
+    field_to_u64,
+    lab_value::{commit_lab_value, lab_path, pack_loinc, LabTree},
+    rng::default_rng,
+    units, EligibilityError, PlonkishComponents,
+};
+
+pub use eligibility_gadgets::metric::METRIC_SCALE;
+
+/// Circuit size (2^k rows): the credential check and two lab tree paths
+pub const METRIC_K: usize = 14;
+
+/// Number of public inputs of [`MetricCircuit`]
+pub const METRIC_PUBLIC_INPUTS: usize = 8;
+
+/// Analyte of the cigarettes smoked per day in a smoking history (packed LOINC codes
+/// have at most 8 digits)
+pub const CIGARETTES_PER_DAY_ANALYTE: u64 = 100_000_001;
+
+/// Analyte of the years smoked in a smoking history
+pub const YEARS_SMOKED_ANALYTE: u64 = 100_000_002;
+
+/// Metric computed from two attested measurements
+///
+/// The code is the `metric` public input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    #[default]
+    Bmi = 0, // kg/m2, from weight and height
+    MeanArterialPressure = 1, // mm[Hg], from systolic and diastolic pressure
+    PackYears = 2,            // {pack-years}, from cigarettes per day and years smoked
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Bmi, Metric::MeanArterialPressure, Metric::PackYears];
+
+    pub fn code(self) -> u64 {
+        self as u64
+    }
+
+    pub fn from_code(code: u64) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    /// UCUM unit of the metric
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::Bmi => "kg/m2",
+            Metric::MeanArterialPressure => "mm[Hg]",
+            Metric::PackYears => "{pack-years}",
+        }
+    }
+
+    /// LOINC codes of the two vital signs, in `MetricChip` argument order
+    ///
+    /// `None` for pack-years, which reads a smoking history instead.
+    pub fn measurements(self) -> Option<[&'static str; 2]> {
+        match self {
+            Metric::Bmi => Some(["29463-7", "8302-2"]),
+            Metric::MeanArterialPressure => Some(["8480-6", "8462-4"]),
+            Metric::PackYears => None,
+        }
+    }
+
+    /// Analyte and scale of each measurement
+    pub fn analytes(self) -> [(u64, u32); 2] {
+        match self.measurements() {
+            Some(loincs) => loincs.map(|loinc| {
+                let analyte = units::lookup(loinc).expect("measurements have a canonical unit");
+                (
+                    pack_loinc(loinc).expect("measurements are valid LOINC codes"),
+                    analyte.scale,
+                )
+            }),
+            None => [(CIGARETTES_PER_DAY_ANALYTE, 0), (YEARS_SMOKED_ANALYTE, 0)],
+        }
+    }
+
+    /// Lab tree leaves of both measurements: `Poseidon(analyte, value, scale, salt)`
+    pub fn commitments(self, values: [u64; 2], salts: [Fr; 2]) -> [Fr; 2] {
+        let analytes = self.analytes();
+        std::array::from_fn(|i| {
+            let (analyte, scale) = analytes[i];
+            commit_lab_value(Fr::from(analyte), values[i], scale, salts[i])
+        })
+    }
+
+    /// Metric in tenths, `None` if undefined (zero height)
+    pub fn compute(self, a: u64, b: u64) -> Option<u64> {
+        match self {
+            Metric::Bmi => bmi_tenths(a, b),
+            Metric::MeanArterialPressure => Some(mean_arterial_pressure_tenths(a, b)),
+            Metric::PackYears => Some(pack_years_tenths(a, b)),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = EligibilityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "bmi" => Ok(Metric::Bmi),
+            "mean_arterial_pressure" => Ok(Metric::MeanArterialPressure),
+            "pack_years" => Ok(Metric::PackYears),
+            other => Err(EligibilityError(format!("Unknown metric: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Metric::Bmi => "bmi",
+            Metric::MeanArterialPressure => "mean_arterial_pressure",
+            Metric::PackYears => "pack_years",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Metric Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct MetricCircuitConfig<F: PrimeField> {
+    pub measurement: Column<Advice>, // Private: measurements and their salts
+    pub constant: Column<Advice>,    // Fixed: metric code, analytes and scales
+    pub public: Column<Advice>,      // Public: min, max, study_id, context_hash, epoch
+    pub instance: Column<Instance>,
+    pub metric: MetricConfig<F>,
+    pub poseidon: PoseidonConfig,
+    pub merkle: MerkleConfig,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Metric Circuit
+///
+/// Proves: min <= metric <= max for a metric of attested measurements
+///
+/// ## Constraints
+/// 1. `Poseidon(analyte_i, measurement_i, scale_i, salt_i)` is a leaf of the lab tree
+///    for both measurements, with the analytes and scales of `metric` fixed
+/// 2. Both paths lead to the same `lab_root`, and `CredentialChip` verifies the
+///    credential signing it against `provider_root` and `revocation_root` at `epoch`
+/// 3. `MetricChip` computes the metric in tenths and checks `min <= metric <= max`
+///
+/// ## Public Inputs (instance column)
+/// Row 0: metric, row 1: min, row 2: max, row 3: study_id, row 4: context_hash,
+/// row 5: epoch, row 6: provider_root, row 7: revocation_root.
+#[derive(Clone)]
+pub struct MetricCircuit<F: EddsaField> {
+    pub metric: Metric,                             // Circuit shape, public input
+    pub measurements: [Value<F>; 2], // Private: canonical units at the analytes' scales
+    pub salts: [Value<F>; 2],        // Private: commitment salts
+    pub lab_paths: [[Value<F>; LAB_TREE_DEPTH]; 2], // Private: siblings up to lab_root, bottom-up
+    pub lab_bits: [[Value<bool>; LAB_TREE_DEPTH]; 2],
+    pub credential: CredentialWitness<F>, // Private: signs lab_root
+    pub min: F,                           // Public input (tenths)
+    pub max: F,                           // Public input (tenths)
+    pub study_id: F,                      // Public input (binds proof to specific study)
+    pub context_hash: F, // Public input (binds proof to wallet, chain and registry)
+    pub epoch: F,        // Public input (as-of day, see `epoch`)
+    pub provider_root: F, // Public: registered provider tree root
+    pub revocation_root: F, // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for MetricCircuit<F> {
+    fn default() -> Self {
+        Self {
+            metric: Metric::default(),
+            measurements: [Value::unknown(); 2],
+            salts: [Value::unknown(); 2],
+            lab_paths: [[Value::unknown(); LAB_TREE_DEPTH]; 2],
+            lab_bits: [[Value::unknown(); LAB_TREE_DEPTH]; 2],
+            credential: CredentialWitness::default(),
+            min: F::ZERO,
+            max: F::ZERO,
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> MetricCircuit<F> {
+    /// Circuit without witnesses, for key generation
+    pub fn for_metric(metric: Metric) -> Self {
+        Self {
+            metric,
+            ..Self::default()
+        }
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for MetricCircuit<F> {
+    type Config = MetricCircuitConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::for_metric(self.metric)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let measurement = meta.advice_column();
+        let constant = meta.advice_column();
+        let public = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let instance = meta.instance_column();
+
+        for column in [measurement, constant, public] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        let metric = MetricChip::configure(meta, running_sum);
+        // Poseidon enables the constants column the metric code, analytes and scales use
+        let poseidon = PoseidonChip::configure(meta);
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let merkle = MerkleChip::configure(meta, poseidon.clone());
+        let issuer = IssuerChip::configure(meta, eddsa, merkle.clone());
+        let credential = CredentialChip::configure(meta, issuer);
+
+        MetricCircuitConfig {
+            measurement,
+            constant,
+            public,
+            instance,
+            metric,
+            poseidon,
+            merkle,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let analytes = self.metric.analytes();
+
+        let (code, measurements, leaves, public) = layouter.assign_region(
+            || "measurements",
+            |mut region| {
+                let code = region.assign_advice_from_constant(
+                    || "metric",
+                    config.constant,
+                    0,
+                    F::from(self.metric.code()),
+                )?;
+
+                // Leaf inputs in commitment order: analyte, measurement, scale, salt
+                let mut measurements = Vec::with_capacity(2);
+                let mut leaves = Vec::with_capacity(2);
+                for (i, (analyte, scale)) in analytes.iter().enumerate() {
+                    let value = region.assign_advice(
+                        || format!("measurement {}", i),
+                        config.measurement,
+                        2 * i,
+                        || self.measurements[i],
+                    )?;
+                    let salt = region.assign_advice(
+                        || format!("salt {}", i),
+                        config.measurement,
+                        2 * i + 1,
+                        || self.salts[i],
+                    )?;
+                    let analyte = region.assign_advice_from_constant(
+                        || format!("analyte {}", i),
+                        config.constant,
+                        2 * i + 1,
+                        F::from(*analyte),
+                    )?;
+                    let scale = region.assign_advice_from_constant(
+                        || format!("scale {}", i),
+                        config.constant,
+                        2 * i + 2,
+                        F::from(*scale as u64),
+                    )?;
+                    measurements.push(value.clone());
+                    leaves.push([analyte, value, scale, salt]);
+                }
+
+                // Assign public values, in instance order
+                let mut public = Vec::with_capacity(5);
+                for (row, (name, field)) in [
+                    ("min", self.min),
+                    ("max", self.max),
+                    ("study_id", self.study_id),
+                    ("context_hash", self.context_hash),
+                    ("epoch", self.epoch),
+                ]
+                .into_iter()
+                .enumerate()
+                {
+                    public.push(region.assign_advice(
+                        || name,
+                        config.public,
+                        row,
+                        || Value::known(field),
+                    )?);
+                }
+
+                Ok((code, measurements, leaves, public))
+            },
+        )?;
+
+        // Both measurements are attested under the lab_root of the credential
+        let poseidon_chip = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let mut roots = Vec::with_capacity(2);
+        for (i, leaf) in leaves.iter().enumerate() {
+            let commitment =
+                poseidon_chip.hash(layouter.namespace(|| format!("commitment {}", i)), leaf)?;
+            roots.push(merkle_chip.compute_root(
+                layouter.namespace(|| format!("lab root {}", i)),
+                &commitment,
+                &self.lab_paths[i],
+                &self.lab_bits[i],
+            )?);
+        }
+        layouter.assign_region(
+            || "same lab root",
+            |mut region| region.constrain_equal(roots[0].cell(), roots[1].cell()),
+        )?;
+
+        let credential_chip = CredentialChip::<F>::construct(config.credential.clone());
+        let opened = CredentialAttributes {
+            lab_root: Some(&roots[0]),
+            ..Default::default()
+        };
+        let (provider_root, revocation_root) = credential_chip.verify_at(
+            layouter.namespace(|| "credential"),
+            opened,
+            &self.credential,
+            &public[4],
+        )?;
+
+        // The metric of the attested measurements is within [min, max]
+        let metric_chip = MetricChip::construct(config.metric.clone());
+        let (a, b) = (&measurements[0], &measurements[1]);
+        let namespace = layouter.namespace(|| "metric");
+        let metric = match self.metric {
+            Metric::Bmi => metric_chip.bmi(namespace, a, b)?,
+            Metric::MeanArterialPressure => metric_chip.mean_arterial_pressure(namespace, a, b)?,
+            Metric::PackYears => metric_chip.pack_years(namespace, a, b)?,
+        };
+        metric_chip.check_range(
+            layouter.namespace(|| "metric range"),
+            &metric,
+            &public[0],
+            &public[1],
+        )?;
+
+        // Bind every public value to its instance row
+        for (row, cell) in [&code]
+            .into_iter()
+            .chain(&public)
+            .chain([&provider_root, &revocation_root])
+            .enumerate()
+        {
+            layouter.constrain_instance(cell.cell(), config.instance, row)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for MetricCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: metric, min, max, study_id, context_hash, epoch,
+        // provider_root, revocation_root
+        vec![vec![
+            F::from(self.metric.code()),
+            self.min,
+            self.max,
+            self.study_id,
+            self.context_hash,
+            self.epoch,
+            self.provider_root,
+            self.revocation_root,
+        ]]
+    }
+}
+
+/// Add the `measurement_path` and `measurement_index` inputs of both commitments
+///
+/// The paths are concatenated, first measurement first.
+pub fn insert_measurement_inputs(
+    lab_tree: &LabTree,
+    commitments: &[Fr; 2],
+    inputs: &mut HashMap<String, Vec<Fr>>,
+) -> Result<(), EligibilityError> {
+    let paths = commitments
+        .iter()
+        .map(|commitment| lab_tree.path(commitment))
+        .collect::<Result<Vec<_>, _>>()?;
+    inputs.insert(
+        "measurement_path".to_string(),
+        paths
+            .iter()
+            .flat_map(|path| path.siblings.clone())
+            .collect(),
+    );
+    inputs.insert(
+        "measurement_index".to_string(),
+        paths
+            .iter()
+            .map(|path| Fr::from(path.index as u64))
+            .collect(),
+    );
+    Ok(())
+}
+
+/// Client-side validation (called before proof generation)
+///
+/// The circuit enforces the same range; this check only gives fast feedback.
+pub fn validate_metric(
+    metric: Metric,
+    a: u64,
+    b: u64,
+    min: u64,
+    max: u64,
+) -> Result<u64, EligibilityError> {
+    if a >= 1 << MEASUREMENT_BITS || b >= 1 << MEASUREMENT_BITS {
+        return Err(EligibilityError(format!(
+            "Measurements exceed {} bits",
+            MEASUREMENT_BITS
+        )));
+    }
+    let value = metric.compute(a, b).ok_or(EligibilityError(format!(
+        "{} is undefined for these measurements",
+        metric
+    )))?;
+    if !(min..=max).contains(&value) {
+        return Err(EligibilityError(format!(
+            "{} {} is outside [{}, {}] (tenths of {})",
+            metric,
+            value,
+            min,
+            max,
+            metric.unit()
+        )));
+    }
+    Ok(value)
+}
+
+/// Generate metric proof
+///
+/// Inputs: `metric` (a [`Metric`] code), `measurements` and `salts` (two values each,
+/// in canonical units at the analytes' scales), `min` and `max` (tenths), `study_id`,
+/// `context_hash`, `epoch`, the `measurement_path` and `measurement_index` of [`insert_measurement_inputs`] and
+/// the credential inputs. `prover_parameters` must be those of the metric's circuit.
+pub fn generate_metric_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_metric_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_metric_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_metric_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = METRIC_K;
+
+    // Extract inputs
+    let get = |name: &str| -> Result<Fr, EligibilityError> {
+        inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?
+            .first()
+            .copied()
+            .ok_or(EligibilityError(format!("Invalid {}", name)))
+    };
+    let pair = |name: &str| -> Result<[Fr; 2], EligibilityError> {
+        inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?
+            .as_slice()
+            .try_into()
+            .map_err(|_| EligibilityError(format!("Invalid {} (expected 2 values)", name)))
+    };
+    let metric = Metric::from_code(field_to_u64(&get("metric")?)?)
+        .ok_or(EligibilityError("Invalid metric code".to_string()))?;
+    let measurements = pair("measurements")?;
+    let salts = pair("salts")?;
+    let min = get("min")?;
+    let max = get("max")?;
+    let study_id = get("study_id")?;
+    let context_hash = get("context_hash")?;
+    let epoch = get("epoch")?;
+    let siblings = inputs
+        .get("measurement_path")
+        .filter(|siblings| siblings.len() == 2 * LAB_TREE_DEPTH)
+        .ok_or(EligibilityError(format!(
+            "Invalid measurement_path (expected {} values)",
+            2 * LAB_TREE_DEPTH
+        )))?;
+    let indices = pair("measurement_index")?;
+    let paths = [
+        lab_path(&siblings[..LAB_TREE_DEPTH], &indices[0])?,
+        lab_path(&siblings[LAB_TREE_DEPTH..], &indices[1])?,
+    ];
+    let credential = checked_credential_at(&inputs, &epoch)?;
+
+    // Client-side validation
+    // Fails fast instead of producing an unsatisfiable circuit
+    let values = [
+        field_to_u64(&measurements[0])?,
+        field_to_u64(&measurements[1])?,
+    ];
+    validate_metric(
+        metric,
+        values[0],
+        values[1],
+        field_to_u64(&min)?,
+        field_to_u64(&max)?,
+    )?;
+    for (i, commitment) in metric.commitments(values, salts).into_iter().enumerate() {
+        if paths[i].compute_root(commitment) != credential.attributes().lab_root {
+            return Err(EligibilityError(format!(
+                "Measurement {} of {} is not attested by the credential",
+                i, metric
+            )));
+        }
+    }
+
+    // Create circuit with validated inputs
+    let bits = [paths[0].bits(), paths[1].bits()];
+    let circuit = MetricCircuit::<Fr> {
+        metric,
+        measurements: measurements.map(Value::known),
+        salts: salts.map(Value::known),
+        lab_paths: std::array::from_fn(|i| {
+            std::array::from_fn(|j| Value::known(paths[i].siblings[j]))
+        }),
+        lab_bits: std::array::from_fn(|i| std::array::from_fn(|j| Value::known(bits[i][j]))),
+        credential: credential.witness(),
+        min,
+        max,
+        study_id,
+        context_hash,
+        epoch,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit =
+        Halo2Circuit::<Fr, MetricCircuit<Fr>>::new::<PC::ProvingBackend>(k, circuit.clone());
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify metric proof
+///
+/// `verifier_parameters` must be those of the metric named by the first public input.
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and checking `epoch` against its [`FreshnessWindow`](crate::epoch::FreshnessWindow).
+pub fn verify_metric_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != METRIC_PUBLIC_INPUTS {
+        return Err(EligibilityError(
+            "Invalid number of public inputs (expected 8: metric, min, max, study_id, context_hash, epoch, provider_root, revocation_root)"
+                .to_string(),
+        ));
+    }
+
+    // Verify the proof
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| EligibilityError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::{
+        testing::{issue, EPOCH, ISSUED_AT},
+        MAX_CREDENTIAL_AGE,
+    };
+    use eligibility_gadgets::days_to_field;
+    use halo2_proofs::dev::MockProver;
+
+    /// Circuit over two measurements the test provider attested next to an HbA1c reading
+    fn metric_circuit(metric: Metric, a: u64, b: u64, min: u64, max: u64) -> MetricCircuit<Fr> {
+        let salts = [Fr::from(9), Fr::from(10)];
+        let commitments = metric.commitments([a, b], salts);
+        let hba1c = commit_lab_value(Fr::from(45484), 72, 1, Fr::from(11));
+        let labs = LabTree::new(&[hba1c, commitments[0], commitments[1]]).unwrap();
+        let credential = issue(CredentialAttributes {
+            lab_root: labs.root(),
+            issued_at: days_to_field(ISSUED_AT),
+            ..Default::default()
+        });
+        let paths = commitments
+            .iter()
+            .map(|c| labs.path(c).unwrap())
+            .collect::<Vec<_>>();
+        let bits = paths.iter().map(|path| path.bits()).collect::<Vec<_>>();
+
+        MetricCircuit {
+            metric,
+            measurements: [Value::known(Fr::from(a)), Value::known(Fr::from(b))],
+            salts: salts.map(Value::known),
+            lab_paths: std::array::from_fn(|i| {
+                std::array::from_fn(|j| Value::known(paths[i].siblings[j]))
+            }),
+            lab_bits: std::array::from_fn(|i| std::array::from_fn(|j| Value::known(bits[i][j]))),
+            credential: credential.witness(),
+            min: Fr::from(min),
+            max: Fr::from(max),
+            study_id: Fr::from(1),
+            context_hash: Fr::from(7),
+            epoch: days_to_field(EPOCH),
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        }
+    }
+
+    fn mock_verify(circuit: &MetricCircuit<Fr>) -> bool {
+        MockProver::run(METRIC_K as u32, circuit, circuit.instances())
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_metric_table() {
+        for metric in Metric::ALL {
+            assert_eq!(metric.to_string().parse::<Metric>().unwrap(), metric);
+            assert_eq!(Metric::from_code(metric.code()), Some(metric));
+        }
+        assert_eq!(Metric::Bmi.analytes(), [(294637, 3), (83022, 1)]);
+        assert_eq!(Metric::MeanArterialPressure.compute(120, 80), Some(933));
+        assert_eq!(Metric::PackYears.compute(15, 30), Some(225));
+        assert_eq!(
+            Metric::PackYears.analytes(),
+            [(CIGARETTES_PER_DAY_ANALYTE, 0), (YEARS_SMOKED_ANALYTE, 0)]
+        );
+        assert!(Metric::PackYears.measurements().is_none());
+        assert!("packyears".parse::<Metric>().is_err());
+    }
+
+    const MAX_BOUND: u64 = u32::MAX as u64;
+
+    #[test]
+    fn test_validate_metric() {
+        // 80 kg, 1.80 m: BMI 24.6
+        assert_eq!(
+            validate_metric(Metric::Bmi, 80_000, 1_800, 185, 249).unwrap(),
+            246
+        );
+        assert!(validate_metric(Metric::Bmi, 80_000, 1_800, 300, MAX_BOUND).is_err());
+        assert!(validate_metric(Metric::Bmi, 80_000, 0, 0, MAX_BOUND).is_err());
+        assert!(validate_metric(Metric::Bmi, 1 << MEASUREMENT_BITS, 1_800, 0, MAX_BOUND).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_metrics_in_range() {
+        assert!(mock_verify(&metric_circuit(
+            Metric::Bmi,
+            80_000,
+            1_800,
+            185,
+            249
+        )));
+        assert!(mock_verify(&metric_circuit(
+            Metric::Bmi,
+            80_000,
+            1_800,
+            246,
+            246
+        ))); // Edges
+        assert!(mock_verify(&metric_circuit(
+            Metric::MeanArterialPressure,
+            120,
+            80,
+            0,
+            1099
+        )));
+        // 15 cigarettes a day for 30 years: 22.5 pack-years
+        assert!(mock_verify(&metric_circuit(
+            Metric::PackYears,
+            15,
+            30,
+            200,
+            MAX_BOUND
+        )));
+    }
+
+    #[test]
+    fn test_circuit_rejects_metrics_out_of_range() {
+        // Bypasses validate_metric: a modified client must not be able to prove these
+        assert!(!mock_verify(&metric_circuit(
+            Metric::Bmi,
+            80_000,
+            1_800,
+            300,
+            MAX_BOUND
+        )));
+        assert!(!mock_verify(&metric_circuit(
+            Metric::Bmi,
+            80_000,
+            1_800,
+            0,
+            245
+        )));
+        assert!(!mock_verify(&metric_circuit(
+            Metric::MeanArterialPressure,
+            160,
+            100,
+            0,
+            1099
+        )));
+        assert!(!mock_verify(&metric_circuit(
+            Metric::PackYears,
+            15,
+            20,
+            200,
+            MAX_BOUND
+        )));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_measurements() {
+        // A lighter weight than the provider attested
+        let mut circuit = metric_circuit(Metric::Bmi, 100_000, 1_800, 0, 249);
+        circuit.measurements[0] = Value::known(Fr::from(80_000));
+        assert!(!mock_verify(&circuit));
+
+        // Measurements of another metric: the analytes are fixed by the circuit
+        let mut circuit = metric_circuit(Metric::MeanArterialPressure, 120, 80, 0, MAX_BOUND);
+        circuit.metric = Metric::Bmi;
+        assert!(!mock_verify(&circuit));
+
+        // Blood pressure passed off as a smoking history
+        let mut circuit = metric_circuit(Metric::MeanArterialPressure, 120, 80, 0, MAX_BOUND);
+        circuit.metric = Metric::PackYears;
+        assert!(!mock_verify(&circuit));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = metric_circuit(Metric::Bmi, 80_000, 1_800, 185, 249);
+        let names = [
+            "metric",
+            "min",
+            "max",
+            "study_id",
+            "context_hash",
+            "epoch",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            let prover = MockProver::run(METRIC_K as u32, &circuit, instances).unwrap();
+            assert!(
+                prover.verify().is_err(),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_measurement_inputs() {
+        let labs = LabTree::new(&[Fr::from(11), Fr::from(12), Fr::from(13)]).unwrap();
+        let mut inputs = HashMap::new();
+        insert_measurement_inputs(&labs, &[Fr::from(13), Fr::from(11)], &mut inputs).unwrap();
+        assert_eq!(inputs["measurement_path"].len(), 2 * LAB_TREE_DEPTH);
+        assert_eq!(inputs["measurement_index"], vec![Fr::from(2), Fr::from(0)]);
+        assert!(
+            insert_measurement_inputs(&labs, &[Fr::from(14), Fr::from(11)], &mut inputs).is_err()
+        );
+    }
+
+    #[test]
+    fn test_circuit_rejects_expired_credential() {
+        // The credential was issued on ISSUED_AT
+        let mut circuit = metric_circuit(Metric::Bmi, 80_000, 1_800, 185, 249);
+        circuit.epoch = days_to_field(ISSUED_AT + MAX_CREDENTIAL_AGE as i64 + 1);
+        assert!(!mock_verify(&circuit));
+    }
+}
@@ -0,0 +1,1084 @@
+//! Non-Participation Circuit - Conflicting Trial Verification
+//!
+//! Proves that a patient is not enrolled in any of a public list of conflicting studies
+//! (e.g. other interventional trials running at the same time), against the published
+//! root of the [`ActiveEnrollmentTree`].
+//!
+//! ## Security Model
+//! - Private Inputs: identity secret, credential (see [`credential`](crate::credential));
+//!   for each conflicting study, the two adjacent leaves bracketing the patient's
+//!   enrollment key and their authentication paths
+//! - Public Inputs: conflicting_study_ids (MAX_CONFLICTING_STUDIES), study_id, nullifier,
+//!   active_enrollment_root, provider_root, revocation_root
+//! - Constraints: Poseidon(tag, identity_secret) is the identity_commitment of a
+//!   credential signed by a provider registered under `provider_root` and not revoked
+//!   under `revocation_root`, nullifier = Poseidon(identity_secret, study_id), and for
+//!   every conflicting study, key(Poseidon(identity_secret, conflicting_study_id)) ∉
+//!   active keys
+//!
+//! The public nullifier is the one of the age proof for the same study (see
+//! [`nullifier`](crate::nullifier)), so a registry accepts both proofs only from the
+//! same patient. Absence only means something for the secret the patient enrolled
+//! with: any other secret has no enrollments at all. The credential pins the secret to
+//! the identity commitment a provider signed, so a patient cannot prove absence with a
+//! fresh one.
+//!
+//! ## Enrollment Keys
+//! The circuit computes each conflicting nullifier and splits it as
+//! `key_high * 2^ENROLLMENT_KEY_BITS + key`. Both halves are range checked, and the split
+//! is forced to be the canonical one (below the field modulus): a bit `at_max` is set
+//! exactly when `key_high` is the high half of `p - 1`, in which case `key` must not
+//! exceed its low half. Without this, `nullifier + p` would give the prover a second key.
+//!
+//! ## Adjacent-Leaf Proofs
+//! As in diagnosis exclusion, the key is absent exactly when two adjacent leaves `low`
+//! (index `i`) and `high` (index `i + 1`) satisfy `low < key < high`. Both leaves are
+//! under the public root, their indices differ by one, and `key - low - 1` and
+//! `high - key - 1` fit in ENROLLMENT_KEY_BITS bits. The circuit does not check that the
+//! tree is sorted: verifiers must only accept the root published from
+//! [`ActiveEnrollmentTree`].
+//!
+//! Shorter conflicting lists are padded by repeating their first entry.
+
+use std::{collections::HashMap, io::Cursor};
+
+use eligibility_gadgets::{
+    identity_commitment, CredentialAttributes, CredentialChip, CredentialConfig, CredentialWitness,
+    EddsaChip, EddsaField, IssuerChip, MerkleChip, MerkleConfig, MerklePath, PoseidonChip,
+    PoseidonField, RangeCheckChip, RangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    credential::{checked_credential, CredentialInputs},
+    enrollment::{
+        enrollment_key, split_key, ActiveEnrollmentTree, ACTIVE_ENROLLMENT_TREE_DEPTH,
+        ENROLLMENT_KEY_BITS,
+    },
+    field_to_u64,
+    nullifier::compute_nullifier,
+    rng::default_rng,
+    EligibilityError, PlonkishComponents,
+};
+
+/// Maximum number of conflicting studies per proof
+pub const MAX_CONFLICTING_STUDIES: usize = 4;
+
+/// Public inputs: conflicting_study_ids, study_id, nullifier, active_enrollment_root,
+/// provider_root, revocation_root
+pub const NON_PARTICIPATION_PUBLIC_INPUTS: usize = MAX_CONFLICTING_STUDIES + 5;
+
+/// Circuit size: 2 * ACTIVE_ENROLLMENT_TREE_DEPTH + 1 Poseidon hashes per conflicting study
+/// and the credential check
+pub const NON_PARTICIPATION_K: usize = 15;
+
+/// Non-Participation Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct NonParticipationConfig<F: PoseidonField> {
+    pub lhs: Column<Advice>,
+    pub rhs: Column<Advice>,
+    pub gap: Column<Advice>,             // lhs - rhs - 1
+    pub nullifier: Column<Advice>,       // Nullifier of a conflicting study
+    pub key_high: Column<Advice>,        // High half of the nullifier
+    pub key: Column<Advice>,             // Low half of the nullifier: the enrollment key
+    pub at_max: Column<Advice>,          // 1 iff key_high is the high half of p - 1
+    pub high_slack: Column<Advice>,      // max_high - key_high - 1 + at_max
+    pub low_slack: Column<Advice>,       // at_max * (max_low - key)
+    pub identity_secret: Column<Advice>, // Private: patient identity secret
+    pub study_id: Column<Advice>,        // Public: study and conflicting study IDs
+    pub q_gap: Selector,
+    pub q_key: Selector,
+    pub instance: Column<Instance>,
+    pub merkle: MerkleConfig,
+    pub range_check: RangeCheckConfig<F, ENROLLMENT_KEY_BITS>,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Private witness for one conflicting study
+#[derive(Clone, Copy)]
+pub struct AdjacentLeaves<F: PoseidonField> {
+    pub low: Value<F>,
+    pub low_siblings: [Value<F>; ACTIVE_ENROLLMENT_TREE_DEPTH],
+    pub low_bits: [Value<bool>; ACTIVE_ENROLLMENT_TREE_DEPTH],
+    pub high: Value<F>,
+    pub high_siblings: [Value<F>; ACTIVE_ENROLLMENT_TREE_DEPTH],
+    pub high_bits: [Value<bool>; ACTIVE_ENROLLMENT_TREE_DEPTH],
+}
+
+impl<F: PoseidonField> Default for AdjacentLeaves<F> {
+    fn default() -> Self {
+        Self {
+            low: Value::unknown(),
+            low_siblings: [Value::unknown(); ACTIVE_ENROLLMENT_TREE_DEPTH],
+            low_bits: [Value::unknown(); ACTIVE_ENROLLMENT_TREE_DEPTH],
+            high: Value::unknown(),
+            high_siblings: [Value::unknown(); ACTIVE_ENROLLMENT_TREE_DEPTH],
+            high_bits: [Value::unknown(); ACTIVE_ENROLLMENT_TREE_DEPTH],
+        }
+    }
+}
+
+impl AdjacentLeaves<Fr> {
+    fn known(low: Fr, low_path: &MerklePath<Fr>, high: Fr, high_path: &MerklePath<Fr>) -> Self {
+        let low_bits = low_path.bits();
+        let high_bits = high_path.bits();
+
+        Self {
+            low: Value::known(low),
+            low_siblings: std::array::from_fn(|i| Value::known(low_path.siblings[i])),
+            low_bits: std::array::from_fn(|i| Value::known(low_bits[i])),
+            high: Value::known(high),
+            high_siblings: std::array::from_fn(|i| Value::known(high_path.siblings[i])),
+            high_bits: std::array::from_fn(|i| Value::known(high_bits[i])),
+        }
+    }
+}
+
+/// Non-Participation Circuit
+///
+/// Proves: the patient has no active enrollment in any conflicting study
+///
+/// ## Public Inputs (instance column)
+/// Rows 0..MAX_CONFLICTING_STUDIES: conflicting_study_ids, then study_id, nullifier,
+/// active_enrollment_root, provider_root and revocation_root. All are
+/// copy-constrained to their advice cells.
+#[derive(Clone)]
+pub struct NonParticipationCircuit<F: EddsaField> {
+    pub witnesses: [AdjacentLeaves<F>; MAX_CONFLICTING_STUDIES], // Private: bracketing leaves
+    pub identity_secret: Value<F>, // Private: secret of the signed identity commitment
+    pub credential: CredentialWitness<F>, // Private: signs the identity commitment
+    pub conflicting_study_ids: [F; MAX_CONFLICTING_STUDIES], // Public: studies that conflict
+    pub study_id: F,               // Public: study applied to
+    pub nullifier: F,              // Public: Poseidon(identity_secret, study_id)
+    pub active_enrollment_root: F, // Public: published tree root
+    pub provider_root: F,          // Public: root of the registered provider keys
+    pub revocation_root: F,        // Public: root of the revoked credential IDs
+}
+
+impl<F: EddsaField> Default for NonParticipationCircuit<F> {
+    fn default() -> Self {
+        Self {
+            witnesses: [AdjacentLeaves::default(); MAX_CONFLICTING_STUDIES],
+            identity_secret: Value::unknown(),
+            credential: CredentialWitness::default(),
+            conflicting_study_ids: [F::ZERO; MAX_CONFLICTING_STUDIES],
+            study_id: F::ZERO,
+            nullifier: F::ZERO,
+            active_enrollment_root: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> NonParticipationCircuit<F> {
+    /// Assign `lhs - rhs - 1` on one row of the gap gate
+    fn assign_gap(
+        config: &NonParticipationConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        name: &str,
+        lhs: &AssignedCell<F, F>,
+        rhs: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || format!("gap {}", name),
+            |mut region| {
+                config.q_gap.enable(&mut region, 0)?;
+
+                let lhs = lhs.copy_advice(|| "lhs", &mut region, config.lhs, 0)?;
+                let rhs = rhs.copy_advice(|| "rhs", &mut region, config.rhs, 0)?;
+
+                let gap = lhs
+                    .value()
+                    .copied()
+                    .zip(rhs.value().copied())
+                    .map(|(lhs, rhs)| lhs - rhs - F::ONE);
+                region.assign_advice(|| "gap", config.gap, 0, || gap)
+            },
+        )
+    }
+
+    /// Split `nullifier` on one row of the key gate, returning the enrollment key and
+    /// the other cells to range check
+    fn assign_key(
+        config: &NonParticipationConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        k: usize,
+        nullifier: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, [AssignedCell<F, F>; 3]), Error> {
+        let (max_high, max_low) = split_key(&-F::ONE);
+
+        layouter.assign_region(
+            || format!("enrollment key {}", k),
+            |mut region| {
+                config.q_key.enable(&mut region, 0)?;
+
+                let nullifier =
+                    nullifier.copy_advice(|| "nullifier", &mut region, config.nullifier, 0)?;
+                let halves = nullifier.value().map(split_key);
+                let at_max = halves.map(|(high, _)| F::from((high == max_high) as u64));
+
+                let key_high = region.assign_advice(
+                    || "key_high",
+                    config.key_high,
+                    0,
+                    || halves.map(|(high, _)| high),
+                )?;
+                let key =
+                    region.assign_advice(|| "key", config.key, 0, || halves.map(|(_, low)| low))?;
+                region.assign_advice(|| "at_max", config.at_max, 0, || at_max)?;
+
+                let high_slack = region.assign_advice(
+                    || "high_slack",
+                    config.high_slack,
+                    0,
+                    || {
+                        halves
+                            .zip(at_max)
+                            .map(|((high, _), at_max)| max_high - high - F::ONE + at_max)
+                    },
+                )?;
+                let low_slack = region.assign_advice(
+                    || "low_slack",
+                    config.low_slack,
+                    0,
+                    || {
+                        halves
+                            .zip(at_max)
+                            .map(|((_, low), at_max)| at_max * (max_low - low))
+                    },
+                )?;
+
+                Ok((key, [key_high, high_slack, low_slack]))
+            },
+        )
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for NonParticipationCircuit<F> {
+    type Config = NonParticipationConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let lhs = meta.advice_column();
+        let rhs = meta.advice_column();
+        let gap = meta.advice_column();
+        let nullifier = meta.advice_column();
+        let key_high = meta.advice_column();
+        let key = meta.advice_column();
+        let at_max = meta.advice_column();
+        let high_slack = meta.advice_column();
+        let low_slack = meta.advice_column();
+        let identity_secret = meta.advice_column();
+        let study_id = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let q_gap = meta.selector();
+        let q_key = meta.selector();
+        let instance = meta.instance_column();
+
+        for column in [
+            lhs,
+            rhs,
+            gap,
+            nullifier,
+            key_high,
+            key,
+            high_slack,
+            low_slack,
+            identity_secret,
+            study_id,
+        ] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        // Gate: gap = lhs - rhs - 1 (lhs > rhs once gap is range checked)
+        meta.create_gate("non-participation gap", |meta| {
+            let q = meta.query_selector(q_gap);
+            let lhs = meta.query_advice(lhs, Rotation::cur());
+            let rhs = meta.query_advice(rhs, Rotation::cur());
+            let gap = meta.query_advice(gap, Rotation::cur());
+
+            vec![q * (gap - (lhs - rhs - Expression::Constant(F::ONE)))]
+        });
+
+        // Gate: canonical split of a nullifier into key_high and key (see the module docs)
+        meta.create_gate("enrollment key", |meta| {
+            let q = meta.query_selector(q_key);
+            let nullifier = meta.query_advice(nullifier, Rotation::cur());
+            let key_high = meta.query_advice(key_high, Rotation::cur());
+            let key = meta.query_advice(key, Rotation::cur());
+            let at_max = meta.query_advice(at_max, Rotation::cur());
+            let high_slack = meta.query_advice(high_slack, Rotation::cur());
+            let low_slack = meta.query_advice(low_slack, Rotation::cur());
+
+            let one = Expression::Constant(F::ONE);
+            let (max_high, max_low) = split_key(&-F::ONE);
+            let shift = F::from(2).pow_vartime([ENROLLMENT_KEY_BITS as u64]);
+
+            vec![
+                // nullifier = key_high * 2^ENROLLMENT_KEY_BITS + key
+                q.clone() * (nullifier - (key_high.clone() * shift + key.clone())),
+                // at_max ∈ {0, 1}, and at_max = 1 only if key_high = max_high
+                q.clone() * at_max.clone() * (one.clone() - at_max.clone()),
+                q.clone() * at_max.clone() * (key_high.clone() - Expression::Constant(max_high)),
+                // key_high < max_high, or key_high = max_high and key <= max_low
+                q.clone()
+                    * (high_slack
+                        - (Expression::Constant(max_high) - key_high - one + at_max.clone())),
+                q * (low_slack - at_max * (Expression::Constant(max_low) - key)),
+            ]
+        });
+
+        let poseidon = PoseidonChip::configure(meta);
+        let eddsa = EddsaChip::configure(meta, poseidon.clone());
+        let merkle = MerkleChip::configure(meta, poseidon);
+        let range_check = RangeCheckChip::configure(meta, running_sum);
+        let issuer = IssuerChip::configure(meta, eddsa, merkle.clone());
+        let credential = CredentialChip::configure(meta, issuer);
+
+        NonParticipationConfig {
+            lhs,
+            rhs,
+            gap,
+            nullifier,
+            key_high,
+            key,
+            at_max,
+            high_slack,
+            low_slack,
+            identity_secret,
+            study_id,
+            q_gap,
+            q_key,
+            instance,
+            merkle,
+            range_check,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let poseidon_chip = PoseidonChip::<F>::construct(config.merkle.poseidon.clone());
+        let range_chip = RangeCheckChip::construct(config.range_check.clone());
+
+        let (identity_secret, study_id) = layouter.assign_region(
+            || "non-participation inputs",
+            |mut region| {
+                // Assign private identity secret
+                let identity_secret = region.assign_advice(
+                    || "identity_secret",
+                    config.identity_secret,
+                    0,
+                    || self.identity_secret,
+                )?;
+
+                // Assign public study_id
+                let study_id = region.assign_advice(
+                    || "study_id",
+                    config.study_id,
+                    0,
+                    || Value::known(self.study_id),
+                )?;
+
+                Ok((identity_secret, study_id))
+            },
+        )?;
+
+        // The identity secret is the one a registered provider signed
+        let credential_chip = CredentialChip::<F>::construct(config.credential.clone());
+        let identity_commitment = credential_chip.identity_commitment(
+            layouter.namespace(|| "identity commitment"),
+            &identity_secret,
+        )?;
+        let opened = CredentialAttributes {
+            identity_commitment: Some(&identity_commitment),
+            dob_commitment: None,
+            diagnosis_commitment: None,
+            lab_root: None,
+            issued_at: None,
+        };
+        let (provider_root, revocation_root) = credential_chip.verify(
+            layouter.namespace(|| "credential"),
+            opened,
+            &self.credential,
+        )?;
+
+        for (k, (conflicting_study_id, witness)) in self
+            .conflicting_study_ids
+            .iter()
+            .zip(self.witnesses.iter())
+            .enumerate()
+        {
+            let (conflicting, low, high) = layouter.assign_region(
+                || format!("conflicting study {}", k),
+                |mut region| {
+                    let conflicting = region.assign_advice(
+                        || "conflicting_study_id",
+                        config.study_id,
+                        0,
+                        || Value::known(*conflicting_study_id),
+                    )?;
+                    let low = region.assign_advice(|| "low", config.lhs, 0, || witness.low)?;
+                    let high = region.assign_advice(|| "high", config.rhs, 0, || witness.high)?;
+
+                    Ok((conflicting, low, high))
+                },
+            )?;
+            layouter.constrain_instance(conflicting.cell(), config.instance, k)?;
+
+            // The patient's nullifier for the conflicting study, reduced to its key
+            let nullifier = poseidon_chip.hash(
+                layouter.namespace(|| format!("conflicting nullifier {}", k)),
+                &[identity_secret.clone(), conflicting],
+            )?;
+            let (key, split) = Self::assign_key(&config, &mut layouter, k, &nullifier)?;
+            range_chip.assign(layouter.namespace(|| format!("key {}", k)), &key)?;
+            for (i, cell) in split.iter().enumerate() {
+                range_chip.assign(
+                    layouter.namespace(|| format!("key split {} {}", k, i)),
+                    cell,
+                )?;
+            }
+
+            // Both leaves are under the published root...
+            let (low_root, low_index) = merkle_chip.compute_root_with_index(
+                layouter.namespace(|| format!("low leaf {}", k)),
+                &low,
+                &witness.low_siblings,
+                &witness.low_bits,
+            )?;
+            let (high_root, high_index) = merkle_chip.compute_root_with_index(
+                layouter.namespace(|| format!("high leaf {}", k)),
+                &high,
+                &witness.high_siblings,
+                &witness.high_bits,
+            )?;
+            layouter.constrain_instance(
+                low_root.cell(),
+                config.instance,
+                MAX_CONFLICTING_STUDIES + 2,
+            )?;
+            layouter.constrain_instance(
+                high_root.cell(),
+                config.instance,
+                MAX_CONFLICTING_STUDIES + 2,
+            )?;
+
+            // ...adjacent...
+            let index_gap = Self::assign_gap(
+                &config,
+                &mut layouter,
+                &format!("index {}", k),
+                &high_index,
+                &low_index,
+            )?;
+            layouter.assign_region(
+                || format!("adjacent leaves {}", k),
+                |mut region| region.constrain_constant(index_gap.cell(), F::ZERO),
+            )?;
+
+            // ...and strictly bracket the key
+            let low_gap =
+                Self::assign_gap(&config, &mut layouter, &format!("low {}", k), &key, &low)?;
+            let high_gap =
+                Self::assign_gap(&config, &mut layouter, &format!("high {}", k), &high, &key)?;
+
+            range_chip.assign(layouter.namespace(|| format!("low gap {}", k)), &low_gap)?;
+            range_chip.assign(layouter.namespace(|| format!("high gap {}", k)), &high_gap)?;
+        }
+
+        // Same nullifier as the age proof for this study
+        let nullifier = poseidon_chip.hash(
+            layouter.namespace(|| "nullifier"),
+            &[identity_secret, study_id.clone()],
+        )?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(study_id.cell(), config.instance, MAX_CONFLICTING_STUDIES)?;
+        layouter.constrain_instance(
+            nullifier.cell(),
+            config.instance,
+            MAX_CONFLICTING_STUDIES + 1,
+        )?;
+        layouter.constrain_instance(
+            provider_root.cell(),
+            config.instance,
+            MAX_CONFLICTING_STUDIES + 3,
+        )?;
+        layouter.constrain_instance(
+            revocation_root.cell(),
+            config.instance,
+            MAX_CONFLICTING_STUDIES + 4,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for NonParticipationCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: conflicting_study_ids, study_id, nullifier, active_enrollment_root,
+        // provider_root, revocation_root
+        let mut instances = self.conflicting_study_ids.to_vec();
+        instances.push(self.study_id);
+        instances.push(self.nullifier);
+        instances.push(self.active_enrollment_root);
+        instances.push(self.provider_root);
+        instances.push(self.revocation_root);
+        vec![instances]
+    }
+}
+
+/// Pad a conflicting study list to MAX_CONFLICTING_STUDIES by repeating its first entry
+pub fn pad_conflicting_study_ids(
+    conflicting_study_ids: &[Fr],
+) -> Result<[Fr; MAX_CONFLICTING_STUDIES], EligibilityError> {
+    if conflicting_study_ids.is_empty() || conflicting_study_ids.len() > MAX_CONFLICTING_STUDIES {
+        return Err(EligibilityError(format!(
+            "Expected 1 to {} conflicting studies, got {}",
+            MAX_CONFLICTING_STUDIES,
+            conflicting_study_ids.len()
+        )));
+    }
+
+    Ok(std::array::from_fn(|i| {
+        conflicting_study_ids
+            .get(i)
+            .copied()
+            .unwrap_or(conflicting_study_ids[0])
+    }))
+}
+
+impl ActiveEnrollmentTree {
+    /// Bracketing leaves of the key of `nullifier`, failing if it is active
+    fn adjacent_leaves(&self, nullifier: &Fr) -> Result<AdjacentLeaves<Fr>, EligibilityError> {
+        let index = self.bracketing_index(nullifier)?;
+        let (low_path, high_path) = self.adjacent_paths(index)?;
+        let leaves = self.tree.leaves();
+
+        Ok(AdjacentLeaves::known(
+            leaves[index],
+            &low_path,
+            leaves[index + 1],
+            &high_path,
+        ))
+    }
+
+    /// Circuit proving that the patient has no active enrollment in `conflicting_study_ids`
+    ///
+    /// `credential` attests the identity commitment of `identity_secret` (see
+    /// [`credential_inputs`](crate::credential::credential_inputs)).
+    pub fn non_participation_circuit(
+        &self,
+        identity_secret: Fr,
+        credential: &CredentialInputs,
+        study_id: Fr,
+        conflicting_study_ids: &[Fr],
+    ) -> Result<NonParticipationCircuit<Fr>, EligibilityError> {
+        let conflicting_study_ids = pad_conflicting_study_ids(conflicting_study_ids)?;
+        check_identity(identity_secret, credential)?;
+
+        let mut witnesses = [AdjacentLeaves::default(); MAX_CONFLICTING_STUDIES];
+        for (witness, conflicting) in witnesses.iter_mut().zip(conflicting_study_ids.iter()) {
+            *witness = self.adjacent_leaves(&compute_nullifier(identity_secret, *conflicting))?;
+        }
+
+        Ok(NonParticipationCircuit {
+            witnesses,
+            identity_secret: Value::known(identity_secret),
+            credential: credential.witness(),
+            conflicting_study_ids,
+            study_id,
+            nullifier: compute_nullifier(identity_secret, study_id),
+            active_enrollment_root: self.root(),
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        })
+    }
+
+    /// Input map for [`generate_non_participation_proof`]
+    pub fn non_participation_inputs(
+        &self,
+        identity_secret: Fr,
+        credential: &CredentialInputs,
+        study_id: Fr,
+        conflicting_study_ids: &[Fr],
+    ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
+        pad_conflicting_study_ids(conflicting_study_ids)?;
+        check_identity(identity_secret, credential)?;
+
+        let mut low_indices = Vec::new();
+        let mut low_leaves = Vec::new();
+        let mut high_leaves = Vec::new();
+        let mut low_siblings = Vec::new();
+        let mut high_siblings = Vec::new();
+        for conflicting in conflicting_study_ids.iter() {
+            let index = self.bracketing_index(&compute_nullifier(identity_secret, *conflicting))?;
+            let (low_path, high_path) = self.adjacent_paths(index)?;
+
+            low_indices.push(Fr::from(index as u64));
+            low_leaves.push(self.tree.leaves()[index]);
+            high_leaves.push(self.tree.leaves()[index + 1]);
+            low_siblings.extend(low_path.siblings);
+            high_siblings.extend(high_path.siblings);
+        }
+
+        let mut inputs = HashMap::new();
+        inputs.insert(
+            "conflicting_study_ids".to_string(),
+            conflicting_study_ids.to_vec(),
+        );
+        inputs.insert("study_id".to_string(), vec![study_id]);
+        inputs.insert(
+            "nullifier".to_string(),
+            vec![compute_nullifier(identity_secret, study_id)],
+        );
+        inputs.insert("active_enrollment_root".to_string(), vec![self.root()]);
+        inputs.insert("identity_secret".to_string(), vec![identity_secret]);
+        inputs.insert("low_indices".to_string(), low_indices);
+        inputs.insert("low_leaves".to_string(), low_leaves);
+        inputs.insert("high_leaves".to_string(), high_leaves);
+        inputs.insert("low_siblings".to_string(), low_siblings);
+        inputs.insert("high_siblings".to_string(), high_siblings);
+        credential.insert_inputs(&mut inputs);
+
+        Ok(inputs)
+    }
+}
+
+/// Fail unless `credential` attests the identity commitment of `identity_secret`
+fn check_identity(
+    identity_secret: Fr,
+    credential: &CredentialInputs,
+) -> Result<(), EligibilityError> {
+    if identity_commitment(identity_secret) != credential.attributes().identity_commitment {
+        return Err(EligibilityError(
+            "Identity secret does not match identity_commitment".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Generate non-participation proof
+///
+/// Expects the input map built by [`ActiveEnrollmentTree::non_participation_inputs`]:
+/// `conflicting_study_ids` (1 to MAX_CONFLICTING_STUDIES values), `study_id`,
+/// `nullifier`, `active_enrollment_root`, `identity_secret`, and per conflicting study:
+/// `low_indices`, `low_leaves`, `high_leaves`, plus `low_siblings` and `high_siblings`
+/// (ACTIVE_ENROLLMENT_TREE_DEPTH values each, bottom-up), and the credential inputs,
+/// which include the `identity_commitment` of `identity_secret`. The credential and the
+/// bracketing leaves are checked before proving.
+pub fn generate_non_participation_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_non_participation_proof_with_rng::<PC, _>(
+        srs,
+        prover_parameters,
+        inputs,
+        default_rng(),
+    )
+}
+
+/// [`generate_non_participation_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_non_participation_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), EligibilityError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = NON_PARTICIPATION_K;
+
+    // Extract inputs
+    let get = |name: &str, len: usize| -> Result<&[Fr], EligibilityError> {
+        let values = inputs
+            .get(name)
+            .ok_or(EligibilityError(format!("Missing {}", name)))?;
+        if values.len() != len {
+            return Err(EligibilityError(format!(
+                "Invalid {} (expected {} values)",
+                name, len
+            )));
+        }
+        Ok(values)
+    };
+
+    let studies = inputs.get("conflicting_study_ids").ok_or(EligibilityError(
+        "Missing conflicting_study_ids".to_string(),
+    ))?;
+    let count = studies.len();
+    let conflicting_study_ids = pad_conflicting_study_ids(studies)?;
+
+    let study_id = get("study_id", 1)?[0];
+    let nullifier = get("nullifier", 1)?[0];
+    let active_enrollment_root = get("active_enrollment_root", 1)?[0];
+    let identity_secret = get("identity_secret", 1)?[0];
+    let low_indices = get("low_indices", count)?;
+    let low_leaves = get("low_leaves", count)?;
+    let high_leaves = get("high_leaves", count)?;
+    let low_siblings = get("low_siblings", count * ACTIVE_ENROLLMENT_TREE_DEPTH)?;
+    let high_siblings = get("high_siblings", count * ACTIVE_ENROLLMENT_TREE_DEPTH)?;
+
+    // Client-side validation: the identity secret is the attested one, the nullifier is
+    // the patient's, and each pair of leaves is under the root and brackets the key of a
+    // conflicting nullifier
+    let credential = checked_credential(&inputs)?;
+    check_identity(identity_secret, &credential)?;
+    if compute_nullifier(identity_secret, study_id) != nullifier {
+        return Err(EligibilityError(
+            "Nullifier does not match identity_secret and study_id".to_string(),
+        ));
+    }
+
+    let mut witnesses = Vec::with_capacity(count);
+    for i in 0..count {
+        let index = field_to_u64(&low_indices[i])? as usize;
+        if index + 1 >= 1 << ACTIVE_ENROLLMENT_TREE_DEPTH {
+            return Err(EligibilityError(format!("Invalid low_indices: {}", index)));
+        }
+
+        let siblings = |all: &[Fr]| {
+            all[i * ACTIVE_ENROLLMENT_TREE_DEPTH..(i + 1) * ACTIVE_ENROLLMENT_TREE_DEPTH].to_vec()
+        };
+        let low_path = MerklePath {
+            index,
+            siblings: siblings(low_siblings),
+        };
+        let high_path = MerklePath {
+            index: index + 1,
+            siblings: siblings(high_siblings),
+        };
+
+        let key = enrollment_key(&compute_nullifier(identity_secret, studies[i]));
+        if low_path.compute_root(low_leaves[i]) != active_enrollment_root
+            || high_path.compute_root(high_leaves[i]) != active_enrollment_root
+            || enrollment_key(&low_leaves[i]) >= key
+            || key >= enrollment_key(&high_leaves[i])
+        {
+            return Err(EligibilityError(format!(
+                "Patient is not provably absent from the active enrollments of conflicting study {}",
+                i
+            )));
+        }
+
+        witnesses.push(AdjacentLeaves::known(
+            low_leaves[i],
+            &low_path,
+            high_leaves[i],
+            &high_path,
+        ));
+    }
+
+    // Create circuit with validated inputs; padding repeats the first entry
+    let circuit = NonParticipationCircuit::<Fr> {
+        witnesses: std::array::from_fn(|i| witnesses.get(i).copied().unwrap_or(witnesses[0])),
+        identity_secret: Value::known(identity_secret),
+        credential: credential.witness(),
+        conflicting_study_ids,
+        study_id,
+        nullifier,
+        active_enrollment_root,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit = Halo2Circuit::<Fr, NonParticipationCircuit<Fr>>::new::<PC::ProvingBackend>(
+        k,
+        circuit.clone(),
+    );
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| EligibilityError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify non-participation proof
+///
+/// The caller must also check that `active_enrollment_root`, `provider_root` and
+/// `revocation_root` are the currently published roots and `nullifier` the one of the
+/// applicant's age proof.
+pub fn verify_non_participation_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, EligibilityError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != NON_PARTICIPATION_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of public inputs (expected {}: conflicting_study_ids, study_id, nullifier, active_enrollment_root, provider_root, revocation_root)",
+            NON_PARTICIPATION_PUBLIC_INPUTS
+        )));
+    }
+
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| EligibilityError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::testing::issue;
+    use halo2_proofs::{
+        dev::MockProver,
+        halo2curves::ff::{Field, PrimeField},
+    };
+
+    const STUDY: u64 = 10;
+    const CONFLICTING: [u64; 2] = [11, 12];
+
+    fn patient() -> Fr {
+        Fr::from(42)
+    }
+
+    /// Credential attesting the identity commitment of `identity_secret`
+    fn credential(identity_secret: Fr) -> CredentialInputs {
+        issue(CredentialAttributes {
+            identity_commitment: identity_commitment(identity_secret),
+            dob_commitment: Fr::from(11),
+            diagnosis_commitment: Fr::from(12),
+            lab_root: Fr::from(13),
+            ..Default::default()
+        })
+    }
+
+    fn conflicting_study_ids() -> Vec<Fr> {
+        CONFLICTING.iter().map(|id| Fr::from(*id)).collect()
+    }
+
+    /// Other patients enrolled in the conflicting studies, plus the patient in study 13
+    fn active_enrollments() -> ActiveEnrollmentTree {
+        let mut nullifiers = (100..110)
+            .flat_map(|other| {
+                CONFLICTING.map(|study| compute_nullifier(Fr::from(other), Fr::from(study)))
+            })
+            .collect::<Vec<_>>();
+        nullifiers.push(compute_nullifier(patient(), Fr::from(13)));
+        ActiveEnrollmentTree::new(&nullifiers).unwrap()
+    }
+
+    fn mock_verify(circuit: &NonParticipationCircuit<Fr>, instances: Vec<Vec<Fr>>) -> bool {
+        MockProver::run(NON_PARTICIPATION_K as u32, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_pad_conflicting_study_ids() {
+        let ids = [Fr::from(1), Fr::from(2)];
+        assert_eq!(
+            pad_conflicting_study_ids(&ids).unwrap(),
+            [Fr::from(1), Fr::from(2), Fr::from(1), Fr::from(1)]
+        );
+        assert!(pad_conflicting_study_ids(&[]).is_err());
+        assert!(pad_conflicting_study_ids(&[Fr::ONE; MAX_CONFLICTING_STUDIES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_patient_without_conflicting_enrollment() {
+        let circuit = active_enrollments()
+            .non_participation_circuit(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            circuit.nullifier,
+            compute_nullifier(patient(), Fr::from(STUDY))
+        );
+        assert!(mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_active_enrollment() {
+        let tree = active_enrollments();
+        let enrolled = Fr::from(13);
+        assert!(tree
+            .non_participation_circuit(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &[enrolled]
+            )
+            .is_err());
+
+        // Claim the enrollment in study 13 is absent using the leaves around its key
+        let mut circuit = tree
+            .non_participation_circuit(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+        let key = Fr::from_u128(enrollment_key(&compute_nullifier(patient(), enrolled)));
+        let index = tree
+            .tree
+            .leaves()
+            .iter()
+            .position(|leaf| *leaf == key)
+            .unwrap();
+        let low = tree.tree.path(index - 1).unwrap();
+        let high = tree.tree.path(index + 1).unwrap();
+        circuit.conflicting_study_ids[0] = enrolled;
+        circuit.witnesses[0] = AdjacentLeaves::known(
+            tree.tree.leaves()[index - 1],
+            &low,
+            tree.tree.leaves()[index + 1],
+            &high,
+        );
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_identity_secret() {
+        // A fresh secret has no enrollments, so its absence proves nothing
+        let tree = active_enrollments();
+        let fresh = Fr::from(43);
+        assert!(tree
+            .non_participation_circuit(
+                fresh,
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids()
+            )
+            .is_err());
+
+        let mut circuit = tree
+            .non_participation_circuit(
+                fresh,
+                &credential(fresh),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+        assert!(mock_verify(&circuit, circuit.instances()));
+
+        circuit.credential = credential(patient()).witness();
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = active_enrollments()
+            .non_participation_circuit(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+
+        for row in 0..NON_PARTICIPATION_PUBLIC_INPUTS {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            assert!(
+                !mock_verify(&circuit, instances),
+                "tampered row {} must not verify",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_inputs_match_circuit() {
+        let tree = active_enrollments();
+        let inputs = tree
+            .non_participation_inputs(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+        let circuit = tree
+            .non_participation_circuit(
+                patient(),
+                &credential(patient()),
+                Fr::from(STUDY),
+                &conflicting_study_ids(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            inputs["active_enrollment_root"],
+            vec![circuit.active_enrollment_root]
+        );
+        assert_eq!(inputs["nullifier"], vec![circuit.nullifier]);
+        assert_eq!(inputs["provider_root"], vec![circuit.provider_root]);
+        assert_eq!(checked_credential(&inputs).unwrap(), credential(patient()));
+        assert_eq!(
+            inputs["low_siblings"].len(),
+            CONFLICTING.len() * ACTIVE_ENROLLMENT_TREE_DEPTH
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Per-Study Nullifiers
+//!
+//! Registries deduplicating applications by wallet address let one patient apply once
+//! per wallet. [`AgeRangeCircuit`](crate::AgeRangeCircuit) and
+//! [`CriteriaCircuit`](crate::criteria::CriteriaCircuit) therefore also output
+//!
+//! ```text
+//!   nullifier = Poseidon(identity_secret, study_id)
+//! ```
+//!
+//! The identity secret is derived once per patient (see [`derive_identity_secret`]) and
+//! never leaves the device. The patient hands its identity commitment to their provider,
+//! who signs it into the [`credential`](crate::credential), and both circuits recompute
+//! that commitment from the secret they use. A patient therefore cannot pick a fresh
+//! secret per application: the same patient always gets the same nullifier for a study,
+//! whatever wallet submits the proof, so a registry can reject repeats. This holds per
+//! credential; providers issue one identity commitment per patient record. Nullifiers of
+//! different studies cannot be linked to each other or to the patient.
+
+use eligibility_gadgets::PoseidonField;
+use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+
+use crate::EligibilityError;
+
+/// Minimum seed length, so the identity secret cannot be brute-forced from nullifiers
+pub const MIN_IDENTITY_SEED_BYTES: usize = 32;
+
+/// Domain separation tag absorbed before the seed
+const IDENTITY_TAG: &[u8; 8] = b"\0IDSEC01";
+
+/// Bytes packed into each field element (always below the modulus)
+const CHUNK_BYTES: usize = 31;
+
+/// Derive a patient's identity secret from a high-entropy seed
+///
+/// The seed is whatever the wallet or extension keeps for the patient (e.g. random
+/// bytes in its encrypted storage). It is hashed as
+/// `Poseidon(tag, length, chunk_0, chunk_1, ...)` over little-endian 31-byte chunks.
+pub fn derive_identity_secret(seed: &[u8]) -> Result<Fr, EligibilityError> {
+    if seed.len() < MIN_IDENTITY_SEED_BYTES {
+        return Err(EligibilityError(format!(
+            "Identity seed has {} bytes (expected at least {})",
+            seed.len(),
+            MIN_IDENTITY_SEED_BYTES
+        )));
+    }
+
+    let mut inputs = vec![
+        Fr::from(u64::from_be_bytes(*IDENTITY_TAG)),
+        Fr::from(seed.len() as u64),
+    ];
+    inputs.extend(seed.chunks(CHUNK_BYTES).map(|chunk| {
+        let mut repr = [0u8; 32];
+        repr[..chunk.len()].copy_from_slice(chunk);
+        Fr::from_repr(repr).unwrap()
+    }));
+
+    Ok(Fr::poseidon_hash(&inputs))
+}
+
+/// Nullifier of an identity for a study: `Poseidon(identity_secret, study_id)`
+pub fn compute_nullifier(identity_secret: Fr, study_id: Fr) -> Fr {
+    Fr::poseidon_hash(&[identity_secret, study_id])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_identity_secret_is_deterministic() {
+        let seed = [7u8; 40];
+        assert_eq!(
+            derive_identity_secret(&seed).unwrap(),
+            derive_identity_secret(&seed).unwrap()
+        );
+
+        let mut other = seed;
+        other[39] = 8;
+        assert_ne!(
+            derive_identity_secret(&seed).unwrap(),
+            derive_identity_secret(&other).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_derive_identity_secret_rejects_short_seed() {
+        assert!(derive_identity_secret(&[1u8; MIN_IDENTITY_SEED_BYTES - 1]).is_err());
+    }
+
+    #[test]
+    fn test_nullifier_depends_on_study() {
+        let secret = derive_identity_secret(&[3u8; 32]).unwrap();
+        assert_eq!(
+            compute_nullifier(secret, Fr::from(1)),
+            compute_nullifier(secret, Fr::from(1))
+        );
+        assert_ne!(
+            compute_nullifier(secret, Fr::from(1)),
+            compute_nullifier(secret, Fr::from(2))
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Patient Commitments
+//!
+//! Age is proven with [`AgeRangeCircuit`](crate::AgeRangeCircuit) (Halo2) and the
+//! eligibility code with the Circom `EligibilityCode` circuit (Groth16). On their own
+//! the two proofs could come from two people, so both publish
+//!
+//! ```text
+//!   patient_commitment = Poseidon(PATIENT_COMMITMENT_TAG, identity_secret, context_hash)
+//! ```
+//!
+//! computed in-circuit from the patient's identity secret (see
+//! [`nullifier`](crate::nullifier)). The Circom circuit hashes with the Poseidon of the
+//! Halo2 circuits, not circomlib's (see `halo2_poseidon.circom`), so a verifier only
+//! has to compare the two public inputs.
+//!
+//! Hashing the context hash keeps commitments of one patient unlinkable across studies
+//! and wallets; the tag keeps them distinct from nullifiers.
+//!
+//! ## Attestation
+//! The link rests on the identity commitment the provider signs in the patient's
+//! [`credential`](crate::credential): the age range circuit constrains the identity
+//! secret to it, so its patient commitment belongs to a credential holder. The Circom
+//! circuit verifies no credential; it can only match that commitment with the same
+//! identity secret. Two people can therefore combine their proofs only if the
+//! credential holder hands over their identity secret, which also gives away their
+//! nullifiers, as handing over the credential would. The eligibility code itself is
+//! not attested; studies that need attested diagnoses or lab values prove them with
+//! [`criteria`](crate::criteria) instead.
+
+use eligibility_gadgets::PoseidonField;
+use halo2_proofs::halo2curves::bn256::Fr;
+
+/// Domain separation tag absorbed first (`"\0PATCOM1"` as a big-endian integer)
+pub const PATIENT_COMMITMENT_TAG: u64 = u64::from_be_bytes(*b"\0PATCOM1");
+
+/// Commitment of an identity for an application (see the module documentation)
+pub fn compute_patient_commitment(identity_secret: Fr, context_hash: Fr) -> Fr {
+    Fr::poseidon_hash(&[
+        Fr::from(PATIENT_COMMITMENT_TAG),
+        identity_secret,
+        context_hash,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nullifier::{compute_nullifier, derive_identity_secret};
+
+    #[test]
+    fn test_patient_commitment_depends_on_identity_and_context() {
+        let secret = derive_identity_secret(&[3u8; 32]).unwrap();
+        let other = derive_identity_secret(&[4u8; 32]).unwrap();
+        let commitment = compute_patient_commitment(secret, Fr::from(7));
+
+        assert_eq!(commitment, compute_patient_commitment(secret, Fr::from(7)));
+        assert_ne!(commitment, compute_patient_commitment(other, Fr::from(7)));
+        assert_ne!(commitment, compute_patient_commitment(secret, Fr::from(8)));
+
+        // Not the nullifier of a study whose ID equals the context hash
+        assert_ne!(commitment, compute_nullifier(secret, Fr::from(7)));
+        assert_eq!(PATIENT_COMMITMENT_TAG, 22589828299181361); // Hardcoded in eligibility_code.circom
+    }
+}
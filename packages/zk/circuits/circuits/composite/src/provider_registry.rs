@@ -0,0 +1,196 @@
+//! Registered Provider Tree
+//!
+//! Revealing the attesting provider's key leaks a lot ("patient of the oncology clinic
+//! in town X"), so the circuits only prove that the credential was signed by *some* provider of `MedicalProviderRegistry`. The
+//! registry is mirrored as a Poseidon Merkle tree of depth PROVIDER_TREE_DEPTH whose
+//! leaves are `Poseidon(A.x, A.y)` (see `eligibility_gadgets::issuer`), and only its root
+//! is public.
+//!
+//! The tree is built from a JSON export of the registry pairing every provider with its
+//! Baby Jubjub key:
+//!
+//! ```json
+//! [{ "address": "0x…", "publicKey": ["0x…", "0x…"], "isActive": true, "expiresAt": 0 }]
+//! ```
+//!
+//! Revoked and expired providers are left out, and leaves are sorted, so the root only
+//! depends on the set of providers in good standing. Anyone can rebuild it from the
+//! registry and compare it with the published `provider_root`.
+
+use eligibility_gadgets::{issuer_leaf, EdwardsPoint, MerklePath, MerkleTree};
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::Deserialize;
+
+use crate::{serialization::parse_field_element, EligibilityError};
+
+pub use eligibility_gadgets::credential::PROVIDER_TREE_DEPTH;
+
+/// Provider entry of a `MedicalProviderRegistry` export
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredProvider {
+    pub address: String,
+    pub public_key: [String; 2], // Baby Jubjub key [x, y], as circuit input strings
+    pub is_active: bool,
+    pub expires_at: u64, // Unix seconds, 0 = never expires
+}
+
+impl RegisteredProvider {
+    /// Whether the provider may attest credentials at `now` (Unix seconds)
+    pub fn in_good_standing(&self, now: u64) -> bool {
+        self.is_active && (self.expires_at == 0 || self.expires_at > now)
+    }
+
+    pub fn key(&self) -> Result<EdwardsPoint<Fr>, EligibilityError> {
+        Ok(EdwardsPoint {
+            x: parse_field_element(&self.public_key[0])?,
+            y: parse_field_element(&self.public_key[1])?,
+        })
+    }
+}
+
+/// Parse a registry export (see the module documentation)
+pub fn parse_registry_export(json: &str) -> Result<Vec<RegisteredProvider>, EligibilityError> {
+    serde_json::from_str(json)
+        .map_err(|e| EligibilityError(format!("Invalid provider registry export: {}", e)))
+}
+
+/// Merkle tree of the keys of registered providers
+#[derive(Debug, Clone)]
+pub struct ProviderTree {
+    keys: Vec<EdwardsPoint<Fr>>, // In leaf order
+    tree: MerkleTree<Fr>,
+}
+
+impl ProviderTree {
+    pub fn new(keys: &[EdwardsPoint<Fr>]) -> Result<Self, EligibilityError> {
+        if let Some(key) = keys.iter().find(|key| !key.is_on_curve()) {
+            return Err(EligibilityError(format!(
+                "Provider key {:?} is not on the curve",
+                key
+            )));
+        }
+
+        let mut leaves: Vec<(Fr, EdwardsPoint<Fr>)> =
+            keys.iter().map(|key| (issuer_leaf(key), *key)).collect();
+        leaves.sort_by_key(|(leaf, _)| sort_key(leaf));
+        leaves.dedup_by_key(|(leaf, _)| *leaf);
+
+        let (leaves, keys): (Vec<Fr>, Vec<EdwardsPoint<Fr>>) = leaves.into_iter().unzip();
+        let tree = MerkleTree::new(&leaves, PROVIDER_TREE_DEPTH)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+
+        Ok(Self { keys, tree })
+    }
+
+    /// Tree of the providers in good standing at `now` (Unix seconds)
+    pub fn from_registry(
+        providers: &[RegisteredProvider],
+        now: u64,
+    ) -> Result<Self, EligibilityError> {
+        let keys = providers
+            .iter()
+            .filter(|provider| provider.in_good_standing(now))
+            .map(RegisteredProvider::key)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(&keys)
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    pub fn keys(&self) -> &[EdwardsPoint<Fr>] {
+        &self.keys
+    }
+
+    /// Authentication path of a registered key
+    pub fn path(&self, key: &EdwardsPoint<Fr>) -> Result<MerklePath<Fr>, EligibilityError> {
+        let index = self
+            .keys
+            .iter()
+            .position(|registered| registered == key)
+            .ok_or(EligibilityError(
+                "Provider key is not registered".to_string(),
+            ))?;
+        self.tree
+            .path(index)
+            .map_err(|e| EligibilityError(e.to_string()))
+    }
+}
+
+/// Big-endian bytes, so leaves sort by their integer value
+fn sort_key(value: &Fr) -> [u8; 32] {
+    let mut bytes = value.to_bytes();
+    bytes.reverse();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::format_field_element;
+    use eligibility_gadgets::SigningKey;
+
+    fn key(seed: u8) -> EdwardsPoint<Fr> {
+        SigningKey::from_seed(&[seed; 32]).unwrap().public_key()
+    }
+
+    fn export(entries: &[(u8, bool, u64)]) -> String {
+        let entries: Vec<String> = entries
+            .iter()
+            .map(|(seed, active, expires_at)| {
+                let key = key(*seed);
+                format!(
+                    r#"{{"address": "0x{:040x}", "publicKey": ["{}", "{}"], "isActive": {}, "expiresAt": {}}}"#,
+                    seed,
+                    format_field_element(&key.x),
+                    format_field_element(&key.y),
+                    active,
+                    expires_at
+                )
+            })
+            .collect();
+        format!("[{}]", entries.join(","))
+    }
+
+    #[test]
+    fn test_tree_ignores_order_and_duplicates() {
+        let tree = ProviderTree::new(&[key(1), key(2), key(3)]).unwrap();
+        let shuffled = ProviderTree::new(&[key(3), key(1), key(2), key(1)]).unwrap();
+        assert_eq!(tree.root(), shuffled.root());
+
+        for k in [key(1), key(2), key(3)] {
+            let path = tree.path(&k).unwrap();
+            assert_eq!(path.compute_root(issuer_leaf(&k)), tree.root());
+        }
+        assert!(tree.path(&key(4)).is_err());
+    }
+
+    #[test]
+    fn test_tree_from_registry_export() {
+        let now = 1_750_000_000;
+        let providers = parse_registry_export(&export(&[
+            (1, true, 0),
+            (2, false, 0),        // Revoked
+            (3, true, now - 1),   // Expired
+            (4, true, now + 100), // Certified until later
+        ]))
+        .unwrap();
+
+        let tree = ProviderTree::from_registry(&providers, now).unwrap();
+        assert_eq!(
+            tree.root(),
+            ProviderTree::new(&[key(1), key(4)]).unwrap().root()
+        );
+    }
+
+    #[test]
+    fn test_tree_rejects_invalid_keys() {
+        let off_curve = EdwardsPoint {
+            x: Fr::from(1),
+            y: Fr::from(2),
+        };
+        assert!(ProviderTree::new(&[key(1), off_curve]).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! Credential Revocation
+//!
+//! Providers revoke wrong or withdrawn attestations by credential ID. Revoked IDs are
+//! the non-zero leaves of a sparse Poseidon Merkle tree keyed by the ID itself:
+//!
+//! ```text
+//!   leaf[credential_id] = REVOKED if the credential is revoked, else 0
+//! ```
+//!
+//! Non-revocation is then an ordinary Merkle proof of a zero leaf, against the public
+//! `revocation_root`. `CredentialChip` (see [`credential`](crate::credential)) recomputes
+//! the root from a zero leaf and constrains the path index to the signed `credential_id`, so
+//! a path only proves non-revocation of the credential the predicates use. Unlike the
+//! sorted trees of diagnosis exclusion, no adjacent leaves are needed: every ID has its
+//! own slot, which is why credential IDs are below 2^REVOCATION_TREE_DEPTH and must be
+//! unique across providers (e.g. allocated in ranges per provider).
+//!
+//! ## Files
+//! Until the root is published on-chain, the tooling keeps two JSON files:
+//! - the revocation list, `{ "revoked": [1001, 1002] }`
+//! - the published root, `{ "revocationRoot": "0x…", "revokedCount": 2, "updatedAt": 1750000000 }`,
+//!   the stand-in for the on-chain root that provers and verifiers read
+
+use std::collections::BTreeSet;
+
+use eligibility_gadgets::{revocation_index, MerklePath, SparseMerkleTree};
+use halo2_proofs::halo2curves::ff::Field;
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    serialization::{format_field_element, parse_field_element},
+    EligibilityError,
+};
+
+pub use eligibility_gadgets::credential::REVOCATION_TREE_DEPTH;
+
+/// Leaf of a revoked credential
+pub const REVOKED: u64 = 1;
+
+/// Revocation list file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationList {
+    pub revoked: Vec<u64>, // Credential IDs, ascending
+}
+
+/// Published root file, standing in for the on-chain root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishedRevocationRoot {
+    pub revocation_root: String, // 0x-prefixed big-endian hex
+    pub revoked_count: usize,
+    pub updated_at: u64, // Unix seconds
+}
+
+impl PublishedRevocationRoot {
+    pub fn root(&self) -> Result<Fr, EligibilityError> {
+        parse_field_element(&self.revocation_root)
+    }
+}
+
+/// Slot of a credential ID in the revocation tree
+pub fn credential_index(credential_id: &Fr) -> Result<usize, EligibilityError> {
+    revocation_index(credential_id).map_err(|e| EligibilityError(e.to_string()))
+}
+
+/// Sparse Merkle tree of revoked credential IDs
+#[derive(Debug, Clone)]
+pub struct RevocationTree {
+    revoked: BTreeSet<usize>,
+    tree: SparseMerkleTree<Fr>,
+}
+
+impl Default for RevocationTree {
+    fn default() -> Self {
+        Self {
+            revoked: BTreeSet::new(),
+            tree: SparseMerkleTree::new(REVOCATION_TREE_DEPTH),
+        }
+    }
+}
+
+impl RevocationTree {
+    pub fn from_list(list: &RevocationList) -> Result<Self, EligibilityError> {
+        let mut tree = Self::default();
+        for id in list.revoked.iter() {
+            tree.revoke(&Fr::from(*id))?;
+        }
+        Ok(tree)
+    }
+
+    pub fn list(&self) -> RevocationList {
+        RevocationList {
+            revoked: self.revoked.iter().map(|id| *id as u64).collect(),
+        }
+    }
+
+    /// Revoke a credential, returning false if it already was
+    pub fn revoke(&mut self, credential_id: &Fr) -> Result<bool, EligibilityError> {
+        self.update(credential_id, true)
+    }
+
+    /// Undo a revocation, returning false if the credential was not revoked
+    pub fn reinstate(&mut self, credential_id: &Fr) -> Result<bool, EligibilityError> {
+        self.update(credential_id, false)
+    }
+
+    pub fn is_revoked(&self, credential_id: &Fr) -> bool {
+        credential_index(credential_id).map_or(false, |index| self.revoked.contains(&index))
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Path proving that a credential is not revoked
+    pub fn path(&self, credential_id: &Fr) -> Result<MerklePath<Fr>, EligibilityError> {
+        if self.is_revoked(credential_id) {
+            return Err(EligibilityError("Credential is revoked".to_string()));
+        }
+        let index = credential_index(credential_id)?;
+        self.tree
+            .path(index)
+            .map_err(|e| EligibilityError(e.to_string()))
+    }
+
+    /// Root file contents for the current tree, published at `updated_at` (Unix seconds)
+    pub fn published_root(&self, updated_at: u64) -> PublishedRevocationRoot {
+        PublishedRevocationRoot {
+            revocation_root: format_field_element(&self.root()),
+            revoked_count: self.revoked.len(),
+            updated_at,
+        }
+    }
+
+    fn update(&mut self, credential_id: &Fr, revoked: bool) -> Result<bool, EligibilityError> {
+        let index = credential_index(credential_id)?;
+        let changed = match revoked {
+            true => self.revoked.insert(index),
+            false => self.revoked.remove(&index),
+        };
+
+        let leaf = if revoked { Fr::from(REVOKED) } else { Fr::ZERO };
+        self.tree
+            .set(index, leaf)
+            .map_err(|e| EligibilityError(e.to_string()))?;
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revocation_changes_the_root() {
+        let mut tree = RevocationTree::default();
+        let empty = tree.root();
+
+        assert!(tree.revoke(&Fr::from(1001)).unwrap());
+        assert!(!tree.revoke(&Fr::from(1001)).unwrap());
+        assert!(tree.is_revoked(&Fr::from(1001)));
+        assert_ne!(tree.root(), empty);
+
+        assert!(tree.reinstate(&Fr::from(1001)).unwrap());
+        assert!(!tree.is_revoked(&Fr::from(1001)));
+        assert_eq!(tree.root(), empty);
+    }
+
+    #[test]
+    fn test_non_revocation_paths() {
+        let tree = RevocationTree::from_list(&RevocationList {
+            revoked: vec![1000, 1002],
+        })
+        .unwrap();
+
+        let path = tree.path(&Fr::from(1001)).unwrap();
+        assert_eq!(path.index, 1001);
+        assert_eq!(path.compute_root(Fr::ZERO), tree.root());
+
+        assert!(tree.path(&Fr::from(1002)).is_err());
+        assert!(tree.path(&Fr::from(1 << REVOCATION_TREE_DEPTH)).is_err());
+        assert!(tree.path(&-Fr::from(1)).is_err());
+    }
+
+    #[test]
+    fn test_revocation_files_roundtrip() {
+        let list: RevocationList = serde_json::from_str(r#"{ "revoked": [1002, 1000] }"#).unwrap();
+        let tree = RevocationTree::from_list(&list).unwrap();
+        assert_eq!(tree.list().revoked, vec![1000, 1002]);
+
+        let json = serde_json::to_string(&tree.published_root(1_750_000_000)).unwrap();
+        assert!(json.contains("\"revocationRoot\":\"0x"));
+        let published: PublishedRevocationRoot = serde_json::from_str(&json).unwrap();
+        assert_eq!(published.root().unwrap(), tree.root());
+        assert_eq!(published.revoked_count, 2);
+    }
+}
@@ -11,28 +11,72 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 pub struct InputsSerialisationWrapper(pub Vec<Fr>);
 pub use InputsSerialisationWrapper as InputsSerializationWrapper;
 
+/// Parse circuit inputs
+///
+/// Values are decimal integers up to `u128` (a leading '-' negates, e.g. day counts
+/// before 1970), or `0x`-prefixed big-endian hex for full-range field elements
+/// (identity secrets, commitments). Lab values and bounds given as decimals are read
+/// by [`deserialize_lab_inputs`](crate::lab_value::deserialize_lab_inputs) instead.
 pub fn deserialize_circuit_inputs(
     ser_inputs: HashMap<String, Vec<String>>,
 ) -> Result<HashMap<String, Vec<Fr>>, EligibilityError> {
     ser_inputs
         .iter()
         .map(|(k, v)| {
-            let fp_vec: Result<Vec<Fr>, EligibilityError> = v
-                .iter()
-                .map(|s| {
-                    // TODO - support big integers full range, not just u128
-                    let int = u128::from_str(s).map_err(|e| {
-                        EligibilityError(format!("Failed to parse input as u128: {}", e))
-                    });
-
-                    int.map(|i| Fr::from_u128(i))
-                })
-                .collect();
+            let fp_vec: Result<Vec<Fr>, EligibilityError> =
+                v.iter().map(|s| parse_field_element(s)).collect();
             fp_vec.map(|v| (k.clone(), v))
         })
         .collect()
 }
 
+/// Parse one input value (see [`deserialize_circuit_inputs`])
+pub fn parse_field_element(s: &str) -> Result<Fr, EligibilityError> {
+    let Some(hex) = s.strip_prefix("0x") else {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let int = u128::from_str(digits)
+            .map_err(|e| EligibilityError(format!("Failed to parse input as u128: {}", e)))?;
+        return Ok(if negative {
+            -Fr::from_u128(int)
+        } else {
+            Fr::from_u128(int)
+        });
+    };
+
+    if hex.is_empty() || hex.len() > 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(EligibilityError(format!(
+            "Invalid hex field element: {}",
+            s
+        )));
+    }
+
+    let padded = format!("{:0>64}", hex);
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&padded[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes.reverse(); // Little-endian representation
+
+    Option::from(Fr::from_bytes(&bytes)).ok_or(EligibilityError(format!(
+        "Hex value exceeds the field modulus: {}",
+        s
+    )))
+}
+
+/// `0x`-prefixed big-endian hex of a field element, as read by [`parse_field_element`]
+pub fn format_field_element(fp: &Fr) -> String {
+    let hex: String = fp
+        .to_bytes()
+        .iter()
+        .rev()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("0x{}", hex)
+}
+
 impl Serialize for InputsSerialisationWrapper {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -109,4 +153,42 @@ mod tests {
         assert_eq!(deserialized.get("out").unwrap()[0], Fr::from(1));
         assert_eq!(deserialized.get("out").unwrap()[1], Fr::from(2));
     }
+
+    #[test]
+    fn test_circuit_inputs_deserialization_negative() {
+        let mut serialized = HashMap::new();
+        serialized.insert("dob".to_string(), vec!["-3650".to_string()]);
+        let deserialized = deserialize_circuit_inputs(serialized).unwrap();
+        assert_eq!(deserialized.get("dob").unwrap()[0], -Fr::from(3650));
+    }
+
+    #[test]
+    fn test_circuit_inputs_deserialization_hex() {
+        let mut serialized = HashMap::new();
+        serialized.insert("identity_secret".to_string(), vec!["0x1f".to_string()]);
+        let deserialized = deserialize_circuit_inputs(serialized).unwrap();
+        assert_eq!(
+            deserialized.get("identity_secret").unwrap()[0],
+            Fr::from(31)
+        );
+
+        assert_eq!(
+            parse_field_element(&format_field_element(&-Fr::from(31))).unwrap(),
+            -Fr::from(31)
+        );
+
+        let mut too_large = HashMap::new();
+        too_large.insert(
+            "identity_secret".to_string(),
+            vec![format!("0x{}", "f".repeat(64))],
+        );
+        assert!(deserialize_circuit_inputs(too_large).is_err());
+    }
+
+    #[test]
+    fn test_circuit_inputs_deserialization_rejects_decimals() {
+        let mut serialized = HashMap::new();
+        serialized.insert("value".to_string(), vec!["7.25".to_string()]);
+        assert!(deserialize_circuit_inputs(serialized).is_err());
+    }
 }
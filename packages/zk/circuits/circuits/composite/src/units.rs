@@ -0,0 +1,438 @@
+//! Lab Unit Normalization
+//!
+//! Lab value proofs compare integers, so a study threshold and a patient's observation
+//! must be in the same unit and at the same scale before witness generation. Every
+//! analyte in [`ANALYTES`] has a canonical UCUM unit and fixed-point scale; providers
+//! commit values in that unit, and values in another supported unit are converted:
+//!
+//! ```text
+//!   canonical = (mul * value + add) / div
+//! ```
+//!
+//! e.g. glucose mmol/L -> mg/dL is `18.016 * value`, HbA1c IFCC mmol/mol -> NGSP % is
+//! `0.09148 * value + 2.152`. Unsupported units are rejected, never passed through.
+//!
+//! The vital signs read by [`metric`](crate::metric) proofs are committed the same way;
+//! their scales make the fixed-point values grams, millimetres and mmHg.
+//!
+//! ## Rounding
+//! Neither converted values nor values reported with more decimals than the scale
+//! ("95.5 mg/dL" at scale 0) land on the scale, so both are rounded the same way, with
+//! a rounding the caller picks: observations round to nearest, while thresholds round
+//! toward the comparison so that the integer comparison accepts exactly the fixed-point
+//! values the real-valued threshold accepts (see [`Rounding::for_bound`]).
+//!
+//! UCUM unit codes are case-sensitive ("mg/dL", not "mg/dl" or "MG/DL"), so units are
+//! compared as written.
+
+use crate::{
+    lab_value::{to_fixed_point, Comparison, MAX_LAB_VALUE},
+    EligibilityError,
+};
+
+/// Conversion of a unit to the canonical unit: `(mul * value + add) / div`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conversion {
+    pub unit: &'static str, // UCUM
+    pub mul: u128,
+    pub add: u128, // In units of `1 / div`
+    pub div: u128,
+}
+
+/// Analyte with a canonical unit and fixed-point scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Analyte {
+    pub loinc: &'static str,
+    pub name: &'static str,
+    pub unit: &'static str, // Canonical UCUM unit
+    pub scale: u32,         // Decimal places of committed values
+    pub conversions: &'static [Conversion],
+}
+
+/// Cholesterol fractions: 1 mmol/L = 38.67 mg/dL
+const CHOLESTEROL_MMOL: &[Conversion] = &[Conversion {
+    unit: "mmol/L",
+    mul: 3867,
+    add: 0,
+    div: 100,
+}];
+
+/// Analytes with a canonical unit and scale
+pub const ANALYTES: &[Analyte] = &[
+    Analyte {
+        loinc: "4548-4",
+        name: "Hemoglobin A1c",
+        unit: "%",
+        scale: 1,
+        // IFCC to NGSP master equation
+        conversions: &[Conversion {
+            unit: "mmol/mol",
+            mul: 9148,
+            add: 215_200,
+            div: 100_000,
+        }],
+    },
+    Analyte {
+        loinc: "2345-7",
+        name: "Glucose",
+        unit: "mg/dL",
+        scale: 0,
+        conversions: &[Conversion {
+            unit: "mmol/L",
+            mul: 18_016,
+            add: 0,
+            div: 1000,
+        }],
+    },
+    Analyte {
+        loinc: "2093-3",
+        name: "Cholesterol",
+        unit: "mg/dL",
+        scale: 0,
+        conversions: CHOLESTEROL_MMOL,
+    },
+    Analyte {
+        loinc: "13457-7",
+        name: "LDL cholesterol",
+        unit: "mg/dL",
+        scale: 0,
+        conversions: CHOLESTEROL_MMOL,
+    },
+    Analyte {
+        loinc: "2085-9",
+        name: "HDL cholesterol",
+        unit: "mg/dL",
+        scale: 0,
+        conversions: CHOLESTEROL_MMOL,
+    },
+    Analyte {
+        loinc: "2160-0",
+        name: "Creatinine",
+        unit: "mg/dL",
+        scale: 2,
+        conversions: &[Conversion {
+            unit: "umol/L",
+            mul: 100,
+            add: 0,
+            div: 8842,
+        }],
+    },
+    Analyte {
+        loinc: "62238-1",
+        name: "eGFR (CKD-EPI)",
+        unit: "mL/min/{1.73_m2}",
+        scale: 0,
+        conversions: &[],
+    },
+    Analyte {
+        loinc: "29463-7",
+        name: "Body weight",
+        unit: "kg",
+        scale: 3, // Grams
+        conversions: &[
+            Conversion {
+                unit: "g",
+                mul: 1,
+                add: 0,
+                div: 1000,
+            },
+            Conversion {
+                unit: "[lb_av]",
+                mul: 45_359_237,
+                add: 0,
+                div: 100_000_000,
+            },
+        ],
+    },
+    Analyte {
+        loinc: "8302-2",
+        name: "Body height",
+        unit: "cm",
+        scale: 1, // Millimetres
+        conversions: &[
+            Conversion {
+                unit: "m",
+                mul: 100,
+                add: 0,
+                div: 1,
+            },
+            Conversion {
+                unit: "[in_i]",
+                mul: 254,
+                add: 0,
+                div: 100,
+            },
+        ],
+    },
+    Analyte {
+        loinc: "8480-6",
+        name: "Systolic blood pressure",
+        unit: "mm[Hg]",
+        scale: 0,
+        conversions: &[],
+    },
+    Analyte {
+        loinc: "8462-4",
+        name: "Diastolic blood pressure",
+        unit: "mm[Hg]",
+        scale: 0,
+        conversions: &[],
+    },
+];
+
+/// Known analyte with LOINC code `loinc`
+pub fn lookup(loinc: &str) -> Option<&'static Analyte> {
+    ANALYTES
+        .iter()
+        .find(|analyte| analyte.loinc == loinc.trim())
+}
+
+/// Whether two unit strings denote the same UCUM unit
+///
+/// Codes are case-sensitive; only the micro sign ("µmol/L") is read as UCUM's "u".
+pub fn same_unit(a: &str, b: &str) -> bool {
+    let normalize = |unit: &str| unit.trim().replace(['µ', 'μ'], "u");
+    normalize(a) == normalize(b)
+}
+
+/// Rounding of converted values to the fixed-point scale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Nearest, // Halves round up
+}
+
+impl Rounding {
+    /// Rounding of a threshold such that, on fixed-point values, `value <op> rounded`
+    /// holds exactly when `value <op> threshold` does
+    ///
+    /// `value > t` is `value > floor(t)` and `value >= t` is `value >= ceil(t)`; the
+    /// lower bound of `between` is a `>=` and the upper bound a `<=`.
+    pub fn for_bound(comparison: Comparison, upper: bool) -> Self {
+        match comparison {
+            Comparison::Gt | Comparison::Le => Rounding::Floor,
+            Comparison::Ge | Comparison::Lt => Rounding::Ceil,
+            Comparison::Between if upper => Rounding::Floor,
+            Comparison::Between => Rounding::Ceil,
+        }
+    }
+
+    fn divide(self, num: u128, den: u128) -> u128 {
+        match self {
+            Rounding::Floor => num / den,
+            Rounding::Ceil => num / den + (num % den != 0) as u128,
+            Rounding::Nearest => num / den + (2 * (num % den) >= den) as u128,
+        }
+    }
+}
+
+/// Fixed-point value of a decimal string at `scale`, rounding digits beyond the scale
+pub fn round_to_scale(
+    value: &str,
+    scale: u32,
+    rounding: Rounding,
+) -> Result<u128, EligibilityError> {
+    convert(value.trim(), 1, 0, 1, scale, rounding)?
+        .ok_or(EligibilityError(format!("Value {} is too large", value)))
+}
+
+/// Fixed-point value of `value` in `unit`, in the analyte's canonical unit at `scale`
+pub fn to_canonical(
+    loinc: &str,
+    value: &str,
+    unit: &str,
+    scale: u32,
+    rounding: Rounding,
+) -> Result<u64, EligibilityError> {
+    let analyte = lookup(loinc).ok_or(EligibilityError(format!(
+        "LOINC {} has no canonical unit",
+        loinc
+    )))?;
+    let too_large = || {
+        EligibilityError(format!(
+            "{} value {} {} is too large",
+            analyte.name, value, unit
+        ))
+    };
+
+    let (mul, add, div) = if same_unit(unit, analyte.unit) {
+        (1, 0, 1)
+    } else {
+        let conversion = analyte
+            .conversions
+            .iter()
+            .find(|conversion| same_unit(unit, conversion.unit))
+            .ok_or(EligibilityError(format!(
+                "Cannot convert {} ({}) from {} to {}",
+                analyte.name, analyte.loinc, unit, analyte.unit
+            )))?;
+        (conversion.mul, conversion.add, conversion.div)
+    };
+    let fixed_point =
+        convert(value.trim(), mul, add, div, scale, rounding)?.ok_or_else(too_large)?;
+
+    u64::try_from(fixed_point)
+        .ok()
+        .filter(|value| *value <= MAX_LAB_VALUE)
+        .ok_or_else(too_large)
+}
+
+/// `(mul * value + add) / div` at `scale`, `None` on overflow
+fn convert(
+    value: &str,
+    mul: u128,
+    add: u128,
+    div: u128,
+    scale: u32,
+    rounding: Rounding,
+) -> Result<Option<u128>, EligibilityError> {
+    // value = digits / 10^places, exactly
+    let places = value
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len()) as u32;
+    let digits = to_fixed_point(value, places)?;
+
+    // (mul * digits / 10^places + add) / div at `scale`
+    let pow = |exp: u32| 10u128.checked_pow(exp);
+    let num = mul
+        .checked_mul(digits)
+        .and_then(|x| x.checked_add(add.checked_mul(pow(places)?)?))
+        .and_then(|x| x.checked_mul(pow(scale)?));
+    let den = pow(places).and_then(|x| div.checked_mul(x));
+    Ok(num.zip(den).map(|(num, den)| rounding.divide(num, den)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lab_value::pack_loinc;
+
+    #[test]
+    fn test_analyte_table() {
+        for analyte in ANALYTES {
+            assert!(pack_loinc(analyte.loinc).is_ok(), "{}", analyte.loinc);
+        }
+        assert_eq!(lookup("4548-4").unwrap().unit, "%");
+        assert!(lookup("0000-0").is_none());
+    }
+
+    #[test]
+    fn test_same_unit() {
+        assert!(same_unit("mg/dL", " mg/dL"));
+        assert!(same_unit("µmol/L", "umol/L"));
+        assert!(!same_unit("mg/dL", "mmol/L"));
+        // UCUM is case-sensitive
+        assert!(!same_unit("mg/dL", "MG/DL"));
+        assert!(!same_unit("mmol/L", "mmol/l"));
+    }
+
+    #[test]
+    fn test_converts_to_canonical_unit() {
+        let nearest = Rounding::Nearest;
+
+        // Canonical unit: rounded like converted values
+        assert_eq!(
+            to_canonical("2345-7", "126", "mg/dL", 0, nearest).unwrap(),
+            126
+        );
+        assert_eq!(
+            to_canonical("2345-7", "126.4", "mg/dL", 0, nearest).unwrap(),
+            126
+        );
+        assert_eq!(
+            to_canonical("2345-7", "95.5", "mg/dL", 0, nearest).unwrap(),
+            96
+        );
+        assert_eq!(
+            to_canonical("4548-4", "7.25", "%", 1, Rounding::Floor).unwrap(),
+            72
+        );
+        assert_eq!(round_to_scale("7.25", 1, nearest).unwrap(), 73);
+        assert_eq!(round_to_scale("7", 2, nearest).unwrap(), 700);
+
+        // Glucose 7.0 mmol/L = 126.112 mg/dL
+        assert_eq!(
+            to_canonical("2345-7", "7.0", "mmol/L", 0, nearest).unwrap(),
+            126
+        );
+        assert_eq!(
+            to_canonical("2345-7", "7.0", "mmol/L", 0, Rounding::Ceil).unwrap(),
+            127
+        );
+        // Creatinine 88.42 umol/L = 1.00 mg/dL
+        assert_eq!(
+            to_canonical("2160-0", "88.42", "µmol/L", 2, nearest).unwrap(),
+            100
+        );
+        // HbA1c 53 mmol/mol = 7.00044 %
+        assert_eq!(
+            to_canonical("4548-4", "53", "mmol/mol", 1, nearest).unwrap(),
+            70
+        );
+        // LDL 3.0 mmol/L = 116.01 mg/dL
+        assert_eq!(
+            to_canonical("13457-7", "3.0", "mmol/L", 0, Rounding::Floor).unwrap(),
+            116
+        );
+        // 176 lb = 79.832 kg, 70 in = 177.8 cm
+        assert_eq!(
+            to_canonical("29463-7", "176", "[lb_av]", 3, nearest).unwrap(),
+            79_832
+        );
+        assert_eq!(
+            to_canonical("8302-2", "70", "[in_i]", 1, nearest).unwrap(),
+            1778
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsupported_conversions() {
+        assert!(to_canonical("2345-7", "7.0", "g/L", 0, Rounding::Nearest).is_err());
+        assert!(to_canonical("62238-1", "60", "mL/s", 0, Rounding::Nearest).is_err());
+        assert!(to_canonical("1234-0", "1", "mg/dL", 0, Rounding::Nearest).is_err());
+        assert!(to_canonical("2345-7", "-1", "mmol/L", 0, Rounding::Nearest).is_err());
+        assert!(to_canonical("2345-7", "7.0", "mmol/l", 0, Rounding::Nearest).is_err());
+    }
+
+    #[test]
+    fn test_bound_rounding_is_exact() {
+        // value > 126.112 and value >= 127 agree on integers; so do the other operators
+        let threshold = (126_112u64, 1000u64); // 126.112
+        let holds = |comparison: Comparison, value: u64, t: (u64, u64)| match comparison {
+            Comparison::Lt => value * t.1 < t.0,
+            Comparison::Le => value * t.1 <= t.0,
+            Comparison::Gt => value * t.1 > t.0,
+            Comparison::Ge => value * t.1 >= t.0,
+            Comparison::Between => unreachable!(),
+        };
+
+        for comparison in [
+            Comparison::Lt,
+            Comparison::Le,
+            Comparison::Gt,
+            Comparison::Ge,
+        ] {
+            let rounded = to_canonical(
+                "2345-7",
+                "7.0",
+                "mmol/L",
+                0,
+                Rounding::for_bound(comparison, false),
+            )
+            .unwrap();
+            for value in 120..130 {
+                let integer = comparison
+                    .interval(rounded, 0)
+                    .map_or(false, |(low, high)| (low..=high).contains(&value));
+                assert_eq!(
+                    integer,
+                    holds(comparison, value, threshold),
+                    "{} {}",
+                    comparison,
+                    value
+                );
+            }
+        }
+    }
+}
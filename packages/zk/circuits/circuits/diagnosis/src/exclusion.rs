@@ -0,0 +1,934 @@
+//! Diagnosis Exclusion Circuit - Non-Membership Verification
+//!
+//! Proves that a patient has none of a public list of excluded diagnoses
+//! (e.g., "no prior myocardial infarction, no active cancer") against the same
+//! `diagnosis_commitment` used by [`DiagnosisMembershipCircuit`](crate::DiagnosisMembershipCircuit).
+//!
+//! ## Security Model
+//! - Private Inputs: for each excluded code, the two adjacent leaves bracketing it and
+//!   their authentication paths; prefix root; commitment salt; credential
+//! - Public Inputs: excluded_hashes (MAX_EXCLUDED_DIAGNOSES), study_id, context_hash,
+//!   epoch, provider_root, revocation_root
+//! - Constraint: excluded_diagnosis ∉ patient_diagnoses, for every excluded diagnosis, on
+//!   a diagnosis_commitment signed by a registered provider at most MAX_CREDENTIAL_AGE
+//!   days before epoch (see the crate documentation)
+//!
+//! ## Adjacent-Leaf Proofs
+//! [`DiagnosisTree`] leaves are sorted and bracketed by sentinels. A code hash `x` is
+//! absent exactly when two adjacent leaves `low` (index `i`) and `high` (index `i + 1`)
+//! satisfy `low < x < high`. The circuit proves both leaves are under the committed
+//! root, that their indices differ by one, and that `x - low - 1` and `high - x - 1`
+//! fit in CODE_HASH_BITS bits (code hashes are truncated to that size, so neither
+//! difference can wrap around the field).
+//!
+//! The circuit does not check that the tree is sorted: exclusion proofs are only as
+//! sound as the party building the commitment, which must use [`DiagnosisTree`]. The
+//! provider signing the commitment is that party.
+//!
+//! Shorter excluded lists are padded by repeating their first entry.
+
+use std::{collections::HashMap, io::Cursor};
+
+use eligibility_gadgets::{
+    icd10::CODE_HASH_BITS, CredentialConfig, CredentialInputs, CredentialWitness, EddsaField,
+    MerkleChip, MerkleConfig, MerklePath, PoseidonChip, PoseidonField, RangeCheckChip,
+    RangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    checked_credential, commit_diagnosis_root, configure_credential, field_to_u64, get_input,
+    hash_diagnosis_code, rng::default_rng, sort_key, verify_credential, DiagnosisError,
+    DiagnosisTree, PlonkishComponents, DIAGNOSIS_TREE_DEPTH,
+};
+
+/// Maximum number of excluded diagnoses per proof
+pub const MAX_EXCLUDED_DIAGNOSES: usize = 4;
+
+/// Circuit size: 2 * DIAGNOSIS_TREE_DEPTH Poseidon hashes per excluded diagnosis, plus
+/// the credential check
+pub const EXCLUSION_K: usize = 14;
+
+/// Number of public inputs of `DiagnosisExclusionCircuit`
+pub const EXCLUSION_PUBLIC_INPUTS: usize = MAX_EXCLUDED_DIAGNOSES + 5;
+
+/// Diagnosis Exclusion Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct DiagnosisExclusionConfig<F: PoseidonField> {
+    pub lhs: Column<Advice>,
+    pub rhs: Column<Advice>,
+    pub gap: Column<Advice>,          // lhs - rhs - 1
+    pub study_id: Column<Advice>,     // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,        // Public: proof day (days since epoch)
+    pub salt: Column<Advice>,         // Private: commitment salt
+    pub prefix_root: Column<Advice>,  // Private: root of the prefix tree
+    pub q_gap: Selector,
+    pub instance: Column<Instance>,
+    pub merkle: MerkleConfig,
+    pub range_check: RangeCheckConfig<F, CODE_HASH_BITS>,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Private witness for one excluded diagnosis
+#[derive(Clone, Copy)]
+pub struct AdjacentLeaves<F: PoseidonField> {
+    pub low: Value<F>,
+    pub low_siblings: [Value<F>; DIAGNOSIS_TREE_DEPTH],
+    pub low_bits: [Value<bool>; DIAGNOSIS_TREE_DEPTH],
+    pub high: Value<F>,
+    pub high_siblings: [Value<F>; DIAGNOSIS_TREE_DEPTH],
+    pub high_bits: [Value<bool>; DIAGNOSIS_TREE_DEPTH],
+}
+
+impl<F: PoseidonField> Default for AdjacentLeaves<F> {
+    fn default() -> Self {
+        Self {
+            low: Value::unknown(),
+            low_siblings: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            low_bits: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            high: Value::unknown(),
+            high_siblings: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            high_bits: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+        }
+    }
+}
+
+impl AdjacentLeaves<Fr> {
+    fn known(low: Fr, low_path: &MerklePath<Fr>, high: Fr, high_path: &MerklePath<Fr>) -> Self {
+        let low_bits = low_path.bits();
+        let high_bits = high_path.bits();
+
+        Self {
+            low: Value::known(low),
+            low_siblings: std::array::from_fn(|i| Value::known(low_path.siblings[i])),
+            low_bits: std::array::from_fn(|i| Value::known(low_bits[i])),
+            high: Value::known(high),
+            high_siblings: std::array::from_fn(|i| Value::known(high_path.siblings[i])),
+            high_bits: std::array::from_fn(|i| Value::known(high_bits[i])),
+        }
+    }
+}
+
+/// Diagnosis Exclusion Circuit
+///
+/// Proves: excluded_diagnosis ∉ patient_diagnoses, for every excluded diagnosis
+///
+/// ## Public Inputs (instance column)
+/// Rows 0..MAX_EXCLUDED_DIAGNOSES: excluded_hashes, then study_id, context_hash, epoch,
+/// provider_root and revocation_root. All are copy-constrained to their advice cells; the
+/// commitment stays private.
+#[derive(Clone)]
+pub struct DiagnosisExclusionCircuit<F: EddsaField> {
+    pub witnesses: [AdjacentLeaves<F>; MAX_EXCLUDED_DIAGNOSES], // Private: bracketing leaves
+    pub salt: Value<F>,                                         // Private: commitment salt
+    pub prefix_root: Value<F>,                                  // Private: root of the prefix tree
+    pub credential: CredentialWitness<F>, // Private: signs diagnosis_commitment
+    pub excluded_hashes: [F; MAX_EXCLUDED_DIAGNOSES], // Public: excluded diagnosis hashes
+    pub study_id: F,                      // Public: binds proof to study
+    pub context_hash: F,                  // Public: binds proof to wallet, chain and registry
+    pub epoch: F,                         // Public: proof day (days since epoch)
+    pub provider_root: F,                 // Public: registered provider tree root
+    pub revocation_root: F,               // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for DiagnosisExclusionCircuit<F> {
+    fn default() -> Self {
+        Self {
+            witnesses: [AdjacentLeaves::default(); MAX_EXCLUDED_DIAGNOSES],
+            salt: Value::unknown(),
+            prefix_root: Value::unknown(),
+            credential: CredentialWitness::default(),
+            excluded_hashes: [F::ZERO; MAX_EXCLUDED_DIAGNOSES],
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> DiagnosisExclusionCircuit<F> {
+    /// Assign `lhs - rhs - 1` on one row of the gap gate
+    fn assign_gap(
+        config: &DiagnosisExclusionConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        name: &str,
+        lhs: &AssignedCell<F, F>,
+        rhs: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || format!("gap {}", name),
+            |mut region| {
+                config.q_gap.enable(&mut region, 0)?;
+
+                let lhs = lhs.copy_advice(|| "lhs", &mut region, config.lhs, 0)?;
+                let rhs = rhs.copy_advice(|| "rhs", &mut region, config.rhs, 0)?;
+
+                let gap = lhs
+                    .value()
+                    .copied()
+                    .zip(rhs.value().copied())
+                    .map(|(lhs, rhs)| lhs - rhs - F::ONE);
+                region.assign_advice(|| "gap", config.gap, 0, || gap)
+            },
+        )
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for DiagnosisExclusionCircuit<F> {
+    type Config = DiagnosisExclusionConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let lhs = meta.advice_column();
+        let rhs = meta.advice_column();
+        let gap = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let salt = meta.advice_column();
+        let prefix_root = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let q_gap = meta.selector();
+        let instance = meta.instance_column();
+
+        for column in [
+            lhs,
+            rhs,
+            gap,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            prefix_root,
+        ] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        // Gate: gap = lhs - rhs - 1 (lhs > rhs once gap is range checked)
+        meta.create_gate("exclusion gap", |meta| {
+            let q = meta.query_selector(q_gap);
+            let lhs = meta.query_advice(lhs, Rotation::cur());
+            let rhs = meta.query_advice(rhs, Rotation::cur());
+            let gap = meta.query_advice(gap, Rotation::cur());
+
+            vec![q * (gap - (lhs - rhs - Expression::Constant(F::ONE)))]
+        });
+
+        let (merkle, credential) = configure_credential(meta);
+        let range_check = RangeCheckChip::configure(meta, running_sum);
+
+        DiagnosisExclusionConfig {
+            lhs,
+            rhs,
+            gap,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            prefix_root,
+            q_gap,
+            instance,
+            merkle,
+            range_check,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let range_chip = RangeCheckChip::construct(config.range_check.clone());
+
+        let (study_id, context_hash, epoch, salt, prefix_root) = layouter.assign_region(
+            || "exclusion inputs",
+            |mut region| {
+                // Assign public study_id
+                let study_id = region.assign_advice(
+                    || "study_id",
+                    config.study_id,
+                    0,
+                    || Value::known(self.study_id),
+                )?;
+
+                // Assign public context hash
+                let context_hash = region.assign_advice(
+                    || "context_hash",
+                    config.context_hash,
+                    0,
+                    || Value::known(self.context_hash),
+                )?;
+
+                // Assign public epoch
+                let epoch = region.assign_advice(
+                    || "epoch",
+                    config.epoch,
+                    0,
+                    || Value::known(self.epoch),
+                )?;
+
+                // Assign private salt and prefix root
+                let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+                let prefix_root = region.assign_advice(
+                    || "prefix_root",
+                    config.prefix_root,
+                    0,
+                    || self.prefix_root,
+                )?;
+
+                Ok((study_id, context_hash, epoch, salt, prefix_root))
+            },
+        )?;
+
+        let mut root: Option<AssignedCell<F, F>> = None;
+        for (k, (excluded_hash, witness)) in self
+            .excluded_hashes
+            .iter()
+            .zip(self.witnesses.iter())
+            .enumerate()
+        {
+            let (excluded, low, high) = layouter.assign_region(
+                || format!("excluded diagnosis {}", k),
+                |mut region| {
+                    let excluded = region.assign_advice(
+                        || "excluded_hash",
+                        config.lhs,
+                        0,
+                        || Value::known(*excluded_hash),
+                    )?;
+                    let low = region.assign_advice(|| "low", config.rhs, 0, || witness.low)?;
+                    let high = region.assign_advice(|| "high", config.gap, 0, || witness.high)?;
+
+                    Ok((excluded, low, high))
+                },
+            )?;
+            layouter.constrain_instance(excluded.cell(), config.instance, k)?;
+
+            // Both leaves are under the committed root...
+            let (low_root, low_index) = merkle_chip.compute_root_with_index(
+                layouter.namespace(|| format!("low leaf {}", k)),
+                &low,
+                &witness.low_siblings,
+                &witness.low_bits,
+            )?;
+            let (high_root, high_index) = merkle_chip.compute_root_with_index(
+                layouter.namespace(|| format!("high leaf {}", k)),
+                &high,
+                &witness.high_siblings,
+                &witness.high_bits,
+            )?;
+
+            let root = root.get_or_insert_with(|| low_root.clone()).clone();
+            layouter.assign_region(
+                || format!("same root {}", k),
+                |mut region| {
+                    region.constrain_equal(low_root.cell(), root.cell())?;
+                    region.constrain_equal(high_root.cell(), root.cell())
+                },
+            )?;
+
+            // ...adjacent...
+            let index_gap = Self::assign_gap(
+                &config,
+                &mut layouter,
+                &format!("index {}", k),
+                &high_index,
+                &low_index,
+            )?;
+            layouter.assign_region(
+                || format!("adjacent leaves {}", k),
+                |mut region| region.constrain_constant(index_gap.cell(), F::ZERO),
+            )?;
+
+            // ...and strictly bracket the excluded hash
+            let low_gap = Self::assign_gap(
+                &config,
+                &mut layouter,
+                &format!("low {}", k),
+                &excluded,
+                &low,
+            )?;
+            let high_gap = Self::assign_gap(
+                &config,
+                &mut layouter,
+                &format!("high {}", k),
+                &high,
+                &excluded,
+            )?;
+
+            range_chip.assign(layouter.namespace(|| format!("low gap {}", k)), &low_gap)?;
+            range_chip.assign(layouter.namespace(|| format!("high gap {}", k)), &high_gap)?;
+        }
+
+        let root = root.expect("at least one excluded diagnosis");
+        let poseidon_chip = PoseidonChip::<F>::construct(config.merkle.poseidon.clone());
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "diagnosis commitment"),
+            &[root, prefix_root, salt],
+        )?;
+        let (provider_root, revocation_root) = verify_credential(
+            layouter.namespace(|| "credential"),
+            &config.credential,
+            &commitment,
+            &epoch,
+            &self.credential,
+        )?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(study_id.cell(), config.instance, MAX_EXCLUDED_DIAGNOSES)?;
+        layouter.constrain_instance(
+            context_hash.cell(),
+            config.instance,
+            MAX_EXCLUDED_DIAGNOSES + 1,
+        )?;
+        layouter.constrain_instance(epoch.cell(), config.instance, MAX_EXCLUDED_DIAGNOSES + 2)?;
+        layouter.constrain_instance(
+            provider_root.cell(),
+            config.instance,
+            MAX_EXCLUDED_DIAGNOSES + 3,
+        )?;
+        layouter.constrain_instance(
+            revocation_root.cell(),
+            config.instance,
+            MAX_EXCLUDED_DIAGNOSES + 4,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for DiagnosisExclusionCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: excluded_hashes, study_id, context_hash, epoch, provider_root,
+        // revocation_root
+        let mut instances = self.excluded_hashes.to_vec();
+        instances.push(self.study_id);
+        instances.push(self.context_hash);
+        instances.push(self.epoch);
+        instances.push(self.provider_root);
+        instances.push(self.revocation_root);
+        vec![instances]
+    }
+}
+
+/// Pad an excluded list to MAX_EXCLUDED_DIAGNOSES by repeating its first entry
+pub fn pad_excluded_hashes(
+    excluded_hashes: &[Fr],
+) -> Result<[Fr; MAX_EXCLUDED_DIAGNOSES], DiagnosisError> {
+    if excluded_hashes.is_empty() || excluded_hashes.len() > MAX_EXCLUDED_DIAGNOSES {
+        return Err(DiagnosisError(format!(
+            "Expected 1 to {} excluded diagnoses, got {}",
+            MAX_EXCLUDED_DIAGNOSES,
+            excluded_hashes.len()
+        )));
+    }
+
+    Ok(std::array::from_fn(|i| {
+        excluded_hashes
+            .get(i)
+            .copied()
+            .unwrap_or(excluded_hashes[0])
+    }))
+}
+
+impl DiagnosisTree {
+    /// Index of the leaf directly below `excluded_hash`
+    ///
+    /// Fails if the hash is one of the patient's diagnoses.
+    pub fn bracketing_index(&self, excluded_hash: Fr) -> Result<usize, DiagnosisError> {
+        let key = sort_key(&excluded_hash);
+
+        self.tree
+            .leaves()
+            .windows(2)
+            .position(|pair| sort_key(&pair[0]) < key && key < sort_key(&pair[1]))
+            .ok_or(DiagnosisError(
+                "Patient has an excluded diagnosis".to_string(),
+            ))
+    }
+
+    /// Circuit proving that none of `excluded_codes` is in this tree, attested by `credential`
+    pub fn exclusion_circuit(
+        &self,
+        excluded_codes: &[String],
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<DiagnosisExclusionCircuit<Fr>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let hashes = excluded_codes
+            .iter()
+            .map(|code| hash_diagnosis_code(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        let excluded_hashes = pad_excluded_hashes(&hashes)?;
+
+        let mut witnesses = [AdjacentLeaves::default(); MAX_EXCLUDED_DIAGNOSES];
+        for (witness, excluded_hash) in witnesses.iter_mut().zip(excluded_hashes.iter()) {
+            let index = self.bracketing_index(*excluded_hash)?;
+            let low_path = self
+                .tree
+                .path(index)
+                .map_err(|e| DiagnosisError(e.to_string()))?;
+            let high_path = self
+                .tree
+                .path(index + 1)
+                .map_err(|e| DiagnosisError(e.to_string()))?;
+
+            *witness = AdjacentLeaves::known(
+                self.tree.leaves()[index],
+                &low_path,
+                self.tree.leaves()[index + 1],
+                &high_path,
+            );
+        }
+
+        Ok(DiagnosisExclusionCircuit {
+            witnesses,
+            salt: Value::known(salt),
+            prefix_root: Value::known(self.prefix_root()),
+            credential: credential.witness(),
+            excluded_hashes,
+            study_id,
+            context_hash,
+            epoch,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        })
+    }
+
+    /// Input map for [`generate_exclusion_proof`]
+    pub fn exclusion_inputs(
+        &self,
+        excluded_codes: &[String],
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let hashes = excluded_codes
+            .iter()
+            .map(|code| hash_diagnosis_code(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        pad_excluded_hashes(&hashes)?;
+
+        let mut low_indices = Vec::new();
+        let mut low_leaves = Vec::new();
+        let mut high_leaves = Vec::new();
+        let mut low_siblings = Vec::new();
+        let mut high_siblings = Vec::new();
+        for excluded_hash in hashes.iter() {
+            let index = self.bracketing_index(*excluded_hash)?;
+            let low_path = self
+                .tree
+                .path(index)
+                .map_err(|e| DiagnosisError(e.to_string()))?;
+            let high_path = self
+                .tree
+                .path(index + 1)
+                .map_err(|e| DiagnosisError(e.to_string()))?;
+
+            low_indices.push(Fr::from(index as u64));
+            low_leaves.push(self.tree.leaves()[index]);
+            high_leaves.push(self.tree.leaves()[index + 1]);
+            low_siblings.extend(low_path.siblings);
+            high_siblings.extend(high_path.siblings);
+        }
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert("excluded_hashes".to_string(), hashes);
+        inputs.insert("study_id".to_string(), vec![study_id]);
+        inputs.insert("context_hash".to_string(), vec![context_hash]);
+        inputs.insert("epoch".to_string(), vec![epoch]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("prefix_root".to_string(), vec![self.prefix_root()]);
+        inputs.insert("low_indices".to_string(), low_indices);
+        inputs.insert("low_leaves".to_string(), low_leaves);
+        inputs.insert("high_leaves".to_string(), high_leaves);
+        inputs.insert("low_siblings".to_string(), low_siblings);
+        inputs.insert("high_siblings".to_string(), high_siblings);
+
+        Ok(inputs)
+    }
+}
+
+/// Generate diagnosis exclusion proof
+///
+/// Expects the input map built by [`DiagnosisTree::exclusion_inputs`]:
+/// `excluded_hashes` (1 to MAX_EXCLUDED_DIAGNOSES values), `study_id`, `context_hash`,
+/// `epoch`, `salt`, `prefix_root`, per excluded hash: `low_indices`, `low_leaves`,
+/// `high_leaves`, plus `low_siblings` and `high_siblings` (DIAGNOSIS_TREE_DEPTH values each,
+/// bottom-up), and the credential inputs, which include `diagnosis_commitment`. The
+/// credential (including its age at `epoch`) and the bracketing leaves are checked before
+/// proving.
+pub fn generate_exclusion_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_exclusion_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_exclusion_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_exclusion_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = EXCLUSION_K;
+
+    // Extract inputs
+    let hashes = inputs
+        .get("excluded_hashes")
+        .ok_or(DiagnosisError("Missing excluded_hashes".to_string()))?
+        .clone();
+    let count = hashes.len();
+    let excluded_hashes = pad_excluded_hashes(&hashes)?;
+
+    let study_id = get_input(&inputs, "study_id", 1)?[0];
+    let context_hash = get_input(&inputs, "context_hash", 1)?[0];
+    let epoch = get_input(&inputs, "epoch", 1)?[0];
+    let credential = checked_credential(&inputs, &epoch)?;
+    let diagnosis_commitment = credential.attributes().diagnosis_commitment;
+    let salt = get_input(&inputs, "salt", 1)?[0];
+    let prefix_root = get_input(&inputs, "prefix_root", 1)?[0];
+    let low_indices = get_input(&inputs, "low_indices", count)?;
+    let low_leaves = get_input(&inputs, "low_leaves", count)?;
+    let high_leaves = get_input(&inputs, "high_leaves", count)?;
+    let low_siblings = get_input(&inputs, "low_siblings", count * DIAGNOSIS_TREE_DEPTH)?;
+    let high_siblings = get_input(&inputs, "high_siblings", count * DIAGNOSIS_TREE_DEPTH)?;
+
+    // Client-side validation: each pair of leaves opens the commitment and brackets its hash
+    let mut witnesses = Vec::with_capacity(count);
+    for i in 0..count {
+        let index = field_to_u64(&low_indices[i])? as usize;
+        if index + 1 >= 1 << DIAGNOSIS_TREE_DEPTH {
+            return Err(DiagnosisError(format!("Invalid low_indices: {}", index)));
+        }
+
+        let siblings =
+            |all: &[Fr]| all[i * DIAGNOSIS_TREE_DEPTH..(i + 1) * DIAGNOSIS_TREE_DEPTH].to_vec();
+        let low_path = MerklePath {
+            index,
+            siblings: siblings(low_siblings),
+        };
+        let high_path = MerklePath {
+            index: index + 1,
+            siblings: siblings(high_siblings),
+        };
+
+        let opens = |path: &MerklePath<Fr>, leaf: Fr| {
+            commit_diagnosis_root(path.compute_root(leaf), prefix_root, salt)
+                == diagnosis_commitment
+        };
+        let key = sort_key(&hashes[i]);
+        if !opens(&low_path, low_leaves[i])
+            || !opens(&high_path, high_leaves[i])
+            || sort_key(&low_leaves[i]) >= key
+            || key >= sort_key(&high_leaves[i])
+        {
+            return Err(DiagnosisError(format!(
+                "Excluded diagnosis {} is not provably absent from the committed diagnosis set",
+                i
+            )));
+        }
+
+        witnesses.push(AdjacentLeaves::known(
+            low_leaves[i],
+            &low_path,
+            high_leaves[i],
+            &high_path,
+        ));
+    }
+
+    // Create circuit with validated inputs; padding repeats the first entry
+    let circuit = DiagnosisExclusionCircuit::<Fr> {
+        witnesses: std::array::from_fn(|i| witnesses.get(i).copied().unwrap_or(witnesses[0])),
+        salt: Value::known(salt),
+        prefix_root: Value::known(prefix_root),
+        credential: credential.witness(),
+        excluded_hashes,
+        study_id,
+        context_hash,
+        epoch,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit = Halo2Circuit::<Fr, DiagnosisExclusionCircuit<Fr>>::new::<PC::ProvingBackend>(
+        k,
+        circuit.clone(),
+    );
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            &prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| DiagnosisError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify diagnosis exclusion proof
+///
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and for checking `epoch` against its own clock.
+pub fn verify_exclusion_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != EXCLUSION_PUBLIC_INPUTS {
+        return Err(DiagnosisError(format!(
+            "Invalid number of public inputs (expected {}: excluded_hashes, study_id, context_hash, epoch, provider_root, revocation_root)",
+            EXCLUSION_PUBLIC_INPUTS
+        )));
+    }
+
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        &verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| DiagnosisError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{issue, EPOCH},
+        DIAGNOSIS_K,
+    };
+    use halo2_proofs::{dev::MockProver, halo2curves::ff::Field};
+
+    fn patient_tree() -> DiagnosisTree {
+        DiagnosisTree::new(&[
+            "E11.9".to_string(), // Type 2 Diabetes
+            "I10".to_string(),   // Hypertension
+        ])
+        .unwrap()
+    }
+
+    fn excluded_codes() -> Vec<String> {
+        vec![
+            "I21.9".to_string(), // Myocardial infarction
+            "C50.9".to_string(), // Breast cancer
+        ]
+    }
+
+    /// Circuit excluding `codes` with salt 1234, study 1, context 7 and epoch [`EPOCH`],
+    /// attested by a test credential
+    fn circuit(
+        tree: &DiagnosisTree,
+        codes: &[String],
+    ) -> Result<DiagnosisExclusionCircuit<Fr>, DiagnosisError> {
+        let salt = Fr::from(1234);
+        tree.exclusion_circuit(
+            codes,
+            salt,
+            Fr::from(1),
+            Fr::from(7),
+            Fr::from(EPOCH),
+            &issue(tree.commitment(salt)),
+        )
+    }
+
+    fn mock_verify(circuit: &DiagnosisExclusionCircuit<Fr>, instances: Vec<Vec<Fr>>) -> bool {
+        MockProver::run(EXCLUSION_K as u32, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_pad_excluded_hashes() {
+        let hashes = [Fr::from(1), Fr::from(2)];
+        assert_eq!(
+            pad_excluded_hashes(&hashes).unwrap(),
+            [Fr::from(1), Fr::from(2), Fr::from(1), Fr::from(1)]
+        );
+        assert!(pad_excluded_hashes(&[]).is_err());
+        assert!(pad_excluded_hashes(&[Fr::ONE; MAX_EXCLUDED_DIAGNOSES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_bracketing_index() {
+        let tree = patient_tree();
+
+        for code in excluded_codes() {
+            let hash = hash_diagnosis_code(&code).unwrap();
+            let index = tree.bracketing_index(hash).unwrap();
+            let leaves = tree.tree.leaves();
+            assert!(sort_key(&leaves[index]) < sort_key(&hash));
+            assert!(sort_key(&hash) < sort_key(&leaves[index + 1]));
+        }
+
+        // A diagnosis the patient has cannot be bracketed
+        let hash = hash_diagnosis_code("I10").unwrap();
+        assert!(tree.bracketing_index(hash).is_err());
+        assert!(circuit(&tree, &["I10".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_absent_diagnoses() {
+        let circuit = circuit(&patient_tree(), &excluded_codes()).unwrap();
+        assert!(mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_present_diagnosis() {
+        let tree = patient_tree();
+        let mut circuit = circuit(&tree, &excluded_codes()).unwrap();
+
+        // Claim that "I10" is absent using the leaves around it
+        let present = hash_diagnosis_code("I10").unwrap();
+        let index = tree
+            .tree
+            .leaves()
+            .iter()
+            .position(|l| *l == present)
+            .unwrap();
+        let low = tree.tree.path(index - 1).unwrap();
+        let high = tree.tree.path(index + 1).unwrap();
+        circuit.excluded_hashes[0] = present;
+        circuit.witnesses[0] = AdjacentLeaves::known(
+            tree.tree.leaves()[index - 1],
+            &low,
+            tree.tree.leaves()[index + 1],
+            &high,
+        );
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = circuit(&patient_tree(), &excluded_codes()).unwrap();
+
+        for row in 0..EXCLUSION_PUBLIC_INPUTS {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            assert!(
+                !mock_verify(&circuit, instances),
+                "tampered row {} must not verify",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_membership_and_exclusion_share_the_commitment() {
+        let tree = patient_tree();
+        let salt = Fr::from(1234);
+        let credential = issue(tree.commitment(salt));
+        let epoch = Fr::from(EPOCH);
+
+        let membership = tree
+            .circuit("E11.9", salt, Fr::from(1), Fr::from(7), epoch, &credential)
+            .unwrap();
+        let exclusion = tree
+            .exclusion_circuit(
+                &excluded_codes(),
+                salt,
+                Fr::from(1),
+                Fr::from(7),
+                epoch,
+                &credential,
+            )
+            .unwrap();
+
+        // One credential over one commitment attests both proofs
+        assert!(
+            MockProver::run(DIAGNOSIS_K as u32, &membership, membership.instances())
+                .unwrap()
+                .verify()
+                .is_ok()
+        );
+        assert!(mock_verify(&exclusion, exclusion.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_commitment() {
+        // A tree without the patient's hypertension, so "I10" could be excluded
+        let tree = DiagnosisTree::new(&["E11.9".to_string()]).unwrap();
+        let salt = Fr::from(1234);
+        let signed = issue(patient_tree().commitment(salt));
+        assert!(tree
+            .exclusion_circuit(
+                &["I10".to_string()],
+                salt,
+                Fr::from(1),
+                Fr::from(7),
+                Fr::from(EPOCH),
+                &signed
+            )
+            .is_err());
+
+        let mut circuit = circuit(&tree, &["I10".to_string()]).unwrap();
+        circuit.credential = signed.witness();
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+}
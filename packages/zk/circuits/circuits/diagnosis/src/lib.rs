@@ -4,49 +4,87 @@
 //! without revealing their complete medical history.
 //!
 //! ## Security Model
-//! - Private Input: Patient's diagnosis codes array (up to MAX_DIAGNOSES)
-//! - Public Inputs: required_diagnosis_hash, study_id
-//! - Constraint: required_diagnosis ∈ patient_diagnoses
+//! - Private Inputs: Merkle authentication path of the required diagnosis, prefix root,
+//!   commitment salt, credential
+//! - Public Inputs: required_diagnosis_hash, study_id, context_hash, epoch, provider_root,
+//!   revocation_root
+//! - Constraint: required_diagnosis ∈ patient_diagnoses, and diagnosis_commitment is
+//!   signed by a provider registered under provider_root in a credential not revoked
+//!   under revocation_root and issued at most MAX_CREDENTIAL_AGE days before epoch
 //!
-//! ## Current Implementation (MVP)
-//! Uses a hybrid approach:
-//! 1. Client-side membership check (UX feedback)
-//! 2. ZK proof of diagnosis knowledge with hash commitment
-//! 3. On-chain verification of proof + metadata
+//! ## Diagnosis Commitment
+//! The patient's diagnosis codes (up to MAX_DIAGNOSES) are hashed into the leaves of a
+//! Poseidon Merkle tree of depth DIAGNOSIS_TREE_DEPTH. Leaves are sorted and bracketed by
+//! sentinels, so the same commitment also supports exclusion proofs (see [`exclusion`]).
+//! A second tree of the same depth holds one leaf per code committing to its ICD-10
+//! prefixes, for category-level criteria (see [`prefix`]). k-of-n criteria are proven
+//! over all MAX_DIAGNOSES code slots at once (see [`threshold`]). The provider signs
+//! `diagnosis_commitment = Poseidon(root, prefix_root, salt)`: the salt keeps the roots
+//! from being brute-forced over the small space of ICD-10 code sets, so the commitment
+//! reveals nothing about the other diagnoses.
 //!
-//! ## TODO (Post-MVP): Set Membership Proofs
-//! Implement proper cryptographic set membership using:
-//! - Merkle tree inclusion proofs
-//! - Poseidon hash for diagnosis codes
-//! - Efficient batch verification for multiple diagnoses
-//! See: /MVP_STUDY_SCENARIOS.md for details
+//! The circuit recomputes the root from `required_hash` and the private path, then
+//! recomputes the commitment (with the private `prefix_root`) and opens it in the
+//! credential. The commitment itself stays private, so proofs for different studies
+//! cannot be linked by it, and the leaf position is private as well.
+//!
+//! ## Credential
+//! A commitment the patient builds alone proves nothing about their record. Every circuit
+//! of this crate therefore opens the commitment as the `diagnosis_commitment` attribute of
+//! a provider-signed credential (see `eligibility_gadgets::credential`) and appends
+//! `provider_root` and `revocation_root` to its public inputs. The credential travels in
+//! the input maps next to the diagnosis inputs.
+//!
+//! ## Context
+//! Every circuit of this crate also exposes a `context_hash` public input binding the
+//! proof to the applicant wallet, chain and registry. The circuits do not compute it:
+//! the verifier recomputes it from the submitting wallet, as for the age range proof of
+//! `composite-eligibility-circuit`, so a copied proof does not verify for another wallet.
+//!
+//! The `epoch` public input (days since the Unix epoch) bounds the age of the credential:
+//! the signed `issued_at` day must lie at most `MAX_CREDENTIAL_AGE` days before it. The
+//! verifier checks the epoch against its own clock, so a stale credential cannot be
+//! replayed by claiming an old epoch.
+//!
+//! Use [`DiagnosisTree`] to build the tree and the proof inputs from ICD-10 codes.
 
 use std::{collections::HashMap, io::Cursor};
 
+use eligibility_gadgets::{
+    hash_icd10_code, icd10::CODE_HASH_BITS, normalize_icd10_code, CredentialAttributes,
+    CredentialChip, CredentialConfig, CredentialError, CredentialInputs, CredentialWitness,
+    EddsaChip, EddsaField, HashVersion, IssuerChip, MerkleChip, MerkleConfig, MerklePath,
+    MerkleTree, PoseidonChip, PoseidonField,
+};
 use halo2_proofs::{
-    circuit::{Layouter, SimpleFloorPlanner, Value},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
     halo2curves::ff::{Field, PrimeField},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance, Selector},
-    poly::Rotation,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
 };
 use plonkish_backend::{
     backend::PlonkishBackend,
     frontend::halo2::{CircuitExt, Halo2Circuit},
     halo2_curves::bn256::Fr,
     pcs::{CommitmentChunk, PolynomialCommitmentScheme},
-    util::{
-        test::std_rng,
-        transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
-    },
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
 };
-use rand::RngCore;
+use rand::{CryptoRng, RngCore};
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
+pub mod exclusion;
 pub mod io;
+pub mod prefix;
 pub mod serialization;
+pub mod threshold;
+
+pub use eligibility_gadgets::rng;
 
-use crate::serialization::{deserialize_circuit_inputs, InputsSerializationWrapper};
+use crate::{
+    prefix::prefix_leaf,
+    rng::default_rng,
+    serialization::{deserialize_circuit_inputs, InputsSerializationWrapper},
+};
 
 pub trait PlonkishComponents {
     type Param: Clone + Serialize + DeserializeOwned;
@@ -76,46 +114,76 @@ pub type ProofTranscript = Keccak256Transcript<Cursor<Vec<u8>>>;
 /// Maximum number of diagnoses a patient can have in the circuit
 pub const MAX_DIAGNOSES: usize = 10;
 
+/// Depth of the diagnosis Merkle tree (2^4 = 16 leaves >= MAX_DIAGNOSES + 2 sentinels)
+pub const DIAGNOSIS_TREE_DEPTH: usize = 4;
+
+/// Circuit size: the credential check plus DIAGNOSIS_TREE_DEPTH + 1 Poseidon hashes
+pub const DIAGNOSIS_K: usize = 13;
+
+/// Number of public inputs of `DiagnosisMembershipCircuit`
+pub const DIAGNOSIS_PUBLIC_INPUTS: usize = 6;
+
 /// Diagnosis Membership Circuit Configuration
 #[derive(Debug, Clone)]
-pub struct DiagnosisMembershipConfig {
-    pub diagnosis_hash: Column<Advice>,      // Private: hash of patient diagnosis
-    pub required_hash: Column<Advice>,       // Public: required diagnosis hash
-    pub selector: Selector,
+pub struct DiagnosisMembershipConfig<F: PoseidonField> {
+    pub required_hash: Column<Advice>, // Public: required diagnosis hash (the leaf)
+    pub study_id: Column<Advice>,      // Public: study identifier
+    pub context_hash: Column<Advice>,  // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,         // Public: proof day (days since epoch)
+    pub salt: Column<Advice>,          // Private: commitment salt
+    pub prefix_root: Column<Advice>,   // Private: root of the prefix tree
     pub instance: Column<Instance>,
+    pub merkle: MerkleConfig,
+    pub credential: CredentialConfig<F>,
 }
 
 /// Diagnosis Membership Circuit
 ///
 /// Proves: required_diagnosis ∈ patient_diagnoses
 ///
-/// ## MVP Limitation
-/// For MVP, we use client-side validation + hash commitment.
-/// The circuit proves the prover knows a diagnosis hash matching the requirement.
+/// `required_hash` is a leaf of the patient's diagnosis tree, and
+/// `Poseidon(root, prefix_root, salt)` equals the private `diagnosis_commitment`
+/// a registered provider signed at most `MAX_CREDENTIAL_AGE` days before `epoch`.
 ///
-/// ## TODO (Production): Implement proper set membership
-/// - Merkle tree of patient diagnoses
-/// - Inclusion proof for required diagnosis
-/// - Zero-knowledge of other diagnoses
+/// ## Public Inputs (instance column)
+/// Row 0: required_hash, row 1: study_id, row 2: context_hash, row 3: epoch,
+/// row 4: provider_root, row 5: revocation_root. All are copy-constrained to their advice cells, so a proof
+/// for one study or applicant does not verify for another.
 #[derive(Clone)]
-pub struct DiagnosisMembershipCircuit<F: Field> {
-    pub diagnosis_hash: Value<F>,    // Private: hash of matching diagnosis
-    pub required_hash: F,            // Public: required diagnosis hash
-    pub study_id: F,                 // Public: binds proof to study
+pub struct DiagnosisMembershipCircuit<F: EddsaField> {
+    pub siblings: [Value<F>; DIAGNOSIS_TREE_DEPTH], // Private: authentication path
+    pub path_bits: [Value<bool>; DIAGNOSIS_TREE_DEPTH], // Private: leaf index bits
+    pub salt: Value<F>,                             // Private: commitment salt
+    pub prefix_root: Value<F>,                      // Private: root of the prefix tree
+    pub credential: CredentialWitness<F>,           // Private: signs diagnosis_commitment
+    pub required_hash: F,                           // Public: required diagnosis hash
+    pub study_id: F,                                // Public: binds proof to study
+    pub context_hash: F,    // Public: binds proof to wallet, chain and registry
+    pub epoch: F,           // Public: proof day (days since epoch)
+    pub provider_root: F,   // Public: registered provider tree root
+    pub revocation_root: F, // Public: revoked credential tree root
 }
 
-impl<F: Field> Default for DiagnosisMembershipCircuit<F> {
+impl<F: EddsaField> Default for DiagnosisMembershipCircuit<F> {
     fn default() -> Self {
         Self {
-            diagnosis_hash: Value::unknown(),
+            siblings: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            path_bits: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            salt: Value::unknown(),
+            prefix_root: Value::unknown(),
+            credential: CredentialWitness::default(),
             required_hash: F::ZERO,
             study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
         }
     }
 }
 
-impl<F: Field + PrimeField> Circuit<F> for DiagnosisMembershipCircuit<F> {
-    type Config = DiagnosisMembershipConfig;
+impl<F: EddsaField> Circuit<F> for DiagnosisMembershipCircuit<F> {
+    type Config = DiagnosisMembershipConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -123,33 +191,34 @@ impl<F: Field + PrimeField> Circuit<F> for DiagnosisMembershipCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let diagnosis_hash = meta.advice_column();
         let required_hash = meta.advice_column();
-        let selector = meta.selector();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let salt = meta.advice_column();
+        let prefix_root = meta.advice_column();
         let instance = meta.instance_column();
 
-        meta.enable_equality(diagnosis_hash);
         meta.enable_equality(required_hash);
+        meta.enable_equality(study_id);
+        meta.enable_equality(context_hash);
+        meta.enable_equality(epoch);
+        meta.enable_equality(salt);
+        meta.enable_equality(prefix_root);
         meta.enable_equality(instance);
 
-        // Gate: Prove diagnosis hash matches required hash
-        meta.create_gate("diagnosis membership", |meta| {
-            let s = meta.query_selector(selector);
-            let diag_hash = meta.query_advice(diagnosis_hash, Rotation::cur());
-            let _req_hash = meta.query_advice(required_hash, Rotation::cur());  // Will be used in production Merkle proof
-
-            // TODO (Production): Replace with Merkle proof verification
-            // For MVP: Just prove knowledge of diagnosis hash
-            vec![
-                s * (diag_hash.clone() - diag_hash), // Proves diagnosis_hash exists (always 0)
-            ]
-        });
+        let (merkle, credential) = configure_credential(meta);
 
         DiagnosisMembershipConfig {
-            diagnosis_hash,
             required_hash,
-            selector,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            prefix_root,
             instance,
+            merkle,
+            credential,
         }
     }
 
@@ -158,64 +227,373 @@ impl<F: Field + PrimeField> Circuit<F> for DiagnosisMembershipCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        layouter.assign_region(
-            || "diagnosis membership check",
-            |mut region| {
-                config.selector.enable(&mut region, 0)?;
-
-                // Assign private diagnosis hash
-                region.assign_advice(
-                    || "diagnosis_hash",
-                    config.diagnosis_hash,
-                    0,
-                    || self.diagnosis_hash,
-                )?;
-
-                // Assign public required hash
-                region.assign_advice(
-                    || "required_hash",
-                    config.required_hash,
-                    0,
-                    || Value::known(self.required_hash),
-                )?;
-
-                Ok(())
-            },
+        let (required_hash, study_id, context_hash, epoch, salt, prefix_root) = layouter
+            .assign_region(
+                || "diagnosis membership inputs",
+                |mut region| {
+                    // Assign public required hash
+                    let required_hash = region.assign_advice(
+                        || "required_hash",
+                        config.required_hash,
+                        0,
+                        || Value::known(self.required_hash),
+                    )?;
+
+                    // Assign public study_id
+                    let study_id = region.assign_advice(
+                        || "study_id",
+                        config.study_id,
+                        0,
+                        || Value::known(self.study_id),
+                    )?;
+
+                    // Assign public context hash
+                    let context_hash = region.assign_advice(
+                        || "context_hash",
+                        config.context_hash,
+                        0,
+                        || Value::known(self.context_hash),
+                    )?;
+
+                    // Assign public epoch
+                    let epoch = region.assign_advice(
+                        || "epoch",
+                        config.epoch,
+                        0,
+                        || Value::known(self.epoch),
+                    )?;
+
+                    // Assign private salt and prefix root
+                    let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+                    let prefix_root = region.assign_advice(
+                        || "prefix_root",
+                        config.prefix_root,
+                        0,
+                        || self.prefix_root,
+                    )?;
+
+                    Ok((
+                        required_hash,
+                        study_id,
+                        context_hash,
+                        epoch,
+                        salt,
+                        prefix_root,
+                    ))
+                },
+            )?;
+
+        // required_hash is a leaf under the committed root
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let root = merkle_chip.compute_root(
+            layouter.namespace(|| "diagnosis root"),
+            &required_hash,
+            &self.siblings,
+            &self.path_bits,
+        )?;
+
+        let poseidon_chip = PoseidonChip::<F>::construct(config.merkle.poseidon.clone());
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "diagnosis commitment"),
+            &[root, prefix_root, salt],
+        )?;
+        let (provider_root, revocation_root) = verify_credential(
+            layouter.namespace(|| "credential"),
+            &config.credential,
+            &commitment,
+            &epoch,
+            &self.credential,
         )?;
 
+        // Bind every public value to its instance row
+        layouter.constrain_instance(required_hash.cell(), config.instance, 0)?;
+        layouter.constrain_instance(study_id.cell(), config.instance, 1)?;
+        layouter.constrain_instance(context_hash.cell(), config.instance, 2)?;
+        layouter.constrain_instance(epoch.cell(), config.instance, 3)?;
+        layouter.constrain_instance(provider_root.cell(), config.instance, 4)?;
+        layouter.constrain_instance(revocation_root.cell(), config.instance, 5)?;
+
         Ok(())
     }
 }
 
-impl<F: Field + PrimeField> CircuitExt<F> for DiagnosisMembershipCircuit<F> {
+impl<F: EddsaField> CircuitExt<F> for DiagnosisMembershipCircuit<F> {
     fn rand(_: usize, _: impl RngCore) -> Self {
-        unimplemented!()
+        Self::default()
     }
 
     fn instances(&self) -> Vec<Vec<F>> {
-        // Public inputs: required_hash, study_id
-        vec![vec![self.required_hash, self.study_id]]
+        // Public inputs: required_hash, study_id, context_hash, epoch, provider_root,
+        // revocation_root
+        vec![vec![
+            self.required_hash,
+            self.study_id,
+            self.context_hash,
+            self.epoch,
+            self.provider_root,
+            self.revocation_root,
+        ]]
     }
 }
 
+/// Merkle and credential configurations sharing one Poseidon chip
+pub(crate) fn configure_credential<F: EddsaField>(
+    meta: &mut ConstraintSystem<F>,
+) -> (MerkleConfig, CredentialConfig<F>) {
+    let poseidon = PoseidonChip::configure(meta);
+    let eddsa = EddsaChip::configure(meta, poseidon.clone());
+    let merkle = MerkleChip::configure(meta, poseidon);
+    let issuer = IssuerChip::configure(meta, eddsa, merkle.clone());
+    (merkle, CredentialChip::configure(meta, issuer))
+}
+
+/// Verify that `commitment` is the diagnosis commitment of a credential issued at most
+/// `MAX_CREDENTIAL_AGE` days before `epoch`, returning the `provider_root` and
+/// `revocation_root` cells
+pub(crate) fn verify_credential<F: EddsaField>(
+    layouter: impl Layouter<F>,
+    config: &CredentialConfig<F>,
+    commitment: &AssignedCell<F, F>,
+    epoch: &AssignedCell<F, F>,
+    credential: &CredentialWitness<F>,
+) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+    let opened = CredentialAttributes {
+        diagnosis_commitment: Some(commitment),
+        ..Default::default()
+    };
+    CredentialChip::<F>::construct(config.clone()).verify_at(layouter, opened, credential, epoch)
+}
+
+/// Read the credential of an input map, checked at `epoch` as `CredentialChip` checks it
+pub(crate) fn checked_credential(
+    inputs: &HashMap<String, Vec<Fr>>,
+    epoch: &Fr,
+) -> Result<CredentialInputs, DiagnosisError> {
+    let credential_error =
+        |e: CredentialError| DiagnosisError(format!("Invalid credential: {}", e));
+    let credential = CredentialInputs::from_inputs(inputs).map_err(credential_error)?;
+    credential.check().map_err(credential_error)?;
+    credential.check_at(epoch).map_err(credential_error)?;
+    Ok(credential)
+}
+
 /// Hash a diagnosis code to field element
 ///
-/// For MVP, we use a simple hash. In production, use Poseidon hash.
-pub fn hash_diagnosis_code(code: &str) -> Result<u64, DiagnosisError> {
-    // Simple hash: sum of byte values
-    // TODO (Production): Use Poseidon hash for ZK-friendly hashing
-    let hash = code.bytes().map(|b| b as u64).sum::<u64>();
-    Ok(hash)
+/// Canonical Poseidon encoding of the ICD-10 code (see `eligibility_gadgets::icd10`),
+/// using the current hash version. Spellings such as "E11.9" and "e119" share a hash.
+pub fn hash_diagnosis_code(code: &str) -> Result<Fr, DiagnosisError> {
+    hash_icd10_code(code, HashVersion::CURRENT).map_err(|e| DiagnosisError(e.to_string()))
+}
+
+/// Commitment to the diagnosis and prefix tree roots: `Poseidon(root, prefix_root, salt)`
+pub fn commit_diagnosis_root(root: Fr, prefix_root: Fr, salt: Fr) -> Fr {
+    Fr::poseidon_hash(&[root, prefix_root, salt])
+}
+
+/// Lower sentinel leaf, below every code hash
+pub fn lower_sentinel() -> Fr {
+    Fr::ZERO
+}
+
+/// Upper sentinel leaf (`2^CODE_HASH_BITS - 1`), above every code hash
+pub fn upper_sentinel() -> Fr {
+    Fr::from(2).pow_vartime([CODE_HASH_BITS as u64]) - Fr::ONE
+}
+
+/// Big-endian bytes, for ordering code hashes as integers
+pub(crate) fn sort_key(value: &Fr) -> [u8; 32] {
+    let mut bytes = value.to_bytes();
+    bytes.reverse();
+    bytes
+}
+
+/// Host-side Poseidon Merkle tree over a patient's diagnosis codes
+///
+/// Built by the patient (or the issuing provider) from the codes in their record.
+/// Leaves are `[lower_sentinel, code hashes in ascending order, upper_sentinel, ...]`,
+/// with the upper sentinel repeated to fill the tree. Duplicate codes share a leaf.
+///
+/// The prefix tree holds the [`prefix_leaf`] of each code in the same order,
+/// padded with zeros.
+#[derive(Debug, Clone)]
+pub struct DiagnosisTree {
+    codes: Vec<String>, // Normalized, in leaf order
+    tree: MerkleTree<Fr>,
+    prefix_tree: MerkleTree<Fr>,
+}
+
+impl DiagnosisTree {
+    pub fn new(codes: &[String]) -> Result<Self, DiagnosisError> {
+        if codes.len() > MAX_DIAGNOSES {
+            return Err(DiagnosisError(format!(
+                "Too many diagnoses: {} (maximum {})",
+                codes.len(),
+                MAX_DIAGNOSES
+            )));
+        }
+
+        let mut hashed = codes
+            .iter()
+            .map(|code| {
+                let normalized =
+                    normalize_icd10_code(code).map_err(|e| DiagnosisError(e.to_string()))?;
+                Ok((hash_diagnosis_code(&normalized)?, normalized))
+            })
+            .collect::<Result<Vec<_>, DiagnosisError>>()?;
+        hashed.sort_by_key(|(hash, _)| sort_key(hash));
+        hashed.dedup_by_key(|(hash, _)| *hash);
+
+        let mut leaves = vec![lower_sentinel()];
+        leaves.extend(hashed.iter().map(|(hash, _)| *hash));
+        leaves.resize(1 << DIAGNOSIS_TREE_DEPTH, upper_sentinel());
+
+        let prefix_leaves = hashed
+            .iter()
+            .map(|(_, code)| prefix_leaf(code))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tree = MerkleTree::new(&leaves, DIAGNOSIS_TREE_DEPTH)
+            .map_err(|e| DiagnosisError(e.to_string()))?;
+        let prefix_tree = MerkleTree::new(&prefix_leaves, DIAGNOSIS_TREE_DEPTH)
+            .map_err(|e| DiagnosisError(e.to_string()))?;
+
+        Ok(Self {
+            codes: hashed.into_iter().map(|(_, code)| code).collect(),
+            tree,
+            prefix_tree,
+        })
+    }
+
+    pub fn root(&self) -> Fr {
+        self.tree.root()
+    }
+
+    /// Normalized codes, in leaf order
+    pub fn codes(&self) -> &[String] {
+        &self.codes
+    }
+
+    pub fn prefix_root(&self) -> Fr {
+        self.prefix_tree.root()
+    }
+
+    pub fn commitment(&self, salt: Fr) -> Fr {
+        commit_diagnosis_root(self.root(), self.prefix_root(), salt)
+    }
+
+    /// Check that `credential` signs the commitment of this tree under `salt`
+    pub(crate) fn check_attested(
+        &self,
+        salt: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<(), DiagnosisError> {
+        if credential.attributes().diagnosis_commitment != self.commitment(salt) {
+            return Err(DiagnosisError(
+                "Diagnosis commitment is not attested by the credential".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Authentication path of `code`
+    pub fn path(&self, code: &str) -> Result<MerklePath<Fr>, DiagnosisError> {
+        let leaf = hash_diagnosis_code(code)?;
+        let index = self
+            .tree
+            .leaves()
+            .iter()
+            .position(|l| *l == leaf)
+            .ok_or(DiagnosisError(format!(
+                "Patient does not have required diagnosis: {}",
+                code
+            )))?;
+
+        self.tree
+            .path(index)
+            .map_err(|e| DiagnosisError(e.to_string()))
+    }
+
+    /// Circuit proving that `required_code` is in this tree, attested by `credential`
+    pub fn circuit(
+        &self,
+        required_code: &str,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<DiagnosisMembershipCircuit<Fr>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let path = self.path(required_code)?;
+        let bits = path.bits();
+
+        Ok(DiagnosisMembershipCircuit {
+            siblings: std::array::from_fn(|i| Value::known(path.siblings[i])),
+            path_bits: std::array::from_fn(|i| Value::known(bits[i])),
+            salt: Value::known(salt),
+            prefix_root: Value::known(self.prefix_root()),
+            credential: credential.witness(),
+            required_hash: hash_diagnosis_code(required_code)?,
+            study_id,
+            context_hash,
+            epoch,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        })
+    }
+
+    /// Input map for [`generate_proof`]
+    pub fn proof_inputs(
+        &self,
+        required_code: &str,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let path = self.path(required_code)?;
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert(
+            "required_hash".to_string(),
+            vec![hash_diagnosis_code(required_code)?],
+        );
+        inputs.insert("study_id".to_string(), vec![study_id]);
+        inputs.insert("context_hash".to_string(), vec![context_hash]);
+        inputs.insert("epoch".to_string(), vec![epoch]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("prefix_root".to_string(), vec![self.prefix_root()]);
+        inputs.insert("merkle_siblings".to_string(), path.siblings);
+        inputs.insert(
+            "merkle_index".to_string(),
+            vec![Fr::from(path.index as u64)],
+        );
+
+        Ok(inputs)
+    }
 }
 
 /// Client-side validation: Check if patient has required diagnosis
 ///
-/// This validates the diagnosis membership outside the circuit.
+/// This validates the diagnosis membership outside the circuit. Codes are
+/// compared in canonical form, so "E11.9" matches "e119".
 pub fn validate_diagnosis_membership(
     patient_diagnoses: &[String],
     required_diagnosis: &str,
 ) -> Result<(), DiagnosisError> {
-    if !patient_diagnoses.contains(&required_diagnosis.to_string()) {
+    let normalize =
+        |code: &str| normalize_icd10_code(code).map_err(|e| DiagnosisError(e.to_string()));
+
+    let required = normalize(required_diagnosis)?;
+    let patient = patient_diagnoses
+        .iter()
+        .map(|code| normalize(code))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !patient.contains(&required) {
         return Err(DiagnosisError(format!(
             "Patient does not have required diagnosis: {}",
             required_diagnosis
@@ -226,29 +604,38 @@ pub fn validate_diagnosis_membership(
 
 /// Generate diagnosis membership proof
 ///
-/// ## MVP Hybrid Approach
-/// 1. Validates diagnosis membership client-side
-/// 2. Generates hash of matching diagnosis
-/// 3. Generates ZK proof of hash knowledge bound to study_id
+/// Expects the input map built by [`DiagnosisTree::proof_inputs`]:
+/// `required_hash`, `study_id`, `context_hash`, `epoch`, `salt`, `prefix_root`,
+/// `merkle_siblings` (DIAGNOSIS_TREE_DEPTH values, bottom-up), `merkle_index` and the
+/// credential inputs, which include `diagnosis_commitment`. The credential (including its
+/// age at `epoch`) and the path are checked before proving.
 pub fn generate_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_proof_with_rng<PC, R>(
     _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
     prover_parameters: &PC::ProverParam,
     inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
 ) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
 where
     PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
     Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
 {
-    let k = 4usize;
+    let k = DIAGNOSIS_K;
 
     // Extract inputs
-    let diagnosis_hash: Fr = inputs
-        .get("diagnosis_hash")
-        .ok_or(DiagnosisError("Missing diagnosis_hash".to_string()))?
-        .get(0)
-        .ok_or(DiagnosisError("Invalid diagnosis_hash".to_string()))?
-        .clone();
-
     let required_hash: Fr = inputs
         .get("required_hash")
         .ok_or(DiagnosisError("Missing required_hash".to_string()))?
@@ -263,23 +650,82 @@ where
         .ok_or(DiagnosisError("Invalid study_id".to_string()))?
         .clone();
 
-    // Client-side validation (MVP hybrid approach)
-    // Verify hashes match
-    let diag_hash_u64 = field_to_u64(&diagnosis_hash)?;
-    let req_hash_u64 = field_to_u64(&required_hash)?;
+    let context_hash: Fr = inputs
+        .get("context_hash")
+        .ok_or(DiagnosisError("Missing context_hash".to_string()))?
+        .get(0)
+        .ok_or(DiagnosisError("Invalid context_hash".to_string()))?
+        .clone();
 
-    if diag_hash_u64 != req_hash_u64 {
+    let epoch: Fr = inputs
+        .get("epoch")
+        .ok_or(DiagnosisError("Missing epoch".to_string()))?
+        .get(0)
+        .ok_or(DiagnosisError("Invalid epoch".to_string()))?
+        .clone();
+
+    let credential = checked_credential(&inputs, &epoch)?;
+    let diagnosis_commitment = credential.attributes().diagnosis_commitment;
+
+    let salt: Fr = inputs
+        .get("salt")
+        .ok_or(DiagnosisError("Missing salt".to_string()))?
+        .get(0)
+        .ok_or(DiagnosisError("Invalid salt".to_string()))?
+        .clone();
+
+    let prefix_root: Fr = inputs
+        .get("prefix_root")
+        .ok_or(DiagnosisError("Missing prefix_root".to_string()))?
+        .get(0)
+        .ok_or(DiagnosisError("Invalid prefix_root".to_string()))?
+        .clone();
+
+    let siblings = inputs
+        .get("merkle_siblings")
+        .ok_or(DiagnosisError("Missing merkle_siblings".to_string()))?
+        .clone();
+    if siblings.len() != DIAGNOSIS_TREE_DEPTH {
         return Err(DiagnosisError(format!(
-            "Diagnosis hash {} does not match required hash {}",
-            diag_hash_u64, req_hash_u64
+            "Invalid merkle_siblings (expected {} values)",
+            DIAGNOSIS_TREE_DEPTH
         )));
     }
 
+    let index = inputs
+        .get("merkle_index")
+        .ok_or(DiagnosisError("Missing merkle_index".to_string()))?
+        .get(0)
+        .ok_or(DiagnosisError("Invalid merkle_index".to_string()))?;
+    let index = field_to_u64(index)? as usize;
+    if index >= 1 << DIAGNOSIS_TREE_DEPTH {
+        return Err(DiagnosisError(format!("Invalid merkle_index: {}", index)));
+    }
+
+    // Client-side validation: the path must open the commitment
+    let path = MerklePath { index, siblings };
+    if commit_diagnosis_root(path.compute_root(required_hash), prefix_root, salt)
+        != diagnosis_commitment
+    {
+        return Err(DiagnosisError(
+            "Required diagnosis is not in the committed diagnosis set".to_string(),
+        ));
+    }
+
     // Create circuit with validated inputs
+    let bits = path.bits();
     let circuit = DiagnosisMembershipCircuit::<Fr> {
-        diagnosis_hash: Value::known(diagnosis_hash),
+        siblings: std::array::from_fn(|i| Value::known(path.siblings[i])),
+        path_bits: std::array::from_fn(|i| Value::known(bits[i])),
+        salt: Value::known(salt),
+        prefix_root: Value::known(prefix_root),
+        credential: credential.witness(),
         required_hash,
         study_id,
+        context_hash,
+        epoch,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
     };
 
     let halo2_circuit = Halo2Circuit::<Fr, DiagnosisMembershipCircuit<Fr>>::new::<PC::ProvingBackend>(k, circuit.clone());
@@ -291,7 +737,7 @@ where
             &prover_parameters,
             &halo2_circuit,
             &mut proof_transcript,
-            std_rng(),
+            rng,
         )
         .map_err(|e| DiagnosisError(format!("Proof generation failed: {:?}", e)))?;
 
@@ -299,12 +745,15 @@ where
     };
 
     let proof = proof_transcript.into_proof();
-    let public_inputs = vec![required_hash, study_id];
+    let public_inputs = circuit.instances().remove(0);
 
     Ok((proof, public_inputs))
 }
 
 /// Verify diagnosis membership proof
+///
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and for checking `epoch` against its own clock.
 pub fn verify_proof<PC>(
     _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
     verifier_parameters: &PC::VerifierParam,
@@ -315,20 +764,44 @@ where
     PC: PlonkishComponents,
     Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
 {
-    if inputs.len() != 2 {
-        return Err(DiagnosisError(
-            "Invalid number of public inputs (expected 2: required_hash, study_id)".to_string(),
-        ));
+    if inputs.len() != DIAGNOSIS_PUBLIC_INPUTS {
+        return Err(DiagnosisError(format!(
+            "Invalid number of public inputs (expected {}: required_hash, study_id, context_hash, epoch, provider_root, revocation_root)",
+            DIAGNOSIS_PUBLIC_INPUTS
+        )));
     }
 
     let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
-    let result = PC::ProvingBackend::verify(&verifier_parameters, &[inputs], &mut transcript, std_rng());
+    let result = PC::ProvingBackend::verify(
+        &verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
 
     result
         .map(|_| true)
         .map_err(|e| DiagnosisError(format!("Verification failed: {:?}", e)))
 }
 
+// Helper function to read an input of exactly `len` values
+fn get_input<'a>(
+    inputs: &'a HashMap<String, Vec<Fr>>,
+    name: &str,
+    len: usize,
+) -> Result<&'a [Fr], DiagnosisError> {
+    let values = inputs
+        .get(name)
+        .ok_or(DiagnosisError(format!("Missing {}", name)))?;
+    if values.len() != len {
+        return Err(DiagnosisError(format!(
+            "Invalid {} (expected {} values)",
+            name, len
+        )));
+    }
+    Ok(values)
+}
+
 // Helper function to convert field element to u64
 fn field_to_u64<F: PrimeField>(field: &F) -> Result<u64, DiagnosisError> {
     let bytes = field.to_repr();
@@ -385,9 +858,87 @@ where
     Ok(is_valid)
 }
 
+/// Registered provider and credential shared by the circuit tests
+#[cfg(test)]
+pub(crate) mod testing {
+    use eligibility_gadgets::{
+        issuer_leaf, AttributeCredential, SigningKey, SparseMerkleTree, PROVIDER_TREE_DEPTH,
+        REVOCATION_TREE_DEPTH,
+    };
+
+    use super::*;
+
+    /// Issuance day of the test credentials (2025-01-01, days since the Unix epoch)
+    pub(crate) const ISSUED_AT: u64 = 20089;
+
+    /// Proof epoch of the circuit tests (2025-06-15)
+    pub(crate) const EPOCH: u64 = 20254;
+
+    /// Credential 1001 over `diagnosis_commitment`, signed by provider 5 on [`ISSUED_AT`],
+    /// with credential 1000 revoked
+    pub(crate) fn issue(diagnosis_commitment: Fr) -> CredentialInputs {
+        let provider = SigningKey::from_seed(&[5u8; 32]).unwrap();
+        let mut providers = SparseMerkleTree::new(PROVIDER_TREE_DEPTH);
+        providers
+            .set(5, issuer_leaf(&provider.public_key()))
+            .unwrap();
+        let mut revocations = SparseMerkleTree::new(REVOCATION_TREE_DEPTH);
+        revocations.set(1000, Fr::ONE).unwrap();
+
+        CredentialInputs {
+            credential: AttributeCredential {
+                credential_id: Fr::from(1001),
+                attributes: CredentialAttributes {
+                    diagnosis_commitment,
+                    issued_at: Fr::from(ISSUED_AT),
+                    ..Default::default()
+                },
+            }
+            .sign(&provider),
+            provider_root: providers.root(),
+            provider_path: providers.path(5).unwrap(),
+            revocation_root: revocations.root(),
+            revocation_path: revocations.path(1001).unwrap(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::testing::{issue, EPOCH, ISSUED_AT};
     use super::*;
+    use eligibility_gadgets::MAX_CREDENTIAL_AGE;
+    use halo2_proofs::dev::MockProver;
+
+    fn patient_codes() -> Vec<String> {
+        vec![
+            "E11.9".to_string(), // Type 2 Diabetes
+            "I10".to_string(),   // Hypertension
+            "J45".to_string(),   // Asthma
+        ]
+    }
+
+    /// Circuit for `code` with salt 1234, study 1, context 7 and epoch [`EPOCH`],
+    /// attested by a test credential
+    fn circuit(tree: &DiagnosisTree, code: &str) -> DiagnosisMembershipCircuit<Fr> {
+        let salt = Fr::from(1234);
+        tree.circuit(
+            code,
+            salt,
+            Fr::from(1),
+            Fr::from(7),
+            Fr::from(EPOCH),
+            &issue(tree.commitment(salt)),
+        )
+        .unwrap()
+    }
+
+    fn mock_verify(circuit: &DiagnosisMembershipCircuit<Fr>, instances: Vec<Vec<Fr>>) -> bool {
+        MockProver::run(DIAGNOSIS_K as u32, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
 
     #[test]
     fn test_hash_diagnosis_code() {
@@ -397,6 +948,12 @@ mod tests {
 
         assert_eq!(hash1, hash2); // Same code = same hash
         assert_ne!(hash1, hash3); // Different code = different hash
+
+        // Byte permutations no longer collide
+        assert_ne!(hash1, hash_diagnosis_code("E19.1").unwrap());
+        // Canonical form
+        assert_eq!(hash1, hash_diagnosis_code("e119").unwrap());
+        assert!(hash_diagnosis_code("not a code").is_err());
     }
 
     #[test]
@@ -408,6 +965,7 @@ mod tests {
 
         assert!(validate_diagnosis_membership(&patient_diagnoses, "E11.9").is_ok());
         assert!(validate_diagnosis_membership(&patient_diagnoses, "I10").is_ok());
+        assert!(validate_diagnosis_membership(&patient_diagnoses, "e119").is_ok());
     }
 
     #[test]
@@ -419,4 +977,160 @@ mod tests {
         assert!(validate_diagnosis_membership(&patient_diagnoses, "I10").is_err());
         assert!(validate_diagnosis_membership(&patient_diagnoses, "J45").is_err());
     }
+
+    #[test]
+    fn test_diagnosis_tree() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+        let salt = Fr::from(1234);
+
+        // Every path opens the commitment
+        for code in patient_codes() {
+            let path = tree.path(&code).unwrap();
+            let leaf = hash_diagnosis_code(&code).unwrap();
+            assert_eq!(
+                commit_diagnosis_root(path.compute_root(leaf), tree.prefix_root(), salt),
+                tree.commitment(salt)
+            );
+        }
+
+        // The salt hides the root
+        assert_ne!(tree.commitment(salt), tree.commitment(Fr::from(4321)));
+
+        assert!(tree.path("C50").is_err());
+
+        // Sorted between the sentinels, regardless of input order and duplicates
+        let mut shuffled = patient_codes();
+        shuffled.reverse();
+        shuffled.push("e11.9".to_string());
+        let reordered = DiagnosisTree::new(&shuffled).unwrap();
+        assert_eq!(reordered.root(), tree.root());
+        assert_eq!(reordered.prefix_root(), tree.prefix_root());
+
+        let leaves = tree.tree.leaves();
+        assert_eq!(leaves[0], lower_sentinel());
+        assert_eq!(leaves[patient_codes().len() + 1], upper_sentinel());
+        assert!(leaves
+            .windows(2)
+            .all(|pair| sort_key(&pair[0]) <= sort_key(&pair[1])));
+        assert!(DiagnosisTree::new(&vec!["I10".to_string(); MAX_DIAGNOSES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_members() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+
+        for code in patient_codes() {
+            let circuit = circuit(&tree, &code);
+            assert!(
+                mock_verify(&circuit, circuit.instances()),
+                "{} must verify",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_non_members() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+
+        // Reuse the path of "I10" for a diagnosis the patient does not have
+        let mut circuit = circuit(&tree, "I10");
+        circuit.required_hash = hash_diagnosis_code("C50").unwrap();
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_wrong_salt() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+
+        let mut circuit = circuit(&tree, "E11.9");
+        circuit.salt = Value::known(Fr::from(4321));
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_wrong_prefix_root() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+
+        let mut circuit = circuit(&tree, "E11.9");
+        circuit.prefix_root = Value::known(tree.prefix_root() + Fr::ONE);
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+        let circuit = circuit(&tree, "E11.9");
+        let names = [
+            "required_hash",
+            "study_id",
+            "context_hash",
+            "epoch",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            assert!(
+                !mock_verify(&circuit, instances),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_commitment() {
+        // The patient's own tree, with a credential over another commitment
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+        let salt = Fr::from(1234);
+        let other = issue(tree.commitment(Fr::from(4321)));
+        let epoch = Fr::from(EPOCH);
+        assert!(tree
+            .circuit("E11.9", salt, Fr::from(1), Fr::from(7), epoch, &other)
+            .is_err());
+        assert!(tree
+            .proof_inputs("E11.9", salt, Fr::from(1), Fr::from(7), epoch, &other)
+            .is_err());
+
+        let mut circuit = circuit(&tree, "E11.9");
+        circuit.credential = other.witness();
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_expired_credential() {
+        let tree = DiagnosisTree::new(&patient_codes()).unwrap();
+
+        // The last day of the credential's validity still verifies
+        let mut circuit = circuit(&tree, "E11.9");
+        circuit.epoch = Fr::from(ISSUED_AT + MAX_CREDENTIAL_AGE);
+        assert!(mock_verify(&circuit, circuit.instances()));
+
+        // One day later, or before issuance, it does not
+        for epoch in [ISSUED_AT + MAX_CREDENTIAL_AGE + 1, ISSUED_AT - 1] {
+            circuit.epoch = Fr::from(epoch);
+            assert!(
+                !mock_verify(&circuit, circuit.instances()),
+                "epoch {} must not verify",
+                epoch
+            );
+        }
+
+        // The input map is checked the same way before proving
+        let salt = Fr::from(1234);
+        let credential = issue(tree.commitment(salt));
+        let late = Fr::from(ISSUED_AT + MAX_CREDENTIAL_AGE + 1);
+        let inputs = tree
+            .proof_inputs("E11.9", salt, Fr::from(1), Fr::from(7), late, &credential)
+            .unwrap();
+        assert!(checked_credential(&inputs, &late).is_err());
+        assert!(checked_credential(&inputs, &Fr::from(EPOCH)).is_ok());
+    }
 }
@@ -0,0 +1,785 @@
+//! Diagnosis Prefix Circuit - Category-Level Membership
+//!
+//! Proves that a patient has some diagnosis under a public ICD-10 prefix (e.g. any
+//! code under E11, type 2 diabetes of any subtype) against the same
+//! `diagnosis_commitment` used by [`DiagnosisMembershipCircuit`](crate::DiagnosisMembershipCircuit).
+//!
+//! ## Security Model
+//! - Private Inputs: prefix hashes and hash of the matching code, its prefix tree path,
+//!   diagnosis tree root, commitment salt, credential
+//! - Public Inputs: prefix_hash, study_id, context_hash, epoch, provider_root,
+//!   revocation_root
+//! - Constraint: some code in patient_diagnoses starts with the public prefix, on a
+//!   diagnosis_commitment signed by a registered provider at most MAX_CREDENTIAL_AGE
+//!   days before epoch (see the crate documentation)
+//!
+//! ## Prefix Leaves
+//! Each code is committed to as `Poseidon(chapter, category, subcategory, code_hash)`:
+//! the hashes of its prefixes at every `PrefixDepth`, then its own hash. The circuit
+//! opens one leaf of the prefix tree and checks that `prefix_hash` is one of its three
+//! prefix hashes. Prefix hashes include their length, so the depth is chosen by the
+//! public prefix itself: "E" matches on the chapter, "E11" on the category and "E11.9"
+//! on the subcategory.
+
+use std::{collections::HashMap, io::Cursor};
+
+use eligibility_gadgets::{
+    hash_icd10_prefix, icd10::PREFIX_DEPTHS, icd10_prefix_hashes, normalize_icd10_code,
+    normalize_icd10_prefix, CredentialConfig, CredentialInputs, CredentialWitness, EddsaField,
+    HashVersion, MerkleChip, MerkleConfig, MerklePath, PoseidonChip, PoseidonField,
+};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance, Selector},
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    checked_credential, commit_diagnosis_root, configure_credential, field_to_u64, get_input,
+    hash_diagnosis_code, rng::default_rng, verify_credential, DiagnosisError, DiagnosisTree,
+    PlonkishComponents, DIAGNOSIS_TREE_DEPTH,
+};
+
+/// Circuit size: the credential check, a four-input leaf hash and DIAGNOSIS_TREE_DEPTH + 1
+/// Poseidon hashes
+pub const PREFIX_K: usize = 13;
+
+/// Number of public inputs of `DiagnosisPrefixCircuit`
+pub const PREFIX_PUBLIC_INPUTS: usize = 6;
+
+/// Diagnosis Prefix Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct DiagnosisPrefixConfig<F: PoseidonField> {
+    pub prefix_hash: Column<Advice>, // Public: required prefix hash
+    pub prefixes: [Column<Advice>; PREFIX_DEPTHS], // Private: prefix hashes of the code
+    pub code_hash: Column<Advice>,   // Private: hash of the code
+    pub study_id: Column<Advice>,    // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,       // Public: proof day (days since epoch)
+    pub salt: Column<Advice>,        // Private: commitment salt
+    pub root: Column<Advice>,        // Private: root of the diagnosis tree
+    pub q_match: Selector,
+    pub instance: Column<Instance>,
+    pub merkle: MerkleConfig,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Diagnosis Prefix Circuit
+///
+/// Proves: some diagnosis in patient_diagnoses starts with the required prefix
+///
+/// ## Public Inputs (instance column)
+/// Row 0: prefix_hash, row 1: study_id, row 2: context_hash, row 3: epoch,
+/// row 4: provider_root, row 5: revocation_root. All are copy-constrained to their advice cells; the
+/// commitment stays private.
+#[derive(Clone)]
+pub struct DiagnosisPrefixCircuit<F: EddsaField> {
+    pub prefixes: [Value<F>; PREFIX_DEPTHS], // Private: prefix hashes of the code
+    pub code_hash: Value<F>,                 // Private: hash of the code
+    pub siblings: [Value<F>; DIAGNOSIS_TREE_DEPTH], // Private: prefix tree path
+    pub path_bits: [Value<bool>; DIAGNOSIS_TREE_DEPTH], // Private: leaf index bits
+    pub root: Value<F>,                      // Private: root of the diagnosis tree
+    pub salt: Value<F>,                      // Private: commitment salt
+    pub credential: CredentialWitness<F>,    // Private: signs diagnosis_commitment
+    pub prefix_hash: F,                      // Public: required prefix hash
+    pub study_id: F,                         // Public: binds proof to study
+    pub context_hash: F,                     // Public: binds proof to wallet, chain and registry
+    pub epoch: F,                            // Public: proof day (days since epoch)
+    pub provider_root: F,                    // Public: registered provider tree root
+    pub revocation_root: F,                  // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for DiagnosisPrefixCircuit<F> {
+    fn default() -> Self {
+        Self {
+            prefixes: [Value::unknown(); PREFIX_DEPTHS],
+            code_hash: Value::unknown(),
+            siblings: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            path_bits: [Value::unknown(); DIAGNOSIS_TREE_DEPTH],
+            root: Value::unknown(),
+            salt: Value::unknown(),
+            credential: CredentialWitness::default(),
+            prefix_hash: F::ZERO,
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> Circuit<F> for DiagnosisPrefixCircuit<F> {
+    type Config = DiagnosisPrefixConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let prefix_hash = meta.advice_column();
+        let prefixes: [Column<Advice>; PREFIX_DEPTHS] =
+            std::array::from_fn(|_| meta.advice_column());
+        let code_hash = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let salt = meta.advice_column();
+        let root = meta.advice_column();
+        let q_match = meta.selector();
+        let instance = meta.instance_column();
+
+        for column in [
+            prefix_hash,
+            code_hash,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            root,
+        ]
+        .into_iter()
+        .chain(prefixes)
+        {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        // Gate: prefix_hash is one of the code's prefix hashes
+        meta.create_gate("prefix match", |meta| {
+            let q = meta.query_selector(q_match);
+            let prefix_hash = meta.query_advice(prefix_hash, Rotation::cur());
+
+            let mismatch = prefixes.iter().fold(q, |acc, column| {
+                acc * (prefix_hash.clone() - meta.query_advice(*column, Rotation::cur()))
+            });
+            vec![mismatch]
+        });
+
+        let (merkle, credential) = configure_credential(meta);
+
+        DiagnosisPrefixConfig {
+            prefix_hash,
+            prefixes,
+            code_hash,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            root,
+            q_match,
+            instance,
+            merkle,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (prefix_hash, leaf_inputs, study_id, context_hash, epoch, salt, root) = layouter
+            .assign_region(
+                || "diagnosis prefix inputs",
+                |mut region| {
+                    config.q_match.enable(&mut region, 0)?;
+
+                    // Assign public prefix hash
+                    let prefix_hash = region.assign_advice(
+                        || "prefix_hash",
+                        config.prefix_hash,
+                        0,
+                        || Value::known(self.prefix_hash),
+                    )?;
+
+                    // Assign the private leaf preimage
+                    let mut leaf_inputs = self
+                        .prefixes
+                        .iter()
+                        .zip(config.prefixes.iter())
+                        .enumerate()
+                        .map(|(i, (prefix, column))| {
+                            region.assign_advice(|| format!("prefix {}", i), *column, 0, || *prefix)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    leaf_inputs.push(region.assign_advice(
+                        || "code_hash",
+                        config.code_hash,
+                        0,
+                        || self.code_hash,
+                    )?);
+
+                    // Assign public study_id
+                    let study_id = region.assign_advice(
+                        || "study_id",
+                        config.study_id,
+                        0,
+                        || Value::known(self.study_id),
+                    )?;
+
+                    // Assign public context hash
+                    let context_hash = region.assign_advice(
+                        || "context_hash",
+                        config.context_hash,
+                        0,
+                        || Value::known(self.context_hash),
+                    )?;
+
+                    // Assign public epoch
+                    let epoch = region.assign_advice(
+                        || "epoch",
+                        config.epoch,
+                        0,
+                        || Value::known(self.epoch),
+                    )?;
+
+                    // Assign private salt and diagnosis root
+                    let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+                    let root = region.assign_advice(|| "root", config.root, 0, || self.root)?;
+
+                    Ok((
+                        prefix_hash,
+                        leaf_inputs,
+                        study_id,
+                        context_hash,
+                        epoch,
+                        salt,
+                        root,
+                    ))
+                },
+            )?;
+
+        // The leaf commits to the code and all of its prefixes...
+        let poseidon_chip = PoseidonChip::<F>::construct(config.merkle.poseidon.clone());
+        let leaf = poseidon_chip.hash(layouter.namespace(|| "prefix leaf"), &leaf_inputs)?;
+
+        // ...and is under the committed prefix root
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let prefix_root = merkle_chip.compute_root(
+            layouter.namespace(|| "prefix root"),
+            &leaf,
+            &self.siblings,
+            &self.path_bits,
+        )?;
+
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "diagnosis commitment"),
+            &[root, prefix_root, salt],
+        )?;
+        let (provider_root, revocation_root) = verify_credential(
+            layouter.namespace(|| "credential"),
+            &config.credential,
+            &commitment,
+            &epoch,
+            &self.credential,
+        )?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(prefix_hash.cell(), config.instance, 0)?;
+        layouter.constrain_instance(study_id.cell(), config.instance, 1)?;
+        layouter.constrain_instance(context_hash.cell(), config.instance, 2)?;
+        layouter.constrain_instance(epoch.cell(), config.instance, 3)?;
+        layouter.constrain_instance(provider_root.cell(), config.instance, 4)?;
+        layouter.constrain_instance(revocation_root.cell(), config.instance, 5)?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for DiagnosisPrefixCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: prefix_hash, study_id, context_hash, epoch, provider_root,
+        // revocation_root
+        vec![vec![
+            self.prefix_hash,
+            self.study_id,
+            self.context_hash,
+            self.epoch,
+            self.provider_root,
+            self.revocation_root,
+        ]]
+    }
+}
+
+/// Hash an ICD-10 prefix (e.g. "E11.*") to field element
+///
+/// Canonical Poseidon encoding of the prefix (see `eligibility_gadgets::icd10`),
+/// using the current hash version.
+pub fn hash_diagnosis_prefix(prefix: &str) -> Result<Fr, DiagnosisError> {
+    hash_icd10_prefix(prefix, HashVersion::CURRENT).map_err(|e| DiagnosisError(e.to_string()))
+}
+
+/// Prefix hashes of a code, from the chapter down (see `eligibility_gadgets::PrefixDepth`)
+pub fn diagnosis_prefix_hashes(code: &str) -> Result<[Fr; PREFIX_DEPTHS], DiagnosisError> {
+    icd10_prefix_hashes(code, HashVersion::CURRENT).map_err(|e| DiagnosisError(e.to_string()))
+}
+
+/// Leaf of the prefix tree: `Poseidon(chapter, category, subcategory, code_hash)`
+pub fn prefix_leaf(code: &str) -> Result<Fr, DiagnosisError> {
+    let mut inputs = diagnosis_prefix_hashes(code)?.to_vec();
+    inputs.push(hash_diagnosis_code(code)?);
+    Ok(Fr::poseidon_hash(&inputs))
+}
+
+/// Client-side validation: Check if patient has a diagnosis under `prefix`
+///
+/// `prefix` is a chapter ("E"), category ("E11" or "E11.*") or subcategory ("E11.9").
+pub fn validate_diagnosis_prefix(
+    patient_diagnoses: &[String],
+    prefix: &str,
+) -> Result<(), DiagnosisError> {
+    let (prefix, _) = normalize_icd10_prefix(prefix).map_err(|e| DiagnosisError(e.to_string()))?;
+
+    for code in patient_diagnoses {
+        let code = normalize_icd10_code(code).map_err(|e| DiagnosisError(e.to_string()))?;
+        if code.starts_with(&prefix) {
+            return Ok(());
+        }
+    }
+
+    Err(DiagnosisError(format!(
+        "Patient has no diagnosis under prefix: {}",
+        prefix
+    )))
+}
+
+impl DiagnosisTree {
+    /// Index of the first code under `prefix`, in leaf order
+    pub fn prefix_index(&self, prefix: &str) -> Result<usize, DiagnosisError> {
+        let prefix_hash = hash_diagnosis_prefix(prefix)?;
+
+        for (index, code) in self.codes.iter().enumerate() {
+            if diagnosis_prefix_hashes(code)?.contains(&prefix_hash) {
+                return Ok(index);
+            }
+        }
+
+        Err(DiagnosisError(format!(
+            "Patient has no diagnosis under prefix: {}",
+            prefix
+        )))
+    }
+
+    /// Circuit proving that some code in this tree is under `prefix`, attested by `credential`
+    pub fn prefix_circuit(
+        &self,
+        prefix: &str,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<DiagnosisPrefixCircuit<Fr>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let index = self.prefix_index(prefix)?;
+        let code = &self.codes[index];
+        let path = self
+            .prefix_tree
+            .path(index)
+            .map_err(|e| DiagnosisError(e.to_string()))?;
+        let bits = path.bits();
+        let prefixes = diagnosis_prefix_hashes(code)?;
+
+        Ok(DiagnosisPrefixCircuit {
+            prefixes: prefixes.map(Value::known),
+            code_hash: Value::known(hash_diagnosis_code(code)?),
+            siblings: std::array::from_fn(|i| Value::known(path.siblings[i])),
+            path_bits: std::array::from_fn(|i| Value::known(bits[i])),
+            root: Value::known(self.root()),
+            salt: Value::known(salt),
+            credential: credential.witness(),
+            prefix_hash: hash_diagnosis_prefix(prefix)?,
+            study_id,
+            context_hash,
+            epoch,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        })
+    }
+
+    /// Input map for [`generate_prefix_proof`]
+    pub fn prefix_inputs(
+        &self,
+        prefix: &str,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let index = self.prefix_index(prefix)?;
+        let code = &self.codes[index];
+        let path = self
+            .prefix_tree
+            .path(index)
+            .map_err(|e| DiagnosisError(e.to_string()))?;
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert(
+            "prefix_hash".to_string(),
+            vec![hash_diagnosis_prefix(prefix)?],
+        );
+        inputs.insert("study_id".to_string(), vec![study_id]);
+        inputs.insert("context_hash".to_string(), vec![context_hash]);
+        inputs.insert("epoch".to_string(), vec![epoch]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("diagnosis_root".to_string(), vec![self.root()]);
+        inputs.insert(
+            "code_prefixes".to_string(),
+            diagnosis_prefix_hashes(code)?.to_vec(),
+        );
+        inputs.insert("code_hash".to_string(), vec![hash_diagnosis_code(code)?]);
+        inputs.insert("merkle_siblings".to_string(), path.siblings);
+        inputs.insert(
+            "merkle_index".to_string(),
+            vec![Fr::from(path.index as u64)],
+        );
+
+        Ok(inputs)
+    }
+}
+
+/// Generate diagnosis prefix proof
+///
+/// Expects the input map built by [`DiagnosisTree::prefix_inputs`]:
+/// `prefix_hash`, `study_id`, `context_hash`, `epoch`, `salt`, `diagnosis_root`, `code_prefixes`
+/// (PREFIX_DEPTHS values), `code_hash`, `merkle_siblings` (DIAGNOSIS_TREE_DEPTH values,
+/// bottom-up), `merkle_index` and the credential inputs, which include
+/// `diagnosis_commitment`. The credential (including its age at `epoch`) and the leaf are
+/// checked before proving.
+pub fn generate_prefix_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_prefix_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_prefix_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_prefix_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = PREFIX_K;
+
+    // Extract inputs
+    let prefix_hash = get_input(&inputs, "prefix_hash", 1)?[0];
+    let study_id = get_input(&inputs, "study_id", 1)?[0];
+    let context_hash = get_input(&inputs, "context_hash", 1)?[0];
+    let epoch = get_input(&inputs, "epoch", 1)?[0];
+    let credential = checked_credential(&inputs, &epoch)?;
+    let diagnosis_commitment = credential.attributes().diagnosis_commitment;
+    let salt = get_input(&inputs, "salt", 1)?[0];
+    let root = get_input(&inputs, "diagnosis_root", 1)?[0];
+    let prefixes = get_input(&inputs, "code_prefixes", PREFIX_DEPTHS)?;
+    let code_hash = get_input(&inputs, "code_hash", 1)?[0];
+    let siblings = get_input(&inputs, "merkle_siblings", DIAGNOSIS_TREE_DEPTH)?.to_vec();
+
+    let index = field_to_u64(&get_input(&inputs, "merkle_index", 1)?[0])? as usize;
+    if index >= 1 << DIAGNOSIS_TREE_DEPTH {
+        return Err(DiagnosisError(format!("Invalid merkle_index: {}", index)));
+    }
+
+    // Client-side validation: the leaf matches the prefix and opens the commitment
+    let path = MerklePath { index, siblings };
+    let mut leaf_inputs = prefixes.to_vec();
+    leaf_inputs.push(code_hash);
+    let prefix_root = path.compute_root(Fr::poseidon_hash(&leaf_inputs));
+    if !prefixes.contains(&prefix_hash)
+        || commit_diagnosis_root(root, prefix_root, salt) != diagnosis_commitment
+    {
+        return Err(DiagnosisError(
+            "No diagnosis under the required prefix in the committed diagnosis set".to_string(),
+        ));
+    }
+
+    // Create circuit with validated inputs
+    let bits = path.bits();
+    let circuit = DiagnosisPrefixCircuit::<Fr> {
+        prefixes: std::array::from_fn(|i| Value::known(prefixes[i])),
+        code_hash: Value::known(code_hash),
+        siblings: std::array::from_fn(|i| Value::known(path.siblings[i])),
+        path_bits: std::array::from_fn(|i| Value::known(bits[i])),
+        root: Value::known(root),
+        salt: Value::known(salt),
+        credential: credential.witness(),
+        prefix_hash,
+        study_id,
+        context_hash,
+        epoch,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit = Halo2Circuit::<Fr, DiagnosisPrefixCircuit<Fr>>::new::<PC::ProvingBackend>(
+        k,
+        circuit.clone(),
+    );
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            &prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| DiagnosisError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify diagnosis prefix proof
+///
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and for checking `epoch` against its own clock.
+pub fn verify_prefix_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != PREFIX_PUBLIC_INPUTS {
+        return Err(DiagnosisError(format!(
+            "Invalid number of public inputs (expected {}: prefix_hash, study_id, context_hash, epoch, provider_root, revocation_root)",
+            PREFIX_PUBLIC_INPUTS
+        )));
+    }
+
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        &verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| DiagnosisError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{issue, EPOCH},
+        DIAGNOSIS_K,
+    };
+    use eligibility_gadgets::PrefixDepth;
+    use halo2_proofs::{dev::MockProver, halo2curves::ff::Field};
+
+    fn patient_tree() -> DiagnosisTree {
+        DiagnosisTree::new(&[
+            "E11.65".to_string(), // Type 2 Diabetes with hyperglycemia
+            "I10".to_string(),    // Hypertension
+        ])
+        .unwrap()
+    }
+
+    /// Circuit for `prefix` with salt 1234, study 1, context 7 and epoch [`EPOCH`],
+    /// attested by a test credential
+    fn circuit(
+        tree: &DiagnosisTree,
+        prefix: &str,
+    ) -> Result<DiagnosisPrefixCircuit<Fr>, DiagnosisError> {
+        let salt = Fr::from(1234);
+        tree.prefix_circuit(
+            prefix,
+            salt,
+            Fr::from(1),
+            Fr::from(7),
+            Fr::from(EPOCH),
+            &issue(tree.commitment(salt)),
+        )
+    }
+
+    fn mock_verify(circuit: &DiagnosisPrefixCircuit<Fr>, instances: Vec<Vec<Fr>>) -> bool {
+        MockProver::run(PREFIX_K as u32, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_validate_diagnosis_prefix() {
+        let patient_diagnoses = vec!["E11.65".to_string(), "I10".to_string()];
+
+        for prefix in ["E", "E11", "E11.*", "e11.6", "I10"] {
+            assert!(
+                validate_diagnosis_prefix(&patient_diagnoses, prefix).is_ok(),
+                "{}",
+                prefix
+            );
+        }
+        for prefix in ["C", "E10", "E11.9", "I10.0"] {
+            assert!(
+                validate_diagnosis_prefix(&patient_diagnoses, prefix).is_err(),
+                "{}",
+                prefix
+            );
+        }
+        assert!(validate_diagnosis_prefix(&patient_diagnoses, "E1").is_err());
+    }
+
+    #[test]
+    fn test_prefix_depths() {
+        let depths = ["E", "E11", "E11.6"].map(|p| normalize_icd10_prefix(p).unwrap().1);
+        assert_eq!(depths, PrefixDepth::ALL);
+    }
+
+    #[test]
+    fn test_circuit_accepts_prefixes_at_every_depth() {
+        let tree = patient_tree();
+
+        for prefix in ["E", "E11.*", "E11.6", "I", "I10"] {
+            let circuit = circuit(&tree, prefix).unwrap();
+            assert!(
+                mock_verify(&circuit, circuit.instances()),
+                "{} must verify",
+                prefix
+            );
+        }
+    }
+
+    #[test]
+    fn test_tree_rejects_missing_prefixes() {
+        let tree = patient_tree();
+
+        for prefix in ["C", "E10", "E11.9", "I10.0"] {
+            assert!(circuit(&tree, prefix).is_err(), "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_other_prefix() {
+        let tree = patient_tree();
+
+        // Reuse the leaf of "E11.65" for a category the patient does not have
+        let mut circuit = circuit(&tree, "E11").unwrap();
+        circuit.prefix_hash = hash_diagnosis_prefix("E10").unwrap();
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_forged_leaf() {
+        let tree = patient_tree();
+
+        // Claim a prefix the leaf does not commit to
+        let mut circuit = circuit(&tree, "E11").unwrap();
+        circuit.prefixes[1] = Value::known(hash_diagnosis_prefix("C50").unwrap());
+        circuit.prefix_hash = hash_diagnosis_prefix("C50").unwrap();
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = circuit(&patient_tree(), "E11").unwrap();
+        let names = [
+            "prefix_hash",
+            "study_id",
+            "context_hash",
+            "epoch",
+            "provider_root",
+            "revocation_root",
+        ];
+
+        for (row, name) in names.iter().enumerate() {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            assert!(
+                !mock_verify(&circuit, instances),
+                "tampered {} must not verify",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefix_and_membership_share_the_commitment() {
+        let tree = patient_tree();
+        let salt = Fr::from(1234);
+
+        let credential = issue(tree.commitment(salt));
+        let epoch = Fr::from(EPOCH);
+
+        let membership = tree
+            .circuit("I10", salt, Fr::from(1), Fr::from(7), epoch, &credential)
+            .unwrap();
+        let prefix = tree
+            .prefix_circuit("E11", salt, Fr::from(1), Fr::from(7), epoch, &credential)
+            .unwrap();
+
+        // One credential over one commitment attests both proofs
+        assert!(
+            MockProver::run(DIAGNOSIS_K as u32, &membership, membership.instances())
+                .unwrap()
+                .verify()
+                .is_ok()
+        );
+        assert!(mock_verify(&prefix, prefix.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_commitment() {
+        // A tree with a cancer diagnosis the provider did not record
+        let tree = DiagnosisTree::new(&["C50.9".to_string()]).unwrap();
+        let salt = Fr::from(1234);
+        let signed = issue(patient_tree().commitment(salt));
+        assert!(tree
+            .prefix_circuit(
+                "C50",
+                salt,
+                Fr::from(1),
+                Fr::from(7),
+                Fr::from(EPOCH),
+                &signed
+            )
+            .is_err());
+
+        let mut circuit = circuit(&tree, "C50").unwrap();
+        circuit.credential = signed.witness();
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+}
@@ -11,28 +11,65 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 pub struct InputsSerialisationWrapper(pub Vec<Fr>);
 pub use InputsSerialisationWrapper as InputsSerializationWrapper;
 
+/// Parse circuit inputs
+///
+/// Values are decimal integers up to `u128`, or `0x`-prefixed big-endian hex for
+/// full-range field elements (hashes, Merkle siblings, salts).
 pub fn deserialize_circuit_inputs(
     ser_inputs: HashMap<String, Vec<String>>,
 ) -> Result<HashMap<String, Vec<Fr>>, DiagnosisError> {
     ser_inputs
         .iter()
         .map(|(k, v)| {
-            let fp_vec: Result<Vec<Fr>, DiagnosisError> = v
-                .iter()
-                .map(|s| {
-                    // TODO - support big integers full range, not just u128
-                    let int = u128::from_str(s).map_err(|e| {
-                        DiagnosisError(format!("Failed to parse input as u128: {}", e))
-                    });
-
-                    int.map(|i| Fr::from_u128(i))
-                })
-                .collect();
+            let fp_vec: Result<Vec<Fr>, DiagnosisError> =
+                v.iter().map(|s| parse_field_element(s)).collect();
             fp_vec.map(|v| (k.clone(), v))
         })
         .collect()
 }
 
+/// Inverse of [`deserialize_circuit_inputs`], encoding every value as hex
+pub fn serialize_circuit_inputs(inputs: &HashMap<String, Vec<Fr>>) -> HashMap<String, Vec<String>> {
+    inputs
+        .iter()
+        .map(|(k, v)| (k.clone(), v.iter().map(field_to_hex).collect()))
+        .collect()
+}
+
+fn parse_field_element(s: &str) -> Result<Fr, DiagnosisError> {
+    let Some(hex) = s.strip_prefix("0x") else {
+        let int = u128::from_str(s)
+            .map_err(|e| DiagnosisError(format!("Failed to parse input as u128: {}", e)))?;
+        return Ok(Fr::from_u128(int));
+    };
+
+    if hex.is_empty() || hex.len() > 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(DiagnosisError(format!("Invalid hex field element: {}", s)));
+    }
+
+    let padded = format!("{:0>64}", hex);
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&padded[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes.reverse(); // Little-endian representation
+
+    Option::from(Fr::from_bytes(&bytes)).ok_or(DiagnosisError(format!(
+        "Hex value exceeds the field modulus: {}",
+        s
+    )))
+}
+
+fn field_to_hex(fp: &Fr) -> String {
+    let hex: String = fp
+        .to_bytes()
+        .iter()
+        .rev()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("0x{}", hex)
+}
+
 impl Serialize for InputsSerialisationWrapper {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -109,4 +146,23 @@ mod tests {
         assert_eq!(deserialized.get("out").unwrap()[0], Fr::from(1));
         assert_eq!(deserialized.get("out").unwrap()[1], Fr::from(2));
     }
+
+    #[test]
+    fn test_circuit_inputs_full_range() {
+        let mut inputs = HashMap::new();
+        inputs.insert("out".to_string(), vec![-Fr::from(1), Fr::from(7)]);
+
+        let serialized = serialize_circuit_inputs(&inputs);
+        assert_eq!(serialized.get("out").unwrap()[1], format!("0x{:0>64}", "7"));
+        assert_eq!(deserialize_circuit_inputs(serialized).unwrap(), inputs);
+
+        // Non-canonical and malformed values are rejected
+        let mut invalid = HashMap::new();
+        invalid.insert("out".to_string(), vec![format!("0x{}", "f".repeat(64))]);
+        assert!(deserialize_circuit_inputs(invalid).is_err());
+
+        let mut invalid = HashMap::new();
+        invalid.insert("out".to_string(), vec!["0xzz".to_string()]);
+        assert!(deserialize_circuit_inputs(invalid).is_err());
+    }
 }
@@ -0,0 +1,1170 @@
+//! Diagnosis Threshold Circuit - k-of-n Membership
+//!
+//! Proves that a patient has at least `threshold` of a public list of required
+//! diagnoses (e.g. "at least 2 of: hypertension, dyslipidemia, obesity") against the
+//! same `diagnosis_commitment` used by [`DiagnosisMembershipCircuit`](crate::DiagnosisMembershipCircuit),
+//! without revealing which ones.
+//!
+//! ## Security Model
+//! - Private Inputs: every leaf of the diagnosis tree, prefix root, commitment salt,
+//!   credential
+//! - Public Inputs: required_hashes (MAX_REQUIRED_DIAGNOSES), threshold, study_id,
+//!   context_hash, epoch, provider_root, revocation_root
+//! - Constraint: |required_diagnoses ∩ patient_diagnoses| >= threshold, on a
+//!   diagnosis_commitment signed by a registered provider at most MAX_CREDENTIAL_AGE
+//!   days before epoch (see the crate documentation)
+//!
+//! ## Counting Matches
+//! The circuit recomputes the diagnosis root from all of its leaves, so the leaves
+//! are exactly the committed ones. For each required hash `r` it accumulates
+//! `prod (r - leaf)` over the MAX_DIAGNOSES code slots (the leaves between the lower
+//! sentinel and the padding); `r` matches when the product is zero. The matches are
+//! summed and `count - threshold` is range checked to COUNT_BITS bits.
+//!
+//! Shorter required lists are padded with the lower sentinel, which never occupies a
+//! code slot. The circuit constrains every other required hash to differ from the
+//! upper sentinel, which fills the unused code slots, and from every earlier required
+//! hash, so no diagnosis is counted more than once. Verifiers additionally reject
+//! lists whose padding is not trailing (see [`check_required_hashes`]).
+
+use std::{collections::HashMap, io::Cursor};
+
+use eligibility_gadgets::{
+    icd10::CODE_HASH_BITS, normalize_icd10_code, CredentialConfig, CredentialInputs,
+    CredentialWitness, EddsaField, MerkleChip, MerkleConfig, MerkleTree, PoseidonChip,
+    PoseidonField, RangeCheckChip, RangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    halo2curves::ff::Field,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use plonkish_backend::{
+    backend::PlonkishBackend,
+    frontend::halo2::{CircuitExt, Halo2Circuit},
+    halo2_curves::bn256::Fr,
+    pcs::{CommitmentChunk, PolynomialCommitmentScheme},
+    util::transcript::{InMemoryTranscript, Keccak256Transcript, TranscriptRead, TranscriptWrite},
+};
+use rand::{CryptoRng, RngCore};
+
+use crate::{
+    checked_credential, commit_diagnosis_root, configure_credential, field_to_u64, get_input,
+    hash_diagnosis_code, lower_sentinel, rng::default_rng, upper_sentinel, verify_credential,
+    DiagnosisError, DiagnosisTree, PlonkishComponents, DIAGNOSIS_TREE_DEPTH, MAX_DIAGNOSES,
+};
+
+/// Maximum number of required diagnoses per proof
+pub const MAX_REQUIRED_DIAGNOSES: usize = MAX_DIAGNOSES;
+
+/// Number of bits used to range check `count - threshold`
+pub const COUNT_BITS: usize = 8;
+
+/// Number of leaves in the diagnosis tree
+pub const DIAGNOSIS_LEAVES: usize = 1 << DIAGNOSIS_TREE_DEPTH;
+
+/// Circuit size: 2^DIAGNOSIS_TREE_DEPTH Poseidon hashes for the root and commitment,
+/// plus the credential check
+pub const THRESHOLD_K: usize = 14;
+
+/// Number of public inputs of `DiagnosisThresholdCircuit`
+pub const THRESHOLD_PUBLIC_INPUTS: usize = MAX_REQUIRED_DIAGNOSES + 6;
+
+/// Diagnosis Threshold Circuit Configuration
+#[derive(Debug, Clone)]
+pub struct DiagnosisThresholdConfig<F: PoseidonField> {
+    pub required: Column<Advice>,     // Public: required diagnosis hash
+    pub leaf: Column<Advice>,         // Private: diagnosis tree leaf
+    pub product: Column<Advice>,      // Running product of (required - leaf)
+    pub inverse: Column<Advice>,      // Inverse of the product, if non-zero
+    pub matched: Column<Advice>,      // 1 if the product is zero, else 0
+    pub count: Column<Advice>,        // Running sum of the matches
+    pub threshold: Column<Advice>,    // Public: minimum number of matches
+    pub surplus: Column<Advice>,      // count - threshold
+    pub study_id: Column<Advice>,     // Public: study identifier
+    pub context_hash: Column<Advice>, // Public: wallet, chain and registry binding
+    pub epoch: Column<Advice>,        // Public: proof day (days since epoch)
+    pub salt: Column<Advice>,         // Private: commitment salt
+    pub prefix_root: Column<Advice>,  // Private: root of the prefix tree
+    pub padding: Column<Advice>,      // 1 if the required hash is the lower sentinel
+    pub other: Column<Advice>,        // Earlier required hash
+    pub difference_inverse: Column<Advice>, // Inverse of a non-zero difference
+    pub q_product: Selector,
+    pub q_match: Selector,
+    pub q_count: Selector,
+    pub q_surplus: Selector,
+    pub q_required: Selector,
+    pub q_distinct: Selector,
+    pub instance: Column<Instance>,
+    pub merkle: MerkleConfig,
+    pub range_check: RangeCheckConfig<F, COUNT_BITS>,
+    pub credential: CredentialConfig<F>,
+}
+
+/// Diagnosis Threshold Circuit
+///
+/// Proves: at least `threshold` of the required diagnoses are in patient_diagnoses
+///
+/// ## Public Inputs (instance column)
+/// Rows 0..MAX_REQUIRED_DIAGNOSES: required_hashes, then threshold, study_id,
+/// context_hash, epoch, provider_root and revocation_root. All are copy-constrained to their
+/// advice cells; the commitment stays private.
+#[derive(Clone)]
+pub struct DiagnosisThresholdCircuit<F: EddsaField> {
+    pub leaves: [Value<F>; DIAGNOSIS_LEAVES], // Private: diagnosis tree leaves
+    pub salt: Value<F>,                       // Private: commitment salt
+    pub prefix_root: Value<F>,                // Private: root of the prefix tree
+    pub credential: CredentialWitness<F>,     // Private: signs diagnosis_commitment
+    pub required_hashes: [F; MAX_REQUIRED_DIAGNOSES], // Public: required diagnosis hashes
+    pub threshold: F,                         // Public: minimum number of matches
+    pub study_id: F,                          // Public: binds proof to study
+    pub context_hash: F,                      // Public: binds proof to wallet, chain and registry
+    pub epoch: F,                             // Public: proof day (days since epoch)
+    pub provider_root: F,                     // Public: registered provider tree root
+    pub revocation_root: F,                   // Public: revoked credential tree root
+}
+
+impl<F: EddsaField> Default for DiagnosisThresholdCircuit<F> {
+    fn default() -> Self {
+        Self {
+            leaves: [Value::unknown(); DIAGNOSIS_LEAVES],
+            salt: Value::unknown(),
+            prefix_root: Value::unknown(),
+            credential: CredentialWitness::default(),
+            required_hashes: [F::ZERO; MAX_REQUIRED_DIAGNOSES],
+            threshold: F::ZERO,
+            study_id: F::ZERO,
+            context_hash: F::ZERO,
+            epoch: F::ZERO,
+            provider_root: F::ZERO,
+            revocation_root: F::ZERO,
+        }
+    }
+}
+
+impl<F: EddsaField> DiagnosisThresholdCircuit<F> {
+    /// Assign `prod (required - leaf)` over the code slots, returning the required
+    /// hash cell and the match bit
+    fn assign_match(
+        &self,
+        config: &DiagnosisThresholdConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        j: usize,
+        slots: &[AssignedCell<F, F>],
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let required = self.required_hashes[j];
+
+        layouter.assign_region(
+            || format!("required diagnosis {}", j),
+            |mut region| {
+                let required_cell = region.assign_advice(
+                    || "required_hash",
+                    config.required,
+                    0,
+                    || Value::known(required),
+                )?;
+                let mut product = region.assign_advice_from_constant(
+                    || "product 0",
+                    config.product,
+                    0,
+                    F::ONE,
+                )?;
+
+                for (i, slot) in slots.iter().enumerate() {
+                    config.q_product.enable(&mut region, i)?;
+
+                    let leaf = slot.copy_advice(|| "leaf", &mut region, config.leaf, i)?;
+                    if i > 0 {
+                        region.assign_advice(
+                            || "required_hash",
+                            config.required,
+                            i,
+                            || Value::known(required),
+                        )?;
+                    }
+
+                    let next = product
+                        .value()
+                        .copied()
+                        .zip(leaf.value().copied())
+                        .map(|(product, leaf)| product * (required - leaf));
+                    product = region.assign_advice(
+                        || format!("product {}", i + 1),
+                        config.product,
+                        i + 1,
+                        || next,
+                    )?;
+                }
+
+                // Match bit on the last row
+                let last = slots.len();
+                config.q_match.enable(&mut region, last)?;
+                region.assign_advice(
+                    || "required_hash",
+                    config.required,
+                    last,
+                    || Value::known(required),
+                )?;
+
+                let product = product.value().copied();
+                region.assign_advice(
+                    || "inverse",
+                    config.inverse,
+                    last,
+                    || product.map(|p| p.invert().unwrap_or(F::ZERO)),
+                )?;
+                let matched = region.assign_advice(
+                    || "matched",
+                    config.matched,
+                    last,
+                    || product.map(|p| if p == F::ZERO { F::ONE } else { F::ZERO }),
+                )?;
+
+                Ok((required_cell, matched))
+            },
+        )
+    }
+
+    /// Constrain required hash `j` to differ from the upper sentinel and, unless it is
+    /// padding, from every earlier required hash
+    fn assign_distinct(
+        &self,
+        config: &DiagnosisThresholdConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        j: usize,
+        required: &[AssignedCell<F, F>],
+    ) -> Result<(), Error> {
+        let hash = self.required_hashes[j];
+        let upper = upper_sentinel_field::<F>();
+
+        layouter.assign_region(
+            || format!("distinct required diagnosis {}", j),
+            |mut region| {
+                config.q_required.enable(&mut region, 0)?;
+                required[j].copy_advice(|| "required_hash", &mut region, config.required, 0)?;
+                region.assign_advice(
+                    || "inverse",
+                    config.inverse,
+                    0,
+                    || Value::known(hash.invert().unwrap_or(F::ZERO)),
+                )?;
+                let padding = region.assign_advice(
+                    || "padding",
+                    config.padding,
+                    0,
+                    || Value::known(if hash == F::ZERO { F::ONE } else { F::ZERO }),
+                )?;
+                region.assign_advice(
+                    || "upper sentinel difference inverse",
+                    config.difference_inverse,
+                    0,
+                    || Value::known((hash - upper).invert().unwrap_or(F::ZERO)),
+                )?;
+
+                for (i, other) in required[..j].iter().enumerate() {
+                    let row = i + 1;
+                    config.q_distinct.enable(&mut region, row)?;
+                    required[j].copy_advice(
+                        || "required_hash",
+                        &mut region,
+                        config.required,
+                        row,
+                    )?;
+                    padding.copy_advice(|| "padding", &mut region, config.padding, row)?;
+                    other.copy_advice(|| "other", &mut region, config.other, row)?;
+                    region.assign_advice(
+                        || "difference inverse",
+                        config.difference_inverse,
+                        row,
+                        || {
+                            Value::known(hash)
+                                .zip(other.value().copied())
+                                .map(|(hash, other)| (hash - other).invert().unwrap_or(F::ZERO))
+                        },
+                    )?;
+                }
+
+                Ok(())
+            },
+        )
+    }
+}
+
+/// [`upper_sentinel`] in the circuit field
+fn upper_sentinel_field<F: EddsaField>() -> F {
+    F::from(2).pow_vartime([CODE_HASH_BITS as u64]) - F::ONE
+}
+
+impl<F: EddsaField> Circuit<F> for DiagnosisThresholdCircuit<F> {
+    type Config = DiagnosisThresholdConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let required = meta.advice_column();
+        let leaf = meta.advice_column();
+        let product = meta.advice_column();
+        let inverse = meta.advice_column();
+        let matched = meta.advice_column();
+        let count = meta.advice_column();
+        let threshold = meta.advice_column();
+        let surplus = meta.advice_column();
+        let study_id = meta.advice_column();
+        let context_hash = meta.advice_column();
+        let epoch = meta.advice_column();
+        let salt = meta.advice_column();
+        let prefix_root = meta.advice_column();
+        let padding = meta.advice_column();
+        let other = meta.advice_column();
+        let difference_inverse = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let q_product = meta.selector();
+        let q_match = meta.selector();
+        let q_count = meta.selector();
+        let q_surplus = meta.selector();
+        let q_required = meta.selector();
+        let q_distinct = meta.selector();
+        let instance = meta.instance_column();
+
+        for column in [
+            required,
+            leaf,
+            product,
+            matched,
+            count,
+            threshold,
+            surplus,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            prefix_root,
+            padding,
+            other,
+        ] {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        // Gate: product_next = product * (required - leaf), same required on every row
+        meta.create_gate("threshold product", |meta| {
+            let q = meta.query_selector(q_product);
+            let required_cur = meta.query_advice(required, Rotation::cur());
+            let required_next = meta.query_advice(required, Rotation::next());
+            let leaf = meta.query_advice(leaf, Rotation::cur());
+            let product_cur = meta.query_advice(product, Rotation::cur());
+            let product_next = meta.query_advice(product, Rotation::next());
+
+            vec![
+                q.clone() * (product_next - product_cur * (required_cur.clone() - leaf)),
+                q * (required_next - required_cur),
+            ]
+        });
+
+        // Gate: matched = (product == 0), the usual is-zero gadget
+        meta.create_gate("threshold match", |meta| {
+            let q = meta.query_selector(q_match);
+            let product = meta.query_advice(product, Rotation::cur());
+            let inverse = meta.query_advice(inverse, Rotation::cur());
+            let matched = meta.query_advice(matched, Rotation::cur());
+
+            vec![
+                q.clone()
+                    * (matched.clone()
+                        - (Expression::Constant(F::ONE) - product.clone() * inverse)),
+                q * product * matched,
+            ]
+        });
+
+        // Gate: count_next = count + matched
+        meta.create_gate("threshold count", |meta| {
+            let q = meta.query_selector(q_count);
+            let matched = meta.query_advice(matched, Rotation::cur());
+            let count_cur = meta.query_advice(count, Rotation::cur());
+            let count_next = meta.query_advice(count, Rotation::next());
+
+            vec![q * (count_next - (count_cur + matched))]
+        });
+
+        // Gate: surplus = count - threshold (count >= threshold once surplus is range checked)
+        meta.create_gate("threshold surplus", |meta| {
+            let q = meta.query_selector(q_surplus);
+            let count = meta.query_advice(count, Rotation::cur());
+            let threshold = meta.query_advice(threshold, Rotation::cur());
+            let surplus = meta.query_advice(surplus, Rotation::cur());
+
+            vec![q * (surplus - (count - threshold))]
+        });
+
+        // Gate: padding = (required == lower sentinel), and required != upper sentinel
+        meta.create_gate("threshold required hash", |meta| {
+            let q = meta.query_selector(q_required);
+            let required = meta.query_advice(required, Rotation::cur());
+            let inverse = meta.query_advice(inverse, Rotation::cur());
+            let padding = meta.query_advice(padding, Rotation::cur());
+            let upper_inverse = meta.query_advice(difference_inverse, Rotation::cur());
+            let one = Expression::Constant(F::ONE);
+
+            vec![
+                q.clone() * (padding.clone() - (one.clone() - required.clone() * inverse)),
+                q.clone() * required.clone() * padding,
+                q * ((required - Expression::Constant(upper_sentinel_field::<F>()))
+                    * upper_inverse
+                    - one),
+            ]
+        });
+
+        // Gate: required != other, unless required is padding
+        meta.create_gate("threshold distinct", |meta| {
+            let q = meta.query_selector(q_distinct);
+            let required = meta.query_advice(required, Rotation::cur());
+            let padding = meta.query_advice(padding, Rotation::cur());
+            let other = meta.query_advice(other, Rotation::cur());
+            let inverse = meta.query_advice(difference_inverse, Rotation::cur());
+            let one = Expression::Constant(F::ONE);
+
+            vec![q * (one.clone() - padding) * ((required - other) * inverse - one)]
+        });
+
+        let (merkle, credential) = configure_credential(meta);
+        let range_check = RangeCheckChip::configure(meta, running_sum);
+
+        DiagnosisThresholdConfig {
+            required,
+            leaf,
+            product,
+            inverse,
+            matched,
+            count,
+            threshold,
+            surplus,
+            study_id,
+            context_hash,
+            epoch,
+            salt,
+            prefix_root,
+            padding,
+            other,
+            difference_inverse,
+            q_product,
+            q_match,
+            q_count,
+            q_surplus,
+            q_required,
+            q_distinct,
+            instance,
+            merkle,
+            range_check,
+            credential,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (leaves, study_id, context_hash, epoch, salt, prefix_root) = layouter.assign_region(
+            || "threshold inputs",
+            |mut region| {
+                // Assign the private leaves
+                let leaves = self
+                    .leaves
+                    .iter()
+                    .enumerate()
+                    .map(|(i, leaf)| region.assign_advice(|| "leaf", config.leaf, i, || *leaf))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Assign public study_id
+                let study_id = region.assign_advice(
+                    || "study_id",
+                    config.study_id,
+                    0,
+                    || Value::known(self.study_id),
+                )?;
+
+                // Assign public context hash
+                let context_hash = region.assign_advice(
+                    || "context_hash",
+                    config.context_hash,
+                    0,
+                    || Value::known(self.context_hash),
+                )?;
+
+                // Assign public epoch
+                let epoch = region.assign_advice(
+                    || "epoch",
+                    config.epoch,
+                    0,
+                    || Value::known(self.epoch),
+                )?;
+
+                // Assign private salt and prefix root
+                let salt = region.assign_advice(|| "salt", config.salt, 0, || self.salt)?;
+                let prefix_root = region.assign_advice(
+                    || "prefix_root",
+                    config.prefix_root,
+                    0,
+                    || self.prefix_root,
+                )?;
+
+                Ok((leaves, study_id, context_hash, epoch, salt, prefix_root))
+            },
+        )?;
+
+        // The leaves are exactly the committed ones
+        let merkle_chip = MerkleChip::<F>::construct(config.merkle.clone());
+        let root = merkle_chip
+            .compute_root_from_leaves(layouter.namespace(|| "diagnosis root"), &leaves)?;
+
+        let poseidon_chip = PoseidonChip::<F>::construct(config.merkle.poseidon.clone());
+        let commitment = poseidon_chip.hash(
+            layouter.namespace(|| "diagnosis commitment"),
+            &[root, prefix_root, salt],
+        )?;
+        let (provider_root, revocation_root) = verify_credential(
+            layouter.namespace(|| "credential"),
+            &config.credential,
+            &commitment,
+            &epoch,
+            &self.credential,
+        )?;
+
+        // Match every required hash against the code slots
+        let slots = &leaves[1..=MAX_DIAGNOSES];
+        let mut required = Vec::with_capacity(MAX_REQUIRED_DIAGNOSES);
+        let mut matches = Vec::with_capacity(MAX_REQUIRED_DIAGNOSES);
+        for j in 0..MAX_REQUIRED_DIAGNOSES {
+            let (required_cell, matched) = self.assign_match(&config, &mut layouter, j, slots)?;
+            layouter.constrain_instance(required_cell.cell(), config.instance, j)?;
+            required.push(required_cell);
+            matches.push(matched);
+        }
+
+        // Each diagnosis is counted at most once
+        for j in 0..MAX_REQUIRED_DIAGNOSES {
+            self.assign_distinct(&config, &mut layouter, j, &required)?;
+        }
+
+        // Count the matches and compare against the threshold
+        let count = layouter.assign_region(
+            || "threshold count",
+            |mut region| {
+                let mut count =
+                    region.assign_advice_from_constant(|| "count 0", config.count, 0, F::ZERO)?;
+
+                for (j, matched) in matches.iter().enumerate() {
+                    config.q_count.enable(&mut region, j)?;
+
+                    let matched =
+                        matched.copy_advice(|| "matched", &mut region, config.matched, j)?;
+                    let next = count
+                        .value()
+                        .copied()
+                        .zip(matched.value().copied())
+                        .map(|(count, matched)| count + matched);
+                    count = region.assign_advice(
+                        || format!("count {}", j + 1),
+                        config.count,
+                        j + 1,
+                        || next,
+                    )?;
+                }
+
+                Ok(count)
+            },
+        )?;
+
+        let (threshold, surplus) = layouter.assign_region(
+            || "threshold surplus",
+            |mut region| {
+                config.q_surplus.enable(&mut region, 0)?;
+
+                let count = count.copy_advice(|| "count", &mut region, config.count, 0)?;
+                let threshold = region.assign_advice(
+                    || "threshold",
+                    config.threshold,
+                    0,
+                    || Value::known(self.threshold),
+                )?;
+                let surplus = region.assign_advice(
+                    || "count - threshold",
+                    config.surplus,
+                    0,
+                    || count.value().map(|count| *count - self.threshold),
+                )?;
+
+                Ok((threshold, surplus))
+            },
+        )?;
+
+        let range_chip = RangeCheckChip::construct(config.range_check.clone());
+        range_chip.assign(layouter.namespace(|| "count >= threshold"), &surplus)?;
+
+        // Bind every public value to its instance row
+        layouter.constrain_instance(threshold.cell(), config.instance, MAX_REQUIRED_DIAGNOSES)?;
+        layouter.constrain_instance(
+            study_id.cell(),
+            config.instance,
+            MAX_REQUIRED_DIAGNOSES + 1,
+        )?;
+        layouter.constrain_instance(
+            context_hash.cell(),
+            config.instance,
+            MAX_REQUIRED_DIAGNOSES + 2,
+        )?;
+        layouter.constrain_instance(epoch.cell(), config.instance, MAX_REQUIRED_DIAGNOSES + 3)?;
+        layouter.constrain_instance(
+            provider_root.cell(),
+            config.instance,
+            MAX_REQUIRED_DIAGNOSES + 4,
+        )?;
+        layouter.constrain_instance(
+            revocation_root.cell(),
+            config.instance,
+            MAX_REQUIRED_DIAGNOSES + 5,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<F: EddsaField> CircuitExt<F> for DiagnosisThresholdCircuit<F> {
+    fn rand(_: usize, _: impl RngCore) -> Self {
+        Self::default()
+    }
+
+    fn instances(&self) -> Vec<Vec<F>> {
+        // Public inputs: required_hashes, threshold, study_id, context_hash, epoch,
+        // provider_root, revocation_root
+        let mut instances = self.required_hashes.to_vec();
+        instances.push(self.threshold);
+        instances.push(self.study_id);
+        instances.push(self.context_hash);
+        instances.push(self.epoch);
+        instances.push(self.provider_root);
+        instances.push(self.revocation_root);
+        vec![instances]
+    }
+}
+
+/// Pad a required list to MAX_REQUIRED_DIAGNOSES with the lower sentinel
+///
+/// Rejects empty lists, duplicate hashes and sentinels.
+pub fn pad_required_hashes(
+    required_hashes: &[Fr],
+) -> Result<[Fr; MAX_REQUIRED_DIAGNOSES], DiagnosisError> {
+    if required_hashes.is_empty() || required_hashes.len() > MAX_REQUIRED_DIAGNOSES {
+        return Err(DiagnosisError(format!(
+            "Expected 1 to {} required diagnoses, got {}",
+            MAX_REQUIRED_DIAGNOSES,
+            required_hashes.len()
+        )));
+    }
+
+    for (i, hash) in required_hashes.iter().enumerate() {
+        if *hash == lower_sentinel()
+            || *hash == upper_sentinel()
+            || required_hashes[..i].contains(hash)
+        {
+            return Err(DiagnosisError(format!(
+                "Invalid required diagnosis {} (duplicate or sentinel)",
+                i
+            )));
+        }
+    }
+
+    Ok(std::array::from_fn(|i| {
+        required_hashes.get(i).copied().unwrap_or(lower_sentinel())
+    }))
+}
+
+/// Check a padded required list, as published in the public inputs
+///
+/// The required hashes must be distinct and non-empty, contain neither sentinel, and be
+/// followed only by lower sentinel padding.
+pub fn check_required_hashes(padded: &[Fr]) -> Result<(), DiagnosisError> {
+    let len = padded
+        .iter()
+        .position(|hash| *hash == lower_sentinel())
+        .unwrap_or(padded.len());
+    if padded[len..].iter().any(|hash| *hash != lower_sentinel()) {
+        return Err(DiagnosisError(
+            "Required diagnoses are followed by a non-padding hash".to_string(),
+        ));
+    }
+    pad_required_hashes(&padded[..len]).map(|_| ())
+}
+
+/// Client-side validation: Check if patient has at least `threshold` of `required_diagnoses`
+pub fn validate_diagnosis_threshold(
+    patient_diagnoses: &[String],
+    required_diagnoses: &[String],
+    threshold: u64,
+) -> Result<(), DiagnosisError> {
+    let normalize =
+        |code: &String| normalize_icd10_code(code).map_err(|e| DiagnosisError(e.to_string()));
+
+    let patient = patient_diagnoses
+        .iter()
+        .map(normalize)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut required = required_diagnoses
+        .iter()
+        .map(normalize)
+        .collect::<Result<Vec<_>, _>>()?;
+    required.sort();
+    required.dedup();
+
+    validate_threshold(threshold, required.len())?;
+
+    let count = required
+        .iter()
+        .filter(|code| patient.contains(code))
+        .count();
+    if (count as u64) < threshold {
+        return Err(DiagnosisError(format!(
+            "Patient has {} of the required diagnoses (minimum {})",
+            count, threshold
+        )));
+    }
+    Ok(())
+}
+
+fn validate_threshold(threshold: u64, required: usize) -> Result<(), DiagnosisError> {
+    if threshold == 0 || threshold > required as u64 {
+        return Err(DiagnosisError(format!(
+            "Invalid threshold {} for {} required diagnoses",
+            threshold, required
+        )));
+    }
+    Ok(())
+}
+
+/// Number of code slots holding one of `required_hashes`
+fn count_matches(leaves: &[Fr], required_hashes: &[Fr]) -> usize {
+    required_hashes
+        .iter()
+        .filter(|hash| leaves[1..=MAX_DIAGNOSES].contains(*hash))
+        .count()
+}
+
+impl DiagnosisTree {
+    /// Circuit proving that at least `threshold` of `required_codes` are in this tree,
+    /// attested by `credential`
+    pub fn threshold_circuit(
+        &self,
+        required_codes: &[String],
+        threshold: u64,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<DiagnosisThresholdCircuit<Fr>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let hashes = required_codes
+            .iter()
+            .map(|code| hash_diagnosis_code(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        let required_hashes = pad_required_hashes(&hashes)?;
+        validate_threshold(threshold, hashes.len())?;
+
+        let leaves = self.tree.leaves();
+        if (count_matches(leaves, &hashes) as u64) < threshold {
+            return Err(DiagnosisError(
+                "Patient does not have enough of the required diagnoses".to_string(),
+            ));
+        }
+
+        Ok(DiagnosisThresholdCircuit {
+            leaves: std::array::from_fn(|i| Value::known(leaves[i])),
+            salt: Value::known(salt),
+            prefix_root: Value::known(self.prefix_root()),
+            credential: credential.witness(),
+            required_hashes,
+            threshold: Fr::from(threshold),
+            study_id,
+            context_hash,
+            epoch,
+            provider_root: credential.provider_root,
+            revocation_root: credential.revocation_root,
+        })
+    }
+
+    /// Input map for [`generate_threshold_proof`]
+    pub fn threshold_inputs(
+        &self,
+        required_codes: &[String],
+        threshold: u64,
+        salt: Fr,
+        study_id: Fr,
+        context_hash: Fr,
+        epoch: Fr,
+        credential: &CredentialInputs,
+    ) -> Result<HashMap<String, Vec<Fr>>, DiagnosisError> {
+        self.check_attested(salt, credential)?;
+        let hashes = required_codes
+            .iter()
+            .map(|code| hash_diagnosis_code(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        pad_required_hashes(&hashes)?;
+
+        let mut inputs = HashMap::new();
+        credential.insert_inputs(&mut inputs);
+        inputs.insert("required_hashes".to_string(), hashes);
+        inputs.insert("threshold".to_string(), vec![Fr::from(threshold)]);
+        inputs.insert("study_id".to_string(), vec![study_id]);
+        inputs.insert("context_hash".to_string(), vec![context_hash]);
+        inputs.insert("epoch".to_string(), vec![epoch]);
+        inputs.insert("salt".to_string(), vec![salt]);
+        inputs.insert("prefix_root".to_string(), vec![self.prefix_root()]);
+        inputs.insert("diagnosis_leaves".to_string(), self.tree.leaves().to_vec());
+
+        Ok(inputs)
+    }
+}
+
+/// Generate diagnosis threshold proof
+///
+/// Expects the input map built by [`DiagnosisTree::threshold_inputs`]:
+/// `required_hashes` (1 to MAX_REQUIRED_DIAGNOSES distinct values), `threshold`,
+/// `study_id`, `context_hash`, `epoch`, `salt`, `prefix_root`, `diagnosis_leaves`
+/// (2^DIAGNOSIS_TREE_DEPTH values)
+/// and the credential inputs, which include `diagnosis_commitment`. The credential
+/// (including its age at `epoch`) and the leaves are checked before proving.
+pub fn generate_threshold_proof<PC>(
+    srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    generate_threshold_proof_with_rng::<PC, _>(srs, prover_parameters, inputs, default_rng())
+}
+
+/// [`generate_threshold_proof`] with caller-supplied blinding randomness (see [`crate::rng`])
+pub fn generate_threshold_proof_with_rng<PC, R>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    prover_parameters: &PC::ProverParam,
+    inputs: HashMap<String, Vec<Fr>>,
+    rng: R,
+) -> Result<(Vec<u8>, Vec<Fr>), DiagnosisError>
+where
+    PC: PlonkishComponents,
+    R: RngCore + CryptoRng,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptWrite<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    let k = THRESHOLD_K;
+
+    // Extract inputs
+    let hashes = inputs
+        .get("required_hashes")
+        .ok_or(DiagnosisError("Missing required_hashes".to_string()))?
+        .clone();
+    let required_hashes = pad_required_hashes(&hashes)?;
+
+    let threshold = get_input(&inputs, "threshold", 1)?[0];
+    let study_id = get_input(&inputs, "study_id", 1)?[0];
+    let context_hash = get_input(&inputs, "context_hash", 1)?[0];
+    let epoch = get_input(&inputs, "epoch", 1)?[0];
+    let credential = checked_credential(&inputs, &epoch)?;
+    let diagnosis_commitment = credential.attributes().diagnosis_commitment;
+    let salt = get_input(&inputs, "salt", 1)?[0];
+    let prefix_root = get_input(&inputs, "prefix_root", 1)?[0];
+    let leaves = get_input(&inputs, "diagnosis_leaves", DIAGNOSIS_LEAVES)?;
+
+    // Client-side validation: the leaves open the commitment and match often enough
+    let tree =
+        MerkleTree::new(leaves, DIAGNOSIS_TREE_DEPTH).map_err(|e| DiagnosisError(e.to_string()))?;
+    if commit_diagnosis_root(tree.root(), prefix_root, salt) != diagnosis_commitment {
+        return Err(DiagnosisError(
+            "Diagnosis leaves do not open the diagnosis commitment".to_string(),
+        ));
+    }
+
+    let threshold_u64 = field_to_u64(&threshold)?;
+    validate_threshold(threshold_u64, hashes.len())?;
+    if (count_matches(leaves, &hashes) as u64) < threshold_u64 {
+        return Err(DiagnosisError(
+            "Patient does not have enough of the required diagnoses".to_string(),
+        ));
+    }
+
+    // Create circuit with validated inputs
+    let circuit = DiagnosisThresholdCircuit::<Fr> {
+        leaves: std::array::from_fn(|i| Value::known(leaves[i])),
+        salt: Value::known(salt),
+        prefix_root: Value::known(prefix_root),
+        credential: credential.witness(),
+        required_hashes,
+        threshold,
+        study_id,
+        context_hash,
+        epoch,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
+    };
+
+    let halo2_circuit = Halo2Circuit::<Fr, DiagnosisThresholdCircuit<Fr>>::new::<PC::ProvingBackend>(
+        k,
+        circuit.clone(),
+    );
+
+    let proof_transcript = {
+        let mut proof_transcript = Keccak256Transcript::new(());
+
+        PC::ProvingBackend::prove(
+            &prover_parameters,
+            &halo2_circuit,
+            &mut proof_transcript,
+            rng,
+        )
+        .map_err(|e| DiagnosisError(format!("Proof generation failed: {:?}", e)))?;
+
+        proof_transcript
+    };
+
+    let proof = proof_transcript.into_proof();
+    let public_inputs = circuit.instances().remove(0);
+
+    Ok((proof, public_inputs))
+}
+
+/// Verify diagnosis threshold proof
+///
+/// The caller is responsible for recomputing `context_hash` from the submitting wallet
+/// and for checking `epoch` against its own clock.
+pub fn verify_threshold_proof<PC>(
+    _srs: &<PC::Pcs as PolynomialCommitmentScheme<Fr>>::Param,
+    verifier_parameters: &PC::VerifierParam,
+    proof: Vec<u8>,
+    inputs: Vec<Fr>,
+) -> Result<bool, DiagnosisError>
+where
+    PC: PlonkishComponents,
+    Keccak256Transcript<Cursor<Vec<u8>>>: TranscriptRead<CommitmentChunk<Fr, PC::Pcs>, Fr>,
+{
+    if inputs.len() != THRESHOLD_PUBLIC_INPUTS {
+        return Err(DiagnosisError(format!(
+            "Invalid number of public inputs (expected {}: required_hashes, threshold, study_id, context_hash, epoch, provider_root, revocation_root)",
+            THRESHOLD_PUBLIC_INPUTS
+        )));
+    }
+    check_required_hashes(&inputs[..MAX_REQUIRED_DIAGNOSES])?;
+
+    let mut transcript = Keccak256Transcript::from_proof((), proof.as_slice());
+    let result = PC::ProvingBackend::verify(
+        &verifier_parameters,
+        &[inputs],
+        &mut transcript,
+        default_rng(),
+    );
+
+    result
+        .map(|_| true)
+        .map_err(|e| DiagnosisError(format!("Verification failed: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{issue, EPOCH};
+    use halo2_proofs::dev::MockProver;
+
+    fn patient_tree() -> DiagnosisTree {
+        DiagnosisTree::new(&patient_codes()).unwrap()
+    }
+
+    fn patient_codes() -> Vec<String> {
+        vec![
+            "I10".to_string(),   // Hypertension
+            "E78.5".to_string(), // Dyslipidemia
+            "J45".to_string(),   // Asthma
+        ]
+    }
+
+    fn required_codes() -> Vec<String> {
+        vec![
+            "I10".to_string(),   // Hypertension
+            "E78.5".to_string(), // Dyslipidemia
+            "E66.9".to_string(), // Obesity
+        ]
+    }
+
+    /// Circuit for `threshold` of the required codes with salt 1234, study 1, context 7 and
+    /// epoch [`EPOCH`], attested by a test credential
+    fn circuit(
+        tree: &DiagnosisTree,
+        threshold: u64,
+    ) -> Result<DiagnosisThresholdCircuit<Fr>, DiagnosisError> {
+        let salt = Fr::from(1234);
+        let credential = issue(tree.commitment(salt));
+        tree.threshold_circuit(
+            &required_codes(),
+            threshold,
+            salt,
+            Fr::from(1),
+            Fr::from(7),
+            Fr::from(EPOCH),
+            &credential,
+        )
+    }
+
+    fn mock_verify(circuit: &DiagnosisThresholdCircuit<Fr>, instances: Vec<Vec<Fr>>) -> bool {
+        MockProver::run(THRESHOLD_K as u32, circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_pad_required_hashes() {
+        let padded = pad_required_hashes(&[Fr::from(1), Fr::from(2)]).unwrap();
+        assert_eq!(padded[..2], [Fr::from(1), Fr::from(2)]);
+        assert!(padded[2..].iter().all(|hash| *hash == lower_sentinel()));
+
+        assert!(pad_required_hashes(&[]).is_err());
+        assert!(pad_required_hashes(&[Fr::from(1), Fr::from(1)]).is_err());
+        assert!(pad_required_hashes(&[lower_sentinel()]).is_err());
+        assert!(pad_required_hashes(&[upper_sentinel()]).is_err());
+    }
+
+    #[test]
+    fn test_check_required_hashes() {
+        let padded = pad_required_hashes(&[Fr::from(1), Fr::from(2)]).unwrap();
+        assert!(check_required_hashes(&padded).is_ok());
+
+        let mut duplicate = padded;
+        duplicate[2] = Fr::from(1);
+        assert!(check_required_hashes(&duplicate).is_err());
+
+        let mut upper = padded;
+        upper[2] = upper_sentinel();
+        assert!(check_required_hashes(&upper).is_err());
+
+        // A lower sentinel before a required hash is not padding
+        let mut gap = padded;
+        gap[1] = lower_sentinel();
+        gap[2] = Fr::from(2);
+        assert!(check_required_hashes(&gap).is_err());
+
+        assert!(check_required_hashes(&[lower_sentinel(); MAX_REQUIRED_DIAGNOSES]).is_err());
+    }
+
+    #[test]
+    fn test_validate_diagnosis_threshold() {
+        assert!(validate_diagnosis_threshold(&patient_codes(), &required_codes(), 1).is_ok());
+        assert!(validate_diagnosis_threshold(&patient_codes(), &required_codes(), 2).is_ok());
+        assert!(validate_diagnosis_threshold(&patient_codes(), &required_codes(), 3).is_err());
+
+        // Spellings of the same code count once
+        let repeated = vec!["I10".to_string(), "i10".to_string()];
+        assert!(validate_diagnosis_threshold(&patient_codes(), &repeated, 2).is_err());
+        assert!(validate_diagnosis_threshold(&patient_codes(), &required_codes(), 0).is_err());
+    }
+
+    #[test]
+    fn test_circuit_accepts_threshold_met() {
+        let tree = patient_tree();
+
+        for threshold in [1, 2] {
+            let circuit = circuit(&tree, threshold).unwrap();
+            assert!(
+                mock_verify(&circuit, circuit.instances()),
+                "{} of 3 must verify",
+                threshold
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_threshold_not_met() {
+        let tree = patient_tree();
+        assert!(circuit(&tree, 3).is_err());
+
+        // Bypass the host check: only 2 of the 3 codes are committed
+        let mut circuit = circuit(&tree, 2).unwrap();
+        circuit.threshold = Fr::from(3);
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_forged_leaves() {
+        let mut circuit = circuit(&patient_tree(), 2).unwrap();
+
+        // Slip obesity into an empty code slot
+        circuit.leaves[MAX_DIAGNOSES] = Value::known(hash_diagnosis_code("E66.9").unwrap());
+        circuit.threshold = Fr::from(3);
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_tampered_instances() {
+        let circuit = circuit(&patient_tree(), 2).unwrap();
+
+        for row in 0..THRESHOLD_PUBLIC_INPUTS {
+            let mut instances = circuit.instances();
+            instances[0][row] += Fr::ONE;
+
+            assert!(
+                !mock_verify(&circuit, instances),
+                "tampered row {} must not verify",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_rejects_duplicate_required_hashes() {
+        // Hypertension listed twice counts as 3 of the required diagnoses
+        let mut circuit = circuit(&patient_tree(), 2).unwrap();
+        circuit.required_hashes[2] = circuit.required_hashes[0];
+        circuit.threshold = Fr::from(3);
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_upper_sentinel() {
+        // The upper sentinel fills the unused code slots, so it would always match
+        let mut circuit = circuit(&patient_tree(), 2).unwrap();
+        circuit.required_hashes[2] = upper_sentinel();
+        circuit.threshold = Fr::from(3);
+
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+
+    #[test]
+    fn test_circuit_rejects_unattested_commitment() {
+        // A tree with obesity added, so all 3 codes match
+        let mut codes = patient_codes();
+        codes.push("E66.9".to_string());
+        let tree = DiagnosisTree::new(&codes).unwrap();
+        let salt = Fr::from(1234);
+        let signed = issue(patient_tree().commitment(salt));
+        assert!(tree
+            .threshold_circuit(
+                &required_codes(),
+                3,
+                salt,
+                Fr::from(1),
+                Fr::from(7),
+                Fr::from(EPOCH),
+                &signed
+            )
+            .is_err());
+
+        let mut circuit = circuit(&tree, 3).unwrap();
+        circuit.credential = signed.witness();
+        assert!(!mock_verify(&circuit, circuit.instances()));
+    }
+}
@@ -0,0 +1,38 @@
+//! Lab Analytes
+//!
+//! Lab predicates name their analyte by LOINC code. In-circuit the analyte is the code
+//! packed into an integer without the check digit separator ("4548-4" -> 45484), the
+//! `analyte` of [`commit_lab_value`](composite_eligibility_circuit::lab_value::commit_lab_value).
+//!
+//! The canonical unit and scale of known analytes live in
+//! [`units`](composite_eligibility_circuit::units), shared with FHIR ingestion: spec
+//! bounds in another supported unit are converted, and any other unit is rejected.
+
+use composite_eligibility_circuit::lab_value;
+pub use composite_eligibility_circuit::units::{lookup, Analyte, ANALYTES};
+
+use crate::CriteriaError;
+
+/// Pack a LOINC code into the in-circuit analyte, checking its check digit
+pub fn pack_loinc(loinc: &str) -> Result<u64, CriteriaError> {
+    lab_value::pack_loinc(loinc).map_err(|e| CriteriaError(e.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyte_loinc_codes() {
+        assert_eq!(pack_loinc("4548-4").unwrap(), 45484);
+        for analyte in ANALYTES {
+            assert!(pack_loinc(analyte.loinc).is_ok(), "{}", analyte.loinc);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("4548-4").unwrap().unit, "%");
+        assert!(lookup("0000-0").is_none());
+    }
+}
@@ -0,0 +1,45 @@
+use std::{env, fs, process, time::SystemTime};
+
+use composite_eligibility_circuit::enrollment::{ActiveEnrollmentTree, EnrollmentExport};
+
+/// Usage: active-enrollments <enrollments.json> <active_enrollment_root.json>
+///
+/// Builds the active enrollment tree from an enrollment export (`StudyParticipationSBT`
+/// enrollments with their application nullifiers) and publishes its root to the root file.
+pub fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        exit("Please specify the enrollment export and root file paths");
+    }
+    let (export_path, root_path) = (&args[1], &args[2]);
+
+    let source = fs::read_to_string(export_path)
+        .unwrap_or_else(|e| exit(&format!("Couldn't read {}: {}", export_path, e)));
+    let export: EnrollmentExport = serde_json::from_str(&source)
+        .unwrap_or_else(|e| exit(&format!("Invalid enrollment export {}: {}", export_path, e)));
+    let tree = ActiveEnrollmentTree::from_export(&export).unwrap_or_else(|e| exit(&e.0));
+
+    let updated_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("system clock after 1970")
+        .as_secs();
+    let published = tree.published_root(updated_at);
+
+    let json = serde_json::to_string_pretty(&published).expect("enrollment root serializes");
+    fs::write(root_path, format!("{}\n", json))
+        .unwrap_or_else(|e| exit(&format!("Couldn't write {}: {}", root_path, e)));
+    println!(
+        "Enrollments: {} ({} active)",
+        export.enrollments.len(),
+        published.active_count
+    );
+    println!(
+        "Active enrollment root: {}",
+        published.active_enrollment_root
+    );
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
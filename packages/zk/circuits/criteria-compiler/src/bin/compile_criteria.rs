@@ -0,0 +1,35 @@
+use std::{env, fs, path::Path, process};
+
+use study_criteria_compiler::{compile_str, spec::Format};
+
+/// Usage: compile-criteria <spec.json|spec.yaml> [output.json]
+///
+/// Prints the compiled criteria as JSON, or writes them to the output file.
+pub fn main() {
+    let spec_path = env::args()
+        .nth(1)
+        .expect("Please specify the criteria spec path");
+    let spec_path = Path::new(&spec_path);
+
+    let format = Format::from_path(spec_path).unwrap_or_else(|e| exit(&e.to_string()));
+    let source = fs::read_to_string(spec_path)
+        .unwrap_or_else(|e| exit(&format!("Couldn't read {}: {}", spec_path.display(), e)));
+
+    let compiled = compile_str(&source, format).unwrap_or_else(|e| exit(&e.to_string()));
+    let output = serde_json::to_string_pretty(&compiled).expect("compiled criteria serialize");
+
+    match env::args().nth(2) {
+        Some(output_path) => {
+            fs::write(&output_path, output + "\n")
+                .unwrap_or_else(|e| exit(&format!("Couldn't write {}: {}", output_path, e)));
+            println!("Criteria hash: {}", compiled.criteria_hash);
+            println!("Compiled criteria stored in {}", output_path);
+        }
+        None => println!("{}", output),
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
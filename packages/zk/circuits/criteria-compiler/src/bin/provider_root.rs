@@ -0,0 +1,42 @@
+use std::{env, fs, process, time::SystemTime};
+
+use composite_eligibility_circuit::{
+    provider_registry::{parse_registry_export, ProviderTree},
+    serialization::format_field_element,
+};
+
+/// Usage: provider-root <registry.json> [timestamp]
+///
+/// Prints the root of the providers in good standing at the timestamp (Unix seconds,
+/// defaults to now), to publish as `provider_root`.
+pub fn main() {
+    let registry_path = env::args()
+        .nth(1)
+        .expect("Please specify the provider registry export path");
+    let now = match env::args().nth(2) {
+        Some(timestamp) => timestamp
+            .parse()
+            .unwrap_or_else(|_| exit(&format!("Invalid timestamp: {}", timestamp))),
+        None => SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("system clock after 1970")
+            .as_secs(),
+    };
+
+    let source = fs::read_to_string(&registry_path)
+        .unwrap_or_else(|e| exit(&format!("Couldn't read {}: {}", registry_path, e)));
+    let providers = parse_registry_export(&source).unwrap_or_else(|e| exit(&e.0));
+    let tree = ProviderTree::from_registry(&providers, now).unwrap_or_else(|e| exit(&e.0));
+
+    println!(
+        "Providers in good standing: {} of {}",
+        tree.keys().len(),
+        providers.len()
+    );
+    println!("Provider root: {}", format_field_element(&tree.root()));
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
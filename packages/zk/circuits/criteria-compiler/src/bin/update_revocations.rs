@@ -0,0 +1,84 @@
+use std::{env, fs, path::Path, process, time::SystemTime};
+
+use composite_eligibility_circuit::{
+    revocation::{RevocationList, RevocationTree},
+    serialization::parse_field_element,
+};
+
+/// Usage: update-revocations <revocations.json> <revocation_root.json> [revoke|reinstate <credential_id>...]
+///
+/// Applies the revocations or reinstatements to the revocation list (created if
+/// missing), then rewrites it and publishes its root to the root file.
+pub fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        exit("Please specify the revocation list and root file paths");
+    }
+    let (list_path, root_path) = (&args[1], &args[2]);
+
+    let list = match Path::new(list_path).exists() {
+        true => {
+            let source = fs::read_to_string(list_path)
+                .unwrap_or_else(|e| exit(&format!("Couldn't read {}: {}", list_path, e)));
+            serde_json::from_str(&source)
+                .unwrap_or_else(|e| exit(&format!("Invalid revocation list {}: {}", list_path, e)))
+        }
+        false => RevocationList::default(),
+    };
+    let mut tree = RevocationTree::from_list(&list).unwrap_or_else(|e| exit(&e.0));
+
+    if let Some(command) = args.get(3) {
+        let revoke = match command.as_str() {
+            "revoke" => true,
+            "reinstate" => false,
+            _ => exit(&format!(
+                "Unknown command: {} (expected revoke or reinstate)",
+                command
+            )),
+        };
+
+        for id in args[4..].iter() {
+            let credential_id = parse_field_element(id).unwrap_or_else(|e| exit(&e.0));
+            let changed = match revoke {
+                true => tree.revoke(&credential_id),
+                false => tree.reinstate(&credential_id),
+            }
+            .unwrap_or_else(|e| exit(&e.0));
+
+            if !changed {
+                println!(
+                    "Credential {} was already {}",
+                    id,
+                    if revoke { "revoked" } else { "valid" }
+                );
+            }
+        }
+    }
+
+    let updated_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("system clock after 1970")
+        .as_secs();
+    let published = tree.published_root(updated_at);
+
+    write(
+        list_path,
+        &serde_json::to_string_pretty(&tree.list()).expect("revocation list serializes"),
+    );
+    write(
+        root_path,
+        &serde_json::to_string_pretty(&published).expect("revocation root serializes"),
+    );
+    println!("Revoked credentials: {}", published.revoked_count);
+    println!("Revocation root: {}", published.revocation_root);
+}
+
+fn write(path: &str, contents: &str) {
+    fs::write(path, format!("{}\n", contents))
+        .unwrap_or_else(|e| exit(&format!("Couldn't write {}: {}", path, e)));
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
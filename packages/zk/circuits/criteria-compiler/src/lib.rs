@@ -0,0 +1,668 @@
+//! Study Criteria Compiler
+//!
+//! Compiles a declarative criteria spec (see [`spec`]) into the values the contracts
+//! and the circuits consume, so researchers no longer compute hashes by hand:
+//! - the [`Criteria`] of the criteria circuit and its `criteria_hash` commitment
+//! - the hash of every diagnosis code or prefix (`required_hash` / `prefix_hash`)
+//! - the fixed-point inputs of every lab threshold (`analyte`, `scale`, `comparison`,
+//!   `bound`, `bound_high`)
+//! - the public inputs of every metric threshold (`metric`, `min`, `max`), proved by the
+//!   metric circuit next to the criteria proof
+//! - the `setStudyCriteria(minAge, maxAge, eligibilityCodeHash)` and
+//!   `setStudyCriteriaHash(criteriaHash)` arguments
+//!
+//! `eligibilityCodeHash` is the circomlib Poseidon of the study's Circom eligibility
+//! code, which the registry checks code proofs against, not the criteria hash. The
+//! compiler cannot derive it from the criteria, so it is copied from the spec's
+//! `eligibility_code_hash` (0, no code proof, if absent). The criteria hash has its own
+//! registry slot.
+//!
+//! ## Compilation
+//! Every distinct diagnosis and lab threshold gets a predicate slot. `any_of` entries
+//! become OR nodes, the exclusions are ORed and negated, and the age predicate, the
+//! inclusions and the negated exclusions are ANDed into the result. Compilation fails
+//! if the spec needs more slots or nodes than the circuit has.
+//!
+//! Field elements are emitted as `0x`-prefixed big-endian hex, as accepted by the
+//! circuits' input parsers and by `BigInt` in the Next.js app.
+
+use std::str::FromStr;
+
+use composite_eligibility_circuit::{
+    criteria::{
+        diagnosis_signal, lab_signal, node_signal, Criteria, CriteriaNode, LabPredicate,
+        AGE_SIGNAL, CRITERIA_VERSION, MAX_DIAGNOSIS_PREDICATES, MAX_LAB_PREDICATES,
+    },
+    lab_value::{Comparison, MAX_LAB_VALUE},
+    metric::{Metric, METRIC_SCALE},
+    serialization::{format_field_element, parse_field_element},
+    units::{round_to_scale, to_canonical, Rounding},
+};
+use diagnosis_membership_circuit::{hash_diagnosis_code, prefix::hash_diagnosis_prefix};
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::Serialize;
+use thiserror::Error;
+
+pub mod analyte;
+pub mod spec;
+
+use crate::spec::{AgeSpec, CriteriaSpec, LabSpec, MetricSpec, PredicateSpec};
+
+#[derive(Debug, Error)]
+pub struct CriteriaError(pub String);
+
+impl std::fmt::Display for CriteriaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Compiled criteria, serialized as the compiler's output
+#[derive(Debug, Clone, Serialize)]
+pub struct CompiledCriteria {
+    #[serde(skip)]
+    pub criteria: Criteria,
+    pub version: u64,
+    pub criteria_hash: String,
+    pub encoding: Vec<String>, // Private `criteria` input of the criteria circuit
+    pub age: Option<AgeSpec>,
+    pub diagnoses: Vec<CompiledDiagnosis>, // In slot order
+    pub labs: Vec<CompiledLab>,            // In slot order
+    pub metrics: Vec<CompiledMetric>,      // Proved separately, in spec order
+    pub set_study_criteria: StudyCriteriaArgs,
+    pub set_study_criteria_hash: StudyCriteriaHashArgs,
+}
+
+/// Diagnosis predicate slot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompiledDiagnosis {
+    pub diagnosis: String, // As written in the spec
+    pub prefix: bool,
+    pub hash: String,
+}
+
+/// Lab predicate slot, with the public inputs of the lab value circuit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompiledLab {
+    pub loinc: String,
+    pub unit: String,
+    pub analyte: u64,
+    pub scale: u32,
+    pub comparison: u64,
+    pub bound: u64,
+    pub bound_high: u64,
+}
+
+/// Metric threshold, with the public inputs of the metric circuit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompiledMetric {
+    pub name: String,
+    pub unit: String,
+    pub metric: u64,
+    pub min: u64, // Tenths
+    pub max: u64, // Tenths
+}
+
+/// Arguments of `StudyRegistry.setStudyCriteria`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StudyCriteriaArgs {
+    pub min_age: u64,
+    pub max_age: u64,
+    pub eligibility_code_hash: String, // Circom eligibility code hash, 0 if not required
+}
+
+/// Arguments of `StudyRegistry.setStudyCriteriaHash`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StudyCriteriaHashArgs {
+    pub criteria_hash: String,
+}
+
+/// Compile a parsed spec
+pub fn compile(spec: &CriteriaSpec) -> Result<CompiledCriteria, CriteriaError> {
+    if spec.version != CRITERIA_VERSION {
+        return Err(CriteriaError(format!(
+            "Unsupported criteria spec version {} (expected {})",
+            spec.version, CRITERIA_VERSION
+        )));
+    }
+
+    let mut compiler = Compiler::default();
+    let mut terms = Vec::new();
+
+    if let Some(age) = spec.age {
+        compiler.criteria.age = Some((age.min, age.max));
+        terms.push(AGE_SIGNAL);
+    }
+    for predicate in spec.include.iter() {
+        match predicate {
+            PredicateSpec::Metric(metric) => compiler.metric(metric)?,
+            _ => terms.push(compiler.predicate(predicate)?),
+        }
+    }
+    if !spec.exclude.is_empty() {
+        let excluded = spec
+            .exclude
+            .iter()
+            .map(|predicate| compiler.predicate(predicate))
+            .collect::<Result<Vec<_>, _>>()?;
+        let excluded = compiler.fold(CriteriaNode::or, &excluded);
+        terms.push(compiler.push(CriteriaNode::not(excluded)));
+    }
+
+    if terms.is_empty() {
+        return Err(CriteriaError("Criteria spec has no criteria".to_string()));
+    }
+    let result = compiler.fold(CriteriaNode::and, &terms);
+    if compiler.criteria.nodes.is_empty() {
+        // A single predicate still needs a result node
+        compiler.push(CriteriaNode::and(result, result));
+    }
+
+    let criteria = compiler.criteria;
+    let encoding = criteria.encode().map_err(|e| CriteriaError(e.0))?;
+    let criteria_hash = format_field_element(&criteria.hash().map_err(|e| CriteriaError(e.0))?);
+    let (min_age, max_age) = criteria.age.unwrap_or_default();
+    let eligibility_code_hash = match &spec.eligibility_code_hash {
+        Some(hash) => parse_field_element(hash.trim())
+            .map_err(|e| CriteriaError(format!("Invalid eligibility_code_hash: {}", e)))?,
+        None => Fr::from(0),
+    };
+
+    Ok(CompiledCriteria {
+        version: CRITERIA_VERSION,
+        encoding: encoding.iter().map(format_field_element).collect(),
+        age: spec.age,
+        diagnoses: compiler.diagnoses,
+        labs: compiler.labs,
+        metrics: compiler.metrics,
+        set_study_criteria: StudyCriteriaArgs {
+            min_age,
+            max_age,
+            eligibility_code_hash: format_field_element(&eligibility_code_hash),
+        },
+        set_study_criteria_hash: StudyCriteriaHashArgs {
+            criteria_hash: criteria_hash.clone(),
+        },
+        criteria_hash,
+        criteria,
+    })
+}
+
+/// Parse and compile a spec
+pub fn compile_str(source: &str, format: spec::Format) -> Result<CompiledCriteria, CriteriaError> {
+    compile(&CriteriaSpec::parse(source, format)?)
+}
+
+#[derive(Default)]
+struct Compiler {
+    criteria: Criteria,
+    diagnoses: Vec<CompiledDiagnosis>,
+    labs: Vec<CompiledLab>,
+    metrics: Vec<CompiledMetric>,
+}
+
+impl Compiler {
+    /// Signal of a criterion, allocating slots and nodes
+    fn predicate(&mut self, predicate: &PredicateSpec) -> Result<usize, CriteriaError> {
+        match predicate {
+            PredicateSpec::Diagnosis(diagnosis) => self.diagnosis(diagnosis),
+            PredicateSpec::Lab(lab) => self.lab(lab),
+            PredicateSpec::Metric(metric) => Err(CriteriaError(format!(
+                "Metric criterion on {} must be a top-level inclusion",
+                metric.name.trim()
+            ))),
+            PredicateSpec::AnyOf(entries) => {
+                if entries.is_empty() {
+                    return Err(CriteriaError("Empty any_of".to_string()));
+                }
+                let signals = entries
+                    .iter()
+                    .map(|entry| self.predicate(entry))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(self.fold(CriteriaNode::or, &signals))
+            }
+        }
+    }
+
+    fn diagnosis(&mut self, diagnosis: &str) -> Result<usize, CriteriaError> {
+        let prefix = diagnosis.trim().ends_with('*');
+        let hash = if prefix {
+            hash_diagnosis_prefix(diagnosis)
+        } else {
+            hash_diagnosis_code(diagnosis)
+        }
+        .map_err(|e| CriteriaError(e.0))?;
+
+        if let Some(slot) = self.criteria.diagnoses.iter().position(|h| *h == hash) {
+            return Ok(diagnosis_signal(slot));
+        }
+        if self.criteria.diagnoses.len() == MAX_DIAGNOSIS_PREDICATES {
+            return Err(CriteriaError(format!(
+                "Too many diagnosis criteria (maximum {})",
+                MAX_DIAGNOSIS_PREDICATES
+            )));
+        }
+
+        self.criteria.diagnoses.push(hash);
+        self.diagnoses.push(CompiledDiagnosis {
+            diagnosis: diagnosis.trim().to_string(),
+            prefix,
+            hash: format_field_element(&hash),
+        });
+        Ok(diagnosis_signal(self.criteria.diagnoses.len() - 1))
+    }
+
+    fn lab(&mut self, lab: &LabSpec) -> Result<usize, CriteriaError> {
+        let compiled = compile_lab(lab)?;
+        let predicate = LabPredicate {
+            analyte: Fr::from(compiled.analyte),
+            scale: compiled.scale,
+            comparison: Comparison::from_code(compiled.comparison).unwrap(),
+            bound: compiled.bound,
+            bound_high: compiled.bound_high,
+        };
+
+        if let Some(slot) = self.criteria.labs.iter().position(|p| *p == predicate) {
+            return Ok(lab_signal(slot));
+        }
+        if self.criteria.labs.len() == MAX_LAB_PREDICATES {
+            return Err(CriteriaError(format!(
+                "Too many lab criteria (maximum {})",
+                MAX_LAB_PREDICATES
+            )));
+        }
+
+        self.criteria.labs.push(predicate);
+        self.labs.push(compiled);
+        Ok(lab_signal(self.criteria.labs.len() - 1))
+    }
+
+    fn metric(&mut self, metric: &MetricSpec) -> Result<(), CriteriaError> {
+        let compiled = compile_metric(metric)?;
+        if !self.metrics.contains(&compiled) {
+            self.metrics.push(compiled);
+        }
+        Ok(())
+    }
+
+    /// Combine `signals` left to right with `node`
+    fn fold(&mut self, node: fn(usize, usize) -> CriteriaNode, signals: &[usize]) -> usize {
+        signals[1..]
+            .iter()
+            .fold(signals[0], |acc, signal| self.push(node(acc, *signal)))
+    }
+
+    /// Append a node, returning its signal
+    fn push(&mut self, node: CriteriaNode) -> usize {
+        self.criteria.nodes.push(node);
+        node_signal(self.criteria.nodes.len() - 1)
+    }
+}
+
+/// Lab threshold in the analyte's canonical unit and scale
+///
+/// Bounds in another unit or with more decimals than the scale are rounded toward the
+/// comparison (see [`Rounding::for_bound`]), so the integer comparison accepts the same
+/// committed values.
+fn compile_lab(lab: &LabSpec) -> Result<CompiledLab, CriteriaError> {
+    let analyte = analyte::pack_loinc(&lab.loinc)?;
+    let known = analyte::lookup(&lab.loinc);
+
+    let scale = match (known, lab.scale) {
+        (Some(known), Some(scale)) if scale != known.scale => {
+            return Err(CriteriaError(format!(
+                "{} ({}) values are committed at scale {}, not {}",
+                known.name, known.loinc, known.scale, scale
+            )))
+        }
+        (Some(known), _) => known.scale,
+        (None, Some(scale)) => scale,
+        (None, None) => {
+            return Err(CriteriaError(format!(
+                "LOINC {} has no canonical scale; set `scale`",
+                lab.loinc
+            )))
+        }
+    };
+
+    let comparison = Comparison::from_str(&lab.comparison).map_err(|e| CriteriaError(e.0))?;
+    let fixed_point = |value: &spec::Decimal, upper: bool| -> Result<u64, CriteriaError> {
+        let value = value.to_string();
+        let value = match known {
+            Some(_) => to_canonical(
+                &lab.loinc,
+                &value,
+                &lab.unit,
+                scale,
+                Rounding::for_bound(comparison, upper),
+            ),
+            None => round_to_scale(&value, scale, Rounding::for_bound(comparison, upper))
+                .map(|value| u64::try_from(value).unwrap_or(u64::MAX)),
+        }
+        .map_err(|e| CriteriaError(e.0))?;
+        Some(value)
+            .filter(|value| *value <= MAX_LAB_VALUE)
+            .ok_or(CriteriaError(format!(
+                "Lab threshold of {} is too large",
+                lab.loinc
+            )))
+    };
+
+    let bound = fixed_point(&lab.value, false)?;
+    let bound_high = match (comparison, &lab.value_high) {
+        (Comparison::Between, Some(value_high)) => fixed_point(value_high, true)?,
+        (Comparison::Between, None) => {
+            return Err(CriteriaError(format!(
+                "Lab criterion on {} needs value_high",
+                lab.loinc
+            )))
+        }
+        (_, Some(_)) => {
+            return Err(CriteriaError(format!(
+                "value_high is only used with between ({})",
+                lab.loinc
+            )))
+        }
+        (_, None) => 0,
+    };
+    if comparison.interval(bound, bound_high).is_none() {
+        return Err(CriteriaError(format!(
+            "Lab criterion on {} can never hold",
+            lab.loinc
+        )));
+    }
+
+    Ok(CompiledLab {
+        loinc: lab.loinc.trim().to_string(),
+        unit: known
+            .map_or(lab.unit.trim(), |known| known.unit)
+            .to_string(),
+        analyte,
+        scale,
+        comparison: comparison.code(),
+        bound,
+        bound_high,
+    })
+}
+
+/// Metric threshold as the `[min, max]` range of the metric circuit, in tenths
+///
+/// The circuit rounds the metric down to a tenth; bounds with more decimals are rounded
+/// toward the comparison, as lab thresholds are.
+fn compile_metric(metric: &MetricSpec) -> Result<CompiledMetric, CriteriaError> {
+    let name = metric.name.trim();
+    let kind = Metric::from_str(name).map_err(|e| CriteriaError(e.0))?;
+    let comparison = Comparison::from_str(&metric.comparison).map_err(|e| CriteriaError(e.0))?;
+    let fixed_point = |value: &spec::Decimal, upper: bool| -> Result<u64, CriteriaError> {
+        let value = round_to_scale(
+            &value.to_string(),
+            METRIC_SCALE,
+            Rounding::for_bound(comparison, upper),
+        )
+        .map_err(|e| CriteriaError(e.0))?;
+        u64::try_from(value)
+            .ok()
+            .filter(|value| *value <= MAX_LAB_VALUE)
+            .ok_or(CriteriaError(format!(
+                "Metric threshold of {} is too large",
+                name
+            )))
+    };
+
+    let bound = fixed_point(&metric.value, false)?;
+    let bound_high = match (comparison, &metric.value_high) {
+        (Comparison::Between, Some(value_high)) => fixed_point(value_high, true)?,
+        (Comparison::Between, None) => {
+            return Err(CriteriaError(format!(
+                "Metric criterion on {} needs value_high",
+                name
+            )))
+        }
+        (_, Some(_)) => {
+            return Err(CriteriaError(format!(
+                "value_high is only used with between ({})",
+                name
+            )))
+        }
+        (_, None) => 0,
+    };
+    let (min, max) = comparison
+        .interval(bound, bound_high)
+        .ok_or(CriteriaError(format!(
+            "Metric criterion on {} can never hold",
+            name
+        )))?;
+
+    Ok(CompiledMetric {
+        name: kind.to_string(),
+        unit: kind.unit().to_string(),
+        metric: kind.code(),
+        min,
+        max,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use composite_eligibility_circuit::criteria::{evaluate_predicates, slot_hashes, PREDICATES};
+
+    const SPEC: &str = r#"
+version: 1
+age: { min: 18, max: 65 }
+include:
+  - diagnosis: "E11.*"
+  - lab: { loinc: "4548-4", comparison: ">", value: 7.0, unit: "%" }
+exclude:
+  - any_of:
+      - diagnosis: "I21*"
+      - diagnosis: "I63.9"
+"#;
+
+    fn eligible(compiled: &CompiledCriteria, age: u64, codes: &[&str], hba1c: u64) -> bool {
+        let slots: Vec<_> = codes
+            .iter()
+            .map(|code| slot_hashes(code).unwrap())
+            .collect();
+        let predicates: [bool; PREDICATES] =
+            evaluate_predicates(&compiled.criteria, age, &slots, &[hba1c]);
+        compiled.criteria.evaluate(&predicates)
+    }
+
+    #[test]
+    fn test_compile_spec() {
+        let compiled = compile_str(SPEC, spec::Format::Yaml).unwrap();
+
+        assert_eq!(compiled.diagnoses.len(), 3);
+        assert!(compiled.diagnoses[0].prefix && !compiled.diagnoses[2].prefix);
+        assert_eq!(
+            compiled.labs[0],
+            CompiledLab {
+                loinc: "4548-4".to_string(),
+                unit: "%".to_string(),
+                analyte: 45484,
+                scale: 1,
+                comparison: Comparison::Gt.code(),
+                bound: 70,
+                bound_high: 0,
+            }
+        );
+        assert_eq!(compiled.set_study_criteria.min_age, 18);
+        assert_eq!(
+            compiled.set_study_criteria.eligibility_code_hash,
+            format_field_element(&Fr::from(0))
+        );
+        assert_eq!(
+            compiled.set_study_criteria_hash.criteria_hash,
+            compiled.criteria_hash
+        );
+
+        // Decoding the encoding gives back the same criteria
+        let encoding = compiled.criteria.encode().unwrap();
+        assert_eq!(Criteria::decode(&encoding).unwrap(), compiled.criteria);
+    }
+
+    #[test]
+    fn test_compiled_semantics() {
+        let compiled = compile_str(SPEC, spec::Format::Yaml).unwrap();
+
+        assert!(eligible(&compiled, 40, &["E11.9"], 72));
+        assert!(!eligible(&compiled, 70, &["E11.9"], 72)); // Age
+        assert!(!eligible(&compiled, 40, &["E10.9"], 72)); // No E11
+        assert!(!eligible(&compiled, 40, &["E11.9"], 70)); // HbA1c
+        assert!(!eligible(&compiled, 40, &["E11.9", "I21.4"], 72)); // Excluded prefix
+        assert!(!eligible(&compiled, 40, &["E11.9", "I63.9"], 72)); // Excluded code
+        assert!(eligible(&compiled, 40, &["E11.9", "I63.8"], 72));
+    }
+
+    #[test]
+    fn test_compile_is_deterministic() {
+        // Same criteria written in JSON, with duplicates, give the same hash
+        let json = r#"{
+            "version": 1,
+            "age": {"min": 18, "max": 65},
+            "include": [
+                {"diagnosis": "e11*"},
+                {"lab": {"loinc": "4548-4", "comparison": ">", "value": "7.00", "unit": "%"}}
+            ],
+            "exclude": [{"any_of": [{"diagnosis": "I21.*"}, {"diagnosis": "I63.9"}]}]
+        }"#;
+        let yaml = compile_str(SPEC, spec::Format::Yaml).unwrap();
+        let json = compile_str(json, spec::Format::Json).unwrap();
+        assert_eq!(yaml.criteria_hash, json.criteria_hash);
+    }
+
+    #[test]
+    fn test_compile_keeps_eligibility_code_hash() {
+        let source = format!("{}eligibility_code_hash: \"0x1f\"\n", SPEC);
+        let compiled = compile_str(&source, spec::Format::Yaml).unwrap();
+        assert_eq!(
+            compiled.set_study_criteria.eligibility_code_hash,
+            format_field_element(&Fr::from(31))
+        );
+        assert_eq!(
+            compiled.criteria_hash,
+            compile_str(SPEC, spec::Format::Yaml).unwrap().criteria_hash
+        );
+
+        let invalid = format!("{}eligibility_code_hash: \"0xzz\"\n", SPEC);
+        assert!(compile_str(&invalid, spec::Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_compile_converts_units() {
+        let compile_lab = |lab: &str| {
+            let source = format!("version: 1\ninclude:\n  - lab: {}", lab);
+            compile_str(&source, spec::Format::Yaml)
+                .unwrap()
+                .labs
+                .remove(0)
+        };
+
+        // 53 mmol/mol is 7.0004 %: > floors to 7.0, >= ceils to 7.1
+        let gt =
+            compile_lab(r#"{ loinc: "4548-4", comparison: ">", value: 53, unit: "mmol/mol" }"#);
+        assert_eq!((gt.unit.as_str(), gt.bound), ("%", 70));
+        let ge =
+            compile_lab(r#"{ loinc: "4548-4", comparison: ">=", value: 53, unit: "mmol/mol" }"#);
+        assert_eq!(ge.bound, 71);
+
+        // 3.9 to 10 mmol/L is 70.26 to 180.16 mg/dL
+        let between = compile_lab(
+            r#"{ loinc: "2345-7", comparison: "between", value: 3.9, value_high: 10, unit: "mmol/L" }"#,
+        );
+        assert_eq!((between.bound, between.bound_high), (71, 180));
+
+        // Same threshold written in the canonical unit
+        let canonical =
+            compile_lab(r#"{ loinc: "4548-4", comparison: ">", value: 7.0, unit: "%" }"#);
+        assert_eq!(gt, canonical);
+        let upper =
+            compile_lab(r#"{ loinc: "2345-7", comparison: "<", value: 126, unit: "mg/dL" }"#);
+        assert_eq!((upper.unit.as_str(), upper.bound), ("mg/dL", 126));
+
+        // More decimals than the scale: > 7.25 % is > 7.2, >= 7.25 % is >= 7.3
+        let precise_gt =
+            compile_lab(r#"{ loinc: "4548-4", comparison: ">", value: 7.25, unit: "%" }"#);
+        assert_eq!(precise_gt.bound, 72);
+        let precise_ge =
+            compile_lab(r#"{ loinc: "4548-4", comparison: ">=", value: 7.25, unit: "%" }"#);
+        assert_eq!(precise_ge.bound, 73);
+    }
+
+    #[test]
+    fn test_compile_metrics() {
+        let source = format!(
+            "{}  - metric: {{ name: \"mean_arterial_pressure\", comparison: \"<\", value: 110 }}\n",
+            SPEC.split("exclude:").next().unwrap()
+        );
+        let compiled = compile_str(&source, spec::Format::Yaml).unwrap();
+        assert_eq!(
+            compiled.metrics,
+            vec![CompiledMetric {
+                name: "mean_arterial_pressure".to_string(),
+                unit: "mm[Hg]".to_string(),
+                metric: Metric::MeanArterialPressure.code(),
+                min: 0,
+                max: 1099,
+            }]
+        );
+        // Metrics are not part of the criteria circuit
+        let without = SPEC.split("exclude:").next().unwrap();
+        assert_eq!(
+            compiled.criteria_hash,
+            compile_str(without, spec::Format::Yaml)
+                .unwrap()
+                .criteria_hash
+        );
+
+        let compile_metric = |metric: &str| {
+            let source = format!(
+                "version: 1\nage: {{ min: 18, max: 65 }}\ninclude:\n  - metric: {}",
+                metric
+            );
+            compile_str(&source, spec::Format::Yaml)
+        };
+        let bmi = compile_metric(r#"{ name: "bmi", comparison: ">=", value: 30 }"#).unwrap();
+        assert_eq!(
+            (bmi.metrics[0].min, bmi.metrics[0].max),
+            (300, MAX_LAB_VALUE)
+        );
+        let between = compile_metric(
+            r#"{ name: "bmi", comparison: "between", value: 18.5, value_high: 24.99 }"#,
+        )
+        .unwrap();
+        assert_eq!((between.metrics[0].min, between.metrics[0].max), (185, 249));
+
+        let pack_years =
+            compile_metric(r#"{ name: "pack_years", comparison: ">=", value: 20 }"#).unwrap();
+        assert_eq!(
+            (pack_years.metrics[0].min, pack_years.metrics[0].max),
+            (200, MAX_LAB_VALUE)
+        );
+        assert!(compile_metric(r#"{ name: "packs", comparison: ">=", value: 20 }"#).is_err());
+        assert!(compile_metric(r#"{ name: "bmi", comparison: "<", value: 0 }"#).is_err());
+        assert!(compile_metric(r#"{ name: "bmi", comparison: "between", value: 30 }"#).is_err());
+        // Only top-level inclusions
+        let nested = "version: 1\nage: { min: 18, max: 65 }\nexclude:\n  - metric: { name: \"bmi\", comparison: \">\", value: 40 }";
+        assert!(compile_str(nested, spec::Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_specs() {
+        let compile_yaml = |source: &str| compile_str(source, spec::Format::Yaml);
+
+        // Unsupported unit, unknown analyte without a scale, bad LOINC check digit
+        assert!(compile_yaml("version: 1\ninclude:\n  - lab: { loinc: \"4548-4\", comparison: \">\", value: 53, unit: \"mg/dL\" }").is_err());
+        assert!(compile_yaml("version: 1\ninclude:\n  - lab: { loinc: \"1234-0\", comparison: \">\", value: 1, unit: \"mg/dL\" }").is_err());
+        assert!(compile_yaml("version: 1\ninclude:\n  - lab: { loinc: \"4548-5\", comparison: \">\", value: 7, unit: \"%\", scale: 1 }").is_err());
+        // UCUM units are case-sensitive
+        assert!(compile_yaml("version: 1\ninclude:\n  - lab: { loinc: \"2345-7\", comparison: \"<\", value: 126, unit: \"MG/DL\" }").is_err());
+        // Empty, too many slots, bad version
+        assert!(compile_yaml("version: 1").is_err());
+        assert!(compile_yaml("version: 1\ninclude:\n  - diagnosis: A00\n  - diagnosis: A01\n  - diagnosis: A02\n  - diagnosis: A03\n  - diagnosis: A04").is_err());
+        assert!(compile_yaml("version: 2\nage: { min: 18, max: 65 }").is_err());
+        // Inverted age range
+        assert!(compile_yaml("version: 1\nage: { min: 65, max: 18 }").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Criteria Spec
+//!
+//! Declarative study criteria, written by researchers in JSON or YAML:
+//!
+//! ```yaml
+//! version: 1
+//! age: { min: 18, max: 65 }
+//! include:
+//!   - diagnosis: "E11.*"          # any code under E11
+//!   - lab: { loinc: "4548-4", comparison: ">", value: 7.0, unit: "%" }
+//!   - metric: { name: "bmi", comparison: ">=", value: 30 }
+//! exclude:
+//!   - any_of:
+//!       - diagnosis: "I21*"       # any code under I21
+//!       - diagnosis: "I63.9"      # exactly I63.9
+//! ```
+//!
+//! A patient is eligible when the age is in range, every `include` entry holds and no
+//! `exclude` entry holds. A diagnosis ending in `*` is a prefix (chapter, category or
+//! subcategory); any other diagnosis is an exact code.
+//!
+//! A `metric` (`bmi` in kg/m2, `mean_arterial_pressure` in mm[Hg] or `pack_years`) is
+//! computed from attested vital signs or smoking history by its own circuit, not by
+//! the criteria circuit, so metrics
+//! may only be top-level `include` entries; each needs a metric proof next to the
+//! criteria proof.
+//!
+//! Studies that also require a Circom eligibility code proof add its circomlib Poseidon
+//! hash, as shown by the study wizard: `eligibility_code_hash: "0x…"`.
+
+use std::{fmt, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::CriteriaError;
+
+/// Study criteria spec (see the module documentation)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CriteriaSpec {
+    pub version: u64,
+    #[serde(default)]
+    pub age: Option<AgeSpec>,
+    #[serde(default)]
+    pub include: Vec<PredicateSpec>,
+    #[serde(default)]
+    pub exclude: Vec<PredicateSpec>,
+    #[serde(default)]
+    pub eligibility_code_hash: Option<String>, // Hex or decimal, see the module documentation
+}
+
+/// Inclusive age range in years
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgeSpec {
+    pub min: u64,
+    pub max: u64,
+}
+
+/// A single criterion
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PredicateSpec {
+    Diagnosis(String),
+    Lab(LabSpec),
+    Metric(MetricSpec),
+    AnyOf(Vec<PredicateSpec>), // Holds if one of the entries holds
+}
+
+/// Lab threshold: `value <comparison> value` in `unit`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabSpec {
+    pub loinc: String,
+    pub comparison: String, // "<", "<=", ">", ">=" or "between"
+    pub value: Decimal,
+    #[serde(default)]
+    pub value_high: Option<Decimal>, // Upper bound of `between`
+    pub unit: String, // UCUM
+    #[serde(default)]
+    pub scale: Option<u32>, // Required for analytes without a canonical scale
+}
+
+/// Metric threshold: `name <comparison> value` in the metric's unit
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricSpec {
+    pub name: String,       // "bmi", "mean_arterial_pressure" or "pack_years"
+    pub comparison: String, // "<", "<=", ">", ">=" or "between"
+    pub value: Decimal,
+    #[serde(default)]
+    pub value_high: Option<Decimal>, // Upper bound of `between`
+}
+
+/// Decimal written as a number (`7.0`) or a string (`"7.0"`)
+///
+/// Strings keep trailing zeros and avoid float formatting, so they are preferred for
+/// values with many digits.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Decimal {
+    Integer(u64),
+    Float(f64),
+    Text(String),
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decimal::Integer(value) => write!(f, "{}", value),
+            Decimal::Float(value) => write!(f, "{}", value),
+            Decimal::Text(value) => write!(f, "{}", value.trim()),
+        }
+    }
+}
+
+/// Spec file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Format of a spec file, from its extension
+    pub fn from_path(path: &Path) -> Result<Self, CriteriaError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        Self::from_str(extension)
+    }
+}
+
+impl FromStr for Format {
+    type Err = CriteriaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(CriteriaError(format!(
+                "Unknown spec format: {} (expected json or yaml)",
+                s
+            ))),
+        }
+    }
+}
+
+impl CriteriaSpec {
+    pub fn parse(source: &str, format: Format) -> Result<Self, CriteriaError> {
+        match format {
+            Format::Json => serde_json::from_str(source)
+                .map_err(|e| CriteriaError(format!("Invalid criteria spec: {}", e))),
+            Format::Yaml => serde_yaml::from_str(source)
+                .map_err(|e| CriteriaError(format!("Invalid criteria spec: {}", e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+version: 1
+age: { min: 18, max: 65 }
+include:
+  - diagnosis: "E11.*"
+  - lab: { loinc: "4548-4", comparison: ">", value: 7.0, unit: "%" }
+  - metric: { name: "bmi", comparison: ">=", value: 30 }
+exclude:
+  - any_of:
+      - diagnosis: "I21*"
+      - diagnosis: "I63.9"
+"#;
+
+    #[test]
+    fn test_parse_yaml_and_json() {
+        let spec = CriteriaSpec::parse(YAML, Format::Yaml).unwrap();
+        assert_eq!(spec.age, Some(AgeSpec { min: 18, max: 65 }));
+        assert_eq!(spec.include.len(), 3);
+        assert!(matches!(&spec.include[2], PredicateSpec::Metric(metric) if metric.name == "bmi"));
+        assert!(matches!(&spec.exclude[0], PredicateSpec::AnyOf(entries) if entries.len() == 2));
+
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(CriteriaSpec::parse(&json, Format::Json).unwrap(), spec);
+    }
+
+    #[test]
+    fn test_decimal_display() {
+        let lab = |value: &str| {
+            let source = format!(
+                r#"{{"loinc": "4548-4", "comparison": ">", "value": {}, "unit": "%"}}"#,
+                value
+            );
+            serde_json::from_str::<LabSpec>(&source)
+                .unwrap()
+                .value
+                .to_string()
+        };
+        assert_eq!(lab("7"), "7");
+        assert_eq!(lab("7.25"), "7.25");
+        assert_eq!(lab(r#""7.50""#), "7.50");
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        let source = r#"{"version": 1, "age": {"min": 18, "max": 65, "unit": "years"}}"#;
+        assert!(CriteriaSpec::parse(source, Format::Json).is_err());
+        assert_eq!(
+            Format::from_path(Path::new("study.yml")).unwrap(),
+            Format::Yaml
+        );
+        assert!(Format::from_path(Path::new("study.toml")).is_err());
+    }
+}
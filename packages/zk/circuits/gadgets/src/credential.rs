@@ -0,0 +1,899 @@
+//! Provider-Signed Attribute Credentials
+//!
+//! The circuits open commitments that the patient supplies, so on their own they prove
+//! "I claim". A credential ties those commitments, and the patient's identity, to a
+//! provider registered under a public root. The provider signs
+//!
+//! ```text
+//!   M = Poseidon(CREDENTIAL_VERSION, credential_id, identity_commitment,
+//!                dob_commitment, diagnosis_commitment, lab_root, issued_at)
+//! ```
+//!
+//! with EdDSA on Baby Jubjub (see [`eddsa`](crate::eddsa)), where
+//! - `identity_commitment = Poseidon(IDENTITY_COMMITMENT_TAG, identity_secret)` is handed
+//!   over by the patient at issuance. Providers attest one identity commitment per
+//!   patient record, so values derived from the secret in-circuit (nullifiers, patient
+//!   commitments) exist once per patient rather than once per chosen secret.
+//! - `dob_commitment` and `diagnosis_commitment` are the commitments the circuits open.
+//! - `lab_root` is the root of a Merkle tree of depth LAB_TREE_DEPTH over lab value
+//!   commitments, the provider's latest reading of each analyte; unused leaves are zero.
+//! - `issued_at` is the day of issuance, in days since the Unix epoch.
+//!
+//! [`CredentialChip`] verifies the signature under some registered key (see
+//! [`issuer`](crate::issuer)) and proves the credential is not revoked: the leaf at index
+//! `credential_id` of the public revocation tree is zero. Every circuit that evaluates
+//! patient data runs it. Attributes the circuit opens are passed in as cells, so the
+//! signature covers exactly the values the predicates use; the others stay private.
+//!
+//! ## Freshness
+//! A proof's epoch is an as-of day the prover picks, so on its own it says nothing about
+//! how old the attested data is. [`CredentialChip::verify_at`] also proves
+//!
+//! ```text
+//!   issued_at <= epoch <= issued_at + MAX_CREDENTIAL_AGE
+//! ```
+//!
+//! so a proof inside a verifier's freshness window uses a credential issued at most
+//! MAX_CREDENTIAL_AGE days before the oldest epoch it accepts. `issued_at` itself stays
+//! private.
+//!
+//! ## Format
+//! A credential travels as circuit inputs:
+//! - `credential_id`: chosen by the provider, unique across providers and below
+//!   2^REVOCATION_TREE_DEPTH so it can be revoked
+//! - `identity_commitment`, `dob_commitment`, `diagnosis_commitment`, `lab_root`,
+//!   `issued_at`
+//! - `provider_key`: the provider's public key `[A.x, A.y]`
+//! - `credential_signature`: `[R.x, R.y, S]`
+//! - `provider_root`, `provider_path` and `provider_index`: the key's registry path
+//! - `revocation_root` and `revocation_path`: the credential's non-revocation path
+
+use std::collections::HashMap;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+    },
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use thiserror::Error;
+
+use crate::{
+    eddsa::{verify_signature, EddsaField, EdwardsPoint, Signature, SigningKey},
+    issuer::{issuer_leaf, IssuerChip, IssuerConfig},
+    merkle::{MerkleChip, MerklePath},
+    poseidon::{PoseidonChip, PoseidonField},
+    range::{le_bits, RangeCheckChip, RangeCheckConfig},
+};
+
+/// Version of the credential message, the first element it hashes
+pub const CREDENTIAL_VERSION: u64 = 3;
+
+/// Depth of the registered provider tree (2^16 providers)
+pub const PROVIDER_TREE_DEPTH: usize = 16;
+
+/// Depth of the revocation tree: one slot per credential ID below 2^30
+pub const REVOCATION_TREE_DEPTH: usize = 30;
+
+/// Depth of the lab value tree (8 readings)
+pub const LAB_TREE_DEPTH: usize = 3;
+
+/// Maximum days between a credential's issuance and the epoch of a proof using it
+pub const MAX_CREDENTIAL_AGE: u64 = 365;
+
+/// Bits of the credential age range checks (MAX_CREDENTIAL_AGE < 2^9)
+const CREDENTIAL_AGE_BITS: usize = 9;
+
+/// Domain separation tag of identity commitments (`"\0IDCOM01"` as a big-endian integer)
+pub const IDENTITY_COMMITMENT_TAG: u64 = u64::from_be_bytes(*b"\0IDCOM01");
+
+/// Input names of the attributes, in message order
+pub const ATTRIBUTE_INPUTS: [&str; 5] = [
+    "identity_commitment",
+    "dob_commitment",
+    "diagnosis_commitment",
+    "lab_root",
+    "issued_at",
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CredentialError {
+    #[error("missing {0}")]
+    MissingInput(String),
+    #[error("invalid {name} (expected {len} values)")]
+    InvalidInput { name: String, len: usize },
+    #[error("provider_index is out of range for a tree of depth {PROVIDER_TREE_DEPTH}")]
+    InvalidProviderIndex,
+    #[error("credential ID must be below 2^{REVOCATION_TREE_DEPTH} to be revocable")]
+    UnrevocableId,
+    #[error("credential signature is invalid")]
+    InvalidSignature,
+    #[error("provider key is not registered under provider_root")]
+    UnregisteredProvider,
+    #[error("credential is revoked or revocation_path does not match revocation_root")]
+    Revoked,
+    #[error("credential was not issued within {MAX_CREDENTIAL_AGE} days before the epoch")]
+    Expired,
+}
+
+/// Identity commitment of a patient: `Poseidon(IDENTITY_COMMITMENT_TAG, identity_secret)`
+pub fn identity_commitment<F: PoseidonField>(identity_secret: F) -> F {
+    F::poseidon_hash(&[F::from(IDENTITY_COMMITMENT_TAG), identity_secret])
+}
+
+/// Slot of a credential ID in the revocation tree
+pub fn revocation_index(credential_id: &Fr) -> Result<usize, CredentialError> {
+    small_index(credential_id, REVOCATION_TREE_DEPTH).ok_or(CredentialError::UnrevocableId)
+}
+
+/// Integer value of a field element below `2^bits`
+fn small_index(value: &Fr, bits: usize) -> Option<usize> {
+    let repr = value.to_repr();
+    let (low, high) = repr.as_ref().split_at(8);
+    let low = u64::from_le_bytes(low.try_into().unwrap());
+    (high.iter().all(|byte| *byte == 0) && low < 1 << bits).then_some(low as usize)
+}
+
+/// Attributes a provider attests: commitments and the day of issuance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CredentialAttributes<T> {
+    pub identity_commitment: T, // Poseidon(IDENTITY_COMMITMENT_TAG, identity_secret)
+    pub dob_commitment: T,      // Poseidon(dob, salt)
+    pub diagnosis_commitment: T, // Poseidon(root, prefix_root, salt)
+    pub lab_root: T,            // Root of the lab value commitments
+    pub issued_at: T,           // Day of issuance (days since epoch)
+}
+
+impl<T> CredentialAttributes<T> {
+    pub fn from_array(attributes: [T; 5]) -> Self {
+        let [identity_commitment, dob_commitment, diagnosis_commitment, lab_root, issued_at] =
+            attributes;
+        Self {
+            identity_commitment,
+            dob_commitment,
+            diagnosis_commitment,
+            lab_root,
+            issued_at,
+        }
+    }
+
+    /// Attributes in message order
+    pub fn to_array(self) -> [T; 5] {
+        [
+            self.identity_commitment,
+            self.dob_commitment,
+            self.diagnosis_commitment,
+            self.lab_root,
+            self.issued_at,
+        ]
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> CredentialAttributes<U> {
+        CredentialAttributes::from_array(self.to_array().map(f))
+    }
+}
+
+impl<F: PoseidonField> CredentialAttributes<F> {
+    /// Signed message (see the module documentation)
+    pub fn message(&self, credential_id: F) -> F {
+        let mut inputs = vec![F::from(CREDENTIAL_VERSION), credential_id];
+        inputs.extend(self.to_array());
+        F::poseidon_hash(&inputs)
+    }
+}
+
+/// Attributes issued under a credential ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeCredential {
+    pub credential_id: Fr,
+    pub attributes: CredentialAttributes<Fr>,
+}
+
+impl AttributeCredential {
+    pub fn message(&self) -> Fr {
+        self.attributes.message(self.credential_id)
+    }
+
+    /// Issue the credential under a provider's key
+    pub fn sign(self, key: &SigningKey) -> SignedCredential {
+        SignedCredential {
+            credential: self,
+            provider_key: key.public_key(),
+            signature: key.sign(self.message()),
+        }
+    }
+}
+
+/// Credential with the provider's key and signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedCredential {
+    pub credential: AttributeCredential,
+    pub provider_key: EdwardsPoint<Fr>,
+    pub signature: Signature,
+}
+
+impl SignedCredential {
+    pub fn verify(&self) -> bool {
+        verify_signature(
+            &self.provider_key,
+            self.credential.message(),
+            &self.signature,
+        )
+    }
+
+    /// Add `credential_id`, the attributes, `provider_key` and `credential_signature`
+    /// to an input map
+    pub fn insert_inputs(&self, inputs: &mut HashMap<String, Vec<Fr>>) {
+        let (key, signature) = (&self.provider_key, &self.signature);
+        inputs.insert(
+            "credential_id".to_string(),
+            vec![self.credential.credential_id],
+        );
+        for (name, value) in ATTRIBUTE_INPUTS
+            .iter()
+            .zip(self.credential.attributes.to_array())
+        {
+            inputs.insert(name.to_string(), vec![value]);
+        }
+        inputs.insert("provider_key".to_string(), vec![key.x, key.y]);
+        inputs.insert(
+            "credential_signature".to_string(),
+            vec![signature.r.x, signature.r.y, signature.s],
+        );
+    }
+
+    /// Read a credential back from an input map
+    pub fn from_inputs(inputs: &HashMap<String, Vec<Fr>>) -> Result<Self, CredentialError> {
+        let key = get_input(inputs, "provider_key", 2)?;
+        let signature = get_input(inputs, "credential_signature", 3)?;
+
+        let mut attributes = [Fr::ZERO; 5];
+        for (value, name) in attributes.iter_mut().zip(ATTRIBUTE_INPUTS) {
+            *value = get_input(inputs, name, 1)?[0];
+        }
+
+        Ok(Self {
+            credential: AttributeCredential {
+                credential_id: get_input(inputs, "credential_id", 1)?[0],
+                attributes: CredentialAttributes::from_array(attributes),
+            },
+            provider_key: EdwardsPoint {
+                x: key[0],
+                y: key[1],
+            },
+            signature: Signature {
+                r: EdwardsPoint {
+                    x: signature[0],
+                    y: signature[1],
+                },
+                s: signature[2],
+            },
+        })
+    }
+}
+
+/// Credential with its registry and revocation paths, all a circuit needs to verify it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialInputs {
+    pub credential: SignedCredential,
+    pub provider_root: Fr,
+    pub provider_path: MerklePath<Fr>, // Path of issuer_leaf(provider_key)
+    pub revocation_root: Fr,
+    pub revocation_path: MerklePath<Fr>, // Path of the zero leaf at credential_id
+}
+
+impl CredentialInputs {
+    pub fn attributes(&self) -> CredentialAttributes<Fr> {
+        self.credential.credential.attributes
+    }
+
+    /// Add the credential and both paths to an input map (see the module documentation)
+    pub fn insert_inputs(&self, inputs: &mut HashMap<String, Vec<Fr>>) {
+        self.credential.insert_inputs(inputs);
+        inputs.insert("provider_root".to_string(), vec![self.provider_root]);
+        inputs.insert(
+            "provider_path".to_string(),
+            self.provider_path.siblings.clone(),
+        );
+        inputs.insert(
+            "provider_index".to_string(),
+            vec![Fr::from(self.provider_path.index as u64)],
+        );
+        inputs.insert("revocation_root".to_string(), vec![self.revocation_root]);
+        inputs.insert(
+            "revocation_path".to_string(),
+            self.revocation_path.siblings.clone(),
+        );
+    }
+
+    pub fn from_inputs(inputs: &HashMap<String, Vec<Fr>>) -> Result<Self, CredentialError> {
+        let credential = SignedCredential::from_inputs(inputs)?;
+        let provider_index = get_input(inputs, "provider_index", 1)?[0];
+
+        Ok(Self {
+            credential,
+            provider_root: get_input(inputs, "provider_root", 1)?[0],
+            provider_path: MerklePath {
+                index: small_index(&provider_index, PROVIDER_TREE_DEPTH)
+                    .ok_or(CredentialError::InvalidProviderIndex)?,
+                siblings: get_input(inputs, "provider_path", PROVIDER_TREE_DEPTH)?.to_vec(),
+            },
+            revocation_root: get_input(inputs, "revocation_root", 1)?[0],
+            revocation_path: MerklePath {
+                index: revocation_index(&credential.credential.credential_id)?,
+                siblings: get_input(inputs, "revocation_path", REVOCATION_TREE_DEPTH)?.to_vec(),
+            },
+        })
+    }
+
+    /// Client-side validation of what [`CredentialChip::verify`] enforces
+    pub fn check(&self) -> Result<(), CredentialError> {
+        if !self.credential.verify() {
+            return Err(CredentialError::InvalidSignature);
+        }
+        if self.provider_path.siblings.len() != PROVIDER_TREE_DEPTH
+            || self
+                .provider_path
+                .compute_root(issuer_leaf(&self.credential.provider_key))
+                != self.provider_root
+        {
+            return Err(CredentialError::UnregisteredProvider);
+        }
+        if self.revocation_path.siblings.len() != REVOCATION_TREE_DEPTH
+            || self.revocation_path.index
+                != revocation_index(&self.credential.credential.credential_id)?
+            || self.revocation_path.compute_root(Fr::ZERO) != self.revocation_root
+        {
+            return Err(CredentialError::Revoked);
+        }
+        Ok(())
+    }
+
+    /// Client-side validation of the freshness check of [`CredentialChip::verify_at`]
+    pub fn check_at(&self, epoch: &Fr) -> Result<(), CredentialError> {
+        let age = *epoch - self.attributes().issued_at;
+        match small_index(&age, CREDENTIAL_AGE_BITS) {
+            Some(age) if age as u64 <= MAX_CREDENTIAL_AGE => Ok(()),
+            _ => Err(CredentialError::Expired),
+        }
+    }
+
+    /// Circuit witness of a checked credential
+    pub fn witness(&self) -> CredentialWitness<Fr> {
+        let known = Value::known;
+        let (credential, signature) = (&self.credential.credential, &self.credential.signature);
+        let provider_bits = self.provider_path.bits();
+
+        CredentialWitness {
+            credential_id: known(credential.credential_id),
+            attributes: credential.attributes.map(known),
+            provider_key: Value::known(self.credential.provider_key),
+            signature_r: Value::known(signature.r),
+            signature_s: known(signature.s),
+            provider_path: std::array::from_fn(|i| known(self.provider_path.siblings[i])),
+            provider_bits: std::array::from_fn(|i| Value::known(provider_bits[i])),
+            revocation_path: std::array::from_fn(|i| known(self.revocation_path.siblings[i])),
+        }
+    }
+}
+
+/// Read an input of exactly `len` values
+fn get_input<'a>(
+    inputs: &'a HashMap<String, Vec<Fr>>,
+    name: &str,
+    len: usize,
+) -> Result<&'a [Fr], CredentialError> {
+    let values = inputs
+        .get(name)
+        .ok_or(CredentialError::MissingInput(name.to_string()))?;
+    if values.len() != len {
+        return Err(CredentialError::InvalidInput {
+            name: name.to_string(),
+            len,
+        });
+    }
+    Ok(values)
+}
+
+/// Private witnesses of [`CredentialChip::verify`]
+#[derive(Debug, Clone, Copy)]
+pub struct CredentialWitness<F: EddsaField> {
+    pub credential_id: Value<F>,
+    pub attributes: CredentialAttributes<Value<F>>, // Read for the attributes a circuit does not open
+    pub provider_key: Value<EdwardsPoint<F>>,
+    pub signature_r: Value<EdwardsPoint<F>>,
+    pub signature_s: Value<F>,
+    pub provider_path: [Value<F>; PROVIDER_TREE_DEPTH], // Bottom-up
+    pub provider_bits: [Value<bool>; PROVIDER_TREE_DEPTH],
+    pub revocation_path: [Value<F>; REVOCATION_TREE_DEPTH], // Bottom-up
+}
+
+impl<F: EddsaField> Default for CredentialWitness<F> {
+    fn default() -> Self {
+        Self {
+            credential_id: Value::unknown(),
+            attributes: CredentialAttributes::from_array([Value::unknown(); 5]),
+            provider_key: Value::unknown(),
+            signature_r: Value::unknown(),
+            signature_s: Value::unknown(),
+            provider_path: [Value::unknown(); PROVIDER_TREE_DEPTH],
+            provider_bits: [Value::unknown(); PROVIDER_TREE_DEPTH],
+            revocation_path: [Value::unknown(); REVOCATION_TREE_DEPTH],
+        }
+    }
+}
+
+/// Credential Chip Configuration
+#[derive(Debug, Clone)]
+pub struct CredentialConfig<F: PrimeField> {
+    pub value: Column<Advice>, // Version, credential ID, revocation leaf, unopened attributes
+    pub q_age: Selector,       // Credential age: issued_at, epoch, age, MAX_CREDENTIAL_AGE - age
+    pub age_check: RangeCheckConfig<F, CREDENTIAL_AGE_BITS>,
+    pub issuer: IssuerConfig,
+}
+
+/// Credential Chip
+///
+/// A verification takes the seven-input message hash, an issuer proof over a
+/// PROVIDER_TREE_DEPTH path and a REVOCATION_TREE_DEPTH path: about 6,700 rows, so
+/// circuits running it need `K >= 13`.
+#[derive(Debug, Clone)]
+pub struct CredentialChip<F: EddsaField> {
+    config: CredentialConfig<F>,
+    issuer: IssuerChip<F>,
+    merkle: MerkleChip<F>,
+    poseidon: PoseidonChip<F>,
+}
+
+impl<F: EddsaField> CredentialChip<F> {
+    pub fn construct(config: CredentialConfig<F>) -> Self {
+        let issuer = IssuerChip::construct(config.issuer.clone());
+        let merkle = MerkleChip::construct(config.issuer.merkle.clone());
+        let poseidon = PoseidonChip::construct(config.issuer.eddsa.poseidon.clone());
+        Self {
+            config,
+            issuer,
+            merkle,
+            poseidon,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, issuer: IssuerConfig) -> CredentialConfig<F> {
+        let value = meta.advice_column();
+        let running_sum = meta.advice_column();
+        let q_age = meta.selector();
+        meta.enable_equality(value);
+
+        // Gate: age = epoch - issued_at and age + slack = MAX_CREDENTIAL_AGE, with age and
+        // slack range checked, so 0 <= epoch - issued_at <= MAX_CREDENTIAL_AGE
+        meta.create_gate("credential age", |meta| {
+            let q = meta.query_selector(q_age);
+            let issued_at = meta.query_advice(value, Rotation::cur());
+            let epoch = meta.query_advice(value, Rotation::next());
+            let age = meta.query_advice(value, Rotation(2));
+            let slack = meta.query_advice(value, Rotation(3));
+            let max_age = Expression::Constant(F::from(MAX_CREDENTIAL_AGE));
+
+            vec![
+                q.clone() * (epoch - issued_at - age.clone()),
+                q * (age + slack - max_age),
+            ]
+        });
+
+        let age_check = RangeCheckChip::configure(meta, running_sum);
+
+        CredentialConfig {
+            value,
+            q_age,
+            age_check,
+            issuer,
+        }
+    }
+
+    /// Identity commitment of an assigned identity secret
+    pub fn identity_commitment(
+        &self,
+        mut layouter: impl Layouter<F>,
+        identity_secret: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let tag = layouter.assign_region(
+            || "identity commitment tag",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "tag",
+                    self.config.value,
+                    0,
+                    F::from(IDENTITY_COMMITMENT_TAG),
+                )
+            },
+        )?;
+
+        self.poseidon.hash(
+            layouter.namespace(|| "identity commitment"),
+            &[tag, identity_secret.clone()],
+        )
+    }
+
+    /// Verify a credential, returning the `provider_root` and `revocation_root` cells
+    ///
+    /// Attributes the circuit opens are passed as cells; the others are assigned from
+    /// `witness.attributes`.
+    pub fn verify(
+        &self,
+        mut layouter: impl Layouter<F>,
+        opened: CredentialAttributes<Option<&AssignedCell<F, F>>>,
+        witness: &CredentialWitness<F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let (message, credential_id, revocation_leaf) = layouter.assign_region(
+            || "credential",
+            |mut region| {
+                let value = self.config.value;
+                let version = region.assign_advice_from_constant(
+                    || "version",
+                    value,
+                    0,
+                    F::from(CREDENTIAL_VERSION),
+                )?;
+                let credential_id =
+                    region.assign_advice(|| "credential_id", value, 1, || witness.credential_id)?;
+                let revocation_leaf =
+                    region.assign_advice_from_constant(|| "revocation leaf", value, 2, F::ZERO)?;
+
+                let mut message = vec![version, credential_id.clone()];
+                let attributes = opened
+                    .to_array()
+                    .into_iter()
+                    .zip(witness.attributes.to_array());
+                for (offset, (cell, witness)) in attributes.enumerate() {
+                    message.push(match cell {
+                        Some(cell) => cell.clone(),
+                        None => {
+                            region.assign_advice(|| "attribute", value, 3 + offset, || witness)?
+                        }
+                    });
+                }
+
+                Ok((message, credential_id, revocation_leaf))
+            },
+        )?;
+
+        let message = self
+            .poseidon
+            .hash(layouter.namespace(|| "credential message"), &message)?;
+        let provider_root = self.issuer.verify(
+            layouter.namespace(|| "credential issuer"),
+            &message,
+            witness.provider_key,
+            witness.signature_r,
+            witness.signature_s,
+            &witness.provider_path,
+            &witness.provider_bits,
+        )?;
+
+        // The credential is not revoked: the leaf at index credential_id is zero
+        let revocation_bits: Vec<Value<bool>> = (0..REVOCATION_TREE_DEPTH)
+            .map(|i| {
+                witness
+                    .credential_id
+                    .map(|id| le_bits(&id, REVOCATION_TREE_DEPTH)[i])
+            })
+            .collect();
+        let (revocation_root, index) = self.merkle.compute_root_with_index(
+            layouter.namespace(|| "revocation root"),
+            &revocation_leaf,
+            &witness.revocation_path,
+            &revocation_bits,
+        )?;
+        layouter.assign_region(
+            || "revocation index",
+            |mut region| region.constrain_equal(index.cell(), credential_id.cell()),
+        )?;
+
+        Ok((provider_root, revocation_root))
+    }
+
+    /// [`verify`](Self::verify), and constrain the credential to have been issued at most
+    /// MAX_CREDENTIAL_AGE days before `epoch` (see the module documentation)
+    pub fn verify_at(
+        &self,
+        mut layouter: impl Layouter<F>,
+        opened: CredentialAttributes<Option<&AssignedCell<F, F>>>,
+        witness: &CredentialWitness<F>,
+        epoch: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let max_age = F::from(MAX_CREDENTIAL_AGE);
+        let (issued_at, age, slack) = layouter.assign_region(
+            || "credential age",
+            |mut region| {
+                let value = self.config.value;
+                self.config.q_age.enable(&mut region, 0)?;
+
+                let issued_at = match opened.issued_at {
+                    Some(cell) => cell.copy_advice(|| "issued_at", &mut region, value, 0)?,
+                    None => region.assign_advice(
+                        || "issued_at",
+                        value,
+                        0,
+                        || witness.attributes.issued_at,
+                    )?,
+                };
+                epoch.copy_advice(|| "epoch", &mut region, value, 1)?;
+
+                let age = epoch.value().copied() - issued_at.value().copied();
+                let age = region.assign_advice(|| "age", value, 2, || age)?;
+                let slack = age.value().map(|age| max_age - *age);
+                let slack = region.assign_advice(|| "slack", value, 3, || slack)?;
+
+                Ok((issued_at, age, slack))
+            },
+        )?;
+
+        let age_check = RangeCheckChip::construct(self.config.age_check.clone());
+        age_check.assign(layouter.namespace(|| "credential age range"), &age)?;
+        age_check.assign(layouter.namespace(|| "credential age slack range"), &slack)?;
+
+        let opened = CredentialAttributes {
+            issued_at: Some(&issued_at),
+            ..opened
+        };
+        self.verify(layouter, opened, witness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eddsa::EddsaChip, merkle::SparseMerkleTree};
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        plonk::{Circuit, Instance},
+    };
+
+    const K: u32 = 13;
+
+    /// Issuance day of the test credentials (2024-10-04)
+    const ISSUED_AT: u64 = 20000;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_seed(&[seed; 32]).unwrap()
+    }
+
+    fn attributes() -> CredentialAttributes<Fr> {
+        CredentialAttributes {
+            identity_commitment: identity_commitment(Fr::from(42)),
+            dob_commitment: Fr::from(11),
+            diagnosis_commitment: Fr::from(12),
+            lab_root: Fr::from(13),
+            issued_at: Fr::from(ISSUED_AT),
+        }
+    }
+
+    /// Credential 1001 of provider 5, with credential 1000 revoked
+    fn issue(signer: &SigningKey, attributes: CredentialAttributes<Fr>) -> CredentialInputs {
+        let mut providers = SparseMerkleTree::new(PROVIDER_TREE_DEPTH);
+        providers.set(5, issuer_leaf(&key(5).public_key())).unwrap();
+        let mut revocations = SparseMerkleTree::new(REVOCATION_TREE_DEPTH);
+        revocations.set(1000, Fr::ONE).unwrap();
+
+        CredentialInputs {
+            credential: AttributeCredential {
+                credential_id: Fr::from(1001),
+                attributes,
+            }
+            .sign(signer),
+            provider_root: providers.root(),
+            provider_path: providers.path(5).unwrap(),
+            revocation_root: revocations.root(),
+            revocation_path: revocations.path(1001).unwrap(),
+        }
+    }
+
+    /// Opens the identity secret and the dob commitment; instances are the dob
+    /// commitment, provider_root, revocation_root and epoch
+    #[derive(Default)]
+    struct TestCircuit {
+        identity_secret: Value<Fr>,
+        dob_commitment: Value<Fr>,
+        epoch: Value<Fr>,
+        credential: CredentialWitness<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, CredentialConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(value);
+            meta.enable_equality(instance);
+
+            let poseidon = PoseidonChip::configure(meta);
+            let eddsa = EddsaChip::configure(meta, poseidon.clone());
+            let merkle = MerkleChip::configure(meta, poseidon);
+            let issuer = IssuerChip::configure(meta, eddsa, merkle);
+            (value, CredentialChip::configure(meta, issuer), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (value, credential_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let (secret, dob_commitment, epoch) = layouter.assign_region(
+                || "opened",
+                |mut region| {
+                    Ok((
+                        region.assign_advice(|| "secret", value, 0, || self.identity_secret)?,
+                        region.assign_advice(|| "dob", value, 1, || self.dob_commitment)?,
+                        region.assign_advice(|| "epoch", value, 2, || self.epoch)?,
+                    ))
+                },
+            )?;
+
+            let chip = CredentialChip::construct(credential_config);
+            let identity = chip.identity_commitment(layouter.namespace(|| "identity"), &secret)?;
+            let opened = CredentialAttributes {
+                identity_commitment: Some(&identity),
+                dob_commitment: Some(&dob_commitment),
+                diagnosis_commitment: None,
+                lab_root: None,
+                issued_at: None,
+            };
+            let (provider_root, revocation_root) = chip.verify_at(
+                layouter.namespace(|| "credential"),
+                opened,
+                &self.credential,
+                &epoch,
+            )?;
+
+            layouter.constrain_instance(dob_commitment.cell(), instance, 0)?;
+            layouter.constrain_instance(provider_root.cell(), instance, 1)?;
+            layouter.constrain_instance(revocation_root.cell(), instance, 2)?;
+            layouter.constrain_instance(epoch.cell(), instance, 3)
+        }
+    }
+
+    fn run_at(
+        inputs: &CredentialInputs,
+        identity_secret: Fr,
+        dob_commitment: Fr,
+        epoch: Fr,
+    ) -> bool {
+        let circuit = TestCircuit {
+            identity_secret: Value::known(identity_secret),
+            dob_commitment: Value::known(dob_commitment),
+            epoch: Value::known(epoch),
+            credential: inputs.witness(),
+        };
+        let instances = vec![vec![
+            dob_commitment,
+            inputs.provider_root,
+            inputs.revocation_root,
+            epoch,
+        ]];
+        MockProver::run(K, &circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    fn run(inputs: &CredentialInputs, identity_secret: Fr, dob_commitment: Fr) -> bool {
+        run_at(
+            inputs,
+            identity_secret,
+            dob_commitment,
+            Fr::from(ISSUED_AT + 30),
+        )
+    }
+
+    #[test]
+    fn test_credential_inputs_roundtrip() {
+        let inputs = issue(&key(5), attributes());
+        assert_eq!(inputs.check(), Ok(()));
+
+        let mut map = HashMap::new();
+        inputs.insert_inputs(&mut map);
+        assert_eq!(CredentialInputs::from_inputs(&map).unwrap(), inputs);
+
+        map.remove("lab_root");
+        assert_eq!(
+            CredentialInputs::from_inputs(&map).unwrap_err(),
+            CredentialError::MissingInput("lab_root".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_rejects_bad_credentials() {
+        let mut tampered = issue(&key(5), attributes());
+        tampered.credential.credential.attributes.lab_root = Fr::from(99);
+        assert_eq!(tampered.check(), Err(CredentialError::InvalidSignature));
+
+        assert_eq!(
+            issue(&key(6), attributes()).check(),
+            Err(CredentialError::UnregisteredProvider)
+        );
+
+        let mut revoked = issue(&key(5), attributes());
+        revoked.credential = AttributeCredential {
+            credential_id: Fr::from(1000),
+            attributes: attributes(),
+        }
+        .sign(&key(5));
+        assert_eq!(revoked.check(), Err(CredentialError::Revoked));
+
+        assert_eq!(
+            revocation_index(&Fr::from(1 << REVOCATION_TREE_DEPTH)),
+            Err(CredentialError::UnrevocableId)
+        );
+        assert_eq!(
+            revocation_index(&-Fr::ONE),
+            Err(CredentialError::UnrevocableId)
+        );
+    }
+
+    #[test]
+    fn test_chip_accepts_valid_credential() {
+        assert!(run(
+            &issue(&key(5), attributes()),
+            Fr::from(42),
+            Fr::from(11)
+        ));
+    }
+
+    #[test]
+    fn test_chip_rejects_unsigned_attributes() {
+        let inputs = issue(&key(5), attributes());
+
+        // Opened values the provider did not sign
+        assert!(!run(&inputs, Fr::from(43), Fr::from(11)));
+        assert!(!run(&inputs, Fr::from(42), Fr::from(99)));
+
+        // An unopened attribute the provider did not sign
+        let mut tampered = inputs.clone();
+        tampered.credential.credential.attributes.lab_root = Fr::from(99);
+        assert!(!run(&tampered, Fr::from(42), Fr::from(11)));
+    }
+
+    #[test]
+    fn test_chip_rejects_unregistered_and_revoked_credentials() {
+        assert!(!run(
+            &issue(&key(6), attributes()),
+            Fr::from(42),
+            Fr::from(11)
+        ));
+
+        // The non-revocation path of credential 1001, for revoked credential 1000
+        let mut revoked = issue(&key(5), attributes());
+        revoked.credential = AttributeCredential {
+            credential_id: Fr::from(1000),
+            attributes: attributes(),
+        }
+        .sign(&key(5));
+        assert!(!run(&revoked, Fr::from(42), Fr::from(11)));
+    }
+
+    #[test]
+    fn test_chip_rejects_expired_credentials() {
+        let inputs = issue(&key(5), attributes());
+        let run = |epoch: u64| {
+            let epoch = Fr::from(epoch);
+            let accepted = run_at(&inputs, Fr::from(42), Fr::from(11), epoch);
+            assert_eq!(accepted, inputs.check_at(&epoch).is_ok());
+            accepted
+        };
+
+        assert!(run(ISSUED_AT));
+        assert!(run(ISSUED_AT + MAX_CREDENTIAL_AGE)); // Edge: oldest credential accepted
+        assert!(!run(ISSUED_AT + MAX_CREDENTIAL_AGE + 1));
+        assert!(!run(ISSUED_AT - 1)); // Epoch before issuance
+    }
+}
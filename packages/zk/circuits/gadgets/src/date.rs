@@ -0,0 +1,537 @@
+//! Calendar Date Chip
+//!
+//! Derives ages from dates in-circuit, so a patient cannot simply claim an age.
+//! Dates are day counts since 1970-01-01 (negative before), as issued by the provider
+//! or taken from a block timestamp.
+//!
+//! ## Date Keys
+//! Following Howard Hinnant's `civil_from_days`, a day count is split into a
+//! March-based year (starting on 0000-03-01, so the leap day ends the year), a month
+//! (0 = March .. 11 = February) and a day of the month, packed as
+//!
+//! ```text
+//!   key = year * 512 + month * 32 + day
+//! ```
+//!
+//! Keys order like dates and each March-based year holds one birthday, so the age in
+//! whole years on `as_of` of someone born on `dob` is `(key(as_of) - key(dob)) div 512`.
+//! Someone born on February 29 gets a year older on March 1 in common years.
+//!
+//! Every division of the conversion is witnessed as `n = q * d + r`, with `q`, `r`
+//! and `d - 1 - r` range checked to [`DATE_BITS`] bits, so the key is unique.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Fixed, Selector},
+    poly::Rotation,
+};
+
+use crate::range::{RangeCheckChip, RangeCheckConfig};
+
+/// Bit length of every quotient and remainder of the conversion
+///
+/// Covers the day of a 400-year era (< 146097) and eras up to year ~10^8.
+pub const DATE_BITS: usize = 18;
+
+/// Number of terms of the linear combination gate
+pub const DATE_TERMS: usize = 4;
+
+/// Key units per year (see the module documentation)
+pub const KEY_YEAR: u64 = 512;
+
+/// Days from 0000-03-01 to 1970-01-01
+const DAYS_TO_EPOCH: i64 = 719_468;
+/// Days in a 400-year era of the Gregorian calendar
+const DAYS_PER_ERA: i64 = 146_097;
+
+/// Date Chip Configuration
+#[derive(Debug, Clone)]
+pub struct DateConfig<F: PrimeField> {
+    pub terms: [Column<Advice>; DATE_TERMS],
+    pub out: Column<Advice>,
+    pub coeffs: [Column<Fixed>; DATE_TERMS],
+    pub constant: Column<Fixed>,
+    pub q_linear: Selector,
+    pub range_check: RangeCheckConfig<F, DATE_BITS>,
+}
+
+/// A term of a linear combination: an existing cell or a fresh witness
+enum Term<'a, F: PrimeField> {
+    Cell(&'a AssignedCell<F, F>),
+    Witness(Value<F>),
+}
+
+/// Date Chip
+///
+/// One gate, `out = sum(coeff_i * term_i) + constant`, carries every step of the
+/// conversion; divisions add three range checks each.
+#[derive(Debug, Clone)]
+pub struct DateChip<F: PrimeField> {
+    config: DateConfig<F>,
+}
+
+impl<F: PrimeField> DateChip<F> {
+    pub fn construct(config: DateConfig<F>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, running_sum: Column<Advice>) -> DateConfig<F> {
+        let terms: [Column<Advice>; DATE_TERMS] = std::array::from_fn(|_| meta.advice_column());
+        let out = meta.advice_column();
+        let coeffs: [Column<Fixed>; DATE_TERMS] = std::array::from_fn(|_| meta.fixed_column());
+        let constant = meta.fixed_column();
+        let q_linear = meta.selector();
+
+        for column in terms.iter().chain([&out]) {
+            meta.enable_equality(*column);
+        }
+
+        // Gate: out = sum(coeff_i * term_i) + constant
+        meta.create_gate("date linear", |meta| {
+            let q = meta.query_selector(q_linear);
+            let out = meta.query_advice(out, Rotation::cur());
+            let constant = meta.query_fixed(constant, Rotation::cur());
+
+            let sum = terms
+                .iter()
+                .zip(coeffs.iter())
+                .fold(constant, |acc, (term, coeff)| {
+                    acc + meta.query_fixed(*coeff, Rotation::cur())
+                        * meta.query_advice(*term, Rotation::cur())
+                });
+            vec![q * (out - sum)]
+        });
+
+        let range_check = RangeCheckChip::configure(meta, running_sum);
+
+        DateConfig {
+            terms,
+            out,
+            coeffs,
+            constant,
+            q_linear,
+            range_check,
+        }
+    }
+
+    /// Assign `sum(coeff_i * term_i) + constant`, returning the new cell
+    pub fn linear(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        terms: &[(F, &AssignedCell<F, F>)],
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let terms: Vec<(F, Term<F>)> = terms
+            .iter()
+            .map(|(coeff, cell)| (*coeff, Term::Cell(cell)))
+            .collect();
+
+        self.assign_linear(layouter, &terms, constant, None)
+            .map(|(_, out)| out)
+    }
+
+    /// Constrain `value` to lie in `[0, 2^DATE_BITS)`
+    pub fn range_check(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        RangeCheckChip::construct(self.config.range_check.clone())
+            .assign(layouter.namespace(|| "date range check"), value)
+    }
+
+    /// Quotient and remainder of `n` by a constant divisor
+    pub fn div_rem(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        n: &AssignedCell<F, F>,
+        divisor: u64,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let n_value = n.value().map(|n| low_u64(n));
+        let quotient = n_value.map(|n| F::from(n / divisor));
+        let remainder = n_value.map(|n| F::from(n % divisor));
+
+        // n = quotient * divisor + remainder
+        let (cells, _) = self.assign_linear(
+            layouter,
+            &[
+                (F::from(divisor), Term::Witness(quotient)),
+                (F::ONE, Term::Witness(remainder)),
+            ],
+            F::ZERO,
+            Some(n),
+        )?;
+        let (quotient, remainder) = (cells[0].clone(), cells[1].clone());
+
+        // remainder < divisor
+        let slack = self.linear(layouter, &[(-F::ONE, &remainder)], F::from(divisor - 1))?;
+
+        for cell in [&quotient, &remainder, &slack] {
+            self.range_check(layouter, cell)?;
+        }
+
+        Ok((quotient, remainder))
+    }
+
+    /// Date key (see the module documentation) of a day count since 1970-01-01
+    pub fn days_to_key(
+        &self,
+        mut layouter: impl Layouter<F>,
+        days: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let layouter = &mut layouter;
+        let one = F::ONE;
+        let c = |value: u64| F::from(value);
+
+        // Days since 0000-03-01, split into 400-year eras
+        let z = self.linear(layouter, &[(one, days)], c(DAYS_TO_EPOCH as u64))?;
+        let (era, doe) = self.div_rem(layouter, &z, DAYS_PER_ERA as u64)?;
+
+        // Year of the era
+        let (a1, _) = self.div_rem(layouter, &doe, 1460)?;
+        let (a2, _) = self.div_rem(layouter, &doe, 36524)?;
+        let (a3, _) = self.div_rem(layouter, &doe, 146096)?;
+        let t = self.linear(
+            layouter,
+            &[(one, &doe), (-one, &a1), (one, &a2), (-one, &a3)],
+            F::ZERO,
+        )?;
+        let (yoe, _) = self.div_rem(layouter, &t, 365)?;
+
+        // Day of the year
+        let (b1, _) = self.div_rem(layouter, &yoe, 4)?;
+        let (b2, _) = self.div_rem(layouter, &yoe, 100)?;
+        let doy = self.linear(
+            layouter,
+            &[(one, &doe), (-c(365), &yoe), (-one, &b1), (one, &b2)],
+            F::ZERO,
+        )?;
+
+        // Month and day of the month
+        let s = self.linear(layouter, &[(c(5), &doy)], c(2))?;
+        let (month, _) = self.div_rem(layouter, &s, 153)?;
+        let u = self.linear(layouter, &[(c(153), &month)], c(2))?;
+        let (month_start, _) = self.div_rem(layouter, &u, 5)?;
+        let day = self.linear(layouter, &[(one, &doy), (-one, &month_start)], one)?;
+
+        // key = (400 * era + yoe) * 512 + month * 32 + day
+        self.linear(
+            layouter,
+            &[
+                (c(400 * KEY_YEAR), &era),
+                (c(KEY_YEAR), &yoe),
+                (c(32), &month),
+                (one, &day),
+            ],
+            F::ZERO,
+        )
+    }
+
+    /// Age in whole years on `as_of` of someone born on `dob` (both day counts)
+    ///
+    /// Unsatisfiable if `dob` is after `as_of`.
+    pub fn age(
+        &self,
+        mut layouter: impl Layouter<F>,
+        dob: &AssignedCell<F, F>,
+        as_of: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let dob_key = self.days_to_key(layouter.namespace(|| "date of birth"), dob)?;
+        let as_of_key = self.days_to_key(layouter.namespace(|| "as-of date"), as_of)?;
+
+        let layouter = &mut layouter;
+        let diff = self.linear(
+            layouter,
+            &[(F::ONE, &as_of_key), (-F::ONE, &dob_key)],
+            F::ZERO,
+        )?;
+        let (age, _) = self.div_rem(layouter, &diff, KEY_YEAR)?;
+
+        Ok(age)
+    }
+
+    fn assign_linear(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        terms: &[(F, Term<F>)],
+        constant: F,
+        out: Option<&AssignedCell<F, F>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, AssignedCell<F, F>), Error> {
+        assert!(terms.len() <= DATE_TERMS, "too many terms");
+
+        layouter.assign_region(
+            || "date linear",
+            |mut region| {
+                self.config.q_linear.enable(&mut region, 0)?;
+                region.assign_fixed(
+                    || "constant",
+                    self.config.constant,
+                    0,
+                    || Value::known(constant),
+                )?;
+
+                let mut sum = Value::known(constant);
+                let mut cells = Vec::with_capacity(terms.len());
+                for i in 0..DATE_TERMS {
+                    let (coeff, cell) = match terms.get(i) {
+                        Some((coeff, Term::Cell(cell))) => (
+                            *coeff,
+                            cell.copy_advice(
+                                || format!("term {}", i),
+                                &mut region,
+                                self.config.terms[i],
+                                0,
+                            )?,
+                        ),
+                        Some((coeff, Term::Witness(value))) => (
+                            *coeff,
+                            region.assign_advice(
+                                || format!("term {}", i),
+                                self.config.terms[i],
+                                0,
+                                || *value,
+                            )?,
+                        ),
+                        // Unused terms are zero
+                        None => (
+                            F::ZERO,
+                            region.assign_advice(
+                                || format!("term {}", i),
+                                self.config.terms[i],
+                                0,
+                                || Value::known(F::ZERO),
+                            )?,
+                        ),
+                    };
+                    region.assign_fixed(
+                        || format!("coeff {}", i),
+                        self.config.coeffs[i],
+                        0,
+                        || Value::known(coeff),
+                    )?;
+
+                    sum = sum
+                        .zip(cell.value().copied())
+                        .map(|(sum, x)| sum + coeff * x);
+                    if i < terms.len() {
+                        cells.push(cell);
+                    }
+                }
+
+                let out = match out {
+                    Some(out) => out.copy_advice(|| "out", &mut region, self.config.out, 0)?,
+                    None => region.assign_advice(|| "out", self.config.out, 0, || sum)?,
+                };
+
+                Ok((cells, out))
+            },
+        )
+    }
+}
+
+/// Low 64 bits of a field element (assumes a little-endian `Repr`, as for bn256)
+fn low_u64<F: PrimeField>(value: &F) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&value.to_repr().as_ref()[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Day count as a field element (negative counts wrap around the modulus)
+pub fn days_to_field<F: PrimeField>(days: i64) -> F {
+    if days >= 0 {
+        F::from(days as u64)
+    } else {
+        -F::from(days.unsigned_abs())
+    }
+}
+
+/// Day count of a field element, if it is within `i64` range
+pub fn days_from_field<F: PrimeField>(value: &F) -> Option<i64> {
+    let small = |value: &F| -> Option<u64> {
+        let repr = value.to_repr();
+        let bytes = repr.as_ref();
+        bytes[8..]
+            .iter()
+            .all(|byte| *byte == 0)
+            .then(|| low_u64(value))
+    };
+
+    match (small(value), small(&-*value)) {
+        (Some(days), _) => i64::try_from(days).ok(),
+        (None, Some(days)) => i64::try_from(days).ok().map(|days| -days),
+        (None, None) => None,
+    }
+}
+
+/// Days since 1970-01-01 of a Gregorian date (month 1-12)
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * DAYS_PER_ERA + doe - DAYS_TO_EPOCH
+}
+
+/// Gregorian date (year, month 1-12, day) of a day count since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let (year, month, day) = march_date(days);
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = if month <= 2 { year + 1 } else { year };
+
+    (year, month as u32, day as u32)
+}
+
+/// Date key (see the module documentation) of a day count since 1970-01-01
+pub fn date_key(days: i64) -> i64 {
+    let (year, month, day) = march_date(days);
+    year * KEY_YEAR as i64 + month * 32 + day
+}
+
+/// Age in whole years on `as_of` of someone born on `dob` (both day counts)
+pub fn age_on(dob: i64, as_of: i64) -> i64 {
+    (date_key(as_of) - date_key(dob)).div_euclid(KEY_YEAR as i64)
+}
+
+/// March-based (year, month 0-11, day) of a day count since 1970-01-01
+fn march_date(days: i64) -> (i64, i64, i64) {
+    let z = days + DAYS_TO_EPOCH;
+    let era = z.div_euclid(DAYS_PER_ERA);
+    let doe = z - era * DAYS_PER_ERA;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let month = (5 * doy + 2) / 153;
+    let day = doy - (153 * month + 2) / 5 + 1;
+
+    (yoe + era * 400, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    #[test]
+    fn test_civil_conversions() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(
+            days_from_field(&days_to_field::<Fr>(-25_000)),
+            Some(-25_000)
+        );
+        assert_eq!(days_from_field(&Fr::from(19_782)), Some(19_782));
+
+        for days in [-25_000, -1, 0, 59, 10_957, 19_782, 60_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_age_on() {
+        let dob = days_from_civil(1990, 6, 15);
+        assert_eq!(age_on(dob, days_from_civil(2008, 6, 14)), 17);
+        assert_eq!(age_on(dob, days_from_civil(2008, 6, 15)), 18); // Birthday
+        assert_eq!(age_on(dob, days_from_civil(2009, 1, 1)), 18);
+
+        // Leap day birthdays move to March 1 in common years
+        let leap = days_from_civil(2000, 2, 29);
+        assert_eq!(age_on(leap, days_from_civil(2001, 2, 28)), 0);
+        assert_eq!(age_on(leap, days_from_civil(2001, 3, 1)), 1);
+        assert_eq!(age_on(leap, days_from_civil(2004, 2, 29)), 4);
+
+        // Born before the epoch
+        assert_eq!(
+            age_on(days_from_civil(1950, 12, 31), days_from_civil(2025, 12, 30)),
+            74
+        );
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        dob: Value<Fr>,
+        as_of: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, DateConfig<Fr>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let running_sum = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(value);
+            meta.enable_equality(instance);
+
+            (value, DateChip::configure(meta, running_sum), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (value_column, date_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let (dob, as_of) = layouter.assign_region(
+                || "dates",
+                |mut region| {
+                    let dob = region.assign_advice(|| "dob", value_column, 0, || self.dob)?;
+                    let as_of = region.assign_advice(|| "as_of", value_column, 1, || self.as_of)?;
+                    Ok((dob, as_of))
+                },
+            )?;
+
+            let chip = DateChip::construct(date_config);
+            let age = chip.age(layouter.namespace(|| "age"), &dob, &as_of)?;
+            layouter.constrain_instance(age.cell(), instance, 0)
+        }
+    }
+
+    fn run(dob: i64, as_of: i64, age: u64) -> bool {
+        let circuit = TestCircuit {
+            dob: Value::known(days_to_field(dob)),
+            as_of: Value::known(days_to_field(as_of)),
+        };
+        MockProver::run(12, &circuit, vec![vec![Fr::from(age)]])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_chip_derives_age() {
+        let dob = days_from_civil(1990, 6, 15);
+        assert!(run(dob, days_from_civil(2008, 6, 14), 17));
+        assert!(run(dob, days_from_civil(2008, 6, 15), 18));
+
+        let before_epoch = days_from_civil(1950, 12, 31);
+        assert!(run(before_epoch, days_from_civil(2025, 12, 30), 74));
+    }
+
+    #[test]
+    fn test_chip_rejects_wrong_age() {
+        let dob = days_from_civil(1990, 6, 15);
+        assert!(!run(dob, days_from_civil(2008, 6, 14), 18));
+
+        // Born after the as-of date
+        assert!(!run(
+            days_from_civil(2010, 1, 1),
+            days_from_civil(2008, 1, 1),
+            0
+        ));
+    }
+}
@@ -0,0 +1,746 @@
+//! EdDSA over Baby Jubjub
+//!
+//! Providers attest patient data by signing a field element (a Poseidon commitment
+//! to the attributes) with EdDSA on Baby Jubjub, the twisted Edwards curve
+//! `a * x^2 + y^2 = 1 + d * x^2 * y^2` over the bn256 scalar field (EIP-2494).
+//! Points have native coordinates, so a signature is verified in-circuit without
+//! non-native arithmetic.
+//!
+//! ```text
+//!   A = s * B                              public key of the secret scalar s
+//!   r = Poseidon(tag, s, M) mod l          deterministic nonce
+//!   R = r * B
+//!   h = Poseidon(R.x, R.y, A.x, A.y, M)
+//!   S = r + h * s mod l
+//!
+//!   valid  <=>  S < l  and  S * B = R + h * A
+//! ```
+//!
+//! `B` generates the subgroup of prime order `l` (circomlib's "Base8"). `a` is a
+//! square and `d` is not, so the addition law is complete: the same formula adds,
+//! doubles and handles the identity `(0, 1)`.
+//!
+//! ## Layout
+//! Scalar multiplication is double-and-add over the little-endian bits of the
+//! scalar, one row per bit:
+//!
+//! ```text
+//!   acc_{i+1} = acc_i + (b_i ? P_i : identity)     P_{i+1} = 2 * P_i
+//!   z_i       = 2 * z_{i+1} + b_i                  z_0 = scalar, z_n = 0
+//! ```
+//!
+//! The running sum `z` doubles as a range check, so `S` is proven to fit in
+//! [`SIGNATURE_SCALAR_BITS`] bits. The challenge takes all [`CHALLENGE_BITS`] bits
+//! of the field; a decomposition of `h + p` would only change the challenge, which
+//! gives a forger nothing.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    halo2curves::{bn256::Fr, ff::PrimeField},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::{
+    poseidon::{PoseidonChip, PoseidonConfig, PoseidonField},
+    range::le_bits,
+};
+
+/// Bit length of the signature scalar `S` (the subgroup order is below 2^251)
+pub const SIGNATURE_SCALAR_BITS: usize = 251;
+
+/// Bit length of the challenge `h`, a full field element
+pub const CHALLENGE_BITS: usize = 254;
+
+/// Minimum seed length of a signing key
+pub const MIN_SIGNING_SEED_BYTES: usize = 32;
+
+/// Order of the subgroup generated by the base point
+const SUBGROUP_ORDER: &str =
+    "2736030358979909402780800718157159386076813972158567259200215660948447373041";
+
+/// Domain separation tag of the nonce hash
+const NONCE_TAG: &[u8; 8] = b"\0EDDSA01";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EddsaError {
+    #[error("signing key seed has {0} bytes (expected at least {MIN_SIGNING_SEED_BYTES})")]
+    ShortSeed(usize),
+    #[error("signing key seed reduces to zero")]
+    ZeroKey,
+}
+
+/// Fields carrying a twisted Edwards curve for EdDSA
+pub trait EddsaField: PoseidonField {
+    /// Curve coefficient `a`
+    fn edwards_a() -> Self;
+
+    /// Curve coefficient `d`
+    fn edwards_d() -> Self;
+
+    /// Generator of the prime-order subgroup
+    fn edwards_base() -> EdwardsPoint<Self>;
+}
+
+impl EddsaField for Fr {
+    fn edwards_a() -> Fr {
+        Fr::from(168700)
+    }
+
+    fn edwards_d() -> Fr {
+        Fr::from(168696)
+    }
+
+    fn edwards_base() -> EdwardsPoint<Fr> {
+        EdwardsPoint {
+            x: Fr::from_str_vartime(
+                "5299619240641551281634865583518297030282874472190772894086521144482721001553",
+            )
+            .unwrap(),
+            y: Fr::from_str_vartime(
+                "16950150798460657717958625567821834550301663161624707787222815936182638968203",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// Affine point of the Edwards curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdwardsPoint<F> {
+    pub x: F,
+    pub y: F,
+}
+
+impl<F: EddsaField> EdwardsPoint<F> {
+    pub fn identity() -> Self {
+        Self {
+            x: F::ZERO,
+            y: F::ONE,
+        }
+    }
+
+    pub fn is_on_curve(&self) -> bool {
+        let (xx, yy) = (self.x.square(), self.y.square());
+        F::edwards_a() * xx + yy == F::ONE + F::edwards_d() * xx * yy
+    }
+
+    /// Sum of two points (complete for points on the curve)
+    pub fn add(&self, other: &Self) -> Self {
+        let t = F::edwards_d() * self.x * other.x * self.y * other.y;
+        let x = (self.x * other.y + self.y * other.x) * (F::ONE + t).invert().unwrap();
+        let y =
+            (self.y * other.y - F::edwards_a() * self.x * other.x) * (F::ONE - t).invert().unwrap();
+        Self { x, y }
+    }
+
+    pub fn double(&self) -> Self {
+        self.add(self)
+    }
+
+    /// Multiple of the point by a scalar given as little-endian bits (what the chip computes)
+    pub fn mul_bits(&self, bits: &[bool]) -> Self {
+        let mut acc = Self::identity();
+        let mut base = *self;
+        for bit in bits {
+            if *bit {
+                acc = acc.add(&base);
+            }
+            base = base.double();
+        }
+        acc
+    }
+}
+
+/// EdDSA challenge `Poseidon(R.x, R.y, A.x, A.y, M)`
+pub fn challenge<F: EddsaField>(
+    r: &EdwardsPoint<F>,
+    public_key: &EdwardsPoint<F>,
+    message: F,
+) -> F {
+    F::poseidon_hash(&[r.x, r.y, public_key.x, public_key.y, message])
+}
+
+fn subgroup_order() -> BigUint {
+    SUBGROUP_ORDER.parse().unwrap()
+}
+
+fn to_biguint(value: &Fr) -> BigUint {
+    BigUint::from_bytes_le(value.to_repr().as_ref())
+}
+
+/// Field element of an integer below the modulus
+fn from_biguint(value: &BigUint) -> Fr {
+    let mut repr = [0u8; 32];
+    let bytes = value.to_bytes_le();
+    repr[..bytes.len()].copy_from_slice(&bytes);
+    Fr::from_repr(repr).unwrap()
+}
+
+/// EdDSA signature: the nonce point `R` and the scalar `S`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub r: EdwardsPoint<Fr>,
+    pub s: Fr,
+}
+
+/// Secret signing key of a provider
+#[derive(Clone)]
+pub struct SigningKey {
+    scalar: BigUint, // In [1, l)
+}
+
+impl SigningKey {
+    /// Derive a signing key from a high-entropy seed (reduced modulo the subgroup order)
+    pub fn from_seed(seed: &[u8]) -> Result<Self, EddsaError> {
+        if seed.len() < MIN_SIGNING_SEED_BYTES {
+            return Err(EddsaError::ShortSeed(seed.len()));
+        }
+
+        let scalar = BigUint::from_bytes_le(seed) % subgroup_order();
+        if scalar == BigUint::default() {
+            return Err(EddsaError::ZeroKey);
+        }
+
+        Ok(Self { scalar })
+    }
+
+    pub fn public_key(&self) -> EdwardsPoint<Fr> {
+        Fr::edwards_base().mul_bits(&biguint_bits(&self.scalar))
+    }
+
+    /// Sign a field element
+    pub fn sign(&self, message: Fr) -> Signature {
+        let order = subgroup_order();
+        let secret = from_biguint(&self.scalar);
+        let tag = Fr::from(u64::from_be_bytes(*NONCE_TAG));
+
+        let nonce = to_biguint(&Fr::poseidon_hash(&[tag, secret, message])) % &order;
+        let r = Fr::edwards_base().mul_bits(&biguint_bits(&nonce));
+        let h = to_biguint(&challenge(&r, &self.public_key(), message));
+        let s = (nonce + h * &self.scalar) % &order;
+
+        Signature {
+            r,
+            s: from_biguint(&s),
+        }
+    }
+}
+
+fn biguint_bits(value: &BigUint) -> Vec<bool> {
+    (0..value.bits()).map(|i| value.bit(i)).collect()
+}
+
+/// Host-side verification, as done by [`EddsaChip::verify`]
+///
+/// The chip bounds `S` by 2^SIGNATURE_SCALAR_BITS rather than `l`; signers always
+/// produce a reduced `S`.
+pub fn verify_signature(public_key: &EdwardsPoint<Fr>, message: Fr, signature: &Signature) -> bool {
+    if !public_key.is_on_curve() || !signature.r.is_on_curve() {
+        return false;
+    }
+    if to_biguint(&signature.s) >= subgroup_order() {
+        return false;
+    }
+
+    let h = challenge(&signature.r, public_key, message);
+    let left = Fr::edwards_base().mul_bits(&le_bits(&signature.s, SIGNATURE_SCALAR_BITS));
+    let right = signature
+        .r
+        .add(&public_key.mul_bits(&le_bits(&h, CHALLENGE_BITS)));
+    left == right
+}
+
+/// Point assigned in-circuit as `[x, y]`
+pub type AssignedPoint<F> = [AssignedCell<F, F>; 2];
+
+/// EdDSA Chip Configuration
+#[derive(Debug, Clone)]
+pub struct EddsaConfig {
+    pub x: Column<Advice>, // Accumulator / first addend / result
+    pub y: Column<Advice>,
+    pub px: Column<Advice>, // Doubled base point
+    pub py: Column<Advice>,
+    pub sx: Column<Advice>, // Second addend
+    pub sy: Column<Advice>,
+    pub bit: Column<Advice>,
+    pub scalar: Column<Advice>, // Running sum of the scalar bits
+    pub q_mul: Selector,
+    pub q_add: Selector,
+    pub q_on_curve: Selector,
+    pub poseidon: PoseidonConfig,
+}
+
+/// EdDSA Chip
+///
+/// A verification takes a five-input Poseidon hash plus
+/// `SIGNATURE_SCALAR_BITS + CHALLENGE_BITS + 6` rows of its own columns.
+#[derive(Debug, Clone)]
+pub struct EddsaChip<F: EddsaField> {
+    config: EddsaConfig,
+    poseidon: PoseidonChip<F>,
+}
+
+impl<F: EddsaField> EddsaChip<F> {
+    pub fn construct(config: EddsaConfig) -> Self {
+        let poseidon = PoseidonChip::construct(config.poseidon.clone());
+        Self { config, poseidon }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, poseidon: PoseidonConfig) -> EddsaConfig {
+        let [x, y, px, py, sx, sy, bit, scalar] = [(); 8].map(|_| meta.advice_column());
+        let q_mul = meta.selector();
+        let q_add = meta.selector();
+        let q_on_curve = meta.selector();
+
+        for column in [x, y, px, py, sx, sy, scalar] {
+            meta.enable_equality(column);
+        }
+
+        let one = || Expression::Constant(F::ONE);
+        let a = || Expression::Constant(F::edwards_a());
+        let d = || Expression::Constant(F::edwards_d());
+
+        // Gate: (x_next, y_next) = (x, y) + (sx, sy)
+        meta.create_gate("edwards add", |meta| {
+            let q = meta.query_selector(q_mul) + meta.query_selector(q_add);
+            let x_next = meta.query_advice(x, Rotation::next());
+            let y_next = meta.query_advice(y, Rotation::next());
+            let x = meta.query_advice(x, Rotation::cur());
+            let y = meta.query_advice(y, Rotation::cur());
+            let sx = meta.query_advice(sx, Rotation::cur());
+            let sy = meta.query_advice(sy, Rotation::cur());
+
+            let t = d() * x.clone() * y.clone() * sx.clone() * sy.clone();
+            vec![
+                q.clone()
+                    * (x_next * (one() + t.clone())
+                        - (x.clone() * sy.clone() + y.clone() * sx.clone())),
+                q * (y_next * (one() - t) - (y * sy - a() * x * sx)),
+            ]
+        });
+
+        // Gate: one double-and-add step
+        meta.create_gate("edwards mul step", |meta| {
+            let q = meta.query_selector(q_mul);
+            let px_next = meta.query_advice(px, Rotation::next());
+            let py_next = meta.query_advice(py, Rotation::next());
+            let px = meta.query_advice(px, Rotation::cur());
+            let py = meta.query_advice(py, Rotation::cur());
+            let sx = meta.query_advice(sx, Rotation::cur());
+            let sy = meta.query_advice(sy, Rotation::cur());
+            let bit = meta.query_advice(bit, Rotation::cur());
+            let z_cur = meta.query_advice(scalar, Rotation::cur());
+            let z_next = meta.query_advice(scalar, Rotation::next());
+
+            let t = d() * px.clone() * px.clone() * py.clone() * py.clone();
+            vec![
+                // bit ∈ {0, 1}
+                q.clone() * bit.clone() * (one() - bit.clone()),
+                // (sx, sy) = bit ? (px, py) : identity
+                q.clone() * (sx - bit.clone() * px.clone()),
+                q.clone() * (sy - (one() + bit.clone() * (py.clone() - one()))),
+                // (px_next, py_next) = 2 * (px, py)
+                q.clone() * (px_next * (one() + t.clone()) - px.clone() * py.clone() * F::from(2)),
+                q.clone() * (py_next * (one() - t) - (py.clone() * py - a() * px.clone() * px)),
+                // z_i = 2 * z_{i+1} + bit
+                q * (z_cur - (z_next * F::from(2) + bit)),
+            ]
+        });
+
+        // Gate: a * x^2 + y^2 = 1 + d * x^2 * y^2
+        meta.create_gate("edwards on curve", |meta| {
+            let q = meta.query_selector(q_on_curve);
+            let x = meta.query_advice(x, Rotation::cur());
+            let y = meta.query_advice(y, Rotation::cur());
+
+            let (xx, yy) = (x.clone() * x, y.clone() * y);
+            vec![q * (a() * xx.clone() + yy.clone() - (one() + d() * xx * yy))]
+        });
+
+        EddsaConfig {
+            x,
+            y,
+            px,
+            py,
+            sx,
+            sy,
+            bit,
+            scalar,
+            q_mul,
+            q_add,
+            q_on_curve,
+            poseidon,
+        }
+    }
+
+    /// Constrain an assigned point to lie on the curve
+    pub fn assert_on_curve(
+        &self,
+        mut layouter: impl Layouter<F>,
+        point: &AssignedPoint<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "on curve",
+            |mut region| {
+                self.config.q_on_curve.enable(&mut region, 0)?;
+                point[0].copy_advice(|| "x", &mut region, self.config.x, 0)?;
+                point[1].copy_advice(|| "y", &mut region, self.config.y, 0)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Sum of two assigned points
+    pub fn add(
+        &self,
+        mut layouter: impl Layouter<F>,
+        lhs: &AssignedPoint<F>,
+        rhs: &AssignedPoint<F>,
+    ) -> Result<AssignedPoint<F>, Error> {
+        layouter.assign_region(
+            || "edwards add",
+            |mut region| {
+                self.config.q_add.enable(&mut region, 0)?;
+                let x = lhs[0].copy_advice(|| "lhs x", &mut region, self.config.x, 0)?;
+                let y = lhs[1].copy_advice(|| "lhs y", &mut region, self.config.y, 0)?;
+                let sx = rhs[0].copy_advice(|| "rhs x", &mut region, self.config.sx, 0)?;
+                let sy = rhs[1].copy_advice(|| "rhs y", &mut region, self.config.sy, 0)?;
+
+                let sum = point_value(&x, &y)
+                    .zip(point_value(&sx, &sy))
+                    .map(|(lhs, rhs)| lhs.add(&rhs));
+                self.assign_point(&mut region, 1, sum)
+            },
+        )
+    }
+
+    /// Multiple of an assigned point by the `num_bits`-bit value of `scalar`
+    ///
+    /// Also constrains `scalar` to `[0, 2^num_bits)` (up to the field modulus).
+    pub fn mul(
+        &self,
+        mut layouter: impl Layouter<F>,
+        point: &AssignedPoint<F>,
+        scalar: &AssignedCell<F, F>,
+        num_bits: usize,
+    ) -> Result<AssignedPoint<F>, Error> {
+        layouter.assign_region(
+            || format!("edwards mul {} bits", num_bits),
+            |mut region| {
+                let bits = scalar.value().map(|v| le_bits(v, num_bits));
+
+                let mut acc = [
+                    region.assign_advice_from_constant(
+                        || "identity x",
+                        self.config.x,
+                        0,
+                        F::ZERO,
+                    )?,
+                    region.assign_advice_from_constant(
+                        || "identity y",
+                        self.config.y,
+                        0,
+                        F::ONE,
+                    )?,
+                ];
+                let mut base = [
+                    point[0].copy_advice(|| "px", &mut region, self.config.px, 0)?,
+                    point[1].copy_advice(|| "py", &mut region, self.config.py, 0)?,
+                ];
+                let mut z = scalar.copy_advice(|| "z_0", &mut region, self.config.scalar, 0)?;
+
+                for i in 0..num_bits {
+                    self.config.q_mul.enable(&mut region, i)?;
+
+                    let bit = bits.as_ref().map(|bits| bits[i]);
+                    region.assign_advice(
+                        || "bit",
+                        self.config.bit,
+                        i,
+                        || bit.map(|bit| F::from(bit as u64)),
+                    )?;
+
+                    let p = point_value(&base[0], &base[1]);
+                    let selected = p
+                        .zip(bit)
+                        .map(|(p, bit)| if bit { p } else { EdwardsPoint::identity() });
+                    region.assign_advice(|| "sx", self.config.sx, i, || selected.map(|s| s.x))?;
+                    region.assign_advice(|| "sy", self.config.sy, i, || selected.map(|s| s.y))?;
+
+                    let sum = point_value(&acc[0], &acc[1])
+                        .zip(selected)
+                        .map(|(acc, selected)| acc.add(&selected));
+                    acc = self.assign_point(&mut region, i + 1, sum)?;
+
+                    let doubled = p.map(|p| p.double());
+                    base = [
+                        region.assign_advice(
+                            || "px",
+                            self.config.px,
+                            i + 1,
+                            || doubled.map(|p| p.x),
+                        )?,
+                        region.assign_advice(
+                            || "py",
+                            self.config.py,
+                            i + 1,
+                            || doubled.map(|p| p.y),
+                        )?,
+                    ];
+
+                    let z_next = z
+                        .value()
+                        .copied()
+                        .zip(bit)
+                        .map(|(z, bit)| (z - F::from(bit as u64)) * F::TWO_INV);
+                    z = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        self.config.scalar,
+                        i + 1,
+                        || z_next,
+                    )?;
+                }
+
+                // Every bit has been consumed
+                region.constrain_constant(z.cell(), F::ZERO)?;
+
+                Ok(acc)
+            },
+        )
+    }
+
+    /// Verify a signature on `message` under `public_key`
+    ///
+    /// `r` and `s` are the private signature witnesses.
+    pub fn verify(
+        &self,
+        mut layouter: impl Layouter<F>,
+        public_key: &AssignedPoint<F>,
+        message: &AssignedCell<F, F>,
+        r: Value<EdwardsPoint<F>>,
+        s: Value<F>,
+    ) -> Result<(), Error> {
+        let (r, s, base) = layouter.assign_region(
+            || "signature",
+            |mut region| {
+                self.config.q_on_curve.enable(&mut region, 0)?;
+                let r = self.assign_point(&mut region, 0, r)?;
+                let s = region.assign_advice(|| "s", self.config.scalar, 0, || s)?;
+
+                let b = F::edwards_base();
+                let base = [
+                    region.assign_advice_from_constant(|| "base x", self.config.px, 0, b.x)?,
+                    region.assign_advice_from_constant(|| "base y", self.config.py, 0, b.y)?,
+                ];
+                Ok((r, s, base))
+            },
+        )?;
+        self.assert_on_curve(layouter.namespace(|| "public key on curve"), public_key)?;
+
+        let h = self.poseidon.hash(
+            layouter.namespace(|| "challenge"),
+            &[
+                r[0].clone(),
+                r[1].clone(),
+                public_key[0].clone(),
+                public_key[1].clone(),
+                message.clone(),
+            ],
+        )?;
+
+        // S * B = R + h * A
+        let left = self.mul(
+            layouter.namespace(|| "s * base"),
+            &base,
+            &s,
+            SIGNATURE_SCALAR_BITS,
+        )?;
+        let h_a = self.mul(
+            layouter.namespace(|| "h * public key"),
+            public_key,
+            &h,
+            CHALLENGE_BITS,
+        )?;
+        let right = self.add(layouter.namespace(|| "r + h * public key"), &r, &h_a)?;
+
+        layouter.assign_region(
+            || "signature equation",
+            |mut region| {
+                region.constrain_equal(left[0].cell(), right[0].cell())?;
+                region.constrain_equal(left[1].cell(), right[1].cell())
+            },
+        )
+    }
+
+    fn assign_point(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        point: Value<EdwardsPoint<F>>,
+    ) -> Result<AssignedPoint<F>, Error> {
+        Ok([
+            region.assign_advice(|| "x", self.config.x, offset, || point.map(|p| p.x))?,
+            region.assign_advice(|| "y", self.config.y, offset, || point.map(|p| p.y))?,
+        ])
+    }
+}
+
+fn point_value<F: EddsaField>(
+    x: &AssignedCell<F, F>,
+    y: &AssignedCell<F, F>,
+) -> Value<EdwardsPoint<F>> {
+    x.value()
+        .copied()
+        .zip(y.value().copied())
+        .map(|(x, y)| EdwardsPoint { x, y })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::ff::Field,
+        plonk::{Circuit, Instance},
+    };
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_seed(&[seed; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_base_point_generates_the_subgroup() {
+        let base = Fr::edwards_base();
+        assert!(base.is_on_curve());
+        assert_eq!(
+            base.mul_bits(&biguint_bits(&subgroup_order())),
+            EdwardsPoint::identity()
+        );
+    }
+
+    #[test]
+    fn test_signatures_verify() {
+        let key = key(1);
+        let message = Fr::from(42);
+        let signature = key.sign(message);
+        assert!(verify_signature(&key.public_key(), message, &signature));
+
+        assert!(!verify_signature(
+            &key.public_key(),
+            Fr::from(43),
+            &signature
+        ));
+        assert!(!verify_signature(
+            &self::key(2).public_key(),
+            message,
+            &signature
+        ));
+    }
+
+    #[test]
+    fn test_signing_key_rejects_bad_seeds() {
+        assert_eq!(
+            SigningKey::from_seed(&[1u8; 31]).err(),
+            Some(EddsaError::ShortSeed(31))
+        );
+        assert_eq!(
+            SigningKey::from_seed(&[0u8; 32]).err(),
+            Some(EddsaError::ZeroKey)
+        );
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        r: Value<EdwardsPoint<Fr>>,
+        s: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, EddsaConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(value);
+            meta.enable_equality(instance);
+
+            let poseidon = PoseidonChip::configure(meta);
+            (value, EddsaChip::configure(meta, poseidon), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (value, eddsa_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            // Public key and message from the instance column
+            let cells = layouter.assign_region(
+                || "public values",
+                |mut region| {
+                    (0..3)
+                        .map(|row| {
+                            region.assign_advice_from_instance(
+                                || "public",
+                                instance,
+                                row,
+                                value,
+                                row,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+
+            let chip = EddsaChip::construct(eddsa_config);
+            let public_key = [cells[0].clone(), cells[1].clone()];
+            chip.verify(
+                layouter.namespace(|| "verify"),
+                &public_key,
+                &cells[2],
+                self.r,
+                self.s,
+            )
+        }
+    }
+
+    fn run(public_key: EdwardsPoint<Fr>, message: Fr, signature: &Signature) -> bool {
+        let circuit = TestCircuit {
+            r: Value::known(signature.r),
+            s: Value::known(signature.s),
+        };
+        let instances = vec![vec![public_key.x, public_key.y, message]];
+        MockProver::run(10, &circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_chip_accepts_valid_signatures() {
+        let key = key(1);
+        let message = Fr::from(42);
+        assert!(run(key.public_key(), message, &key.sign(message)));
+    }
+
+    #[test]
+    fn test_chip_rejects_forged_signatures() {
+        let key = key(1);
+        let signature = key.sign(Fr::from(42));
+
+        assert!(!run(key.public_key(), Fr::from(43), &signature));
+        assert!(!run(self::key(2).public_key(), Fr::from(42), &signature));
+
+        let mut tampered = signature;
+        tampered.s += Fr::ONE;
+        assert!(!run(key.public_key(), Fr::from(42), &tampered));
+    }
+}
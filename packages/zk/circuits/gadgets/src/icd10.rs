@@ -0,0 +1,379 @@
+//! ICD-10 Code Hashing
+//!
+//! Canonical, versioned encoding of ICD-10 diagnosis codes as field elements.
+//! Every layer that commits to a diagnosis (circuits, wrappers, WASM bindings)
+//! must go through [`hash_icd10_code`] so that the same code always maps to the
+//! same leaf.
+//!
+//! ## Version 1
+//! 1. Normalize: trim, uppercase, drop the dot (`"e11.9"` -> `"E119"`), then check
+//!    the shape `[A-Z][0-9][A-Z0-9]{1,5}` (3 to 7 characters).
+//! 2. Pack the ASCII bytes big-endian into one field element.
+//! 3. Hash `Poseidon(version_tag, packed, length)`.
+//! 4. Keep the low [`CODE_HASH_BITS`] bits, so code hashes can be ordered and
+//!    compared in-circuit with a range check.
+//!
+//! ## Prefixes
+//! Category-level criteria ("any code under E11") match on a prefix of the
+//! normalized code at one of the [`PrefixDepth`]s. A prefix is hashed as
+//! `Poseidon(prefix_tag, packed, length)`, and [`icd10_prefix_hashes`] gives the
+//! hash of every prefix of a code. Depths the code does not reach (the
+//! subcategory of "I10") hash an empty prefix (`packed = 0`), which no valid
+//! public prefix can match.
+
+use halo2_proofs::halo2curves::ff::PrimeField;
+use thiserror::Error;
+
+use crate::poseidon::PoseidonField;
+
+/// Bit length of a code hash
+pub const CODE_HASH_BITS: usize = 248;
+
+/// Longest normalized ICD-10 code (e.g. "S72001A")
+pub const MAX_CODE_LENGTH: usize = 7;
+/// Shortest normalized ICD-10 code, a bare category (e.g. "I10")
+pub const MIN_CODE_LENGTH: usize = 3;
+
+/// Number of prefix levels a code commits to (see [`PrefixDepth`])
+pub const PREFIX_DEPTHS: usize = 3;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Icd10Error {
+    #[error("Invalid ICD-10 code: {0:?}")]
+    InvalidCode(String),
+    #[error("Invalid ICD-10 prefix: {0:?}")]
+    InvalidPrefix(String),
+    #[error("Unsupported code hash version: {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// Version of the code hashing scheme
+///
+/// Commitments record the version they were built with; a new version must
+/// never change the output of an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashVersion {
+    V1,
+}
+
+impl HashVersion {
+    /// Version used for new commitments
+    pub const CURRENT: HashVersion = HashVersion::V1;
+
+    /// Domain separation tag absorbed before the code
+    fn tag(&self) -> u64 {
+        match self {
+            HashVersion::V1 => u64::from_be_bytes(*b"\0ICD10v1"),
+        }
+    }
+
+    /// Domain separation tag absorbed before a prefix
+    fn prefix_tag(&self) -> u64 {
+        match self {
+            HashVersion::V1 => u64::from_be_bytes(*b"\0ICD10p1"),
+        }
+    }
+}
+
+/// Level of the ICD-10 hierarchy a prefix stops at
+///
+/// The chapter is the leading letter: ICD-10 chapters are blocks of categories
+/// sharing it (a few letters are split or shared, e.g. C and D).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixDepth {
+    Chapter,     // "E"
+    Category,    // "E11"
+    Subcategory, // "E119"
+}
+
+impl PrefixDepth {
+    /// Every depth, from the root of the hierarchy down
+    pub const ALL: [PrefixDepth; PREFIX_DEPTHS] = [
+        PrefixDepth::Chapter,
+        PrefixDepth::Category,
+        PrefixDepth::Subcategory,
+    ];
+
+    /// Length of a normalized prefix at this depth
+    pub fn prefix_len(&self) -> usize {
+        match self {
+            PrefixDepth::Chapter => 1,
+            PrefixDepth::Category => MIN_CODE_LENGTH,
+            PrefixDepth::Subcategory => MIN_CODE_LENGTH + 1,
+        }
+    }
+
+    fn from_len(len: usize) -> Option<Self> {
+        PrefixDepth::ALL
+            .into_iter()
+            .find(|depth| depth.prefix_len() == len)
+    }
+}
+
+impl TryFrom<u32> for HashVersion {
+    type Error = Icd10Error;
+
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        match version {
+            1 => Ok(HashVersion::V1),
+            _ => Err(Icd10Error::UnsupportedVersion(version)),
+        }
+    }
+}
+
+impl From<HashVersion> for u32 {
+    fn from(version: HashVersion) -> u32 {
+        match version {
+            HashVersion::V1 => 1,
+        }
+    }
+}
+
+/// Canonical form of an ICD-10 code: uppercase, without the dot
+pub fn normalize_icd10_code(code: &str) -> Result<String, Icd10Error> {
+    let invalid = || Icd10Error::InvalidCode(code.to_string());
+    let trimmed = code.trim().to_ascii_uppercase();
+
+    // The dot may only separate the category from the rest ("E11.9")
+    let normalized = match trimmed.split_once('.') {
+        Some((category, rest)) if category.len() == MIN_CODE_LENGTH && !rest.is_empty() => {
+            format!("{}{}", category, rest)
+        }
+        Some(_) => return Err(invalid()),
+        None => trimmed,
+    };
+
+    let bytes = normalized.as_bytes();
+    let valid = (MIN_CODE_LENGTH..=MAX_CODE_LENGTH).contains(&bytes.len())
+        && bytes[0].is_ascii_uppercase()
+        && bytes[1].is_ascii_digit()
+        && bytes[2..]
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+
+    if !valid {
+        return Err(invalid());
+    }
+
+    Ok(normalized)
+}
+
+/// Hash an ICD-10 code with the given scheme version
+pub fn hash_icd10_code<F: PoseidonField>(
+    code: &str,
+    version: HashVersion,
+) -> Result<F, Icd10Error> {
+    let normalized = normalize_icd10_code(code)?;
+
+    match version {
+        HashVersion::V1 => {
+            let hash = F::poseidon_hash(&[
+                F::from(version.tag()),
+                F::from(pack(&normalized)),
+                F::from(normalized.len() as u64),
+            ]);
+
+            Ok(truncate(hash, CODE_HASH_BITS))
+        }
+    }
+}
+
+/// Canonical form of an ICD-10 prefix and its depth
+///
+/// Accepts a chapter letter ("E"), a category ("E11") or a subcategory ("E11.9"),
+/// optionally followed by a wildcard ("E11.*", "E11*").
+pub fn normalize_icd10_prefix(prefix: &str) -> Result<(String, PrefixDepth), Icd10Error> {
+    let invalid = || Icd10Error::InvalidPrefix(prefix.to_string());
+    let trimmed = prefix.trim().to_ascii_uppercase();
+    let trimmed = trimmed.strip_suffix('*').unwrap_or(&trimmed);
+    let trimmed = trimmed.strip_suffix('.').unwrap_or(trimmed);
+
+    if trimmed.len() == PrefixDepth::Chapter.prefix_len() {
+        if !trimmed.as_bytes()[0].is_ascii_uppercase() {
+            return Err(invalid());
+        }
+        return Ok((trimmed.to_string(), PrefixDepth::Chapter));
+    }
+
+    let normalized = normalize_icd10_code(trimmed).map_err(|_| invalid())?;
+    let depth = PrefixDepth::from_len(normalized.len()).ok_or_else(invalid)?;
+    Ok((normalized, depth))
+}
+
+/// Hash an ICD-10 prefix with the given scheme version
+pub fn hash_icd10_prefix<F: PoseidonField>(
+    prefix: &str,
+    version: HashVersion,
+) -> Result<F, Icd10Error> {
+    let (normalized, depth) = normalize_icd10_prefix(prefix)?;
+    Ok(prefix_hash(&normalized, depth, version))
+}
+
+/// Hashes of every prefix of `code`, indexed like [`PrefixDepth::ALL`]
+///
+/// A prefix longer than the code hashes as empty.
+pub fn icd10_prefix_hashes<F: PoseidonField>(
+    code: &str,
+    version: HashVersion,
+) -> Result<[F; PREFIX_DEPTHS], Icd10Error> {
+    let normalized = normalize_icd10_code(code)?;
+
+    Ok(PrefixDepth::ALL.map(|depth| {
+        let prefix = normalized.get(..depth.prefix_len()).unwrap_or("");
+        prefix_hash(prefix, depth, version)
+    }))
+}
+
+fn prefix_hash<F: PoseidonField>(prefix: &str, depth: PrefixDepth, version: HashVersion) -> F {
+    match version {
+        HashVersion::V1 => F::poseidon_hash(&[
+            F::from(version.prefix_tag()),
+            F::from(pack(prefix)),
+            F::from(depth.prefix_len() as u64),
+        ]),
+    }
+}
+
+/// ASCII bytes packed big-endian (codes are at most 7 bytes)
+fn pack(normalized: &str) -> u64 {
+    normalized
+        .bytes()
+        .fold(0u64, |acc, byte| (acc << 8) | byte as u64)
+}
+
+/// Keep the low `bits` bits (assumes a little-endian `Repr`, as for bn256)
+fn truncate<F: PrimeField>(value: F, bits: usize) -> F {
+    let mut repr = value.to_repr();
+    for (i, byte) in repr.as_mut().iter_mut().enumerate() {
+        if i * 8 >= bits {
+            *byte = 0;
+        } else if (i + 1) * 8 > bits {
+            *byte &= (1u8 << (bits - i * 8)) - 1;
+        }
+    }
+    F::from_repr(repr).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::halo2curves::bn256::Fr;
+
+    fn hash(code: &str) -> Fr {
+        hash_icd10_code(code, HashVersion::V1).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_icd10_code() {
+        assert_eq!(normalize_icd10_code("E11.9").unwrap(), "E119");
+        assert_eq!(normalize_icd10_code(" e11.9 ").unwrap(), "E119");
+        assert_eq!(normalize_icd10_code("E119").unwrap(), "E119");
+        assert_eq!(normalize_icd10_code("I10").unwrap(), "I10");
+        assert_eq!(normalize_icd10_code("S72.001A").unwrap(), "S72001A");
+
+        for invalid in [
+            "",
+            "E1",
+            "E11.",
+            "E1.19",
+            "11.9",
+            "EE1.9",
+            "E11.9.1",
+            "S72.001AB",
+            "E11-9",
+        ] {
+            assert!(
+                normalize_icd10_code(invalid).is_err(),
+                "{:?} must be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_equivalent_spellings_share_a_hash() {
+        assert_eq!(hash("E11.9"), hash("e119"));
+        assert_eq!(hash("I10"), hash(" i10"));
+    }
+
+    #[test]
+    fn test_distinct_codes_do_not_collide() {
+        // Same bytes in a different order
+        assert_ne!(hash("E11.9"), hash("E19.1"));
+        // Prefix of another code
+        assert_ne!(hash("E11"), hash("E11.0"));
+        assert_ne!(hash("I10"), hash("I01"));
+    }
+
+    #[test]
+    fn test_code_hash_fits_in_248_bits() {
+        for code in ["E11.9", "I10", "J45.909", "C50.911"] {
+            assert_eq!(hash(code).to_repr().as_ref()[31], 0);
+        }
+    }
+
+    fn prefix(prefix: &str) -> Fr {
+        hash_icd10_prefix(prefix, HashVersion::V1).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_icd10_prefix() {
+        assert_eq!(
+            normalize_icd10_prefix("e").unwrap(),
+            ("E".to_string(), PrefixDepth::Chapter)
+        );
+        assert_eq!(
+            normalize_icd10_prefix("E11.*").unwrap(),
+            ("E11".to_string(), PrefixDepth::Category)
+        );
+        assert_eq!(
+            normalize_icd10_prefix("E11*").unwrap(),
+            ("E11".to_string(), PrefixDepth::Category)
+        );
+        assert_eq!(
+            normalize_icd10_prefix("E11.9").unwrap(),
+            ("E119".to_string(), PrefixDepth::Subcategory)
+        );
+
+        for invalid in ["", "*", "1", "E1", "E11.65", "S72.001A"] {
+            assert!(
+                normalize_icd10_prefix(invalid).is_err(),
+                "{:?} must be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_code_commits_to_its_prefixes() {
+        let hashes: [Fr; PREFIX_DEPTHS] = icd10_prefix_hashes("E11.65", HashVersion::V1).unwrap();
+        assert_eq!(hashes, [prefix("E"), prefix("E11"), prefix("E11.6")]);
+
+        // Siblings share the upper levels only
+        let sibling: [Fr; PREFIX_DEPTHS] = icd10_prefix_hashes("E11.9", HashVersion::V1).unwrap();
+        assert_eq!(sibling[..2], hashes[..2]);
+        assert_ne!(sibling[2], hashes[2]);
+
+        // A bare category has no subcategory to match
+        let category: [Fr; PREFIX_DEPTHS] = icd10_prefix_hashes("I10", HashVersion::V1).unwrap();
+        assert_eq!(category[1], prefix("I10"));
+        assert!(["I10.0", "I10.9"].iter().all(|p| prefix(p) != category[2]));
+    }
+
+    #[test]
+    fn test_prefix_hashes_are_domain_separated() {
+        // A category prefix is not the code of the same name
+        assert_ne!(prefix("I10"), hash("I10"));
+        assert_ne!(prefix("E"), prefix("E11"));
+    }
+
+    #[test]
+    fn test_hash_version() {
+        assert_eq!(HashVersion::try_from(1).unwrap(), HashVersion::V1);
+        assert_eq!(u32::from(HashVersion::CURRENT), 1);
+        assert_eq!(
+            HashVersion::try_from(2),
+            Err(Icd10Error::UnsupportedVersion(2))
+        );
+    }
+}
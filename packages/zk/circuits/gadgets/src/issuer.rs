@@ -0,0 +1,234 @@
+//! Anonymous Issuer
+//!
+//! Proves that a message was signed by *some* key of a registered set, without revealing
+//! which one. Registered keys are the leaves
+//!
+//! ```text
+//!   leaf = Poseidon(A.x, A.y)
+//! ```
+//!
+//! of a Poseidon Merkle tree (see [`merkle`](crate::merkle)). The chip takes the key as a
+//! private witness, verifies the signature under it (see [`eddsa`](crate::eddsa)) and
+//! recomputes the root from the key's leaf, so only the root is public.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error},
+};
+
+use crate::{
+    eddsa::{EddsaChip, EddsaConfig, EddsaField, EdwardsPoint},
+    merkle::{MerkleChip, MerkleConfig},
+    poseidon::PoseidonChip,
+};
+
+/// Leaf of a registered key
+pub fn issuer_leaf<F: EddsaField>(key: &EdwardsPoint<F>) -> F {
+    F::poseidon_hash(&[key.x, key.y])
+}
+
+/// Issuer Chip Configuration
+#[derive(Debug, Clone)]
+pub struct IssuerConfig {
+    pub key: Column<Advice>, // Private key coordinates, x then y
+    pub eddsa: EddsaConfig,
+    pub merkle: MerkleConfig,
+}
+
+/// Issuer Chip
+///
+/// A proof takes one signature verification, one two-input Poseidon hash for the leaf
+/// and one Merkle path.
+#[derive(Debug, Clone)]
+pub struct IssuerChip<F: EddsaField> {
+    config: IssuerConfig,
+    eddsa: EddsaChip<F>,
+    merkle: MerkleChip<F>,
+    poseidon: PoseidonChip<F>,
+}
+
+impl<F: EddsaField> IssuerChip<F> {
+    pub fn construct(config: IssuerConfig) -> Self {
+        let eddsa = EddsaChip::construct(config.eddsa.clone());
+        let merkle = MerkleChip::construct(config.merkle.clone());
+        let poseidon = PoseidonChip::construct(config.eddsa.poseidon.clone());
+        Self {
+            config,
+            eddsa,
+            merkle,
+            poseidon,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        eddsa: EddsaConfig,
+        merkle: MerkleConfig,
+    ) -> IssuerConfig {
+        let key = meta.advice_column();
+        meta.enable_equality(key);
+
+        IssuerConfig { key, eddsa, merkle }
+    }
+
+    /// Verify a signature on `message` by the private `key`, returning the root cell
+    /// of the registry the key belongs to
+    ///
+    /// `siblings` and `bits` are the key's authentication path, bottom-up.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: &AssignedCell<F, F>,
+        key: Value<EdwardsPoint<F>>,
+        r: Value<EdwardsPoint<F>>,
+        s: Value<F>,
+        siblings: &[Value<F>],
+        bits: &[Value<bool>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let key = layouter.assign_region(
+            || "issuer key",
+            |mut region| {
+                Ok([
+                    region.assign_advice(|| "key x", self.config.key, 0, || key.map(|k| k.x))?,
+                    region.assign_advice(|| "key y", self.config.key, 1, || key.map(|k| k.y))?,
+                ])
+            },
+        )?;
+
+        self.eddsa.verify(
+            layouter.namespace(|| "issuer signature"),
+            &key,
+            message,
+            r,
+            s,
+        )?;
+
+        let leaf = self
+            .poseidon
+            .hash(layouter.namespace(|| "issuer leaf"), &key)?;
+        self.merkle
+            .compute_root(layouter.namespace(|| "issuer root"), &leaf, siblings, bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eddsa::SigningKey, merkle::MerkleTree};
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    const DEPTH: usize = 3;
+
+    fn keys() -> Vec<SigningKey> {
+        (1..=5u8)
+            .map(|seed| SigningKey::from_seed(&[seed; 32]).unwrap())
+            .collect()
+    }
+
+    fn registry() -> MerkleTree<Fr> {
+        let leaves: Vec<Fr> = keys()
+            .iter()
+            .map(|key| issuer_leaf(&key.public_key()))
+            .collect();
+        MerkleTree::new(&leaves, DEPTH).unwrap()
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        key: Value<EdwardsPoint<Fr>>,
+        r: Value<EdwardsPoint<Fr>>,
+        s: Value<Fr>,
+        siblings: Vec<Value<Fr>>,
+        bits: Vec<Value<bool>>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, IssuerConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                siblings: vec![Value::unknown(); DEPTH],
+                bits: vec![Value::unknown(); DEPTH],
+                ..Self::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(value);
+            meta.enable_equality(instance);
+
+            let poseidon = PoseidonChip::configure(meta);
+            let eddsa = EddsaChip::configure(meta, poseidon.clone());
+            let merkle = MerkleChip::configure(meta, poseidon);
+            (value, IssuerChip::configure(meta, eddsa, merkle), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (value, issuer_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let message = layouter.assign_region(
+                || "message",
+                |mut region| {
+                    region.assign_advice_from_instance(|| "message", instance, 0, value, 0)
+                },
+            )?;
+
+            let chip = IssuerChip::construct(issuer_config);
+            let root = chip.verify(
+                layouter.namespace(|| "issuer"),
+                &message,
+                self.key,
+                self.r,
+                self.s,
+                &self.siblings,
+                &self.bits,
+            )?;
+            layouter.constrain_instance(root.cell(), instance, 1)
+        }
+    }
+
+    fn run(signer: &SigningKey, index: usize, message: Fr, root: Fr) -> bool {
+        let path = registry().path(index).unwrap();
+        let signature = signer.sign(message);
+        let circuit = TestCircuit {
+            key: Value::known(signer.public_key()),
+            r: Value::known(signature.r),
+            s: Value::known(signature.s),
+            siblings: path.siblings.iter().map(|s| Value::known(*s)).collect(),
+            bits: path.bits().into_iter().map(Value::known).collect(),
+        };
+        MockProver::run(11, &circuit, vec![vec![message, root]])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_chip_accepts_registered_issuers() {
+        let root = registry().root();
+        for (index, key) in keys().iter().enumerate() {
+            assert!(run(key, index, Fr::from(42), root));
+        }
+    }
+
+    #[test]
+    fn test_chip_rejects_unregistered_issuers() {
+        let outsider = SigningKey::from_seed(&[9u8; 32]).unwrap();
+        assert!(!run(&outsider, 0, Fr::from(42), registry().root()));
+
+        // A registered key, but another registry
+        let other = MerkleTree::new(&[Fr::from(1)], DEPTH).unwrap();
+        assert!(!run(&keys()[0], 0, Fr::from(42), other.root()));
+    }
+}
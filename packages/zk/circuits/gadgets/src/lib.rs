@@ -0,0 +1,52 @@
+//! Reusable Halo2 gadgets for the clinical trial eligibility circuits
+//!
+//! The circuit crates (`composite`, `diagnosis`) share these chips so that every
+//! predicate is enforced with the same constraints.
+//!
+//! ## Gadgets
+//! - `range`: bit-decomposition range check, proves `0 <= value < 2^NUM_BITS`
+//! - `poseidon`: Poseidon hash matching the `poseidon` crate sponge used on the host
+//! - `merkle`: Poseidon Merkle trees, dense and sparse (host-side), and root recomputation (in-circuit)
+//! - `date`: day counts to calendar date keys, for ages derived from a date of birth
+//! - `metric`: BMI, pack-years and mean arterial pressure from raw measurements
+//! - `icd10`: canonical, versioned hashing of ICD-10 diagnosis codes and their prefixes
+//! - `eddsa`: EdDSA over Baby Jubjub, provider signing (host-side) and verification (in-circuit)
+//! - `issuer`: signature by some key of a registered set, without revealing which one
+//! - `credential`: provider-signed attribute credentials, verified with their registry and revocation paths
+//! - `rng`: proof blinding randomness shared by the circuit crates (host-side)
+
+pub mod credential;
+pub mod date;
+pub mod eddsa;
+pub mod icd10;
+pub mod issuer;
+pub mod merkle;
+pub mod metric;
+pub mod poseidon;
+pub mod range;
+pub mod rng;
+
+pub use credential::{
+    identity_commitment, revocation_index, AttributeCredential, CredentialAttributes,
+    CredentialChip, CredentialConfig, CredentialError, CredentialInputs, CredentialWitness,
+    SignedCredential, CREDENTIAL_VERSION, LAB_TREE_DEPTH, MAX_CREDENTIAL_AGE, PROVIDER_TREE_DEPTH,
+    REVOCATION_TREE_DEPTH,
+};
+pub use date::{
+    age_on, civil_from_days, days_from_civil, days_from_field, days_to_field, DateChip, DateConfig,
+};
+pub use eddsa::{
+    verify_signature, AssignedPoint, EddsaChip, EddsaConfig, EddsaError, EddsaField, EdwardsPoint,
+    Signature, SigningKey,
+};
+pub use icd10::{
+    hash_icd10_code, hash_icd10_prefix, icd10_prefix_hashes, normalize_icd10_code,
+    normalize_icd10_prefix, HashVersion, Icd10Error, PrefixDepth,
+};
+pub use issuer::{issuer_leaf, IssuerChip, IssuerConfig};
+pub use merkle::{MerkleChip, MerkleConfig, MerkleError, MerklePath, MerkleTree, SparseMerkleTree};
+pub use metric::{
+    bmi_tenths, mean_arterial_pressure_tenths, pack_years_tenths, MetricChip, MetricConfig,
+};
+pub use poseidon::{PoseidonChip, PoseidonConfig, PoseidonField};
+pub use range::{RangeCheckChip, RangeCheckConfig};
@@ -0,0 +1,624 @@
+//! Poseidon Merkle Tree
+//!
+//! Host-side tree construction plus an in-circuit chip that recomputes the root
+//! from a leaf and its authentication path. [`SparseMerkleTree`] only stores non-zero
+//! leaves, for trees keyed by large identifiers; its paths are ordinary [`MerklePath`]s.
+//!
+//! Parents are `Poseidon(left, right)`; unused leaves are zero. The path bits
+//! (the leaf index, little-endian) are private witnesses, so a proof reveals
+//! neither the position of the leaf nor the size of the set.
+
+use std::collections::HashMap;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use thiserror::Error;
+
+use crate::poseidon::{PoseidonChip, PoseidonConfig, PoseidonField};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MerkleError {
+    #[error("{leaves} leaves do not fit in a tree of depth {depth}")]
+    TooManyLeaves { leaves: usize, depth: usize },
+    #[error("leaf index {index} is out of range for a tree of depth {depth}")]
+    IndexOutOfRange { index: usize, depth: usize },
+}
+
+/// Parent node of two children
+pub fn hash_pair<F: PoseidonField>(left: F, right: F) -> F {
+    F::poseidon_hash(&[left, right])
+}
+
+/// Fixed-depth Poseidon Merkle tree
+#[derive(Debug, Clone)]
+pub struct MerkleTree<F: PoseidonField> {
+    depth: usize,
+    levels: Vec<Vec<F>>, // levels[0] = leaves, levels[depth] = [root]
+}
+
+impl<F: PoseidonField> MerkleTree<F> {
+    /// Build a tree of `2^depth` leaves, padding `leaves` with zeros
+    pub fn new(leaves: &[F], depth: usize) -> Result<Self, MerkleError> {
+        if leaves.len() > 1 << depth {
+            return Err(MerkleError::TooManyLeaves {
+                leaves: leaves.len(),
+                depth,
+            });
+        }
+
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, F::ZERO);
+
+        let mut levels = vec![level];
+        for _ in 0..depth {
+            let parents = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(pair[0], pair[1]))
+                .collect();
+            levels.push(parents);
+        }
+
+        Ok(Self { depth, levels })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> F {
+        self.levels[self.depth][0]
+    }
+
+    pub fn leaves(&self) -> &[F] {
+        &self.levels[0]
+    }
+
+    /// Authentication path of the leaf at `index`
+    pub fn path(&self, index: usize) -> Result<MerklePath<F>, MerkleError> {
+        if index >= 1 << self.depth {
+            return Err(MerkleError::IndexOutOfRange {
+                index,
+                depth: self.depth,
+            });
+        }
+
+        let siblings = (0..self.depth)
+            .map(|level| self.levels[level][(index >> level) ^ 1])
+            .collect();
+
+        Ok(MerklePath { index, siblings })
+    }
+}
+
+/// Authentication path from a leaf to the root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath<F: PoseidonField> {
+    pub index: usize,
+    pub siblings: Vec<F>, // Bottom-up
+}
+
+impl<F: PoseidonField> MerklePath<F> {
+    /// Path bits, little-endian (bit `i` set = node is a right child at level `i`)
+    pub fn bits(&self) -> Vec<bool> {
+        (0..self.siblings.len())
+            .map(|level| (self.index >> level) & 1 == 1)
+            .collect()
+    }
+
+    /// Root reached by hashing `leaf` up the path
+    pub fn compute_root(&self, leaf: F) -> F {
+        self.siblings
+            .iter()
+            .zip(self.bits())
+            .fold(leaf, |node, (sibling, is_right)| {
+                if is_right {
+                    hash_pair(*sibling, node)
+                } else {
+                    hash_pair(node, *sibling)
+                }
+            })
+    }
+}
+
+/// Sparse Poseidon Merkle tree of `2^depth` leaves, all zero until set
+///
+/// Hashes like a [`MerkleTree`] of the same leaves, but stores only the nodes above
+/// non-zero leaves, so `depth` can be as large as the leaf index type allows.
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<F: PoseidonField> {
+    depth: usize,
+    empty: Vec<F>,                     // empty[level] = root of an all-zero subtree
+    nodes: HashMap<(usize, usize), F>, // (level, index) -> node, non-empty nodes only
+}
+
+impl<F: PoseidonField> SparseMerkleTree<F> {
+    pub fn new(depth: usize) -> Self {
+        assert!(
+            depth < usize::BITS as usize,
+            "sparse tree depth exceeds the index width"
+        );
+
+        let mut empty = vec![F::ZERO];
+        for level in 0..depth {
+            empty.push(hash_pair(empty[level], empty[level]));
+        }
+
+        Self {
+            depth,
+            empty,
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> F {
+        self.node(self.depth, 0)
+    }
+
+    pub fn leaf(&self, index: usize) -> F {
+        self.node(0, index)
+    }
+
+    /// Set the leaf at `index` (zero clears it) and update its ancestors
+    pub fn set(&mut self, index: usize, leaf: F) -> Result<(), MerkleError> {
+        self.check_index(index)?;
+
+        let mut node = leaf;
+        for level in 0..=self.depth {
+            let position = index >> level;
+            if node == self.empty[level] {
+                self.nodes.remove(&(level, position));
+            } else {
+                self.nodes.insert((level, position), node);
+            }
+
+            if level < self.depth {
+                let sibling = self.node(level, position ^ 1);
+                node = match position & 1 {
+                    0 => hash_pair(node, sibling),
+                    _ => hash_pair(sibling, node),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Authentication path of the leaf at `index`
+    pub fn path(&self, index: usize) -> Result<MerklePath<F>, MerkleError> {
+        self.check_index(index)?;
+
+        let siblings = (0..self.depth)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect();
+
+        Ok(MerklePath { index, siblings })
+    }
+
+    fn node(&self, level: usize, index: usize) -> F {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    fn check_index(&self, index: usize) -> Result<(), MerkleError> {
+        if index >> self.depth != 0 {
+            return Err(MerkleError::IndexOutOfRange {
+                index,
+                depth: self.depth,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Merkle Chip Configuration
+#[derive(Debug, Clone)]
+pub struct MerkleConfig {
+    pub node: Column<Advice>,
+    pub sibling: Column<Advice>,
+    pub bit: Column<Advice>,
+    pub left: Column<Advice>,
+    pub right: Column<Advice>,
+    pub index: Column<Advice>, // Running sum of the path bits
+    pub q_swap: Selector,
+    pub poseidon: PoseidonConfig,
+}
+
+/// Merkle Chip
+///
+/// Each level takes one swap region (two rows) plus one two-input Poseidon hash.
+#[derive(Debug, Clone)]
+pub struct MerkleChip<F: PoseidonField> {
+    config: MerkleConfig,
+    poseidon: PoseidonChip<F>,
+}
+
+impl<F: PoseidonField> MerkleChip<F> {
+    pub fn construct(config: MerkleConfig) -> Self {
+        let poseidon = PoseidonChip::construct(config.poseidon.clone());
+        Self { config, poseidon }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, poseidon: PoseidonConfig) -> MerkleConfig {
+        let node = meta.advice_column();
+        let sibling = meta.advice_column();
+        let bit = meta.advice_column();
+        let left = meta.advice_column();
+        let right = meta.advice_column();
+        let index = meta.advice_column();
+        let q_swap = meta.selector();
+
+        for column in [node, sibling, bit, left, right, index] {
+            meta.enable_equality(column);
+        }
+
+        // Gate: order (node, sibling) by the path bit
+        meta.create_gate("merkle swap", |meta| {
+            let q = meta.query_selector(q_swap);
+            let node = meta.query_advice(node, Rotation::cur());
+            let sibling = meta.query_advice(sibling, Rotation::cur());
+            let bit = meta.query_advice(bit, Rotation::cur());
+            let left = meta.query_advice(left, Rotation::cur());
+            let right = meta.query_advice(right, Rotation::cur());
+            let z_cur = meta.query_advice(index, Rotation::cur());
+            let z_next = meta.query_advice(index, Rotation::next());
+
+            let swap = bit.clone() * (sibling.clone() - node.clone());
+            vec![
+                // bit ∈ {0, 1}
+                q.clone() * bit.clone() * (Expression::Constant(F::ONE) - bit.clone()),
+                // left = bit ? sibling : node
+                q.clone() * (left - (node.clone() + swap.clone())),
+                // right = bit ? node : sibling
+                q.clone() * (right - (sibling - swap)),
+                // z_level = 2 * z_{level+1} + bit, so z_0 is the leaf index
+                q * (z_cur - (z_next * F::from(2) + bit)),
+            ]
+        });
+
+        MerkleConfig {
+            node,
+            sibling,
+            bit,
+            left,
+            right,
+            index,
+            q_swap,
+            poseidon,
+        }
+    }
+
+    /// Recompute the root from `leaf`, returning the root cell
+    ///
+    /// `siblings` and `bits` are bottom-up and must have the same length.
+    pub fn compute_root(
+        &self,
+        layouter: impl Layouter<F>,
+        leaf: &AssignedCell<F, F>,
+        siblings: &[Value<F>],
+        bits: &[Value<bool>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.compute_root_with_index(layouter, leaf, siblings, bits)
+            .map(|(root, _)| root)
+    }
+
+    /// Like [`MerkleChip::compute_root`], also returning a cell holding the leaf
+    /// index (`sum bits[i] * 2^i`), e.g. to prove that two leaves are adjacent
+    pub fn compute_root_with_index(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: &AssignedCell<F, F>,
+        siblings: &[Value<F>],
+        bits: &[Value<bool>],
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        assert_eq!(siblings.len(), bits.len(), "path length mismatch");
+        let depth = bits.len();
+
+        // z_level = sum_{j >= level} bits[j] * 2^(j - level)
+        let running_sums: Vec<Value<F>> = (0..=depth)
+            .map(|level| {
+                bits[level..]
+                    .iter()
+                    .rev()
+                    .fold(Value::known(F::ZERO), |acc, bit| {
+                        acc.zip(*bit)
+                            .map(|(acc, bit)| acc * F::from(2) + F::from(bit as u64))
+                    })
+            })
+            .collect();
+
+        let mut node = leaf.clone();
+        let mut index = None;
+        let mut z_next: Option<AssignedCell<F, F>> = None;
+        for (level, (sibling, bit)) in siblings.iter().zip(bits.iter()).enumerate() {
+            let (left, right, z_cur, z) = layouter.assign_region(
+                || format!("merkle swap level {}", level),
+                |mut region| {
+                    self.config.q_swap.enable(&mut region, 0)?;
+
+                    let node = node.copy_advice(|| "node", &mut region, self.config.node, 0)?;
+                    region.assign_advice(|| "sibling", self.config.sibling, 0, || *sibling)?;
+                    region.assign_advice(
+                        || "bit",
+                        self.config.bit,
+                        0,
+                        || bit.map(|bit| F::from(bit as u64)),
+                    )?;
+
+                    let ordered = node.value().copied().zip(*sibling).zip(*bit).map(
+                        |((node, sibling), is_right)| {
+                            if is_right {
+                                (sibling, node)
+                            } else {
+                                (node, sibling)
+                            }
+                        },
+                    );
+
+                    let left = region.assign_advice(
+                        || "left",
+                        self.config.left,
+                        0,
+                        || ordered.map(|(left, _)| left),
+                    )?;
+                    let right = region.assign_advice(
+                        || "right",
+                        self.config.right,
+                        0,
+                        || ordered.map(|(_, right)| right),
+                    )?;
+
+                    // Index running sum, chained across levels
+                    let z_cur = match &z_next {
+                        Some(z) => z.copy_advice(|| "z_cur", &mut region, self.config.index, 0)?,
+                        None => region.assign_advice(
+                            || "z_cur",
+                            self.config.index,
+                            0,
+                            || running_sums[level],
+                        )?,
+                    };
+                    let z = region.assign_advice(
+                        || "z_next",
+                        self.config.index,
+                        1,
+                        || running_sums[level + 1],
+                    )?;
+                    if level + 1 == depth {
+                        // Every bit has been consumed
+                        region.constrain_constant(z.cell(), F::ZERO)?;
+                    }
+
+                    Ok((left, right, z_cur, z))
+                },
+            )?;
+
+            if index.is_none() {
+                index = Some(z_cur);
+            }
+            z_next = Some(z);
+
+            node = self.poseidon.hash(
+                layouter.namespace(|| format!("merkle hash level {}", level)),
+                &[left, right],
+            )?;
+        }
+
+        let index = index.expect("merkle path must have at least one level");
+        Ok((node, index))
+    }
+
+    /// Recompute the root from every leaf of the tree, returning the root cell
+    ///
+    /// Takes `2^depth - 1` two-input Poseidon hashes; `leaves.len()` must be a
+    /// power of two.
+    pub fn compute_root_from_leaves(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaves: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        assert!(
+            leaves.len().is_power_of_two(),
+            "leaf count must be a power of two"
+        );
+
+        let mut level = leaves.to_vec();
+        let mut depth = 0;
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .enumerate()
+                .map(|(i, pair)| {
+                    self.poseidon.hash(
+                        layouter.namespace(|| format!("merkle node {} {}", depth, i)),
+                        pair,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            depth += 1;
+        }
+
+        Ok(level.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::{bn256::Fr, ff::Field},
+        plonk::{Circuit, Instance},
+    };
+
+    const DEPTH: usize = 3;
+
+    fn tree() -> MerkleTree<Fr> {
+        let leaves: Vec<Fr> = (1..=5).map(Fr::from).collect();
+        MerkleTree::new(&leaves, DEPTH).unwrap()
+    }
+
+    #[test]
+    fn test_paths_reach_the_root() {
+        let tree = tree();
+        for index in 0..(1 << DEPTH) {
+            let path = tree.path(index).unwrap();
+            assert_eq!(path.compute_root(tree.leaves()[index]), tree.root());
+        }
+    }
+
+    #[test]
+    fn test_tree_rejects_bad_sizes() {
+        let leaves = vec![Fr::from(1); 9];
+        assert_eq!(
+            MerkleTree::new(&leaves, DEPTH).unwrap_err(),
+            MerkleError::TooManyLeaves {
+                leaves: 9,
+                depth: DEPTH
+            }
+        );
+        assert!(tree().path(1 << DEPTH).is_err());
+    }
+
+    #[test]
+    fn test_sparse_tree_matches_dense_tree() {
+        let mut sparse = SparseMerkleTree::new(DEPTH);
+        for (index, leaf) in tree().leaves().iter().enumerate() {
+            sparse.set(index, *leaf).unwrap();
+        }
+        assert_eq!(sparse.root(), tree().root());
+        for index in 0..(1 << DEPTH) {
+            assert_eq!(sparse.path(index).unwrap(), tree().path(index).unwrap());
+        }
+
+        // Clearing every leaf leaves no stored nodes behind
+        for index in 0..(1 << DEPTH) {
+            sparse.set(index, Fr::ZERO).unwrap();
+        }
+        assert_eq!(sparse.root(), MerkleTree::new(&[], DEPTH).unwrap().root());
+        assert!(sparse.nodes.is_empty());
+        assert!(sparse.set(1 << DEPTH, Fr::ONE).is_err());
+    }
+
+    #[test]
+    fn test_sparse_tree_paths_for_large_indices() {
+        let mut sparse = SparseMerkleTree::new(30);
+        let index = 0x2ead_beef;
+        sparse.set(index, Fr::from(7)).unwrap();
+
+        assert_eq!(
+            sparse.path(index).unwrap().compute_root(Fr::from(7)),
+            sparse.root()
+        );
+        assert_eq!(
+            sparse.path(index + 1).unwrap().compute_root(Fr::ZERO),
+            sparse.root()
+        );
+        assert_ne!(
+            sparse.path(index).unwrap().compute_root(Fr::ZERO),
+            sparse.root()
+        );
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        leaf: Value<Fr>,
+        siblings: Vec<Value<Fr>>,
+        bits: Vec<Value<bool>>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, MerkleConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                leaf: Value::unknown(),
+                siblings: vec![Value::unknown(); DEPTH],
+                bits: vec![Value::unknown(); DEPTH],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let leaf = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(leaf);
+            meta.enable_equality(instance);
+
+            let poseidon = PoseidonChip::configure(meta);
+            (leaf, MerkleChip::configure(meta, poseidon), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (leaf_column, merkle_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let leaf = layouter.assign_region(
+                || "leaf",
+                |mut region| region.assign_advice(|| "leaf", leaf_column, 0, || self.leaf),
+            )?;
+
+            let chip = MerkleChip::construct(merkle_config);
+            let (root, index) = chip.compute_root_with_index(
+                layouter.namespace(|| "root"),
+                &leaf,
+                &self.siblings,
+                &self.bits,
+            )?;
+
+            layouter.constrain_instance(root.cell(), instance, 0)?;
+            layouter.constrain_instance(index.cell(), instance, 1)
+        }
+    }
+
+    fn circuit(tree: &MerkleTree<Fr>, index: usize, leaf: Fr) -> TestCircuit {
+        let path = tree.path(index).unwrap();
+        TestCircuit {
+            leaf: Value::known(leaf),
+            siblings: path.siblings.iter().map(|s| Value::known(*s)).collect(),
+            bits: path.bits().into_iter().map(Value::known).collect(),
+        }
+    }
+
+    #[test]
+    fn test_chip_accepts_members() {
+        let tree = tree();
+        for index in [0, 3, 6] {
+            let circuit = circuit(&tree, index, tree.leaves()[index]);
+            let instances = vec![vec![tree.root(), Fr::from(index as u64)]];
+            let prover = MockProver::run(11, &circuit, instances).unwrap();
+            assert!(prover.verify().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_chip_exposes_the_leaf_index() {
+        let tree = tree();
+        let circuit = circuit(&tree, 3, tree.leaves()[3]);
+        let instances = vec![vec![tree.root(), Fr::from(4)]];
+        let prover = MockProver::run(11, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_chip_rejects_non_members() {
+        let tree = tree();
+        let circuit = circuit(&tree, 2, Fr::from(42));
+        let instances = vec![vec![tree.root(), Fr::from(2)]];
+        let prover = MockProver::run(11, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,605 @@
+//! Clinical Metric Chip
+//!
+//! Computes derived metrics in-circuit from raw measurements, so a patient cannot
+//! simply claim a BMI or a smoking history. Metrics are fixed-point values in tenths
+//! ([`METRIC_SCALE`] = 1 decimal place):
+//!
+//! ```text
+//!   BMI                      = weight_g * 10_000 / height_mm^2
+//!   pack-years               = cigarettes_per_day * years / 2
+//!   mean arterial pressure   = 10 * (systolic + 2 * diastolic) / 3
+//! ```
+//!
+//! ## Division
+//! Each metric is `floor(num / den)` for integer `num` and `den`. The field has no
+//! integer division, so the quotient `q` and remainder `r` are witnessed and
+//!
+//! ```text
+//!   num = q * den + r,    0 <= r,    0 <= den - 1 - r
+//! ```
+//!
+//! is enforced with `q`, `r` and `den - 1 - r` range checked, i.e.
+//! `q * den <= num < (q + 1) * den`. The metric then goes through the same
+//! `min <= metric <= max` check as ages (see [`MetricChip::check_range`]); a strict
+//! bound such as "MAP < 110" is `max = 1099`, since `floor(10 * MAP) <= 1099` exactly
+//! when `MAP < 110`.
+//!
+//! Raw measurements are range checked to [`MEASUREMENT_BITS`] bits first, so no
+//! product wraps around the field modulus.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Fixed, Selector},
+    poly::Rotation,
+};
+
+use crate::range::{RangeCheckChip, RangeCheckConfig};
+
+/// Bit length of raw measurements (grams, millimetres, mmHg, counts)
+pub const MEASUREMENT_BITS: usize = 20;
+
+/// Bit length of numerators, denominators, quotients and threshold differences
+///
+/// Products of two measurements and a bound times a squared measurement fit.
+pub const METRIC_BITS: usize = 64;
+
+/// Decimal places of every metric
+pub const METRIC_SCALE: u32 = 1;
+
+/// Number of terms of the metric gate (the first two are multiplied)
+const METRIC_TERMS: usize = 3;
+
+/// Metric Chip Configuration
+#[derive(Debug, Clone)]
+pub struct MetricConfig<F: PrimeField> {
+    pub terms: [Column<Advice>; METRIC_TERMS],
+    pub out: Column<Advice>,
+    pub mul: Column<Fixed>,
+    pub coeffs: [Column<Fixed>; METRIC_TERMS],
+    pub constant: Column<Fixed>,
+    pub q_metric: Selector,
+    pub measurement_check: RangeCheckConfig<F, MEASUREMENT_BITS>,
+    pub metric_check: RangeCheckConfig<F, METRIC_BITS>,
+}
+
+/// A term of the metric gate: an existing cell or a fresh witness
+enum Term<'a, F: PrimeField> {
+    Cell(&'a AssignedCell<F, F>),
+    Witness(Value<F>),
+}
+
+/// Metric Chip
+///
+/// One gate, `out = mul * a * b + coeff_a * a + coeff_b * b + coeff_c * c + constant`,
+/// carries products, linear combinations and divisions.
+#[derive(Debug, Clone)]
+pub struct MetricChip<F: PrimeField> {
+    config: MetricConfig<F>,
+}
+
+impl<F: PrimeField> MetricChip<F> {
+    pub fn construct(config: MetricConfig<F>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        running_sum: Column<Advice>,
+    ) -> MetricConfig<F> {
+        let terms: [Column<Advice>; METRIC_TERMS] = std::array::from_fn(|_| meta.advice_column());
+        let out = meta.advice_column();
+        let mul = meta.fixed_column();
+        let coeffs: [Column<Fixed>; METRIC_TERMS] = std::array::from_fn(|_| meta.fixed_column());
+        let constant = meta.fixed_column();
+        let q_metric = meta.selector();
+
+        for column in terms.iter().chain([&out]) {
+            meta.enable_equality(*column);
+        }
+
+        // Gate: out = mul * a * b + sum(coeff_i * term_i) + constant
+        meta.create_gate("metric", |meta| {
+            let q = meta.query_selector(q_metric);
+            let out = meta.query_advice(out, Rotation::cur());
+            let mul = meta.query_fixed(mul, Rotation::cur());
+            let constant = meta.query_fixed(constant, Rotation::cur());
+            let values: Vec<_> = terms
+                .iter()
+                .map(|term| meta.query_advice(*term, Rotation::cur()))
+                .collect();
+
+            let product = mul * values[0].clone() * values[1].clone();
+            let sum =
+                values
+                    .iter()
+                    .zip(coeffs.iter())
+                    .fold(product + constant, |acc, (value, coeff)| {
+                        acc + meta.query_fixed(*coeff, Rotation::cur()) * value.clone()
+                    });
+            vec![q * (out - sum)]
+        });
+
+        let measurement_check = RangeCheckChip::configure(meta, running_sum);
+        let metric_check = RangeCheckChip::configure(meta, running_sum);
+
+        MetricConfig {
+            terms,
+            out,
+            mul,
+            coeffs,
+            constant,
+            q_metric,
+            measurement_check,
+            metric_check,
+        }
+    }
+
+    /// Assign `a * b`, returning the new cell
+    pub fn mul(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.assign_metric(
+            layouter,
+            F::ONE,
+            &[(F::ZERO, Term::Cell(a)), (F::ZERO, Term::Cell(b))],
+            F::ZERO,
+            None,
+        )
+        .map(|(_, out)| out)
+    }
+
+    /// Assign `sum(coeff_i * term_i) + constant` (up to three terms), returning the new cell
+    pub fn linear(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        terms: &[(F, &AssignedCell<F, F>)],
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let terms: Vec<(F, Term<F>)> = terms
+            .iter()
+            .map(|(coeff, cell)| (*coeff, Term::Cell(cell)))
+            .collect();
+
+        self.assign_metric(layouter, F::ZERO, &terms, constant, None)
+            .map(|(_, out)| out)
+    }
+
+    /// Constrain a raw measurement to lie in `[0, 2^MEASUREMENT_BITS)`
+    pub fn check_measurement(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        RangeCheckChip::construct(self.config.measurement_check.clone())
+            .assign(layouter.namespace(|| "measurement range check"), value)
+    }
+
+    /// Constrain `value` to lie in `[0, 2^METRIC_BITS)`
+    pub fn range_check(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        RangeCheckChip::construct(self.config.metric_check.clone())
+            .assign(layouter.namespace(|| "metric range check"), value)
+    }
+
+    /// `floor(num / den)`, unsatisfiable if `den` is zero
+    ///
+    /// `num` and `den` must already be below `2^METRIC_BITS`.
+    pub fn div_floor(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        num: &AssignedCell<F, F>,
+        den: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let division = num.value().zip(den.value()).map(|(num, den)| {
+            let (num, den) = (low_u128(num), low_u128(den));
+            num.checked_div(den)
+                .zip(num.checked_rem(den))
+                .unwrap_or_default()
+        });
+        let quotient = division.map(|(q, _)| F::from_u128(q));
+        let remainder = division.map(|(_, r)| F::from_u128(r));
+
+        // num = quotient * den + remainder
+        let (cells, _) = self.assign_metric(
+            layouter,
+            F::ONE,
+            &[
+                (F::ZERO, Term::Witness(quotient)),
+                (F::ZERO, Term::Cell(den)),
+                (F::ONE, Term::Witness(remainder)),
+            ],
+            F::ZERO,
+            Some(num),
+        )?;
+        let (quotient, remainder) = (cells[0].clone(), cells[2].clone());
+
+        // remainder < den
+        let slack = self.linear(layouter, &[(F::ONE, den), (-F::ONE, &remainder)], -F::ONE)?;
+
+        for cell in [&quotient, &remainder, &slack] {
+            self.range_check(layouter, cell)?;
+        }
+
+        Ok(quotient)
+    }
+
+    /// BMI in tenths from weight in grams and height in millimetres
+    pub fn bmi(
+        &self,
+        mut layouter: impl Layouter<F>,
+        weight_g: &AssignedCell<F, F>,
+        height_mm: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let layouter = &mut layouter;
+        self.check_measurement(layouter, weight_g)?;
+        self.check_measurement(layouter, height_mm)?;
+
+        let num = self.linear(layouter, &[(F::from(BMI_FACTOR), weight_g)], F::ZERO)?;
+        let den = self.mul(layouter, height_mm, height_mm)?;
+        self.div_floor(layouter, &num, &den)
+    }
+
+    /// Pack-years in tenths from cigarettes per day and years smoked
+    pub fn pack_years(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cigarettes_per_day: &AssignedCell<F, F>,
+        years: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let layouter = &mut layouter;
+        self.check_measurement(layouter, cigarettes_per_day)?;
+        self.check_measurement(layouter, years)?;
+
+        let num = self.mul(layouter, cigarettes_per_day, years)?;
+        let den = self.linear(layouter, &[], F::from(PACK_YEARS_DIVISOR))?;
+        self.div_floor(layouter, &num, &den)
+    }
+
+    /// Mean arterial pressure in tenths of mmHg from systolic and diastolic pressure
+    pub fn mean_arterial_pressure(
+        &self,
+        mut layouter: impl Layouter<F>,
+        systolic: &AssignedCell<F, F>,
+        diastolic: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let layouter = &mut layouter;
+        self.check_measurement(layouter, systolic)?;
+        self.check_measurement(layouter, diastolic)?;
+
+        let num = self.linear(
+            layouter,
+            &[(F::from(10), systolic), (F::from(20), diastolic)],
+            F::ZERO,
+        )?;
+        let den = self.linear(layouter, &[], F::from(3))?;
+        self.div_floor(layouter, &num, &den)
+    }
+
+    /// Constrain `min <= value <= max`, as the age range check does
+    pub fn check_range(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        min: &AssignedCell<F, F>,
+        max: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        let layouter = &mut layouter;
+        let lower_diff = self.linear(layouter, &[(F::ONE, value), (-F::ONE, min)], F::ZERO)?;
+        let upper_diff = self.linear(layouter, &[(F::ONE, max), (-F::ONE, value)], F::ZERO)?;
+
+        self.range_check(layouter, &lower_diff)?;
+        self.range_check(layouter, &upper_diff)
+    }
+
+    fn assign_metric(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        mul: F,
+        terms: &[(F, Term<F>)],
+        constant: F,
+        out: Option<&AssignedCell<F, F>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, AssignedCell<F, F>), Error> {
+        assert!(terms.len() <= METRIC_TERMS, "too many terms");
+
+        layouter.assign_region(
+            || "metric",
+            |mut region| {
+                self.config.q_metric.enable(&mut region, 0)?;
+                region.assign_fixed(|| "mul", self.config.mul, 0, || Value::known(mul))?;
+                region.assign_fixed(
+                    || "constant",
+                    self.config.constant,
+                    0,
+                    || Value::known(constant),
+                )?;
+
+                let mut sum = Value::known(constant);
+                let mut cells = Vec::with_capacity(METRIC_TERMS);
+                for i in 0..METRIC_TERMS {
+                    let (coeff, cell) = match terms.get(i) {
+                        Some((coeff, Term::Cell(cell))) => (
+                            *coeff,
+                            cell.copy_advice(
+                                || format!("term {}", i),
+                                &mut region,
+                                self.config.terms[i],
+                                0,
+                            )?,
+                        ),
+                        Some((coeff, Term::Witness(value))) => (
+                            *coeff,
+                            region.assign_advice(
+                                || format!("term {}", i),
+                                self.config.terms[i],
+                                0,
+                                || *value,
+                            )?,
+                        ),
+                        // Unused terms are zero
+                        None => (
+                            F::ZERO,
+                            region.assign_advice(
+                                || format!("term {}", i),
+                                self.config.terms[i],
+                                0,
+                                || Value::known(F::ZERO),
+                            )?,
+                        ),
+                    };
+                    region.assign_fixed(
+                        || format!("coeff {}", i),
+                        self.config.coeffs[i],
+                        0,
+                        || Value::known(coeff),
+                    )?;
+
+                    sum = sum
+                        .zip(cell.value().copied())
+                        .map(|(sum, x)| sum + coeff * x);
+                    cells.push(cell);
+                }
+
+                let product = cells[0]
+                    .value()
+                    .zip(cells[1].value())
+                    .map(|(a, b)| mul * a * b);
+                let sum = sum + product;
+
+                let out = match out {
+                    Some(out) => out.copy_advice(|| "out", &mut region, self.config.out, 0)?,
+                    None => region.assign_advice(|| "out", self.config.out, 0, || sum)?,
+                };
+
+                Ok((cells, out))
+            },
+        )
+    }
+}
+
+/// BMI numerator factor: grams per millimetre squared to tenths of kg/m^2
+const BMI_FACTOR: u64 = 10_000;
+
+/// Pack-years denominator: 20 cigarettes per pack, tenths of a pack-year
+const PACK_YEARS_DIVISOR: u64 = 2;
+
+/// Low 128 bits of a field element (assumes a little-endian `Repr`, as for bn256)
+fn low_u128<F: PrimeField>(value: &F) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&value.to_repr().as_ref()[..16]);
+    u128::from_le_bytes(bytes)
+}
+
+/// BMI in tenths from weight in grams and height in millimetres (host-side)
+pub fn bmi_tenths(weight_g: u64, height_mm: u64) -> Option<u64> {
+    (weight_g as u128 * BMI_FACTOR as u128)
+        .checked_div(height_mm as u128 * height_mm as u128)
+        .map(|bmi| bmi as u64)
+}
+
+/// Pack-years in tenths from cigarettes per day and years smoked (host-side)
+pub fn pack_years_tenths(cigarettes_per_day: u64, years: u64) -> u64 {
+    cigarettes_per_day * years / PACK_YEARS_DIVISOR
+}
+
+/// Mean arterial pressure in tenths of mmHg (host-side)
+pub fn mean_arterial_pressure_tenths(systolic: u64, diastolic: u64) -> u64 {
+    10 * (systolic + 2 * diastolic) / 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    #[test]
+    fn test_host_metrics() {
+        assert_eq!(bmi_tenths(80_000, 1_800), Some(246)); // 24.69
+        assert_eq!(bmi_tenths(80_000, 0), None);
+        assert_eq!(pack_years_tenths(20, 20), 200);
+        assert_eq!(pack_years_tenths(15, 3), 22); // 2.25
+        assert_eq!(mean_arterial_pressure_tenths(120, 80), 933); // 93.33
+    }
+
+    #[derive(Clone, Copy, Default)]
+    enum Metric {
+        #[default]
+        Bmi,
+        PackYears,
+        MeanArterialPressure,
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        metric: Metric,
+        a: Value<Fr>,
+        b: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, MetricConfig<Fr>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                metric: self.metric,
+                ..Self::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let running_sum = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(value);
+            meta.enable_equality(instance);
+
+            (value, MetricChip::configure(meta, running_sum), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (value_column, metric_config, instance): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let (a, b) = layouter.assign_region(
+                || "measurements",
+                |mut region| {
+                    let a = region.assign_advice(|| "a", value_column, 0, || self.a)?;
+                    let b = region.assign_advice(|| "b", value_column, 1, || self.b)?;
+                    Ok((a, b))
+                },
+            )?;
+
+            let chip = MetricChip::construct(metric_config);
+            let namespace = layouter.namespace(|| "metric");
+            let metric = match self.metric {
+                Metric::Bmi => chip.bmi(namespace, &a, &b)?,
+                Metric::PackYears => chip.pack_years(namespace, &a, &b)?,
+                Metric::MeanArterialPressure => chip.mean_arterial_pressure(namespace, &a, &b)?,
+            };
+            layouter.constrain_instance(metric.cell(), instance, 0)?;
+
+            // Public bounds, as a circuit would take them
+            let (min, max) = layouter.assign_region(
+                || "bounds",
+                |mut region| {
+                    let min = region.assign_advice_from_instance(
+                        || "min",
+                        instance,
+                        1,
+                        value_column,
+                        0,
+                    )?;
+                    let max = region.assign_advice_from_instance(
+                        || "max",
+                        instance,
+                        2,
+                        value_column,
+                        1,
+                    )?;
+                    Ok((min, max))
+                },
+            )?;
+            chip.check_range(layouter.namespace(|| "bounds"), &metric, &min, &max)
+        }
+    }
+
+    fn run_with_bounds(metric: Metric, a: Fr, b: Fr, expected: u64, min: u64, max: u64) -> bool {
+        let circuit = TestCircuit {
+            metric,
+            a: Value::known(a),
+            b: Value::known(b),
+        };
+        let instances = vec![vec![Fr::from(expected), Fr::from(min), Fr::from(max)]];
+        MockProver::run(10, &circuit, instances)
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    fn run(metric: Metric, a: Fr, b: Fr, expected: u64) -> bool {
+        run_with_bounds(metric, a, b, expected, 0, expected)
+    }
+
+    #[test]
+    fn test_chip_computes_metrics() {
+        assert!(run(Metric::Bmi, Fr::from(80_000), Fr::from(1_800), 246));
+        assert!(run(Metric::PackYears, Fr::from(15), Fr::from(3), 22));
+        assert!(run(
+            Metric::MeanArterialPressure,
+            Fr::from(120),
+            Fr::from(80),
+            933
+        ));
+    }
+
+    #[test]
+    fn test_chip_rejects_wrong_metrics() {
+        // Rounded up instead of down
+        assert!(!run(Metric::Bmi, Fr::from(80_000), Fr::from(1_800), 247));
+        assert!(!run(Metric::PackYears, Fr::from(15), Fr::from(3), 23));
+        assert!(!run(
+            Metric::MeanArterialPressure,
+            Fr::from(120),
+            Fr::from(80),
+            932
+        ));
+
+        // Zero height has no BMI
+        assert!(!run(Metric::Bmi, Fr::from(80_000), Fr::from(0), 0));
+
+        // Negative measurements wrap around the modulus and fail the measurement check
+        assert!(!run(Metric::PackYears, -Fr::from(20), -Fr::from(20), 200));
+    }
+
+    #[test]
+    fn test_chip_checks_metric_range() {
+        // BMI 25.0 - 40.0
+        assert!(run_with_bounds(
+            Metric::Bmi,
+            Fr::from(90_000),
+            Fr::from(1_800),
+            277,
+            250,
+            400
+        ));
+        assert!(!run_with_bounds(
+            Metric::Bmi,
+            Fr::from(80_000),
+            Fr::from(1_800),
+            246,
+            250,
+            400
+        ));
+
+        // MAP < 110: 109.67 passes, 110.0 does not
+        assert!(run_with_bounds(
+            Metric::MeanArterialPressure,
+            Fr::from(149),
+            Fr::from(90),
+            1096,
+            0,
+            1099
+        ));
+        assert!(!run_with_bounds(
+            Metric::MeanArterialPressure,
+            Fr::from(150),
+            Fr::from(90),
+            1100,
+            0,
+            1099
+        ));
+    }
+}
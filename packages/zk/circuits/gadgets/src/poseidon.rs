@@ -0,0 +1,615 @@
+//! Poseidon Chip
+//!
+//! In-circuit Poseidon hash that reproduces the sponge of the `poseidon` crate
+//! (width 3, rate 2, x^5 S-box, 8 full + 57 partial rounds over bn256 `Fr`), so
+//! commitments computed on the host and inside a circuit agree.
+//!
+//! ## Layout
+//! The permutation is described as a list of [`PoseidonRound`]s, each of the form
+//!
+//! ```text
+//!   next = matrix * sbox(state) + constants
+//! ```
+//!
+//! where the S-box is applied to no element, the first element or all elements.
+//! The `poseidon` crate's optimized constants (pre-sparse and sparse MDS matrices)
+//! fit this form directly, so one round takes one row: the matrix and constants
+//! live in fixed columns and one gate per S-box kind checks the transition.
+//! Absorbing a chunk of input takes one extra row.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    halo2curves::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+    },
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use poseidon::{Poseidon, SparseMDSMatrix, Spec};
+
+/// State width (capacity 1 + rate 2)
+pub const POSEIDON_WIDTH: usize = 3;
+/// Number of field elements absorbed per permutation
+pub const POSEIDON_RATE: usize = 2;
+pub const POSEIDON_FULL_ROUNDS: usize = 8;
+pub const POSEIDON_PARTIAL_ROUNDS: usize = 57;
+
+type State<F> = [F; POSEIDON_WIDTH];
+
+/// Which state elements a round raises to the fifth power
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SBox {
+    None,
+    First,
+    Full,
+}
+
+/// One row of the permutation: `next = matrix * sbox(state) + constants`
+#[derive(Debug, Clone)]
+pub struct PoseidonRound<F: PrimeField> {
+    pub sbox: SBox,
+    pub matrix: [State<F>; POSEIDON_WIDTH],
+    pub constants: State<F>,
+}
+
+impl<F: PrimeField> PoseidonRound<F> {
+    pub fn apply(&self, state: &State<F>) -> State<F> {
+        let input: State<F> = std::array::from_fn(|i| match self.sbox {
+            SBox::Full => pow5(state[i]),
+            SBox::First if i == 0 => pow5(state[i]),
+            _ => state[i],
+        });
+
+        std::array::from_fn(|i| {
+            self.matrix[i]
+                .iter()
+                .zip(input.iter())
+                .fold(self.constants[i], |acc, (m, x)| acc + *m * x)
+        })
+    }
+}
+
+/// Fields with a Poseidon instance shared by host code and circuits
+pub trait PoseidonField: PrimeField {
+    /// Initial sponge state (capacity element first)
+    fn poseidon_initial_state() -> State<Self>;
+
+    /// Rounds of one permutation
+    fn poseidon_rounds() -> Vec<PoseidonRound<Self>>;
+
+    /// Host-side hash, identical to [`PoseidonChip::hash`]
+    fn poseidon_hash(inputs: &[Self]) -> Self;
+}
+
+impl PoseidonField for Fr {
+    fn poseidon_initial_state() -> State<Fr> {
+        // The `poseidon` crate starts with 2^64 in the capacity element
+        [Fr::from_u128(1 << 64), Fr::ZERO, Fr::ZERO]
+    }
+
+    fn poseidon_rounds() -> Vec<PoseidonRound<Fr>> {
+        let spec = Spec::<Fr, POSEIDON_WIDTH, POSEIDON_RATE>::new(
+            POSEIDON_FULL_ROUNDS,
+            POSEIDON_PARTIAL_ROUNDS,
+        );
+        rounds_from_spec(&spec)
+    }
+
+    fn poseidon_hash(inputs: &[Fr]) -> Fr {
+        let mut hasher = Poseidon::<Fr, POSEIDON_WIDTH, POSEIDON_RATE>::new(
+            POSEIDON_FULL_ROUNDS,
+            POSEIDON_PARTIAL_ROUNDS,
+        );
+        hasher.update(inputs);
+        hasher.squeeze()
+    }
+}
+
+/// Translate the optimized permutation of the `poseidon` crate into rounds
+///
+/// Mirrors `Spec::permute`: the first constants are added before any S-box,
+/// every later "S-box, add constants, mix" step becomes `M * sbox(s) + M * c`.
+fn rounds_from_spec(spec: &Spec<Fr, POSEIDON_WIDTH, POSEIDON_RATE>) -> Vec<PoseidonRound<Fr>> {
+    let constants = spec.constants();
+    let mds = spec.mds_matrices().mds().rows();
+    let pre_sparse_mds = spec.mds_matrices().pre_sparse_mds().rows();
+    let half_full_rounds = POSEIDON_FULL_ROUNDS / 2;
+
+    let mut rounds = Vec::with_capacity(POSEIDON_FULL_ROUNDS + POSEIDON_PARTIAL_ROUNDS + 1);
+
+    // Initial constants, no S-box
+    rounds.push(PoseidonRound {
+        sbox: SBox::None,
+        matrix: identity(),
+        constants: constants.start()[0],
+    });
+
+    // First half of the full rounds
+    for round_constants in constants.start().iter().skip(1).take(half_full_rounds - 1) {
+        rounds.push(PoseidonRound {
+            sbox: SBox::Full,
+            matrix: mds,
+            constants: mat_mul_vec(&mds, round_constants),
+        });
+    }
+    rounds.push(PoseidonRound {
+        sbox: SBox::Full,
+        matrix: pre_sparse_mds,
+        constants: [Fr::ZERO; POSEIDON_WIDTH],
+    });
+
+    // Partial rounds
+    for (round_constant, sparse_mds) in constants
+        .partial()
+        .iter()
+        .zip(spec.mds_matrices().sparse_matrices().iter())
+    {
+        let matrix = sparse_to_dense(sparse_mds);
+        let constants = mat_mul_vec(&matrix, &[*round_constant, Fr::ZERO, Fr::ZERO]);
+        rounds.push(PoseidonRound {
+            sbox: SBox::First,
+            matrix,
+            constants,
+        });
+    }
+
+    // Second half of the full rounds
+    for round_constants in constants.end().iter() {
+        rounds.push(PoseidonRound {
+            sbox: SBox::Full,
+            matrix: mds,
+            constants: mat_mul_vec(&mds, round_constants),
+        });
+    }
+    rounds.push(PoseidonRound {
+        sbox: SBox::Full,
+        matrix: mds,
+        constants: [Fr::ZERO; POSEIDON_WIDTH],
+    });
+
+    rounds
+}
+
+fn sparse_to_dense(
+    sparse: &SparseMDSMatrix<Fr, POSEIDON_WIDTH, POSEIDON_RATE>,
+) -> [State<Fr>; POSEIDON_WIDTH] {
+    std::array::from_fn(|i| {
+        if i == 0 {
+            *sparse.row()
+        } else {
+            std::array::from_fn(|j| match j {
+                0 => sparse.col_hat()[i - 1],
+                j if j == i => Fr::ONE,
+                _ => Fr::ZERO,
+            })
+        }
+    })
+}
+
+fn identity<F: PrimeField>() -> [State<F>; POSEIDON_WIDTH] {
+    std::array::from_fn(|i| std::array::from_fn(|j| if i == j { F::ONE } else { F::ZERO }))
+}
+
+fn mat_mul_vec<F: PrimeField>(matrix: &[State<F>; POSEIDON_WIDTH], v: &State<F>) -> State<F> {
+    std::array::from_fn(|i| {
+        matrix[i]
+            .iter()
+            .zip(v.iter())
+            .fold(F::ZERO, |acc, (m, x)| acc + *m * x)
+    })
+}
+
+fn pow5<F: PrimeField>(x: F) -> F {
+    x.square().square() * x
+}
+
+/// Split `inputs` into the rate-sized chunks absorbed by the sponge
+///
+/// Like the `poseidon` crate, the last chunk is always followed by a `1`
+/// (even when the inputs fill every chunk) and zero padded.
+fn sponge_chunks<T: Clone>(inputs: &[T], one: T, zero: T) -> Vec<Vec<T>> {
+    let full_chunks = inputs.len() / POSEIDON_RATE;
+
+    let mut chunks: Vec<Vec<T>> = inputs[..full_chunks * POSEIDON_RATE]
+        .chunks(POSEIDON_RATE)
+        .map(|chunk| chunk.to_vec())
+        .collect();
+
+    let mut last = inputs[full_chunks * POSEIDON_RATE..].to_vec();
+    last.push(one);
+    last.resize(POSEIDON_RATE, zero);
+    chunks.push(last);
+
+    chunks
+}
+
+/// Host-side sponge evaluated with [`PoseidonRound`]s (what the chip computes)
+pub fn native_poseidon_hash<F: PoseidonField>(inputs: &[F]) -> F {
+    let rounds = F::poseidon_rounds();
+    let mut state = F::poseidon_initial_state();
+
+    for chunk in sponge_chunks(inputs, F::ONE, F::ZERO) {
+        for (word, input) in state.iter_mut().skip(1).zip(chunk.iter()) {
+            *word += input;
+        }
+        for round in rounds.iter() {
+            state = round.apply(&state);
+        }
+    }
+
+    state[1]
+}
+
+/// An element absorbed by the in-circuit sponge
+#[derive(Clone)]
+enum Absorbed<'a, F: PrimeField> {
+    Cell(&'a AssignedCell<F, F>),
+    Constant(F), // Sponge padding
+}
+
+/// Poseidon Chip Configuration
+#[derive(Debug, Clone)]
+pub struct PoseidonConfig {
+    pub state: [Column<Advice>; POSEIDON_WIDTH],
+    pub absorb: [Column<Advice>; POSEIDON_RATE],
+    pub matrix: [[Column<Fixed>; POSEIDON_WIDTH]; POSEIDON_WIDTH],
+    pub round_constants: [Column<Fixed>; POSEIDON_WIDTH],
+    pub constants: Column<Fixed>, // Initial state and padding
+    pub q_absorb: Selector,
+    pub q_linear: Selector,
+    pub q_partial: Selector,
+    pub q_full: Selector,
+}
+
+/// Poseidon Chip
+///
+/// A hash of `n` inputs takes `(n / 2 + 1) * (rounds + 1) + 1` rows.
+#[derive(Debug, Clone)]
+pub struct PoseidonChip<F: PoseidonField> {
+    config: PoseidonConfig,
+    rounds: Vec<PoseidonRound<F>>,
+}
+
+impl<F: PoseidonField> PoseidonChip<F> {
+    pub fn construct(config: PoseidonConfig) -> Self {
+        Self {
+            config,
+            rounds: F::poseidon_rounds(),
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig {
+        let state: [Column<Advice>; POSEIDON_WIDTH] = std::array::from_fn(|_| meta.advice_column());
+        let absorb: [Column<Advice>; POSEIDON_RATE] = std::array::from_fn(|_| meta.advice_column());
+        let matrix: [[Column<Fixed>; POSEIDON_WIDTH]; POSEIDON_WIDTH] =
+            std::array::from_fn(|_| std::array::from_fn(|_| meta.fixed_column()));
+        let round_constants: [Column<Fixed>; POSEIDON_WIDTH] =
+            std::array::from_fn(|_| meta.fixed_column());
+        let constants = meta.fixed_column();
+
+        let q_absorb = meta.selector();
+        let q_linear = meta.selector();
+        let q_partial = meta.selector();
+        let q_full = meta.selector();
+
+        for column in state.iter().chain(absorb.iter()) {
+            meta.enable_equality(*column);
+        }
+        meta.enable_constant(constants);
+
+        // Gate: Add the absorbed chunk to the rate part of the state
+        meta.create_gate("poseidon absorb", |meta| {
+            let q = meta.query_selector(q_absorb);
+            let cur: Vec<Expression<F>> = state
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+            let next: Vec<Expression<F>> = state
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::next()))
+                .collect();
+            let inputs: Vec<Expression<F>> = absorb
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+
+            let mut constraints = vec![q.clone() * (next[0].clone() - cur[0].clone())];
+            for i in 1..POSEIDON_WIDTH {
+                constraints
+                    .push(q.clone() * (next[i].clone() - (cur[i].clone() + inputs[i - 1].clone())));
+            }
+            constraints
+        });
+
+        // Gate: next = matrix * sbox(state) + round_constants
+        meta.create_gate("poseidon round", |meta| {
+            let q_linear = meta.query_selector(q_linear);
+            let q_partial = meta.query_selector(q_partial);
+            let q_full = meta.query_selector(q_full);
+
+            let cur: Vec<Expression<F>> = state
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::cur()))
+                .collect();
+            let next: Vec<Expression<F>> = state
+                .iter()
+                .map(|column| meta.query_advice(*column, Rotation::next()))
+                .collect();
+            let matrix: Vec<Vec<Expression<F>>> = matrix
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|column| meta.query_fixed(*column, Rotation::cur()))
+                        .collect()
+                })
+                .collect();
+            let round_constants: Vec<Expression<F>> = round_constants
+                .iter()
+                .map(|column| meta.query_fixed(*column, Rotation::cur()))
+                .collect();
+
+            let pow5 =
+                |x: &Expression<F>| x.clone() * x.clone() * x.clone() * x.clone() * x.clone();
+            let transition = |input: Vec<Expression<F>>| -> Vec<Expression<F>> {
+                (0..POSEIDON_WIDTH)
+                    .map(|i| {
+                        let mixed = (0..POSEIDON_WIDTH)
+                            .fold(round_constants[i].clone(), |acc, j| {
+                                acc + matrix[i][j].clone() * input[j].clone()
+                            });
+                        next[i].clone() - mixed
+                    })
+                    .collect()
+            };
+
+            let linear_input = cur.clone();
+            let partial_input: Vec<Expression<F>> = cur
+                .iter()
+                .enumerate()
+                .map(|(i, x)| if i == 0 { pow5(x) } else { x.clone() })
+                .collect();
+            let full_input: Vec<Expression<F>> = cur.iter().map(pow5).collect();
+
+            let linear = transition(linear_input)
+                .into_iter()
+                .map(|constraint| q_linear.clone() * constraint);
+            let partial = transition(partial_input)
+                .into_iter()
+                .map(|constraint| q_partial.clone() * constraint);
+            let full = transition(full_input)
+                .into_iter()
+                .map(|constraint| q_full.clone() * constraint);
+
+            linear.chain(partial).chain(full).collect::<Vec<_>>()
+        });
+
+        PoseidonConfig {
+            state,
+            absorb,
+            matrix,
+            round_constants,
+            constants,
+            q_absorb,
+            q_linear,
+            q_partial,
+            q_full,
+        }
+    }
+
+    /// Hash `inputs` in-circuit, returning the squeezed output cell
+    pub fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || format!("poseidon hash of {} inputs", inputs.len()),
+            |mut region| {
+                let initial_state = F::poseidon_initial_state();
+                let mut state = Value::known(initial_state);
+                let mut cells = Vec::with_capacity(POSEIDON_WIDTH);
+                for (i, word) in initial_state.iter().enumerate() {
+                    cells.push(region.assign_advice_from_constant(
+                        || format!("initial state {}", i),
+                        self.config.state[i],
+                        0,
+                        *word,
+                    )?);
+                }
+
+                let chunks = sponge_chunks(
+                    &inputs.iter().map(Absorbed::Cell).collect::<Vec<_>>(),
+                    Absorbed::Constant(F::ONE),
+                    Absorbed::Constant(F::ZERO),
+                );
+
+                let mut offset = 0;
+                for chunk in chunks.iter() {
+                    // Absorb row
+                    self.config.q_absorb.enable(&mut region, offset)?;
+                    for (i, input) in chunk.iter().enumerate() {
+                        let cell = match input {
+                            Absorbed::Cell(cell) => cell.copy_advice(
+                                || format!("absorb {}", i),
+                                &mut region,
+                                self.config.absorb[i],
+                                offset,
+                            )?,
+                            Absorbed::Constant(padding) => region.assign_advice_from_constant(
+                                || format!("padding {}", i),
+                                self.config.absorb[i],
+                                offset,
+                                *padding,
+                            )?,
+                        };
+                        state = state.zip(cell.value().copied()).map(|(mut s, v)| {
+                            s[i + 1] += v;
+                            s
+                        });
+                    }
+
+                    offset += 1;
+                    cells = self.assign_state(&mut region, offset, state)?;
+
+                    // One row per round
+                    for round in self.rounds.iter() {
+                        match round.sbox {
+                            SBox::None => self.config.q_linear.enable(&mut region, offset)?,
+                            SBox::First => self.config.q_partial.enable(&mut region, offset)?,
+                            SBox::Full => self.config.q_full.enable(&mut region, offset)?,
+                        }
+                        self.assign_round(&mut region, offset, round)?;
+
+                        state = state.map(|s| round.apply(&s));
+                        offset += 1;
+                        cells = self.assign_state(&mut region, offset, state)?;
+                    }
+                }
+
+                Ok(cells[1].clone())
+            },
+        )
+    }
+
+    fn assign_state(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        state: Value<State<F>>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        (0..POSEIDON_WIDTH)
+            .map(|i| {
+                region.assign_advice(
+                    || format!("state {}", i),
+                    self.config.state[i],
+                    offset,
+                    || state.map(|s| s[i]),
+                )
+            })
+            .collect()
+    }
+
+    fn assign_round(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        round: &PoseidonRound<F>,
+    ) -> Result<(), Error> {
+        for i in 0..POSEIDON_WIDTH {
+            for j in 0..POSEIDON_WIDTH {
+                region.assign_fixed(
+                    || format!("matrix {} {}", i, j),
+                    self.config.matrix[i][j],
+                    offset,
+                    || Value::known(round.matrix[i][j]),
+                )?;
+            }
+            region.assign_fixed(
+                || format!("round constant {}", i),
+                self.config.round_constants[i],
+                offset,
+                || Value::known(round.constants[i]),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, plonk::Circuit};
+
+    #[test]
+    fn test_native_sponge_matches_poseidon_crate() {
+        for len in 0..6 {
+            let inputs: Vec<Fr> = (0..len).map(|i| Fr::from(i as u64 + 7)).collect();
+            assert_eq!(native_poseidon_hash(&inputs), Fr::poseidon_hash(&inputs));
+        }
+    }
+
+    #[derive(Default)]
+    struct TestCircuit {
+        inputs: Vec<Value<Fr>>,
+        expected: Fr,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, PoseidonConfig);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: vec![Value::unknown(); self.inputs.len()],
+                expected: self.expected,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            meta.enable_equality(value);
+
+            (value, PoseidonChip::configure(meta))
+        }
+
+        fn synthesize(
+            &self,
+            (value_column, poseidon_config): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let (inputs, expected) = layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    let inputs = self
+                        .inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, input)| {
+                            region.assign_advice(|| "input", value_column, i, || *input)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let expected = region.assign_advice_from_constant(
+                        || "expected",
+                        value_column,
+                        self.inputs.len(),
+                        self.expected,
+                    )?;
+                    Ok((inputs, expected))
+                },
+            )?;
+
+            let chip = PoseidonChip::<Fr>::construct(poseidon_config);
+            let output = chip.hash(layouter.namespace(|| "hash"), &inputs)?;
+
+            layouter.assign_region(
+                || "compare",
+                |mut region| region.constrain_equal(output.cell(), expected.cell()),
+            )
+        }
+    }
+
+    #[test]
+    fn test_chip_matches_host_hash() {
+        for len in 1..4 {
+            let inputs: Vec<Fr> = (0..len).map(|i| Fr::from(i as u64 + 1)).collect();
+            let circuit = TestCircuit {
+                inputs: inputs.iter().map(|x| Value::known(*x)).collect(),
+                expected: Fr::poseidon_hash(&inputs),
+            };
+            let prover = MockProver::run(10, &circuit, vec![]).unwrap();
+            assert!(prover.verify().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_chip_rejects_wrong_output() {
+        let inputs = vec![Fr::from(1), Fr::from(2)];
+        let circuit = TestCircuit {
+            inputs: inputs.iter().map(|x| Value::known(*x)).collect(),
+            expected: Fr::poseidon_hash(&inputs) + Fr::ONE,
+        };
+        let prover = MockProver::run(10, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
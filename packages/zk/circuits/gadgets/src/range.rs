@@ -0,0 +1,205 @@
+//! Range Check Chip - Bit Decomposition
+//!
+//! Proves that an assigned value lies in `[0, 2^NUM_BITS)` using a running sum
+//! over a single advice column:
+//!
+//! ```text
+//!   z_0        = value
+//!   z_{i+1}    = (z_i - b_i) / 2      b_i ∈ {0, 1}
+//!   z_NUM_BITS = 0
+//! ```
+//!
+//! Each of the first `NUM_BITS` rows checks that `b_i = z_i - 2 * z_{i+1}` is a bit,
+//! and the last row checks that nothing is left over. A value outside the range
+//! (including a "negative" field element such as `p - 3`) leaves a non-zero
+//! remainder in `z_NUM_BITS`, so the proof cannot be satisfied.
+
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    halo2curves::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+/// Range Check Configuration
+#[derive(Debug, Clone)]
+pub struct RangeCheckConfig<F: PrimeField, const NUM_BITS: usize> {
+    pub running_sum: Column<Advice>,
+    pub q_bit: Selector,       // Enabled on rows 0..NUM_BITS
+    pub q_remainder: Selector, // Enabled on row NUM_BITS
+    _marker: PhantomData<F>,
+}
+
+/// Range Check Chip
+///
+/// Each call to [`RangeCheckChip::assign`] uses `NUM_BITS + 1` rows of the
+/// running sum column.
+#[derive(Debug, Clone)]
+pub struct RangeCheckChip<F: PrimeField, const NUM_BITS: usize> {
+    config: RangeCheckConfig<F, NUM_BITS>,
+}
+
+impl<F: PrimeField, const NUM_BITS: usize> RangeCheckChip<F, NUM_BITS> {
+    pub fn construct(config: RangeCheckConfig<F, NUM_BITS>) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        running_sum: Column<Advice>,
+    ) -> RangeCheckConfig<F, NUM_BITS> {
+        // The decomposition must not wrap around the field modulus
+        assert!(
+            NUM_BITS < F::CAPACITY as usize,
+            "range check of {} bits does not fit in the field",
+            NUM_BITS
+        );
+
+        let q_bit = meta.selector();
+        let q_remainder = meta.selector();
+
+        meta.enable_equality(running_sum);
+
+        meta.create_gate("range check bit", |meta| {
+            let q = meta.query_selector(q_bit);
+            let z_cur = meta.query_advice(running_sum, Rotation::cur());
+            let z_next = meta.query_advice(running_sum, Rotation::next());
+
+            // b_i = z_i - 2 * z_{i+1} must be boolean
+            let bit = z_cur - z_next * F::from(2);
+            vec![q * bit.clone() * (Expression::Constant(F::ONE) - bit)]
+        });
+
+        meta.create_gate("range check remainder", |meta| {
+            let q = meta.query_selector(q_remainder);
+            let z_last = meta.query_advice(running_sum, Rotation::cur());
+
+            // Every bit has been consumed
+            vec![q * z_last]
+        });
+
+        RangeCheckConfig {
+            running_sum,
+            q_bit,
+            q_remainder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constrain `value` to lie in `[0, 2^NUM_BITS)`
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || format!("range check {} bits", NUM_BITS),
+            |mut region| {
+                let mut z = value.copy_advice(|| "z_0", &mut region, self.config.running_sum, 0)?;
+                let bits = value.value().map(|v| le_bits(v, NUM_BITS));
+
+                for i in 0..NUM_BITS {
+                    self.config.q_bit.enable(&mut region, i)?;
+
+                    let bit = bits.as_ref().map(|bits| F::from(bits[i] as u64));
+                    let z_next = z
+                        .value()
+                        .copied()
+                        .zip(bit)
+                        .map(|(z, bit)| (z - bit) * F::TWO_INV);
+
+                    z = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        self.config.running_sum,
+                        i + 1,
+                        || z_next,
+                    )?;
+                }
+
+                self.config.q_remainder.enable(&mut region, NUM_BITS)?;
+
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Little-endian bits of a field element (assumes a little-endian `Repr`, as for bn256)
+pub(crate) fn le_bits<F: PrimeField>(value: &F, num_bits: usize) -> Vec<bool> {
+    value
+        .to_repr()
+        .as_ref()
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .take(num_bits)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr, plonk::Circuit,
+    };
+
+    #[derive(Default)]
+    struct TestCircuit {
+        value: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for TestCircuit {
+        type Config = (Column<Advice>, RangeCheckConfig<Fr, 8>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let value = meta.advice_column();
+            let running_sum = meta.advice_column();
+            meta.enable_equality(value);
+
+            (value, RangeCheckChip::configure(meta, running_sum))
+        }
+
+        fn synthesize(
+            &self,
+            (value_column, range_config): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let value = layouter.assign_region(
+                || "value",
+                |mut region| region.assign_advice(|| "value", value_column, 0, || self.value),
+            )?;
+
+            RangeCheckChip::construct(range_config).assign(layouter.namespace(|| "range"), &value)
+        }
+    }
+
+    fn run(value: Fr) -> bool {
+        let circuit = TestCircuit {
+            value: Value::known(value),
+        };
+        MockProver::run(5, &circuit, vec![])
+            .unwrap()
+            .verify()
+            .is_ok()
+    }
+
+    #[test]
+    fn test_range_check_accepts_values_in_range() {
+        assert!(run(Fr::from(0)));
+        assert!(run(Fr::from(42)));
+        assert!(run(Fr::from(255))); // Edge: 2^8 - 1
+    }
+
+    #[test]
+    fn test_range_check_rejects_values_out_of_range() {
+        assert!(!run(Fr::from(256))); // Edge: 2^8
+        assert!(!run(Fr::from(1_000_000)));
+        assert!(!run(-Fr::from(3))); // "Negative" difference
+    }
+}
@@ -0,0 +1,30 @@
+//! Proof Blinding Randomness
+//!
+//! Proofs are only zero-knowledge if their blinding factors are unpredictable, so the
+//! `generate_*_proof` functions of the circuit crates draw them from [`default_rng`]: the operating system
+//! CSPRNG (`getrandom`, with its `js` backend in WASM). The `generate_*_proof_with_rng`
+//! variants take the RNG from the caller instead.
+//!
+//! The `deterministic-rng` feature turns [`default_rng`] into a fixed-seed RNG so that
+//! golden-vector tests can reproduce proofs byte for byte. The circuit crates forward
+//! their own `deterministic-rng` feature to this one. Never enable it in a build that
+//! produces real proofs.
+
+use rand::{CryptoRng, RngCore};
+
+/// RNG for proof blinding and verifier challenges when the caller supplies none
+#[cfg(not(feature = "deterministic-rng"))]
+pub fn default_rng() -> impl RngCore + CryptoRng {
+    rand::rngs::OsRng
+}
+
+/// RNG for proof blinding and verifier challenges when the caller supplies none
+///
+/// Fixed seed (`deterministic-rng` feature): the same RNG as
+/// `plonkish_backend::util::test::std_rng`, so existing golden vectors still match.
+#[cfg(feature = "deterministic-rng")]
+pub fn default_rng() -> impl RngCore + CryptoRng {
+    use rand::SeedableRng;
+
+    rand::rngs::StdRng::from_seed(Default::default())
+}
@@ -0,0 +1,106 @@
+use std::{env, fs::File, io::Write, path::Path};
+
+use eligibility_gadgets::{poseidon::SBox, PoseidonField};
+use halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+
+/// Export the Poseidon rounds of the Halo2 circuits as Circom functions
+///
+/// `halo2_poseidon.circom` evaluates them, so Circom circuits hash values (e.g. the
+/// patient commitment) exactly like `PoseidonChip`.
+pub fn main() {
+    println!("🔧 Exporting the Halo2 Poseidon rounds for Circom...\n");
+
+    let project_root = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+
+    let out_filename = env::args().nth(1).unwrap_or_else(|| {
+        "../../../circom-circuits/eligibility_code/circuits/halo2_poseidon_constants.circom"
+            .to_string()
+    });
+    let out_path = Path::new(&project_root).join(&out_filename);
+
+    let rounds = Fr::poseidon_rounds();
+    let initial_state = Fr::poseidon_initial_state();
+
+    let sbox = rounds
+        .iter()
+        .map(|round| match round.sbox {
+            SBox::None => "0",
+            SBox::First => "1",
+            SBox::Full => "2",
+        })
+        .collect::<Vec<_>>();
+    let matrices = rounds
+        .iter()
+        .map(|round| {
+            array(
+                round
+                    .matrix
+                    .iter()
+                    .map(|row| array(row.iter().map(field_to_hex))),
+            )
+        })
+        .collect::<Vec<_>>();
+    let constants = rounds
+        .iter()
+        .map(|round| array(round.constants.iter().map(field_to_hex)))
+        .collect::<Vec<_>>();
+
+    let circom = format!(
+        r#"pragma circom 2.0.0;
+
+// Generated by gen-circom-poseidon from eligibility_gadgets::PoseidonField for bn256 Fr.
+// Do not edit: run `yarn generate:poseidon` after changing the Halo2 Poseidon.
+
+function HALO2_POSEIDON_N_ROUNDS() {{
+    return {};
+}}
+
+function HALO2_POSEIDON_INITIAL_STATE() {{
+    return {};
+}}
+
+// 0 = no S-box, 1 = first element, 2 = every element
+function HALO2_POSEIDON_SBOX() {{
+    return [{}];
+}}
+
+function HALO2_POSEIDON_MATRIX() {{
+    return [
+        {}
+    ];
+}}
+
+function HALO2_POSEIDON_CONSTANTS() {{
+    return [
+        {}
+    ];
+}}
+"#,
+        rounds.len(),
+        array(initial_state.iter().map(field_to_hex)),
+        sbox.join(", "),
+        matrices.join(",\n        "),
+        constants.join(",\n        "),
+    );
+
+    let mut out_file = File::create(&out_path).expect("Failed to create the constants file");
+    out_file
+        .write_all(circom.as_bytes())
+        .expect("Failed to write the constants file");
+
+    println!(
+        "✅ {} rounds written to: {}",
+        rounds.len(),
+        out_path.display()
+    );
+}
+
+fn array(elements: impl Iterator<Item = String>) -> String {
+    format!("[{}]", elements.collect::<Vec<_>>().join(", "))
+}
+
+fn field_to_hex(field: &Fr) -> String {
+    let mut bytes = field.to_repr();
+    bytes.reverse(); // Big-endian
+    format!("0x{}", hex::encode(bytes))
+}
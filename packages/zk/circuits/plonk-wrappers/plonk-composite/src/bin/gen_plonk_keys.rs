@@ -1,14 +1,14 @@
 use std::{env, fs::File, path::Path};
 
 use halo2_proofs::{
-    halo2curves::bn256::{Bn256, G1Affine},
+    halo2curves::bn256::G1Affine,
     plonk::{keygen_pk, keygen_vk, ProvingKey, VerifyingKey},
-    poly::{commitment::Params, kzg::commitment::ParamsKZG},
     SerdeFormat::RawBytes,
 };
 
 use composite_eligibility_circuit::AgeRangeCircuit;
-use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
+use halo2_proofs::halo2curves::bn256::Fr;
+use plonk_composite_eligibility::read_params;
 
 pub fn main() {
     // This key generator is based on halo2
@@ -21,8 +21,8 @@ pub fn main() {
     let srs_filename = env::args().nth(1).expect("Please specify SRS file path");
     let srs_path = Path::new(&project_root).join(srs_filename);
     let mut params_fs = File::open(srs_path.clone()).expect("Couldn't load params from SRS file");
-    let params =
-        ParamsKZG::<Bn256>::read(&mut params_fs).expect("Failed to read params from SRS file");
+    // Downsized to the circuit size, exactly as `prove` / `verify` do
+    let params = read_params(&mut params_fs).expect("Failed to read params from SRS file");
 
     // Create the path to the `out` directory under the project's root directory
     let out_dir = Path::new(&project_root).join("out");
@@ -33,12 +33,7 @@ pub fn main() {
     }
 
     // Use empty circuit for getting proving / verifying keys
-    let circuit = AgeRangeCircuit::<Fr> {
-        age: Value::unknown(),
-        min_age: Fr::from(0),
-        max_age: Fr::from(0),
-        study_id: Fr::from(0),
-    };
+    let circuit = AgeRangeCircuit::<Fr>::default();
 
     let verifying_key = keygen_vk::<_, _, _, false>(&params, &circuit)
         .expect("verifying key generation should not fail");
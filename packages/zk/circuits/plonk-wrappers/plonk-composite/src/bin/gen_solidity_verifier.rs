@@ -1,14 +1,14 @@
 use std::{env, fs::File, io::Write, path::Path};
 
 use halo2_proofs::{
-    halo2curves::bn256::{Bn256, Fr},
+    halo2curves::bn256::Fr,
     plonk::{keygen_pk, keygen_vk},
-    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    poly::commitment::Params,
     SerdeFormat::RawBytes,
 };
 
-use composite_eligibility_circuit::AgeRangeCircuit;
-use halo2_proofs::circuit::Value;
+use composite_eligibility_circuit::{AgeRangeCircuit, AGE_RANGE_PUBLIC_INPUTS};
+use plonk_composite_eligibility::read_params;
 
 use snark_verifier_sdk::{
     gen_pk,
@@ -23,13 +23,27 @@ struct AgeRangeCircuitWrapper(AgeRangeCircuit<Fr>);
 
 impl CircuitExt<Fr> for AgeRangeCircuitWrapper {
     fn num_instance(&self) -> Vec<usize> {
-        // We have 3 public inputs: min_age, max_age, study_id
-        vec![3]
+        // min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment,
+        // age bucket bounds, age bucket, provider_root, revocation_root
+        vec![AGE_RANGE_PUBLIC_INPUTS]
     }
 
     fn instances(&self) -> Vec<Vec<Fr>> {
         // Return the public inputs
-        vec![vec![self.0.min_age, self.0.max_age, self.0.study_id]]
+        let mut instances = vec![
+            self.0.min_age,
+            self.0.max_age,
+            self.0.study_id,
+            self.0.nullifier,
+            self.0.context_hash,
+            self.0.epoch,
+            self.0.patient_commitment,
+        ];
+        instances.extend(self.0.bucket_bounds);
+        instances.push(self.0.bucket);
+        instances.push(self.0.provider_root);
+        instances.push(self.0.revocation_root);
+        vec![instances]
     }
 }
 
@@ -39,12 +53,7 @@ impl halo2_proofs::plonk::Circuit<Fr> for AgeRangeCircuitWrapper {
     type Params = ();
 
     fn without_witnesses(&self) -> Self {
-        Self(AgeRangeCircuit {
-            age: Value::unknown(),
-            min_age: Fr::from(0),
-            max_age: Fr::from(0),
-            study_id: Fr::from(0),
-        })
+        Self(AgeRangeCircuit::<Fr>::default())
     }
 
     fn configure(meta: &mut halo2_proofs::plonk::ConstraintSystem<Fr>) -> Self::Config {
@@ -74,20 +83,13 @@ pub fn main() {
     let srs_path = Path::new(&project_root).join(&srs_filename);
     println!("📖 Reading SRS from: {}", srs_path.display());
 
-    let mut params_fs = File::open(srs_path.clone())
-        .expect("Couldn't load params from SRS file");
-    let params = ParamsKZG::<Bn256>::read(&mut params_fs)
-        .expect("Failed to read params from SRS file");
+    let mut params_fs = File::open(srs_path.clone()).expect("Couldn't load params from SRS file");
+    let params = read_params(&mut params_fs).expect("Failed to read params from SRS file");
 
     println!("✅ SRS loaded successfully (k={})\n", params.k());
 
     // Create empty circuit for getting proving / verifying keys
-    let circuit = AgeRangeCircuitWrapper(AgeRangeCircuit::<Fr> {
-        age: Value::unknown(),
-        min_age: Fr::from(0),
-        max_age: Fr::from(0),
-        study_id: Fr::from(0),
-    });
+    let circuit = AgeRangeCircuitWrapper(AgeRangeCircuit::<Fr>::default());
 
     println!("🔑 Generating proving key...");
     // Generate proving key using snark-verifier-sdk
@@ -122,7 +124,7 @@ pragma solidity ^0.8.20;
  * Circuit: AgeRangeCircuit
  * Proving System: PLONK with KZG commitments (SHPLONK)
  * Curve: BN254
- * Public Inputs: [min_age, max_age, study_id]
+ * Public Inputs: [min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment, age bucket bounds (3), age bucket, provider_root, revocation_root]
  *
  * IMPORTANT: This is a generated verifier contract.
  * The actual verification logic is in the deployment bytecode.
@@ -132,12 +134,12 @@ contract AgeRangeVerifier {{
     /**
      * @notice Verifies a PLONK proof
      * @param proof The proof bytes
-     * @param instances Public inputs [min_age, max_age, study_id]
+     * @param instances Public inputs [min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment, age bucket bounds (3), age bucket, provider_root, revocation_root]
      * @return success True if the proof is valid
      */
     function verify(
         bytes calldata proof,
-        uint256[3] calldata instances
+        uint256[13] calldata instances
     ) public view returns (bool success) {{
         // The verification logic is implemented in the contract bytecode
         // Generated by snark-verifier-sdk using SHPLONK
@@ -198,7 +200,7 @@ contract AgeRangeVerifier {{
     println!("🎉 Success!");
     println!("   Verifier contract: {}", verifier_path.display());
     println!("   Deployment bytecode: {}", bytecode_path.display());
-    println!("   Public inputs: 3 (min_age, max_age, study_id)");
+    println!("   Public inputs: {} (min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment, age bucket bounds, age bucket)", AGE_RANGE_PUBLIC_INPUTS);
     println!("   Proving system: PLONK with KZG commitments (SHPLONK)");
     println!("   Curve: BN254");
     println!("   Bytecode size: {} bytes", deployment_code.len());
@@ -5,13 +5,27 @@
 use std::fs::File;
 #[cfg(target_arch = "wasm32")]
 use std::io::BufReader;
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, io::Read};
 
+pub use composite_eligibility_circuit::epoch::{FreshnessWindow, ProofExpiredError};
 use composite_eligibility_circuit::{
-    serialization::*, AgeRangeCircuit, EligibilityError, validate_age_range,
+    age_bucket, context,
+    credential::CredentialInputs,
+    date_of_birth::{commit_date_of_birth, validate_date_of_birth},
+    epoch, nullifier, patient_commitment,
+    serialization::*,
+    AgeRangeCircuit, EligibilityError, AGE_RANGE_K, AGE_RANGE_PUBLIC_INPUTS,
+};
+use eligibility_gadgets::{
+    age_on, days_from_field,
+    icd10::{hash_icd10_code, hash_icd10_prefix, HashVersion},
+    identity_commitment,
 };
 use halo2_proofs::{
-    halo2curves::{bn256::{Bn256, Fr, G1Affine}, ff::PrimeField},
+    halo2curves::{
+        bn256::{Bn256, Fr, G1Affine},
+        ff::PrimeField,
+    },
     plonk::{create_proof, verify_proof, ProvingKey, VerifyingKey},
     poly::{
         commitment::Params,
@@ -28,7 +42,34 @@ use halo2_proofs::{
 };
 use rand::rngs::OsRng;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod linked;
+
 pub type GenerateProofResult = (Vec<u8>, Vec<u8>);
+/// Validity of a proof and the age bucket it reveals, if its study publishes bounds
+pub type VerifyProofResult = (bool, Option<u64>);
+
+/// Read KZG params and downsize them to the circuit size
+///
+/// Any SRS with at least 2^AGE_RANGE_K rows can be used. Keys must be
+/// generated and used with params of the same size, so every entry point
+/// reads the SRS through this function.
+pub fn read_params<R: Read>(reader: &mut R) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    let mut params = ParamsKZG::<Bn256>::read(reader)?;
+
+    let k = AGE_RANGE_K as u32;
+    if params.k() < k {
+        return Err(EligibilityError(format!(
+            "SRS supports 2^{} rows but AgeRangeCircuit needs 2^{}",
+            params.k(),
+            k
+        ))
+        .into());
+    }
+    params.downsize(k);
+
+    Ok(params)
+}
 
 pub fn generate_halo2_proof(
     params: &ParamsKZG<Bn256>,
@@ -97,11 +138,18 @@ fn prove_with_params(
         .map_err(|e| EligibilityError(format!("Failed to deserialize inputs: {}", e)))?;
 
     // Extract age range inputs
-    let age = circuit_inputs
-        .get("age")
-        .ok_or_else(|| EligibilityError("Missing 'age' input".to_string()))?
+    let dob = circuit_inputs
+        .get("dob")
+        .ok_or_else(|| EligibilityError("Missing 'dob' input".to_string()))?
         .get(0)
-        .ok_or_else(|| EligibilityError("Invalid 'age' value".to_string()))?
+        .ok_or_else(|| EligibilityError("Invalid 'dob' value".to_string()))?
+        .clone();
+
+    let dob_salt = circuit_inputs
+        .get("dob_salt")
+        .ok_or_else(|| EligibilityError("Missing 'dob_salt' input".to_string()))?
+        .get(0)
+        .ok_or_else(|| EligibilityError("Invalid 'dob_salt' value".to_string()))?
         .clone();
 
     let min_age = circuit_inputs
@@ -125,24 +173,110 @@ fn prove_with_params(
         .ok_or_else(|| EligibilityError("Invalid 'study_id' value".to_string()))?
         .clone();
 
-    // Client-side validation (hybrid MVP approach)
-    let age_u64 = field_to_u64(&age)?;
+    let identity_secret = circuit_inputs
+        .get("identity_secret")
+        .ok_or_else(|| EligibilityError("Missing 'identity_secret' input".to_string()))?
+        .get(0)
+        .ok_or_else(|| EligibilityError("Invalid 'identity_secret' value".to_string()))?
+        .clone();
+
+    let context_hash = circuit_inputs
+        .get("context_hash")
+        .ok_or_else(|| EligibilityError("Missing 'context_hash' input".to_string()))?
+        .get(0)
+        .ok_or_else(|| EligibilityError("Invalid 'context_hash' value".to_string()))?
+        .clone();
+
+    let epoch = circuit_inputs
+        .get("epoch")
+        .ok_or_else(|| EligibilityError("Missing 'epoch' input".to_string()))?
+        .get(0)
+        .ok_or_else(|| EligibilityError("Invalid 'epoch' value".to_string()))?
+        .clone();
+
+    // Provider-signed credential over the dob commitment, issued at most
+    // MAX_CREDENTIAL_AGE days before the epoch (see `credential`)
+    let credential = CredentialInputs::from_inputs(&circuit_inputs)
+        .and_then(|credential| {
+            credential.check()?;
+            credential.check_at(&epoch)?;
+            Ok(credential)
+        })
+        .map_err(|e| EligibilityError(format!("Invalid credential: {}", e)))?;
+
+    // Client-side validation (the circuit enforces the same checks)
+    let days = |value: &Fr, name: &str| {
+        days_from_field(value)
+            .ok_or_else(|| EligibilityError(format!("Invalid '{}': not a day count", name)))
+    };
+    let dob_days = days(&dob, "dob")?;
+    let epoch_days = days(&epoch, "epoch")?;
+    if commit_date_of_birth(dob_days, dob_salt) != credential.attributes().dob_commitment {
+        return Err(
+            EligibilityError("Date of birth does not match dob_commitment".to_string()).into(),
+        );
+    }
+    if identity_commitment(identity_secret) != credential.attributes().identity_commitment {
+        return Err(EligibilityError(
+            "Identity secret does not match identity_commitment".to_string(),
+        )
+        .into());
+    }
     let min_age_u64 = field_to_u64(&min_age)?;
     let max_age_u64 = field_to_u64(&max_age)?;
 
-    validate_age_range(age_u64, min_age_u64, max_age_u64)
+    validate_date_of_birth(dob_days, epoch_days, min_age_u64, max_age_u64)
         .map_err(|e| Box::new(e) as Box<dyn Error>)?;
+    let age_u64 = age_on(dob_days, epoch_days) as u64;
+
+    // Optional bucket bounds of the study
+    let bounds = match circuit_inputs.get("age_buckets") {
+        Some(bounds) => bounds
+            .iter()
+            .map(field_to_u64)
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    let bounds = age_bucket::bucket_bounds(&bounds)?;
+    let bucket_bounds = bounds.map(Fr::from);
+    let bucket = Fr::from(age_bucket::age_bucket(age_u64, &bounds));
 
     // Create circuit with validated inputs
     use halo2_proofs::circuit::Value;
+    let nullifier = nullifier::compute_nullifier(identity_secret, study_id);
+    let patient_commitment =
+        patient_commitment::compute_patient_commitment(identity_secret, context_hash);
     let circuit = AgeRangeCircuit::<Fr> {
-        age: Value::known(age),
+        dob: Value::known(dob),
+        dob_salt: Value::known(dob_salt),
+        identity_secret: Value::known(identity_secret),
+        credential: credential.witness(),
         min_age,
         max_age,
         study_id,
+        nullifier,
+        context_hash,
+        epoch,
+        patient_commitment,
+        bucket_bounds,
+        bucket,
+        provider_root: credential.provider_root,
+        revocation_root: credential.revocation_root,
     };
 
-    let public_inputs = vec![min_age, max_age, study_id];
+    let mut public_inputs = vec![
+        min_age,
+        max_age,
+        study_id,
+        nullifier,
+        context_hash,
+        epoch,
+        patient_commitment,
+    ];
+    public_inputs.extend(bucket_bounds);
+    public_inputs.push(bucket);
+    public_inputs.push(credential.provider_root);
+    public_inputs.push(credential.revocation_root);
 
     let (proof, unserialized_inputs) =
         generate_halo2_proof(&params, &proving_key, circuit, public_inputs)?;
@@ -167,6 +301,113 @@ fn field_to_u64<F: PrimeField>(field: &F) -> Result<u64, Box<dyn Error>> {
     Ok(u64::from_le_bytes(array))
 }
 
+/// Hash an ICD-10 code with the shared versioned scheme, as `0x`-prefixed hex
+///
+/// Returns the same value the diagnosis circuits commit to, so callers can build
+/// `required_hash` inputs without re-implementing the encoding.
+pub fn hash_diagnosis_code(code: &str, version: u32) -> Result<String, Box<dyn Error>> {
+    let version = HashVersion::try_from(version)?;
+    let hash: Fr = hash_icd10_code(code, version)?;
+
+    Ok(field_to_hex(&hash))
+}
+
+/// Hash an ICD-10 prefix (e.g. "E11.*") with the shared versioned scheme, as hex
+///
+/// Returns the `prefix_hash` public input of the diagnosis prefix circuit.
+pub fn hash_diagnosis_prefix(prefix: &str, version: u32) -> Result<String, Box<dyn Error>> {
+    let version = HashVersion::try_from(version)?;
+    let hash: Fr = hash_icd10_prefix(prefix, version)?;
+
+    Ok(field_to_hex(&hash))
+}
+
+/// Derive the patient's identity secret from a seed of at least 32 bytes, as hex
+///
+/// Pass the result as the `identity_secret` input of `prove`; keep it on the device.
+/// Providers sign its identity commitment (see [`compute_identity_commitment`]) into the
+/// patient's credential, and `prove` only accepts that secret.
+pub fn derive_identity_secret(seed: &[u8]) -> Result<String, Box<dyn Error>> {
+    let identity_secret = nullifier::derive_identity_secret(seed)?;
+
+    Ok(field_to_hex(&identity_secret))
+}
+
+/// Identity commitment of an identity secret, as hex
+///
+/// The patient hands this value to their provider once, who signs it into the
+/// credential as `identity_commitment`.
+pub fn compute_identity_commitment(identity_secret: &str) -> Result<String, Box<dyn Error>> {
+    let commitment = identity_commitment(parse_field_element(identity_secret)?);
+
+    Ok(field_to_hex(&commitment))
+}
+
+/// Nullifier the proof for `study_id` will publish, as hex
+///
+/// Inputs use the same encoding as `prove` (decimal or `0x` hex). Registries compare
+/// this value with the fourth public input to reject repeated applications.
+pub fn compute_nullifier(identity_secret: &str, study_id: &str) -> Result<String, Box<dyn Error>> {
+    let nullifier = nullifier::compute_nullifier(
+        parse_field_element(identity_secret)?,
+        parse_field_element(study_id)?,
+    );
+
+    Ok(field_to_hex(&nullifier))
+}
+
+/// Context hash binding a proof to the submitting wallet, chain and registry, as hex
+///
+/// Addresses are `0x`-prefixed, `study_id` uses the same encoding as `prove`. Pass the
+/// result as the `context_hash` input of `prove`; verifiers recompute it from the
+/// submitting wallet and compare it with the fifth public input. The Circom eligibility
+/// code circuit takes the same value as `contextHash`, and `StudyRegistry.getContextHash`
+/// returns it on-chain.
+pub fn compute_context_hash(
+    wallet: &str,
+    chain_id: u64,
+    registry_address: &str,
+    study_id: &str,
+) -> Result<String, Box<dyn Error>> {
+    let context = context::ProofContext::new(
+        wallet,
+        chain_id,
+        registry_address,
+        parse_field_element(study_id)?,
+    )?;
+
+    Ok(field_to_hex(&context.hash()))
+}
+
+/// Patient commitment linking an age proof to an eligibility code proof, as hex
+///
+/// Inputs use the same encoding as `prove`. The age proof publishes this value as its
+/// seventh public input; pass it as the `patientCommitment` input of the Circom
+/// eligibility code circuit, with the same `identitySecret` (see [`verify_linked`]).
+pub fn compute_patient_commitment(
+    identity_secret: &str,
+    context_hash: &str,
+) -> Result<String, Box<dyn Error>> {
+    let commitment = patient_commitment::compute_patient_commitment(
+        parse_field_element(identity_secret)?,
+        parse_field_element(context_hash)?,
+    );
+
+    Ok(field_to_hex(&commitment))
+}
+
+/// Epoch of a Unix timestamp (seconds), the `epoch` input of `prove`: the day the age is
+/// derived on
+pub fn epoch_of(timestamp: u64) -> u64 {
+    epoch::epoch_of(timestamp)
+}
+
+fn field_to_hex(field: &Fr) -> String {
+    let mut bytes = field.to_repr();
+    bytes.reverse(); // Big-endian
+    format!("0x{}", hex::encode(bytes))
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn prove(
     srs_key_path: &str,
@@ -174,7 +415,7 @@ pub fn prove(
     input: HashMap<String, Vec<String>>,
 ) -> Result<GenerateProofResult, Box<dyn Error>> {
     let mut param_fs = File::open(srs_key_path)?;
-    let params = ParamsKZG::<Bn256>::read(&mut param_fs)?;
+    let params = read_params(&mut param_fs)?;
 
     let mut pk_fs = File::open(proving_key_path)?;
     let proving_key = ProvingKey::read::<_, AgeRangeCircuit<Fr>, false>(&mut pk_fs, RawBytes)?;
@@ -189,7 +430,7 @@ pub fn prove(
     input: HashMap<String, Vec<String>>,
 ) -> Result<GenerateProofResult, Box<dyn Error>> {
     let mut params_reader = BufReader::new(srs_key);
-    let params = ParamsKZG::<Bn256>::read(&mut params_reader)?;
+    let params = read_params(&mut params_reader)?;
 
     let mut pk_reader = BufReader::new(proving_key);
     let proving_key = ProvingKey::read::<_, AgeRangeCircuit<Fr>, false>(&mut pk_reader, RawBytes)?;
@@ -197,19 +438,50 @@ pub fn prove(
     prove_with_params(&params, &proving_key, input)
 }
 
+/// Rows of the `context_hash`, `epoch` and `patient_commitment` public inputs, followed
+/// by the age bucket bounds, the age bucket, `provider_root` and `revocation_root`
+const CONTEXT_HASH_ROW: usize = 4;
+const EPOCH_ROW: usize = 5;
+const PATIENT_COMMITMENT_ROW: usize = 6;
+
+/// Verify a proof, then check its epoch against `window`
+///
+/// A valid proof whose epoch is outside the window fails with a [`ProofExpiredError`],
+/// which callers can tell apart from other errors by downcasting. The epoch is the
+/// as-of day the prover chose, so the window bounds that day, not the time the proof
+/// was generated (see `epoch`). The bucket is only returned for valid proofs.
 fn verify_with_params(
     params: &ParamsKZG<Bn256>,
     verifying_key: &VerifyingKey<G1Affine>,
     proof: Vec<u8>,
     public_inputs: Vec<u8>,
-) -> Result<bool, Box<dyn Error>> {
+    window: &FreshnessWindow,
+) -> Result<VerifyProofResult, Box<dyn Error>> {
     let deserialized_inputs: Vec<Fr> =
         bincode::deserialize::<InputsSerializationWrapper>(&public_inputs)
             .map_err(|e| EligibilityError(e.to_string()))?
             .0;
 
+    if deserialized_inputs.len() != AGE_RANGE_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of public inputs (expected {}: min_age, max_age, study_id, nullifier, context_hash, epoch, patient_commitment, age bucket bounds, age bucket, provider_root, revocation_root)",
+            AGE_RANGE_PUBLIC_INPUTS
+        ))
+        .into());
+    }
+    let epoch = deserialized_inputs[EPOCH_ROW];
+    let (bucket, bounds) = deserialized_inputs
+        [PATIENT_COMMITMENT_ROW + 1..AGE_RANGE_PUBLIC_INPUTS - 2]
+        .split_last()
+        .unwrap();
+    let bucket = age_bucket::revealed_bucket(bounds, bucket)?;
+
     let result = verify_halo2_proof(params, verifying_key, proof, deserialized_inputs)?;
-    Ok(result)
+    if !result {
+        return Ok((false, None));
+    }
+    window.check(&epoch)?;
+    Ok((true, bucket))
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -218,14 +490,15 @@ pub fn verify(
     verifying_key_path: &str,
     proof: Vec<u8>,
     public_inputs: Vec<u8>,
-) -> Result<bool, Box<dyn Error>> {
+    window: &FreshnessWindow,
+) -> Result<VerifyProofResult, Box<dyn Error>> {
     let mut param_fs = File::open(srs_key_path)?;
-    let params = ParamsKZG::<Bn256>::read(&mut param_fs)?;
+    let params = read_params(&mut param_fs)?;
 
     let mut vk_fs = File::open(verifying_key_path)?;
     let verifying_key = VerifyingKey::read::<_, AgeRangeCircuit<Fr>, false>(&mut vk_fs, RawBytes)?;
 
-    verify_with_params(&params, &verifying_key, proof, public_inputs)
+    verify_with_params(&params, &verifying_key, proof, public_inputs, window)
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -234,12 +507,13 @@ pub fn verify(
     verifying_key: &[u8],
     proof: Vec<u8>,
     public_inputs: Vec<u8>,
-) -> Result<bool, Box<dyn Error>> {
+    window: &FreshnessWindow,
+) -> Result<VerifyProofResult, Box<dyn Error>> {
     let mut params_reader = BufReader::new(srs_key);
-    let params = ParamsKZG::<Bn256>::read(&mut params_reader)?;
+    let params = read_params(&mut params_reader)?;
 
     let mut vk_reader = BufReader::new(verifying_key);
     let verifying_key = VerifyingKey::read::<_, AgeRangeCircuit<Fr>, false>(&mut vk_reader, RawBytes)?;
 
-    verify_with_params(&params, &verifying_key, proof, public_inputs)
+    verify_with_params(&params, &verifying_key, proof, public_inputs, window)
 }
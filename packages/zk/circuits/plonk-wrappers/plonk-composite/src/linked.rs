@@ -0,0 +1,205 @@
+//! Linked Age and Eligibility Code Proofs
+//!
+//! Age is proven with `AgeRangeCircuit` (Halo2) and the eligibility code with the
+//! Circom `EligibilityCode` circuit (Groth16). Both publish the same patient
+//! commitment, `Poseidon(tag, identity_secret, context_hash)` with the Halo2 Poseidon,
+//! so a verifier accepting the pair checks both proofs and that they commit to the
+//! same patient and context.
+
+use std::error::Error;
+
+use circom_prover::{
+    prover::{CircomProof, ProofLib, PublicInputs},
+    CircomProver,
+};
+use composite_eligibility_circuit::{serialization::*, EligibilityError, AGE_RANGE_PUBLIC_INPUTS};
+use halo2_proofs::halo2curves::bn256::Fr;
+use num_bigint::BigUint;
+
+use crate::{
+    verify, FreshnessWindow, VerifyProofResult, CONTEXT_HASH_ROW, EPOCH_ROW, PATIENT_COMMITMENT_ROW,
+};
+
+/// Public inputs of the eligibility code proof: requiredCodeHash, contextHash,
+/// patientCommitment
+const CODE_PUBLIC_INPUTS: usize = 3;
+
+/// An age proof and an eligibility code proof submitted together
+#[derive(Debug, Clone)]
+pub struct LinkedProofs {
+    pub age_proof: Vec<u8>,
+    pub age_public_inputs: Vec<u8>, // Serialized, as returned by `prove`
+    pub code_proof: CircomProof,
+}
+
+/// Verify an age proof and an eligibility code proof of the same patient
+///
+/// The pair is valid if both proofs verify (the age proof within `window`) and they
+/// publish the same context hash and patient commitment; two patients' proofs are
+/// rejected. Returns the validity and age bucket, as [`verify`].
+pub fn verify_linked(
+    srs_key_path: &str,
+    verifying_key_path: &str,
+    code_zkey_path: &str,
+    proofs: LinkedProofs,
+    window: &FreshnessWindow,
+) -> Result<VerifyProofResult, Box<dyn Error>> {
+    let age_inputs: Vec<Fr> =
+        bincode::deserialize::<InputsSerializationWrapper>(&proofs.age_public_inputs)
+            .map_err(|e| EligibilityError(e.to_string()))?
+            .0;
+    if !check_link(&age_inputs, &proofs.code_proof.pub_inputs, window)? {
+        return Ok((false, None));
+    }
+
+    let (age_valid, bucket) = verify(
+        srs_key_path,
+        verifying_key_path,
+        proofs.age_proof,
+        proofs.age_public_inputs,
+        window,
+    )?;
+    if !age_valid {
+        return Ok((false, None));
+    }
+
+    let code_valid = CircomProver::verify(
+        ProofLib::Arkworks,
+        proofs.code_proof,
+        code_zkey_path.to_string(),
+    )?;
+    Ok(match code_valid {
+        true => (true, bucket),
+        false => (false, None),
+    })
+}
+
+/// Check that the public inputs of an age proof and an eligibility code proof link them
+///
+/// Both must publish the same context hash and patient commitment, and the age proof's
+/// epoch must be within `window` ([`ProofExpiredError`](crate::ProofExpiredError)
+/// otherwise). The proofs themselves are not verified.
+fn check_link(
+    age_inputs: &[Fr],
+    code_inputs: &PublicInputs,
+    window: &FreshnessWindow,
+) -> Result<bool, Box<dyn Error>> {
+    if age_inputs.len() != AGE_RANGE_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of age proof public inputs (expected {})",
+            AGE_RANGE_PUBLIC_INPUTS
+        ))
+        .into());
+    }
+
+    let code_inputs = code_inputs
+        .0
+        .iter()
+        .map(code_input)
+        .collect::<Result<Vec<_>, _>>()?;
+    if code_inputs.len() != CODE_PUBLIC_INPUTS {
+        return Err(EligibilityError(format!(
+            "Invalid number of eligibility code public inputs (expected {}: requiredCodeHash, contextHash, patientCommitment)",
+            CODE_PUBLIC_INPUTS
+        ))
+        .into());
+    }
+
+    window.check(&age_inputs[EPOCH_ROW])?;
+
+    // Same applicant context and same patient
+    Ok(age_inputs[CONTEXT_HASH_ROW] == code_inputs[1]
+        && age_inputs[PATIENT_COMMITMENT_ROW] == code_inputs[2])
+}
+
+/// Field element of a Groth16 public input, rejecting values outside the field
+fn code_input(input: &BigUint) -> Result<Fr, EligibilityError> {
+    let invalid = || {
+        EligibilityError(format!(
+            "Eligibility code public input {} is not a field element",
+            input
+        ))
+    };
+
+    let bytes = input.to_bytes_le();
+    let mut repr = [0u8; 32];
+    if bytes.len() > repr.len() {
+        return Err(invalid());
+    }
+    repr[..bytes.len()].copy_from_slice(&bytes);
+    Option::from(Fr::from_bytes(&repr)).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProofExpiredError;
+    use halo2_proofs::halo2curves::ff::Field;
+
+    /// 2025-06-15, in the window below
+    const EPOCH: u64 = 20254;
+
+    fn window() -> FreshnessWindow {
+        FreshnessWindow {
+            now: 1_750_000_000, // 2025-06-15
+            max_age_epochs: 30,
+        }
+    }
+
+    fn biguint(value: Fr) -> BigUint {
+        BigUint::from_bytes_le(&value.to_bytes())
+    }
+
+    /// Age proof inputs with a context hash and patient commitment wider than 128 bits
+    fn age_inputs(epoch: u64) -> Vec<Fr> {
+        let mut inputs = vec![Fr::ZERO; AGE_RANGE_PUBLIC_INPUTS];
+        inputs[CONTEXT_HASH_ROW] = -Fr::from(7);
+        inputs[EPOCH_ROW] = Fr::from(epoch);
+        inputs[PATIENT_COMMITMENT_ROW] = -Fr::from(11);
+        inputs
+    }
+
+    /// Eligibility code inputs publishing `context_hash` and `patient_commitment`
+    fn code_inputs(context_hash: Fr, patient_commitment: Fr) -> PublicInputs {
+        PublicInputs(vec![
+            biguint(Fr::from(42)),
+            biguint(context_hash),
+            biguint(patient_commitment),
+        ])
+    }
+
+    #[test]
+    fn test_links_proofs_of_the_same_patient() {
+        let linked = code_inputs(-Fr::from(7), -Fr::from(11));
+        assert!(check_link(&age_inputs(EPOCH), &linked, &window()).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_other_patient_commitment() {
+        let other = code_inputs(-Fr::from(7), -Fr::from(12));
+        assert!(!check_link(&age_inputs(EPOCH), &other, &window()).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_other_context_hash() {
+        let other = code_inputs(-Fr::from(8), -Fr::from(11));
+        assert!(!check_link(&age_inputs(EPOCH), &other, &window()).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_expired_age_epoch() {
+        let linked = code_inputs(-Fr::from(7), -Fr::from(11));
+        let error = check_link(&age_inputs(EPOCH - 31), &linked, &window()).unwrap_err();
+        assert!(error.downcast_ref::<ProofExpiredError>().is_some());
+    }
+
+    #[test]
+    fn test_code_input() {
+        assert_eq!(code_input(&biguint(-Fr::ONE)).unwrap(), -Fr::ONE);
+
+        // The field modulus and wider values are not field elements
+        let modulus = biguint(-Fr::ONE) + 1u32;
+        assert!(code_input(&modulus).is_err());
+        assert!(code_input(&(BigUint::from(1u32) << 256)).is_err());
+    }
+}
@@ -0,0 +1,478 @@
+//! Trial Matching Engine
+//!
+//! Evaluates published study criteria against a patient's facts before any proof is
+//! generated, so the extension can show which studies a patient may be eligible for
+//! and refuse proving jobs that cannot succeed. Matching is offline and deterministic:
+//! no network access, clock or randomness, the as-of date is an argument.
+//!
+//! ## Studies
+//! Each study is given as an input map of the composite circuits (see
+//! [`deserialize_circuit_inputs`]), with a `study_id` and either:
+//! - `criteria`, the encoding of the criteria circuit (see
+//!   [`Criteria::encode`]), checked against `criteria_hash` if present, or
+//! - `min_age` and `max_age`, the public inputs of the age range circuit
+//!
+//! ## Results
+//! Predicates are evaluated the way the criteria circuit evaluates them, except that a
+//! value can be unknown: a lab predicate without an observation of its analyte, or with
+//! one in a unit that cannot be converted (see
+//! [`units`](composite_eligibility_circuit::units)). AND / OR / NOT use three-valued
+//! logic, so a study is a [`MatchStatus::Match`] or [`MatchStatus::NoMatch`] whenever the
+//! known facts decide it. Otherwise the result names a criterion that decides the study:
+//! one that fails for a no-match, one that is unknown for an unknown result.
+//!
+//! Diagnoses are the patient's record: a code that is not in the facts is absent.
+
+use std::collections::HashMap;
+
+use composite_eligibility_circuit::{
+    criteria::{
+        diagnosis_signal, lab_signal, node_signal, slot_hashes, Criteria, CriteriaNode,
+        LabPredicate, Operation, AGE_SIGNAL, PREDICATES,
+    },
+    fhir::{parse_fhir_date, PatientFacts},
+    lab_value::pack_loinc,
+    serialization::deserialize_circuit_inputs,
+    units::ANALYTES,
+};
+use plonkish_backend::halo2_curves::bn256::Fr;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub struct MatchError(pub String);
+
+impl std::fmt::Display for MatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Outcome of matching one study
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    Match,
+    NoMatch,
+    Unknown, // Depends on facts the patient does not have
+}
+
+/// Result of matching one study, in the order the studies were given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StudyMatch {
+    pub study_id: String, // As given in the study's input map
+    pub status: MatchStatus,
+    pub criterion: Option<FailedCriterion>, // None for a match
+}
+
+/// Criterion that decides a no-match or unknown result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailedCriterion {
+    #[serde(flatten)]
+    pub criterion: Criterion,
+    // The predicate's value for the patient, None if unknown. `Some(true)` means the
+    // predicate holds and fails the study through a NOT, e.g. an excluded diagnosis.
+    pub holds: Option<bool>,
+}
+
+/// Predicate of the criteria, with field elements as `0x`-prefixed hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "predicate", rename_all = "snake_case")]
+pub enum Criterion {
+    Age {
+        min_age: u64,
+        max_age: u64,
+    },
+    Diagnosis {
+        hash: String, // Code or prefix hash
+    },
+    Lab {
+        analyte: String,
+        loinc: Option<String>, // For analytes with a canonical unit
+        unit: Option<String>,
+        scale: u32,
+        comparison: String,
+        bound: u64,
+        bound_high: u64,
+    },
+}
+
+/// Match every study against a patient's facts on `as_of_date` (days since epoch)
+pub fn match_studies(
+    studies: &[HashMap<String, Vec<String>>],
+    facts: &PatientFacts,
+    as_of_date: i64,
+) -> Result<Vec<StudyMatch>, MatchError> {
+    studies
+        .iter()
+        .map(|study| match_study(study, facts, as_of_date))
+        .collect()
+}
+
+/// Match every study against the patient of a FHIR R4 bundle on a `YYYY-MM-DD` date
+pub fn match_bundle(
+    bundle: &str,
+    studies: &[HashMap<String, Vec<String>>],
+    as_of_date: &str,
+) -> Result<Vec<StudyMatch>, MatchError> {
+    let facts = PatientFacts::from_bundle(bundle).map_err(|e| MatchError(e.0))?;
+    let as_of_date = parse_fhir_date(as_of_date).map_err(|e| MatchError(e.0))?;
+    match_studies(studies, &facts, as_of_date)
+}
+
+/// Match one study given as an input map (see the module documentation)
+pub fn match_study(
+    study: &HashMap<String, Vec<String>>,
+    facts: &PatientFacts,
+    as_of_date: i64,
+) -> Result<StudyMatch, MatchError> {
+    let study_id = match study.get("study_id").map(Vec::as_slice) {
+        Some([study_id]) => study_id.clone(),
+        _ => return Err(MatchError("Study has no study_id".to_string())),
+    };
+    let invalid = |message: String| MatchError(format!("Study {}: {}", study_id, message));
+
+    let criteria = study_criteria(study).map_err(|e| invalid(e.0))?;
+    let (status, criterion) = evaluate(&criteria, facts, as_of_date).map_err(|e| invalid(e.0))?;
+
+    Ok(StudyMatch {
+        study_id,
+        status,
+        criterion,
+    })
+}
+
+/// Criteria of a study's input map
+pub fn study_criteria(study: &HashMap<String, Vec<String>>) -> Result<Criteria, MatchError> {
+    let inputs = deserialize_circuit_inputs(study.clone()).map_err(|e| MatchError(e.0))?;
+
+    if let Some(encoding) = inputs.get("criteria") {
+        let criteria = Criteria::decode(encoding).map_err(|e| MatchError(e.0))?;
+        if let Some(hash) = inputs.get("criteria_hash") {
+            if *hash != [criteria.hash().map_err(|e| MatchError(e.0))?] {
+                return Err(MatchError(
+                    "criteria do not match criteria_hash".to_string(),
+                ));
+            }
+        }
+        return Ok(criteria);
+    }
+
+    let age = |key: &str| match inputs.get(key).map(Vec::as_slice) {
+        Some([value]) => field_to_int(value).ok_or(MatchError(format!("Invalid {}", key))),
+        _ => Err(MatchError(format!(
+            "Study has neither criteria nor {}",
+            key
+        ))),
+    };
+    let criteria = Criteria {
+        age: Some((age("min_age")?, age("max_age")?)),
+        nodes: vec![CriteriaNode::and(AGE_SIGNAL, AGE_SIGNAL)],
+        ..Default::default()
+    };
+    criteria.validate().map_err(|e| MatchError(e.0))?;
+    Ok(criteria)
+}
+
+/// Status of `criteria` for the patient, with the criterion that decides it
+pub fn evaluate(
+    criteria: &Criteria,
+    facts: &PatientFacts,
+    as_of_date: i64,
+) -> Result<(MatchStatus, Option<FailedCriterion>), MatchError> {
+    let mut signals = predicate_values(criteria, facts, as_of_date)?.to_vec();
+    for node in criteria.nodes.iter() {
+        let (lhs, rhs) = (signals[node.lhs], signals[node.rhs]);
+        signals.push(match node.op {
+            Operation::And if lhs == Some(false) || rhs == Some(false) => Some(false),
+            Operation::Or if lhs == Some(true) || rhs == Some(true) => Some(true),
+            Operation::Not => lhs.map(|value| !value),
+            op => lhs.zip(rhs).map(|(lhs, rhs)| op.apply(lhs, rhs)),
+        });
+    }
+
+    let status = match signals.last().copied().flatten() {
+        Some(true) => return Ok((MatchStatus::Match, None)),
+        Some(false) => MatchStatus::NoMatch,
+        None => MatchStatus::Unknown,
+    };
+
+    // Follow an operand with the node's value down to a predicate: both operands of a
+    // false OR are false, a false AND has a false operand, and an unknown AND / OR has
+    // an unknown operand
+    let mut signal = signals.len() - 1;
+    while signal >= node_signal(0) {
+        let node = criteria.nodes[signal - node_signal(0)];
+        signal = match node.op {
+            Operation::Not => node.lhs,
+            _ if signals[node.lhs] == signals[signal] => node.lhs,
+            _ => node.rhs,
+        };
+    }
+
+    let criterion = FailedCriterion {
+        criterion: describe(criteria, signal),
+        holds: signals[signal],
+    };
+    Ok((status, Some(criterion)))
+}
+
+/// Values of the criteria's predicates, None where unknown
+fn predicate_values(
+    criteria: &Criteria,
+    facts: &PatientFacts,
+    as_of_date: i64,
+) -> Result<[Option<bool>; PREDICATES], MatchError> {
+    let mut predicates = [None; PREDICATES];
+
+    if let Some((min_age, max_age)) = criteria.age {
+        predicates[AGE_SIGNAL] = facts
+            .age_on(as_of_date)
+            .ok()
+            .map(|age| (min_age..=max_age).contains(&age));
+    }
+
+    let slots = facts
+        .diagnoses
+        .iter()
+        .map(|code| slot_hashes(code))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| MatchError(e.0))?;
+    for (i, target) in criteria.diagnoses.iter().enumerate() {
+        predicates[diagnosis_signal(i)] = Some(slots.iter().any(|hashes| hashes.contains(target)));
+    }
+
+    for (i, lab) in criteria.labs.iter().enumerate() {
+        predicates[lab_signal(i)] = lab_unit(facts, lab)
+            .and_then(|unit| facts.lab_value(lab, &unit).ok())
+            .map(|value| lab.holds(value));
+    }
+
+    Ok(predicates)
+}
+
+/// Unit of a lab predicate's bounds: the analyte's canonical unit, or the unit of the
+/// latest observation for analytes without one
+fn lab_unit(facts: &PatientFacts, lab: &LabPredicate) -> Option<String> {
+    let is_analyte = |loinc: &str| pack_loinc(loinc).map(Fr::from).ok() == Some(lab.analyte);
+
+    match ANALYTES.iter().find(|analyte| is_analyte(analyte.loinc)) {
+        Some(analyte) => Some(analyte.unit.to_string()),
+        None => facts
+            .observations
+            .iter()
+            .filter(|observation| is_analyte(&observation.loinc))
+            .max_by_key(|observation| observation.effective)
+            .map(|observation| observation.unit.clone()),
+    }
+}
+
+fn describe(criteria: &Criteria, signal: usize) -> Criterion {
+    if signal == AGE_SIGNAL {
+        let (min_age, max_age) = criteria.age.unwrap_or_default();
+        return Criterion::Age { min_age, max_age };
+    }
+    if signal < lab_signal(0) {
+        return Criterion::Diagnosis {
+            hash: field_to_hex(&criteria.diagnoses[signal - diagnosis_signal(0)]),
+        };
+    }
+
+    let lab = criteria.labs[signal - lab_signal(0)];
+    let analyte = ANALYTES
+        .iter()
+        .find(|analyte| pack_loinc(analyte.loinc).map(Fr::from).ok() == Some(lab.analyte));
+    Criterion::Lab {
+        analyte: field_to_hex(&lab.analyte),
+        loinc: analyte.map(|analyte| analyte.loinc.to_string()),
+        unit: analyte.map(|analyte| analyte.unit.to_string()),
+        scale: lab.scale,
+        comparison: lab.comparison.to_string(),
+        bound: lab.bound,
+        bound_high: lab.bound_high,
+    }
+}
+
+fn field_to_int(fp: &Fr) -> Option<u64> {
+    let bytes = fp.to_bytes();
+    bytes[8..]
+        .iter()
+        .all(|b| *b == 0)
+        .then(|| u64::from_le_bytes(bytes[..8].try_into().unwrap()))
+}
+
+fn field_to_hex(fp: &Fr) -> String {
+    let hex: String = fp
+        .to_bytes()
+        .iter()
+        .rev()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("0x{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use composite_eligibility_circuit::{fhir::Observation, lab_value::Comparison};
+
+    fn code_hash(code: &str) -> Fr {
+        *slot_hashes(code).unwrap().last().unwrap()
+    }
+
+    /// Age 18-65 AND E11.9 AND HbA1c > 7.0 % AND NOT I21.4
+    fn criteria() -> Criteria {
+        Criteria {
+            age: Some((18, 65)),
+            diagnoses: vec![code_hash("E11.9"), code_hash("I21.4")],
+            labs: vec![LabPredicate {
+                analyte: Fr::from(45484),
+                scale: 1,
+                comparison: Comparison::Gt,
+                bound: 70,
+                bound_high: 0,
+            }],
+            nodes: vec![
+                CriteriaNode::and(AGE_SIGNAL, diagnosis_signal(0)),
+                CriteriaNode::and(node_signal(0), lab_signal(0)),
+                CriteriaNode::not(diagnosis_signal(1)),
+                CriteriaNode::and(node_signal(1), node_signal(2)),
+            ],
+        }
+    }
+
+    fn study(criteria: &Criteria) -> HashMap<String, Vec<String>> {
+        let mut study = HashMap::new();
+        study.insert("study_id".to_string(), vec!["42".to_string()]);
+        study.insert(
+            "criteria".to_string(),
+            criteria
+                .encode()
+                .unwrap()
+                .iter()
+                .map(field_to_hex)
+                .collect(),
+        );
+        study.insert(
+            "criteria_hash".to_string(),
+            vec![field_to_hex(&criteria.hash().unwrap())],
+        );
+        study
+    }
+
+    fn patient(diagnoses: &[&str], hba1c: Option<(&str, &str)>) -> PatientFacts {
+        PatientFacts {
+            birth_date: parse_fhir_date("1980-03-14").unwrap(),
+            diagnoses: diagnoses.iter().map(|code| code.to_string()).collect(),
+            observations: hba1c
+                .map(|(value, unit)| Observation {
+                    loinc: "4548-4".to_string(),
+                    value: value.to_string(),
+                    unit: unit.to_string(),
+                    effective: None,
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn match_patient(facts: &PatientFacts) -> StudyMatch {
+        match_study(
+            &study(&criteria()),
+            facts,
+            parse_fhir_date("2025-06-01").unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_match_statuses() {
+        let eligible = match_patient(&patient(&["E11.9"], Some(("7.4", "%"))));
+        assert_eq!(
+            (eligible.study_id.as_str(), eligible.status),
+            ("42", MatchStatus::Match)
+        );
+        assert!(eligible.criterion.is_none());
+
+        // HbA1c in IFCC units: 57 mmol/mol is 7.4 %
+        let converted = match_patient(&patient(&["E11.9"], Some(("57", "mmol/mol"))));
+        assert_eq!(converted.status, MatchStatus::Match);
+
+        let below = match_patient(&patient(&["E11.9"], Some(("6.9", "%"))));
+        assert_eq!(below.status, MatchStatus::NoMatch);
+        assert_eq!(below.criterion.unwrap().holds, Some(false));
+
+        // Excluded diagnosis: the predicate holds and fails the study through the NOT
+        let excluded = match_patient(&patient(&["E11.9", "I21.4"], Some(("7.4", "%"))));
+        assert_eq!(excluded.status, MatchStatus::NoMatch);
+        assert_eq!(
+            excluded.criterion.unwrap(),
+            FailedCriterion {
+                criterion: Criterion::Diagnosis {
+                    hash: field_to_hex(&code_hash("I21.4"))
+                },
+                holds: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_facts() {
+        // No HbA1c, or one in a unit that cannot be converted
+        for hba1c in [None, Some(("7.4", "mg/dL"))] {
+            let unknown = match_patient(&patient(&["E11.9"], hba1c));
+            assert_eq!(unknown.status, MatchStatus::Unknown);
+            let criterion = unknown.criterion.unwrap();
+            assert_eq!(criterion.holds, None);
+            assert!(matches!(
+                criterion.criterion,
+                Criterion::Lab { loinc: Some(ref loinc), bound: 70, .. } if loinc == "4548-4"
+            ));
+        }
+
+        // A failing known criterion decides the study even with unknown facts
+        let no_match = match_patient(&patient(&["E10.9"], None));
+        assert_eq!(no_match.status, MatchStatus::NoMatch);
+        assert_eq!(no_match.criterion.unwrap().holds, Some(false));
+    }
+
+    #[test]
+    fn test_study_inputs() {
+        let facts = patient(&[], None);
+        let as_of = parse_fhir_date("2025-06-01").unwrap();
+
+        // Age range circuit inputs
+        let mut age_study = HashMap::new();
+        age_study.insert("study_id".to_string(), vec!["7".to_string()]);
+        age_study.insert("min_age".to_string(), vec!["50".to_string()]);
+        age_study.insert("max_age".to_string(), vec!["70".to_string()]);
+        let result = match_study(&age_study, &facts, as_of).unwrap();
+        assert_eq!(result.status, MatchStatus::NoMatch);
+        assert_eq!(
+            result.criterion.unwrap().criterion,
+            Criterion::Age {
+                min_age: 50,
+                max_age: 70
+            }
+        );
+
+        let studies = [study(&criteria()), age_study.clone()];
+        let results = match_studies(&studies, &facts, as_of).unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r.study_id.as_str())
+                .collect::<Vec<_>>(),
+            ["42", "7"]
+        );
+
+        // Wrong criteria hash, no criteria, no study id
+        let mut tampered = study(&criteria());
+        tampered.insert("criteria_hash".to_string(), vec!["0x01".to_string()]);
+        assert!(match_study(&tampered, &facts, as_of).is_err());
+        age_study.remove("max_age");
+        assert!(match_study(&age_study, &facts, as_of).is_err());
+        age_study.remove("study_id");
+        assert!(match_study(&age_study, &facts, as_of).is_err());
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 
-use plonk_composite_eligibility;
+use plonk_composite_eligibility::{self, FreshnessWindow, ProofExpiredError};
 
 #[wasm_bindgen]
 pub fn generate_eligibility_proof(
@@ -29,16 +29,116 @@ pub fn verify_eligibility_proof(
     verifying_key: &[u8],
     proof: JsValue,
     public_inputs: JsValue,
+    now: u64,
+    max_age_epochs: u64,
 ) -> Result<JsValue, JsValue> {
     let proof: Vec<u8> = from_value(proof)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse proof: {}", e)))?;
     let public_inputs: Vec<u8> = from_value(public_inputs)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse public_inputs: {}", e)))?;
 
-    // Verify proof
-    let is_valid = plonk_composite_eligibility::verify(srs_key, verifying_key, proof, public_inputs)
-        .map_err(|e| JsValue::from_str(&format!("Proof verification failed: {}", e)))?;
+    // Verify proof, rejecting proofs whose as-of epoch is more than `max_age_epochs`
+    // before `now` or after it
+    let window = FreshnessWindow {
+        now,
+        max_age_epochs,
+    };
+    let (is_valid, age_bucket) =
+        plonk_composite_eligibility::verify(srs_key, verifying_key, proof, public_inputs, &window)
+            .map_err(|e| match e.downcast_ref::<ProofExpiredError>() {
+                Some(expired) => JsValue::from_str(&expired.to_string()),
+                None => JsValue::from_str(&format!("Proof verification failed: {}", e)),
+            })?;
 
-    // Convert result to JsValue
-    to_value(&is_valid).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+    // Convert result to JsValue: [valid, age bucket or null if the study has no buckets]
+    to_value(&(is_valid, age_bucket))
+        .map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn epoch_of(timestamp: u64) -> u64 {
+    // Days since the Unix epoch, the sixth public input of the age proof
+    plonk_composite_eligibility::epoch_of(timestamp)
+}
+
+#[wasm_bindgen]
+pub fn derive_identity_secret(seed: &[u8]) -> Result<JsValue, JsValue> {
+    // Same derivation as the composite circuit's nullifier helpers
+    let identity_secret = plonk_composite_eligibility::derive_identity_secret(seed)
+        .map_err(|e| JsValue::from_str(&format!("Deriving identity secret failed: {}", e)))?;
+
+    to_value(&identity_secret)
+        .map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn compute_identity_commitment(identity_secret: &str) -> Result<JsValue, JsValue> {
+    // Poseidon(tag, identity_secret), signed by the provider into the patient's credential
+    let commitment = plonk_composite_eligibility::compute_identity_commitment(identity_secret)
+        .map_err(|e| JsValue::from_str(&format!("Computing identity commitment failed: {}", e)))?;
+
+    to_value(&commitment).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn compute_nullifier(identity_secret: &str, study_id: &str) -> Result<JsValue, JsValue> {
+    // Poseidon(identity_secret, study_id), the fourth public input of the age proof
+    let nullifier = plonk_composite_eligibility::compute_nullifier(identity_secret, study_id)
+        .map_err(|e| JsValue::from_str(&format!("Computing nullifier failed: {}", e)))?;
+
+    to_value(&nullifier).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn compute_context_hash(
+    wallet: &str,
+    chain_id: u64,
+    registry_address: &str,
+    study_id: &str,
+) -> Result<JsValue, JsValue> {
+    // keccak256(abi.encode(wallet, chain_id, registry_address, study_id)) mod r, the fifth
+    // public input of the age proof
+    let context_hash = plonk_composite_eligibility::compute_context_hash(
+        wallet,
+        chain_id,
+        registry_address,
+        study_id,
+    )
+    .map_err(|e| JsValue::from_str(&format!("Computing context hash failed: {}", e)))?;
+
+    to_value(&context_hash).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn compute_patient_commitment(
+    identity_secret: &str,
+    context_hash: &str,
+) -> Result<JsValue, JsValue> {
+    // Poseidon(tag, identity_secret, context_hash), the seventh public input of the age proof
+    // and the patientCommitment input of the Circom eligibility code proof
+    let commitment =
+        plonk_composite_eligibility::compute_patient_commitment(identity_secret, context_hash)
+            .map_err(|e| {
+                JsValue::from_str(&format!("Computing patient commitment failed: {}", e))
+            })?;
+
+    to_value(&commitment).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn hash_diagnosis_code(code: &str, version: u32) -> Result<JsValue, JsValue> {
+    // Same versioned ICD-10 encoding as the diagnosis circuits
+    let hash = plonk_composite_eligibility::hash_diagnosis_code(code, version)
+        .map_err(|e| JsValue::from_str(&format!("Hashing diagnosis code failed: {}", e)))?;
+
+    to_value(&hash).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn hash_diagnosis_prefix(prefix: &str, version: u32) -> Result<JsValue, JsValue> {
+    // Same versioned ICD-10 prefix encoding as the diagnosis prefix circuit
+    let hash = plonk_composite_eligibility::hash_diagnosis_prefix(prefix, version)
+        .map_err(|e| JsValue::from_str(&format!("Hashing diagnosis prefix failed: {}", e)))?;
+
+    to_value(&hash).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
 }
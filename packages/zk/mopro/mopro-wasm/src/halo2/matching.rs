@@ -0,0 +1,19 @@
+use std::collections::HashMap;
+
+use serde_wasm_bindgen::{from_value, to_value};
+use wasm_bindgen::prelude::*;
+
+use trial_matching_engine;
+
+#[wasm_bindgen]
+pub fn match_studies(bundle: &str, studies: JsValue, as_of_date: &str) -> Result<JsValue, JsValue> {
+    // One input map per study, as accepted by the composite circuits
+    let studies: Vec<HashMap<String, Vec<String>>> = from_value(studies)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse studies: {}", e)))?;
+
+    // Offline evaluation on the FHIR bundle, no proof is generated
+    let results = trial_matching_engine::match_bundle(bundle, &studies, as_of_date)
+        .map_err(|e| JsValue::from_str(&format!("Matching studies failed: {}", e)))?;
+
+    to_value(&results).map_err(|e| JsValue::from_str(&format!("Serialization failed: {}", e)))
+}
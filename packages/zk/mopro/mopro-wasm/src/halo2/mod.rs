@@ -11,3 +11,6 @@ pub mod gemini;
 
 #[cfg(feature = "eligibility")]
 pub mod eligibility;
+
+#[cfg(feature = "matching")]
+pub mod matching;